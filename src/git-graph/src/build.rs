@@ -0,0 +1,115 @@
+//! Progress and cancellation of graph builds, see [BuildProgress].
+//!
+//! With the `tokio` feature, [build_graph_async] builds graphs on tokio's blocking
+//! thread pool, so services laying out large histories don't block their executor.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+#[cfg(feature = "tokio")]
+use {
+    crate::{graph::GitGraph, settings::Settings},
+    git2::Oid,
+    std::{path::PathBuf, sync::Arc},
+};
+
+/// Shared between a graph build and its observers, through [Settings::build_progress].
+/// Counts the commits read so far, and lets other threads cancel the build.
+#[derive(Debug, Default)]
+pub struct BuildProgress {
+    commits: AtomicUsize,
+    cancelled: AtomicBool,
+}
+
+impl BuildProgress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of commits read so far.
+    pub fn commits(&self) -> usize {
+        self.commits.load(Ordering::Relaxed)
+    }
+
+    /// Stops the build at the next commit. The build then fails with an error.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Fails if the build was cancelled.
+    pub(crate) fn check(&self) -> Result<(), String> {
+        if self.is_cancelled() {
+            Err("Graph build cancelled".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Counts a commit read, and fails if the build was cancelled.
+    pub(crate) fn advance(&self) -> Result<(), String> {
+        self.check()?;
+        self.commits.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Builds the graph of the repository at `path` on tokio's blocking thread pool,
+/// like [GitGraph::from_revisions].
+///
+/// Progress is reported through [Settings::build_progress], which is created if not
+/// given. Dropping the returned future, e.g. when a request is aborted, cancels the build.
+#[cfg(feature = "tokio")]
+pub async fn build_graph_async(
+    path: PathBuf,
+    mut settings: Settings,
+    max_count: Option<usize>,
+    revisions: Vec<Oid>,
+) -> Result<GitGraph, String> {
+    let progress = settings
+        .build_progress
+        .get_or_insert_with(Default::default)
+        .clone();
+    let mut guard = CancelOnDrop(Some(progress));
+    let result = tokio::task::spawn_blocking(move || {
+        let repository = crate::get_repo(&path, false).map_err(|err| err.message().to_string())?;
+        GitGraph::from_revisions(&repository, &settings, max_count, &revisions)
+    })
+    .await
+    .map_err(|err| err.to_string())?;
+    guard.0 = None;
+    result
+}
+
+/// Cancels a build when dropped before being disarmed.
+#[cfg(feature = "tokio")]
+struct CancelOnDrop(Option<Arc<BuildProgress>>);
+
+#[cfg(feature = "tokio")]
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if let Some(progress) = &self.0 {
+            progress.cancel();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BuildProgress;
+
+    #[test]
+    fn cancelled_builds_stop_counting() {
+        let progress = BuildProgress::new();
+        progress.advance().unwrap();
+        progress.advance().unwrap();
+        assert_eq!(progress.commits(), 2);
+        assert!(progress.check().is_ok());
+
+        progress.cancel();
+        assert!(progress.is_cancelled());
+        assert_eq!(progress.advance().unwrap_err(), "Graph build cancelled");
+        assert_eq!(progress.commits(), 2);
+    }
+}
@@ -0,0 +1,88 @@
+//! Creation of git bundles from a selection of branches and commits, for sharing
+//! a slice of history offline.
+//!
+//! Bundles are written by the `git` executable, as libgit2 can't create them.
+
+use crate::graph::GitGraph;
+use crate::rebase::ScratchRepository;
+use git2::Oid;
+use std::path::Path;
+use std::process::Command;
+
+/// Prefix of refs created for selected commits without a ref name.
+const SELECTED_PREFIX: &str = "refs/heads/selected/";
+
+/// The refs to bundle for a selection of branch names, tags, revisions or `@N` row
+/// addresses. Branches and tags are bundled under their ref name, other commits as
+/// `refs/heads/selected/<short-id>`.
+pub fn selection_refs(graph: &GitGraph, specs: &[String]) -> Result<Vec<(String, Oid)>, String> {
+    let repository = graph.repository()?;
+    let mut refs: Vec<(String, Oid)> = vec![];
+    for spec in specs {
+        let named = if spec.starts_with('@') {
+            None
+        } else {
+            repository
+                .resolve_reference_from_short_name(spec)
+                .ok()
+                .filter(|reference| {
+                    reference.is_branch() || reference.is_remote() || reference.is_tag()
+                })
+        };
+        let entry = match named {
+            Some(reference) => (
+                reference.name().unwrap_or_default().to_string(),
+                reference
+                    .target()
+                    .ok_or_else(|| format!("'{}' is a symbolic ref", spec))?,
+            ),
+            None => {
+                let oid = match spec.strip_prefix('@') {
+                    Some(_) => graph.commits[graph.resolve_row(spec)?].oid,
+                    None => repository
+                        .revparse_single(spec)
+                        .and_then(|obj| obj.peel_to_commit())
+                        .map(|commit| commit.id())
+                        .map_err(|err| format!("Can't resolve '{}': {}", spec, err.message()))?,
+                };
+                (format!("{}{}", SELECTED_PREFIX, &oid.to_string()[..7]), oid)
+            }
+        };
+        if !refs.contains(&entry) {
+            refs.push(entry);
+        }
+    }
+    Ok(refs)
+}
+
+/// Writes a bundle containing exactly the given refs and their history.
+pub fn create_bundle(graph: &GitGraph, path: &Path, refs: &[(String, Oid)]) -> Result<(), String> {
+    if refs.is_empty() {
+        return Err("Nothing selected to bundle".to_string());
+    }
+    // Bundle from a scratch repository holding only the selected refs
+    let scratch = ScratchRepository::new(graph.repository()?)?;
+    let repository = scratch.open()?;
+    for (name, oid) in refs {
+        repository
+            .reference(name, *oid, true, "")
+            .map_err(|err| err.message().to_string())?;
+    }
+    let output = Command::new("git")
+        .arg("--git-dir")
+        .arg(repository.path())
+        .args(["bundle", "create"])
+        .arg(path)
+        .arg("--all")
+        .output()
+        .map_err(|err| format!("Can't run git: {}", err))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        let message = String::from_utf8_lossy(&output.stderr);
+        Err(format!(
+            "Can't create bundle: {}",
+            message.lines().last().unwrap_or_default()
+        ))
+    }
+}
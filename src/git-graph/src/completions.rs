@@ -0,0 +1,446 @@
+//! Shell completions, generated from the command line definition.
+//!
+//! Static completions are generated by `clap_complete`. Arguments taking refs or
+//! branching models are completed dynamically, by calling the hidden subcommand
+//! `git-graph complete-refs` when completing.
+
+use clap::{Arg, Command};
+use clap_complete::Shell;
+use git2::{BranchType, Repository};
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// Value names (or ids, without value names) of arguments completed with the
+/// repository's branches and tags.
+const REF_VALUES: [&str; 7] = [
+    "commit",
+    "branch",
+    "branch|commit",
+    "ref|commit",
+    "ref1",
+    "ref2",
+    "base",
+];
+/// Value name of arguments completed with the available branching models.
+const MODEL_VALUE: &str = "model";
+
+/// Shells supported for completions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl FromStr for CompletionShell {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "bash" => Ok(CompletionShell::Bash),
+            "zsh" => Ok(CompletionShell::Zsh),
+            "fish" => Ok(CompletionShell::Fish),
+            _ => Err(format!(
+                "Unknown shell '{}'. Must be one of [bash|zsh|fish]",
+                str
+            )),
+        }
+    }
+}
+
+/// What an argument is completed with dynamically.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Candidates {
+    Refs,
+    Models,
+}
+
+impl Candidates {
+    /// The command printing the candidates, one per line.
+    fn command(&self, bin: &str) -> String {
+        match self {
+            Candidates::Refs => format!("{} complete-refs 2>/dev/null", bin),
+            Candidates::Models => format!("{} complete-refs --models 2>/dev/null", bin),
+        }
+    }
+}
+
+/// An argument completed dynamically.
+#[derive(Debug, PartialEq, Eq)]
+struct DynamicArg {
+    /// Subcommands leading to the argument, like `["model", "check"]`
+    path: Vec<String>,
+    /// Long and short flags of options, empty for positional arguments
+    flags: Vec<String>,
+    /// Zero-based index among positional arguments
+    position: Option<usize>,
+    /// Number of values, `None` for any number
+    values: Option<usize>,
+    candidates: Candidates,
+}
+
+/// Generates the completion script for a shell, for the binary named like the command.
+pub fn completions(command: &mut Command, shell: CompletionShell) -> String {
+    let bin = command.get_name().to_string();
+    let mut script = vec![];
+    let generator = match shell {
+        CompletionShell::Bash => Shell::Bash,
+        CompletionShell::Zsh => Shell::Zsh,
+        CompletionShell::Fish => Shell::Fish,
+    };
+    clap_complete::generate(generator, command, &bin, &mut script);
+    let mut script = String::from_utf8_lossy(&script).to_string();
+
+    let mut args = vec![];
+    let mut paths = vec![];
+    dynamic_args(command, &mut vec![], &mut args, &mut paths);
+    script.push('\n');
+    script.push_str(&match shell {
+        CompletionShell::Bash => bash_dynamic(&bin, &script, &args, &paths),
+        CompletionShell::Zsh => zsh_dynamic(&bin, &args, &paths),
+        CompletionShell::Fish => fish_dynamic(&bin, &args),
+    });
+    script
+}
+
+/// Collects the arguments completed dynamically, and all subcommand paths.
+fn dynamic_args(
+    command: &Command,
+    path: &mut Vec<String>,
+    args: &mut Vec<DynamicArg>,
+    paths: &mut Vec<Vec<String>>,
+) {
+    let candidates = |arg: &Arg| {
+        let names: Vec<&str> = match arg.get_value_names() {
+            Some(names) => names.iter().map(|name| name.as_str()).collect(),
+            None => vec![arg.get_id().as_str()],
+        };
+        if names.iter().any(|name| REF_VALUES.contains(name)) {
+            Some(Candidates::Refs)
+        } else if names.contains(&MODEL_VALUE) {
+            Some(Candidates::Models)
+        } else {
+            None
+        }
+    };
+    for (position, arg) in command.get_positionals().enumerate() {
+        if let Some(candidates) = candidates(arg) {
+            args.push(DynamicArg {
+                path: path.clone(),
+                flags: vec![],
+                position: Some(position),
+                values: arg
+                    .get_num_args()
+                    .filter(|range| range.min_values() == range.max_values())
+                    .map(|range| range.max_values()),
+                candidates,
+            });
+        }
+    }
+    for arg in command.get_opts() {
+        if let Some(candidates) = candidates(arg) {
+            let mut flags: Vec<String> = arg
+                .get_long_and_visible_aliases()
+                .into_iter()
+                .flatten()
+                .map(|long| format!("--{}", long))
+                .collect();
+            flags.extend(arg.get_short().map(|short| format!("-{}", short)));
+            args.push(DynamicArg {
+                path: path.clone(),
+                flags,
+                position: None,
+                values: arg.get_num_args().map(|range| range.max_values()),
+                candidates,
+            });
+        }
+    }
+    for sub in command
+        .get_subcommands()
+        .filter(|sub| !sub.is_hide_set() && sub.get_name() != "help")
+    {
+        path.push(sub.get_name().to_string());
+        paths.push(path.clone());
+        dynamic_args(sub, path, args, paths);
+        path.pop();
+    }
+}
+
+/// A subcommand path as matched in completion functions, like `" model check"`.
+fn path_str(path: &[String]) -> String {
+    path.iter().map(|sub| format!(" {}", sub)).collect()
+}
+
+/// The body of a completion function, shared by bash and zsh. Expects the current word
+/// in `$cur` and the words before it, without the binary, in `$words_before`, indexed
+/// from `first_index`. Sets `candidates` to the command printing the candidates,
+/// or leaves it empty.
+fn shell_dispatch(
+    bin: &str,
+    args: &[DynamicArg],
+    paths: &[Vec<String>],
+    first_index: usize,
+) -> String {
+    let mut out = String::new();
+    let sub_patterns: Vec<String> = paths
+        .iter()
+        .map(|path| format!("\"{}\"", path_str(path)))
+        .collect();
+    writeln!(out, "    local path=\"\" word position=0 candidates=\"\"").unwrap();
+    writeln!(out, "    for word in \"${{words_before[@]}}\"; do").unwrap();
+    writeln!(out, "        case \"$path $word\" in").unwrap();
+    if !sub_patterns.is_empty() {
+        writeln!(
+            out,
+            "            {}) path=\"$path $word\"; position=0 ;;",
+            sub_patterns.join("|")
+        )
+        .unwrap();
+    }
+    writeln!(out, "            *\" -\"*) ;;").unwrap();
+    writeln!(out, "            *) position=$((position + 1)) ;;").unwrap();
+    writeln!(out, "        esac").unwrap();
+    writeln!(out, "    done").unwrap();
+
+    // Options, by the option preceding the current word, or the word before for
+    // options taking two values
+    for distance in 1..=2 {
+        let cases: Vec<String> = args
+            .iter()
+            .filter(|arg| !arg.flags.is_empty() && arg.values.unwrap_or(usize::MAX) >= distance)
+            .map(|arg| {
+                let patterns: Vec<String> = arg
+                    .flags
+                    .iter()
+                    .map(|flag| format!("\"{}:{}\"", path_str(&arg.path), flag))
+                    .collect();
+                format!(
+                    "            {}) candidates=\"{}\" ;;",
+                    patterns.join("|"),
+                    arg.candidates.command(bin)
+                )
+            })
+            .collect();
+        if cases.is_empty() {
+            continue;
+        }
+        writeln!(
+            out,
+            "    [ -z \"$candidates\" ] && [ ${{#words_before[@]}} -ge {} ] && \
+             case \"$path:${{words_before[${{#words_before[@]}}{:+}]}}\" in",
+            distance,
+            first_index as isize - distance as isize
+        )
+        .unwrap();
+        for case in cases {
+            writeln!(out, "{}", case).unwrap();
+        }
+        writeln!(out, "        esac").unwrap();
+    }
+
+    // Positional arguments, by subcommand path and position
+    let mut cases: Vec<(String, Vec<String>)> = vec![];
+    for arg in args {
+        let Some(position) = arg.position else {
+            continue;
+        };
+        let condition = match arg.values {
+            Some(_) => format!("[ $position -eq {} ]", position),
+            None => format!("[ $position -ge {} ]", position),
+        };
+        let assignment = format!(
+            "{} && candidates=\"{}\"",
+            condition,
+            arg.candidates.command(bin)
+        );
+        let path = format!("\"{}\"", path_str(&arg.path));
+        match cases.iter_mut().find(|(p, _)| *p == path) {
+            Some((_, assignments)) => assignments.push(assignment),
+            None => cases.push((path, vec![assignment])),
+        }
+    }
+    let cases: Vec<String> = cases
+        .into_iter()
+        .map(|(path, assignments)| format!("            {}) {} ;;", path, assignments.join("; ")))
+        .collect();
+    if !cases.is_empty() {
+        writeln!(
+            out,
+            "    [ -z \"$candidates\" ] && case \"$cur\" in -*) ;; *) case \"$path\" in"
+        )
+        .unwrap();
+        for case in cases {
+            writeln!(out, "{}", case).unwrap();
+        }
+        writeln!(out, "        esac ;; esac").unwrap();
+    }
+    out
+}
+
+fn bash_dynamic(bin: &str, script: &str, args: &[DynamicArg], paths: &[Vec<String>]) -> String {
+    // The name of the generated function differs between versions of clap_complete
+    let function = script
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("complete -F "))
+        .and_then(|line| line.split_whitespace().next())
+        .map_or_else(|| format!("_{}", bin), |name| name.to_string());
+    let mut out = String::new();
+    writeln!(out, "{}_dynamic() {{", function.replace('-', "_")).unwrap();
+    writeln!(out, "    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\"").unwrap();
+    writeln!(
+        out,
+        "    local words_before=(\"${{COMP_WORDS[@]:1:COMP_CWORD-1}}\")"
+    )
+    .unwrap();
+    out.push_str(&shell_dispatch(bin, args, paths, 0));
+    writeln!(out, "    if [ -n \"$candidates\" ]; then").unwrap();
+    writeln!(
+        out,
+        "        COMPREPLY=($(compgen -W \"$(eval \"$candidates\")\" -- \"$cur\"))"
+    )
+    .unwrap();
+    writeln!(out, "        [ ${{#COMPREPLY[@]}} -gt 0 ] && return 0").unwrap();
+    writeln!(out, "    fi").unwrap();
+    writeln!(out, "    {} \"$@\"", function).unwrap();
+    writeln!(out, "}}").unwrap();
+    writeln!(
+        out,
+        "complete -F {}_dynamic -o bashdefault -o default {}",
+        function.replace('-', "_"),
+        bin
+    )
+    .unwrap();
+    out
+}
+
+fn zsh_dynamic(bin: &str, args: &[DynamicArg], paths: &[Vec<String>]) -> String {
+    let function = format!("_{}", bin);
+    let mut out = String::new();
+    writeln!(out, "{}_dynamic() {{", function.replace('-', "_")).unwrap();
+    writeln!(out, "    local cur=\"${{words[CURRENT]}}\"").unwrap();
+    writeln!(out, "    local -a words_before").unwrap();
+    writeln!(out, "    words_before=(\"${{(@)words[2,CURRENT-1]}}\")").unwrap();
+    out.push_str(&shell_dispatch(bin, args, paths, 1));
+    writeln!(out, "    if [ -n \"$candidates\" ]; then").unwrap();
+    writeln!(out, "        local -a refs").unwrap();
+    writeln!(out, "        refs=(${{(f)\"$(eval \"$candidates\")\"}})").unwrap();
+    writeln!(
+        out,
+        "        [ ${{#refs[@]}} -gt 0 ] && compadd -a refs && return 0"
+    )
+    .unwrap();
+    writeln!(out, "    fi").unwrap();
+    writeln!(out, "    {} \"$@\"", function).unwrap();
+    writeln!(out, "}}").unwrap();
+    writeln!(
+        out,
+        "(( $+functions[compdef] )) && compdef {}_dynamic {}",
+        function.replace('-', "_"),
+        bin
+    )
+    .unwrap();
+    out
+}
+
+fn fish_dynamic(bin: &str, args: &[DynamicArg]) -> String {
+    let mut out = String::new();
+    for arg in args {
+        let condition = match arg.path.last() {
+            Some(sub) => format!(" -n '__fish_seen_subcommand_from {}'", sub),
+            None => " -n '__fish_use_subcommand'".to_string(),
+        };
+        let candidates = format!(" -a '({})'", arg.candidates.command(bin));
+        if arg.flags.is_empty() {
+            writeln!(out, "complete -c {}{}{}", bin, condition, candidates).unwrap();
+        }
+        for flag in &arg.flags {
+            let flag = match flag.strip_prefix("--") {
+                Some(long) => format!(" -l {}", long),
+                None => format!(" -s {}", &flag[1..]),
+            };
+            writeln!(
+                out,
+                "complete -c {}{}{} -r -f{}",
+                bin, condition, flag, candidates
+            )
+            .unwrap();
+        }
+    }
+    out
+}
+
+/// The short names of the repository's local and remote branches and tags, for completion.
+pub fn ref_names(repository: &Repository) -> Result<Vec<String>, git2::Error> {
+    let mut names = vec![];
+    for branch in repository.branches(None)? {
+        let (branch, kind) = branch?;
+        if let Some(name) = branch.name()? {
+            if kind == BranchType::Local || !name.ends_with("/HEAD") {
+                names.push(name.to_string());
+            }
+        }
+    }
+    for name in repository.tag_names(None)?.iter().flatten() {
+        names.push(name.to_string());
+    }
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dynamic_args, Candidates, DynamicArg};
+    use clap::{Arg, Command};
+
+    #[test]
+    fn dynamic_args_by_value_name() {
+        let mut command = Command::new("git-graph")
+            .arg(Arg::new("focus").long("focus").value_name("commit"))
+            .arg(
+                Arg::new("model")
+                    .long("model")
+                    .short('m')
+                    .value_name("model"),
+            )
+            .arg(Arg::new("path").long("path").value_name("dir"))
+            .subcommand(
+                Command::new("bundle").arg(Arg::new("file").index(1)).arg(
+                    Arg::new("selection")
+                        .index(2)
+                        .num_args(1..)
+                        .value_name("ref|commit"),
+                ),
+            );
+        command.build();
+        let (mut args, mut paths) = (vec![], vec![]);
+        dynamic_args(&command, &mut vec![], &mut args, &mut paths);
+        assert_eq!(paths, vec![vec!["bundle".to_string()]]);
+        assert_eq!(
+            args,
+            vec![
+                DynamicArg {
+                    path: vec![],
+                    flags: vec!["--focus".to_string()],
+                    position: None,
+                    values: Some(1),
+                    candidates: Candidates::Refs,
+                },
+                DynamicArg {
+                    path: vec![],
+                    flags: vec!["--model".to_string(), "-m".to_string()],
+                    position: None,
+                    values: Some(1),
+                    candidates: Candidates::Models,
+                },
+                DynamicArg {
+                    path: vec!["bundle".to_string()],
+                    flags: vec![],
+                    position: Some(1),
+                    values: None,
+                    candidates: Candidates::Refs,
+                },
+            ]
+        );
+    }
+}
@@ -6,10 +6,55 @@
 //! The [branching models][BranchSettingsDef] themselves are defined in
 //! module [settings][super::settings]
 
-use crate::settings::{BranchSettingsDef, RepoSettings};
-use git2::Repository;
+use crate::settings::{
+    Actions, Aliases, BranchSettingsDef, CommitMap, KeyBindings, LintRules, LintRulesDef,
+    RepoSettings, ReviewStatus, Reviews, DEFAULT_WIP_PATTERNS,
+};
+use git2::{Oid, Repository};
+use itertools::Itertools;
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// File formats of branching models, detected by file extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModelFormat {
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl ModelFormat {
+    /// Supported file extensions, in order of precedence for models of the same name.
+    pub const EXTENSIONS: [&'static str; 4] = ["toml", "yaml", "yml", "json"];
+
+    /// The format of a model file, from its extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "toml" => Some(ModelFormat::Toml),
+            "yaml" | "yml" => Some(ModelFormat::Yaml),
+            "json" => Some(ModelFormat::Json),
+            _ => None,
+        }
+    }
+
+    fn parse(&self, source: &str) -> Result<BranchSettingsDef, String> {
+        match self {
+            ModelFormat::Toml => toml::from_str(source).map_err(|err| err.to_string()),
+            ModelFormat::Yaml => serde_yaml::from_str(source).map_err(|err| err.to_string()),
+            ModelFormat::Json => serde_json::from_str(source).map_err(|err| err.to_string()),
+        }
+    }
+}
+
+/// Finds the file of a model in `APP_DATA/git-graph/models`, in any supported format.
+fn find_model_file<P: AsRef<Path>>(model: &str, app_model_path: &P) -> Option<PathBuf> {
+    ModelFormat::EXTENSIONS
+        .iter()
+        .map(|ext| app_model_path.as_ref().join(format!("{}.{}", model, ext)))
+        .find(|path| path.is_file())
+}
 
 /// Creates the directory `APP_DATA/git-graph/models` if it does not exist,
 /// and writes the files for built-in branching models there.
@@ -40,37 +85,267 @@ pub fn get_available_models<P: AsRef<Path>>(app_model_path: &P) -> Result<Vec<St
         .map_err(|err| err.to_string())?
         .filter_map(|e| match e {
             Ok(e) => {
-                if let (Some(name), Some(ext)) = (e.path().file_name(), e.path().extension()) {
-                    if ext == "toml" {
-                        name.to_str()
-                            .map(|name| (name[..(name.len() - 5)]).to_string())
-                    } else {
-                        None
-                    }
+                if let (Some(name), Some(_)) =
+                    (e.path().file_stem(), ModelFormat::from_path(&e.path()))
+                {
+                    name.to_str().map(|name| name.to_string())
                 } else {
                     None
                 }
             }
             Err(_) => None,
         })
+        .unique()
         .collect::<Vec<_>>();
 
     Ok(models)
 }
 
+/// Get models available in `APP_DATA/git-graph/models`, with their descriptions.
+///
+/// Models without a description fall back to the description of the
+/// built-in model of the same name. Invalid models are reported as such.
+pub fn describe_models<P: AsRef<Path> + AsRef<OsStr>>(
+    app_model_path: &P,
+) -> Result<Vec<(String, String)>, String> {
+    let mut models = get_available_models(app_model_path)?;
+    models.sort();
+    Ok(models
+        .into_iter()
+        .map(|name| {
+            let description = match read_model(&name, app_model_path) {
+                Ok(model) if !model.description.is_empty() => model.description,
+                Ok(_) => BranchSettingsDef::preset(&name)
+                    .map(|model| model.description)
+                    .unwrap_or_default(),
+                Err(_) => format!("(invalid, see 'git-graph model check {}')", name),
+            };
+            (name, description)
+        })
+        .collect())
+}
+
+/// Writes a new, commented model file `<name>.toml` to `APP_DATA/git-graph/models`,
+/// as a copy of model `from`. Returns the path of the new file.
+pub fn init_model<P: AsRef<Path> + AsRef<OsStr>>(
+    name: &str,
+    from: &str,
+    app_model_path: &P,
+) -> Result<PathBuf, String> {
+    if let Some(existing) = find_model_file(name, app_model_path) {
+        return Err(format!(
+            "ERROR: Branching model {} already exists",
+            existing.display()
+        ));
+    }
+
+    let mut model = match read_model(from, app_model_path) {
+        Ok(model) => model,
+        Err(err) => BranchSettingsDef::preset(from).ok_or(err)?,
+    };
+    model.description = format!("Custom model, based on '{}'", from);
+
+    let mut model_file = PathBuf::from(&app_model_path);
+    model_file.push(format!("{}.toml", name));
+    std::fs::write(&model_file, commented_model(&model)?).map_err(|err| err.to_string())?;
+    Ok(model_file)
+}
+
+/// Serializes a model to TOML, with comments explaining each section.
+fn commented_model(model: &BranchSettingsDef) -> Result<String, String> {
+    let comments = [
+        (
+            "description",
+            "# Short description of the model, shown by 'git-graph model list'.",
+        ),
+        (
+            "persistence",
+            "# RegEx patterns for branch groups by persistence, from most persistent\n\
+             # to most short-lived branches. This is used to back-trace branches.\n\
+             # Branches not matching any pattern are assumed least persistent.",
+        ),
+        (
+            "order",
+            "# RegEx patterns for visual ordering of branches, from left to right.\n\
+             # Branches not matching any pattern are displayed further to the right.",
+        ),
+        (
+            "order_names",
+            "# Optional names of the order groups, shown as headers in SVG output.\n\
+             # A name following the last group applies to all other branches.",
+        ),
+        (
+            "type_names",
+            "# Optional names of the branch types matched by the persistence patterns,\n\
+             # in the same order, shown as badges with 'git-graph --type-badges'.",
+        ),
+        (
+            "follow_second_parents",
+            "# Optional RegEx patterns of branches that may follow second parents across\n\
+             # foxtrot merges, i.e. merges of the branch into a feature branch that was\n\
+             # then fast-forwarded into the branch.",
+        ),
+        (
+            "[terminal_colors]",
+            "# Colors of branches in terminal output, as pairs of a RegEx pattern and\n\
+             # a list of colors used alternating. Colors are names like 'bright_blue'\n\
+             # or numbers of the 256-color palette.\n\
+             # `unknown` lists the colors for branches not matching any pattern.",
+        ),
+        (
+            "[svg_colors]",
+            "# Colors of branches in SVG output. Same structure as terminal_colors,\n\
+             # with SVG color names or hex codes like '#ff8800'.",
+        ),
+    ];
+
+    let str = toml::to_string_pretty(model).map_err(|err| err.to_string())?;
+    let mut out = String::new();
+    for line in str.lines() {
+        let key = line.split(" =").next().unwrap_or("");
+        if let Some((_, comment)) = comments.iter().find(|(name, _)| *name == key) {
+            if !out.is_empty() && !out.ends_with("\n\n") {
+                out.push('\n');
+            }
+            out.push_str(comment);
+            out.push('\n');
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
 /// Get the currently set branching model for a repo.
 pub fn get_model_name(repository: &Repository, file_name: &str) -> Result<Option<String>, String> {
     let mut config_path = PathBuf::from(repository.path());
     config_path.push(file_name);
 
-    if config_path.exists() {
-        let repo_config: RepoSettings =
-            toml::from_str(&std::fs::read_to_string(config_path).map_err(|err| err.to_string())?)
-                .map_err(|err| err.to_string())?;
+    Ok(read_repo_settings(&config_path)?.and_then(|repo_config| repo_config.model))
+}
+
+/// Reads a repository settings file. Returns `None` if the file does not exist.
+fn read_repo_settings(path: &Path) -> Result<Option<RepoSettings>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let source = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    toml::from_str(&source)
+        .map(Some)
+        .map_err(|err| format!("Error in repository settings {}: {}", path.display(), err))
+}
+
+/// Reads the saved filter expressions by name: from the shared settings file in the root
+/// of the working tree, committed for the team, overridden by the repo's own settings file.
+pub fn read_filters(
+    repository: &Repository,
+    repo_config_file: &str,
+    shared_config_file: &str,
+) -> Result<BTreeMap<String, String>, String> {
+    let shared = repository.workdir().map(|dir| dir.join(shared_config_file));
+    let own = repository.path().join(repo_config_file);
+    let mut filters = BTreeMap::new();
+    for path in shared.into_iter().chain(std::iter::once(own)) {
+        if let Some(repo_config) = read_repo_settings(&path)? {
+            filters.extend(repo_config.filters);
+        }
+    }
+    Ok(filters)
+}
+
+/// Reads the globs of protected branches: from the shared settings file in the root of
+/// the working tree, e.g. kept in sync with the forge's protection rules, and from the
+/// repo's own settings file.
+pub fn read_protected_branches(
+    repository: &Repository,
+    repo_config_file: &str,
+    shared_config_file: &str,
+) -> Result<Vec<String>, String> {
+    let shared = repository.workdir().map(|dir| dir.join(shared_config_file));
+    let own = repository.path().join(repo_config_file);
+    let mut protected = vec![];
+    for path in shared.into_iter().chain(std::iter::once(own)) {
+        if let Some(repo_config) = read_repo_settings(&path)? {
+            protected.extend(repo_config.protected);
+        }
+    }
+    Ok(protected)
+}
+
+/// Reads the patterns for the tip commit subjects of work-in-progress branches: the
+/// defaults, replaced by those of the shared settings file in the root of the working
+/// tree, replaced by those of the repo's own settings file.
+pub fn read_wip_patterns(
+    repository: &Repository,
+    repo_config_file: &str,
+    shared_config_file: &str,
+) -> Result<Vec<String>, String> {
+    let shared = repository.workdir().map(|dir| dir.join(shared_config_file));
+    let own = repository.path().join(repo_config_file);
+    let mut patterns = DEFAULT_WIP_PATTERNS.map(String::from).to_vec();
+    for path in shared.into_iter().chain(std::iter::once(own)) {
+        if let Some(wip) = read_repo_settings(&path)?.and_then(|settings| settings.wip) {
+            patterns = wip;
+        }
+    }
+    Ok(patterns)
+}
+
+/// Resolves the notes refs to show, like `git log --notes`: short names like `build`
+/// expand to `refs/notes/build`. With `defaults`, they follow the default notes ref
+/// (`core.notesRef`, or `refs/notes/commits`) and the refs of `notes.displayRef`.
+pub fn notes_refs(
+    repository: &Repository,
+    names: &[String],
+    defaults: bool,
+) -> Result<Vec<String>, String> {
+    let to_string = |err: git2::Error| err.message().to_string();
+    let mut candidates = vec![];
+    if defaults {
+        candidates.push(repository.note_default_ref().map_err(to_string)?);
+        let mut display_refs = vec![];
+        repository
+            .config()
+            .and_then(|config| {
+                config
+                    .multivar("notes.displayRef", None)?
+                    .for_each(|entry| display_refs.extend(entry.value().map(expand_notes_ref)))
+            })
+            .map_err(to_string)?;
+        for display_ref in display_refs {
+            // Display refs may be globs, like `refs/notes/*`
+            if display_ref.contains('*') {
+                for name in repository
+                    .references_glob(&display_ref)
+                    .map_err(to_string)?
+                    .names()
+                {
+                    candidates.push(name.map_err(to_string)?.to_string());
+                }
+            } else {
+                candidates.push(display_ref);
+            }
+        }
+    }
+    candidates.extend(names.iter().map(|name| expand_notes_ref(name)));
+
+    let mut refs = vec![];
+    for candidate in candidates {
+        if !refs.contains(&candidate) {
+            refs.push(candidate);
+        }
+    }
+    Ok(refs)
+}
 
-        Ok(Some(repo_config.model))
+/// Expands a notes ref like git: `build` and `notes/build` to `refs/notes/build`.
+fn expand_notes_ref(name: &str) -> String {
+    if name.starts_with("refs/") {
+        name.to_string()
+    } else if name.starts_with("notes/") {
+        format!("refs/{}", name)
     } else {
-        Ok(None)
+        format!("refs/notes/{}", name)
     }
 }
 
@@ -84,23 +359,36 @@ pub fn get_model<P: AsRef<Path> + AsRef<OsStr>>(
 ) -> Result<BranchSettingsDef, String> {
     match model {
         Some(model) => read_model(model, app_model_path),
-        None => {
-            let mut config_path = PathBuf::from(repository.path());
-            config_path.push(repo_config_file);
+        None => match get_model_name(repository, repo_config_file)? {
+            Some(model) => read_model(&model, app_model_path),
+            None => Ok(read_model("git-flow", app_model_path)
+                .unwrap_or_else(|_| BranchSettingsDef::git_flow())),
+        },
+    }
+}
 
-            if config_path.exists() {
-                let repo_config: RepoSettings = toml::from_str(
-                    &std::fs::read_to_string(config_path).map_err(|err| err.to_string())?,
-                )
-                .map_err(|err| err.to_string())?;
+/// Get the files determining the branching model for a repo, as resolved by [get_model]:
+/// the model file (if any) and the repo's config file.
+pub fn get_model_files<P: AsRef<Path> + AsRef<OsStr>>(
+    repository: &Repository,
+    model: Option<&str>,
+    repo_config_file: &str,
+    app_model_path: &P,
+) -> Vec<PathBuf> {
+    let mut config_path = PathBuf::from(repository.path());
+    config_path.push(repo_config_file);
 
-                read_model(&repo_config.model, app_model_path)
-            } else {
-                Ok(read_model("git-flow", app_model_path)
-                    .unwrap_or_else(|_| BranchSettingsDef::git_flow()))
-            }
-        }
-    }
+    let model = match model {
+        Some(model) => Some(model.to_string()),
+        None => get_model_name(repository, repo_config_file)
+            .ok()
+            .map(|name| name.unwrap_or_else(|| "git-flow".to_string())),
+    };
+    model
+        .and_then(|model| find_model_file(&model, app_model_path))
+        .into_iter()
+        .chain(std::iter::once(config_path))
+        .collect()
 }
 
 /// Read a branching model file.
@@ -108,25 +396,102 @@ fn read_model<P: AsRef<Path> + AsRef<OsStr>>(
     model: &str,
     app_model_path: &P,
 ) -> Result<BranchSettingsDef, String> {
-    let mut model_file = PathBuf::from(&app_model_path);
-    model_file.push(format!("{}.toml", model));
+    match find_model_file(model, app_model_path) {
+        Some(model_file) => read_model_file(&model_file),
+        None => Err(unknown_model(model, app_model_path)?),
+    }
+}
 
-    if model_file.exists() {
-        toml::from_str::<BranchSettingsDef>(
-            &std::fs::read_to_string(model_file).map_err(|err| err.to_string())?,
-        )
-        .map_err(|err| err.to_string())
+/// The error message for a model not found, listing the available models.
+fn unknown_model<P: AsRef<Path> + AsRef<OsStr>>(
+    model: &str,
+    app_model_path: &P,
+) -> Result<String, String> {
+    let models = get_available_models(&app_model_path)?;
+    let path: &Path = app_model_path.as_ref();
+    Ok(format!(
+        "ERROR: No branching model named '{}' found in {}\n       Available models are: {}",
+        model,
+        path.display(),
+        itertools::join(models, ", ")
+    ))
+}
+/// Check a branching model, given by name or as path to a model file.
+/// Returns the path of the checked file.
+pub fn check_model_file<P: AsRef<Path> + AsRef<OsStr>>(
+    model: &str,
+    app_model_path: &P,
+) -> Result<PathBuf, String> {
+    let model_file = PathBuf::from(model);
+    let model_file = if model_file.is_file() {
+        model_file
+    } else if let Some(model_file) = find_model_file(model, app_model_path) {
+        model_file
     } else {
-        let models = get_available_models(&app_model_path)?;
-        let path: &Path = app_model_path.as_ref();
-        Err(format!(
-            "ERROR: No branching model named '{}' found in {}\n       Available models are: {}",
-            model,
-            path.display(),
-            itertools::join(models, ", ")
-        ))
+        return Err(unknown_model(model, app_model_path)?);
+    };
+    read_model_file(&model_file)?;
+    Ok(model_file)
+}
+
+/// Read and validate a branching model file. The format is detected by extension,
+/// falling back to TOML.
+pub fn read_model_file(path: &Path) -> Result<BranchSettingsDef, String> {
+    let source = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let format = ModelFormat::from_path(path).unwrap_or(ModelFormat::Toml);
+    check_model(&source, format)
+        .map_err(|err| format!("ERROR: Invalid branching model {}\n{}", path.display(), err))
+}
+
+/// Parse and validate the source of a branching model.
+/// On failure, returns one diagnostic per line, pointing at the offending line and key.
+pub fn check_model(source: &str, format: ModelFormat) -> Result<BranchSettingsDef, String> {
+    let model = format.parse(source).map_err(|err| format!("  {}", err))?;
+
+    let issues = model.validate();
+    if issues.is_empty() {
+        return Ok(model);
     }
+    let mut diagnostics = issues
+        .iter()
+        .map(|issue| {
+            // Search for the value from the line defining the top-level key
+            let root = issue.key.split(['.', '[']).next().unwrap_or("");
+            let start = source
+                .lines()
+                .position(|line| {
+                    let line = line.trim();
+                    line.starts_with(&format!("[{}]", root))
+                        || line
+                            .split(['=', ':'])
+                            .next()
+                            .map(|key| key.trim().trim_matches('"'))
+                            == Some(root)
+                })
+                .unwrap_or(0);
+            let line = if issue.value.is_empty() {
+                Some(start + 1)
+            } else {
+                source
+                    .lines()
+                    .skip(start)
+                    .position(|line| line.contains(&issue.value))
+                    .map(|idx| start + idx + 1)
+            };
+            let text = match line {
+                Some(line) => format!("  line {}: {}: {}", line, issue.key, issue.message),
+                None => format!("  {}: {}", issue.key, issue.message),
+            };
+            (line, text)
+        })
+        .collect::<Vec<_>>();
+    diagnostics.sort_by_key(|(line, _)| *line);
+    Err(itertools::join(
+        diagnostics.into_iter().map(|(_, text)| text),
+        "\n",
+    ))
 }
+
 /// Permanently sets the branching model for a repository
 pub fn set_model<P: AsRef<Path>>(
     repository: &Repository,
@@ -148,12 +513,521 @@ pub fn set_model<P: AsRef<Path>>(
     let mut config_path = PathBuf::from(repository.path());
     config_path.push(repo_config_file);
 
-    let config = RepoSettings {
-        model: model.to_string(),
-    };
+    // Saved filters and protected branches are kept
+    let mut config = read_repo_settings(&config_path)?.unwrap_or_default();
+    config.model = Some(model.to_string());
 
     let str = toml::to_string_pretty(&config).map_err(|err| err.to_string())?;
     std::fs::write(&config_path, str).map_err(|err| err.to_string())?;
 
     Ok(())
 }
+
+/// Reads the pager's key bindings from a TOML file mapping action names to lists of keys.
+/// Actions not in the file keep their default keys. Returns the defaults if the file does not exist.
+pub fn read_key_bindings(path: &Path) -> Result<KeyBindings, String> {
+    if !path.exists() {
+        return Ok(KeyBindings::default());
+    }
+    let source = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let bindings: HashMap<String, Vec<String>> = toml::from_str(&source)
+        .map_err(|err| format!("Error in key bindings {}: {}", path.display(), err))?;
+    KeyBindings::from(bindings)
+        .map_err(|err| format!("Error in key bindings {}: {}", path.display(), err))
+}
+
+/// Reads external actions from a TOML file of action names and command templates.
+/// Returns no actions if the file does not exist.
+pub fn read_actions(path: &Path) -> Result<Actions, String> {
+    if !path.exists() {
+        return Ok(Actions::default());
+    }
+    let source = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let commands: BTreeMap<String, String> = toml::from_str(&source)
+        .map_err(|err| format!("Error in actions {}: {}", path.display(), err))?;
+    Actions::from(commands).map_err(|err| format!("Error in actions {}: {}", path.display(), err))
+}
+
+/// Reads user-defined subcommand aliases from a TOML file of argument strings by name.
+/// Returns no aliases if the file does not exist.
+pub fn read_aliases(path: &Path, subcommands: &[&str]) -> Result<Aliases, String> {
+    if !path.exists() {
+        return Ok(Aliases::default());
+    }
+    let source = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let aliases: BTreeMap<String, String> = toml::from_str(&source)
+        .map_err(|err| format!("Error in aliases {}: {}", path.display(), err))?;
+    Aliases::from(aliases, subcommands)
+        .map_err(|err| format!("Error in aliases {}: {}", path.display(), err))
+}
+
+/// Reads the rules of the commit message lint report from a TOML file.
+/// Rules not in the file keep their defaults. Returns the defaults if the file does not exist.
+pub fn read_lint_rules(path: &Path) -> Result<LintRules, String> {
+    let def = if path.exists() {
+        let source = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        toml::from_str::<LintRulesDef>(&source)
+            .map_err(|err| format!("Error in lint rules {}: {}", path.display(), err))?
+    } else {
+        LintRulesDef::default()
+    };
+    LintRules::from(def).map_err(|err| format!("Error in lint rules {}: {}", path.display(), err))
+}
+
+/// Reads a list of commits, one per line, like the output of `git rev-list`. The first
+/// field of each line is resolved as a revision, so abbreviated ids work, and further
+/// fields (like the parents listed by `git rev-list --parents`) are ignored.
+pub fn read_commit_list(repository: &Repository, source: &str) -> Result<Vec<Oid>, String> {
+    let mut oids = vec![];
+    for (idx, line) in source.lines().enumerate() {
+        let Some(spec) = line.split_whitespace().next() else {
+            continue;
+        };
+        let commit = repository
+            .revparse_single(spec)
+            .and_then(|object| object.peel_to_commit())
+            .map_err(|err| {
+                format!(
+                    "Error in commit list, line {}: '{}': {}",
+                    idx + 1,
+                    spec,
+                    err.message()
+                )
+            })?;
+        oids.push(commit.id());
+    }
+    Ok(oids)
+}
+
+/// Reads a commit map of a history rewrite: one pair of old and new commit ids per line,
+/// separated by whitespace. A header line (like `old new` of git filter-repo) is skipped,
+/// as are further fields (like in the `rewritten-list` of git rebase).
+pub fn read_commit_map(path: &Path) -> Result<CommitMap, String> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|err| format!("Can't read commit map {}: {}", path.display(), err))?;
+    // Full ids only, as abbreviated ids are padded with zeros
+    let parse = |field: &str| {
+        Some(field)
+            .filter(|f| f.len() >= 40)
+            .and_then(|f| Oid::from_str(f).ok())
+    };
+    let mut pairs = vec![];
+    for (idx, line) in source.lines().enumerate() {
+        let mut fields = line.split_whitespace();
+        let pair = match (fields.next(), fields.next()) {
+            (None, _) => continue,
+            (Some(old), Some(new)) => parse(old).zip(parse(new)),
+            (Some(_), None) => None,
+        };
+        match pair {
+            Some(pair) => pairs.push(pair),
+            None if idx == 0 => {}
+            None => {
+                return Err(format!(
+                    "Error in commit map {}, line {}: expected '<old-id> <new-id>', found '{}'",
+                    path.display(),
+                    idx + 1,
+                    line
+                ))
+            }
+        }
+    }
+    Ok(CommitMap::from_pairs(pairs))
+}
+
+/// Reads the review status of commits from a file of `<commit-id> [reviewed|unreviewed]`
+/// lines. The status defaults to `reviewed`, so a plain list of reviewed commits can be
+/// given. Text after the status, like the reviewer, is ignored, as are empty lines and
+/// comments starting with `#`.
+pub fn read_reviews(path: &Path) -> Result<Reviews, String> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|err| format!("Can't read reviews {}: {}", path.display(), err))?;
+    let mut pairs = vec![];
+    for (idx, line) in source.lines().enumerate() {
+        let mut fields = line.split_whitespace();
+        let Some(id) = fields.next().filter(|id| !id.starts_with('#')) else {
+            continue;
+        };
+        // Full ids only, as abbreviated ids are padded with zeros
+        let oid = Some(id)
+            .filter(|id| id.len() >= 40)
+            .and_then(|id| Oid::from_str(id).ok())
+            .ok_or_else(|| {
+                format!(
+                    "Error in reviews {}, line {}: expected a full commit id, found '{}'",
+                    path.display(),
+                    idx + 1,
+                    id
+                )
+            })?;
+        let status = match fields.next() {
+            Some(status) => ReviewStatus::from_str(status).map_err(|err| {
+                format!(
+                    "Error in reviews {}, line {}: {}",
+                    path.display(),
+                    idx + 1,
+                    err
+                )
+            })?,
+            None => ReviewStatus::Reviewed,
+        };
+        pairs.push((oid, status));
+    }
+    Ok(Reviews::from_pairs(pairs))
+}
+
+/// Reads the commits bookmarked for a repository, one commit id per line.
+/// Returns no bookmarks if the file does not exist.
+pub fn read_bookmarks(repository: &Repository, file_name: &str) -> Result<Vec<Oid>, String> {
+    let path = repository.path().join(file_name);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    std::fs::read_to_string(&path)
+        .map_err(|err| err.to_string())?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            Oid::from_str(line)
+                .map_err(|_| format!("Invalid bookmark '{}' in {}", line, path.display()))
+        })
+        .collect()
+}
+
+/// Permanently stores the commits bookmarked for a repository.
+/// Removes the file if there are no bookmarks.
+pub fn write_bookmarks(
+    repository: &Repository,
+    file_name: &str,
+    bookmarks: &[Oid],
+) -> Result<(), String> {
+    let path = repository.path().join(file_name);
+    if bookmarks.is_empty() {
+        if path.exists() {
+            std::fs::remove_file(&path).map_err(|err| err.to_string())?;
+        }
+        return Ok(());
+    }
+    let str: String = bookmarks.iter().map(|oid| format!("{}\n", oid)).collect();
+    std::fs::write(&path, str).map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_model, check_model_file, get_model_name, notes_refs, read_actions, read_aliases,
+        read_bookmarks, read_filters, read_key_bindings, read_protected_branches, read_reviews,
+        read_wip_patterns, write_bookmarks, ModelFormat,
+    };
+    use crate::settings::{BranchSettings, BranchSettingsDef, PagerAction, ReviewStatus, Theme};
+    use crate::testing::{TempDir, TestRepo};
+
+    #[test]
+    fn check_model_diagnostics() {
+        let source = toml::to_string_pretty(&BranchSettingsDef::git_flow()).unwrap();
+        assert!(check_model(&source, ModelFormat::Toml).is_ok());
+
+        let broken = source
+            .replace("'^(develop|dev)$',\n]", "'^(develop|dev$',\n]")
+            .replace("'bright_blue'", "'brite_blue'");
+        let err = check_model(&broken, ModelFormat::Toml).err().unwrap();
+        let lines: Vec<_> = err.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            "  line 13: order[2]: invalid regex: unclosed group"
+        );
+        assert!(lines[1].contains("terminal_colors.matches[0]: Color brite_blue not found"));
+
+        let unknown = format!("colour = 'red'\n{}", source);
+        assert!(check_model(&unknown, ModelFormat::Toml)
+            .err()
+            .unwrap()
+            .contains("unknown field `colour`"));
+    }
+
+    #[test]
+    fn check_model_formats() {
+        let model = BranchSettingsDef::git_flow();
+        let yaml = serde_yaml::to_string(&model).unwrap();
+        let json = serde_json::to_string_pretty(&model).unwrap();
+        assert!(check_model(&yaml, ModelFormat::Yaml).is_ok());
+        assert!(check_model(&json, ModelFormat::Json).is_ok());
+
+        let broken = json.replace("\"^(develop|dev)$\"\n  ]", "\"^(develop|dev$\"\n  ]");
+        let err = check_model(&broken, ModelFormat::Json).err().unwrap();
+        assert!(err.starts_with("  line 14: order[2]: invalid regex"));
+    }
+
+    #[test]
+    fn check_model_files_by_name() {
+        let dir = TempDir::new("git-graph-check-models");
+        let source = toml::to_string(&BranchSettingsDef::simple()).unwrap();
+        std::fs::write(dir.path().join("simple.toml"), source).unwrap();
+
+        let path = check_model_file("simple", &dir.path()).unwrap();
+        assert_eq!(path, dir.path().join("simple.toml"));
+        let err = check_model_file("git-flow", &dir.path()).err().unwrap();
+        assert!(err.starts_with("ERROR: No branching model named 'git-flow' found in"));
+        assert!(err.ends_with("Available models are: simple"));
+    }
+
+    #[test]
+    fn themed_models_are_valid() {
+        for theme in [Theme::Deuteranopia, Theme::Protanopia, Theme::Tritanopia] {
+            for name in ["git-flow", "simple", "none"] {
+                let model = BranchSettingsDef::preset(name).unwrap().with_theme(theme);
+                assert!(model.validate().is_empty());
+                let source = toml::to_string(&model).unwrap();
+                assert!(!source.contains("\"red\"") && !source.contains("\"blue\""));
+            }
+        }
+        let model = BranchSettingsDef::git_flow().with_theme(Theme::Default);
+        assert!(toml::to_string(&model).unwrap().contains("\"bright_blue\""));
+    }
+
+    #[test]
+    fn type_names_default_to_pattern_words() {
+        let mut model = BranchSettingsDef::git_flow();
+        model.type_names.truncate(2);
+        let branches = BranchSettings::from(model).unwrap();
+        assert_eq!(
+            branches.type_names,
+            ["main", "develop", "feature", "release", "hotfix", "bugfix"]
+        );
+        let mut model = BranchSettingsDef::git_flow();
+        model.type_names.clear();
+        let branches = BranchSettings::from(model).unwrap();
+        assert_eq!(branches.type_names[0], "master");
+    }
+
+    #[test]
+    fn read_key_bindings_overrides_defaults() {
+        let dir = TempDir::new("git-graph-keys");
+        let path = dir.path().join("keys.toml");
+        std::fs::write(&path, "down = ['Ctrl-n']\nquit = ['x']\n").unwrap();
+        let keys = read_key_bindings(&path).unwrap();
+        assert_eq!(keys.action("Ctrl-n"), Some(PagerAction::Down));
+        assert_eq!(keys.action("j"), None);
+        assert_eq!(keys.action("q"), None);
+        assert_eq!(keys.action("k"), Some(PagerAction::Up));
+
+        std::fs::write(&path, "down = ['Ctrl-Enterr']\n").unwrap();
+        assert!(read_key_bindings(&path)
+            .err()
+            .unwrap()
+            .contains("Unknown key 'Ctrl-Enterr'"));
+        std::fs::write(&path, "scroll = ['j']\n").unwrap();
+        assert!(read_key_bindings(&path).is_err());
+    }
+
+    #[test]
+    fn read_actions_expands_placeholders() {
+        let dir = TempDir::new("git-graph-actions");
+        let path = dir.path().join("actions.toml");
+        std::fs::write(
+            &path,
+            "tig = 'tig {oid}'\nci = \"ci rerun {branch} {short}\"\n",
+        )
+        .unwrap();
+        let actions = read_actions(&path).unwrap();
+        assert_eq!(actions.names(), vec!["ci", "tig"]);
+        let values = [("oid", "0123abc"), ("short", "0123"), ("branch", "it's")];
+        assert_eq!(actions.command("tig", &values).unwrap(), "tig 0123abc");
+        assert_eq!(
+            actions.command("ci", &values).unwrap(),
+            "ci rerun 'it'\\''s' 0123"
+        );
+        assert!(actions.command("ti", &values).is_err());
+
+        std::fs::write(&path, "tig = 'tig {commit}'\n").unwrap();
+        assert!(read_actions(&path)
+            .err()
+            .unwrap()
+            .contains("Unknown placeholder '{commit}'"));
+    }
+
+    #[test]
+    fn read_aliases_splits_arguments() {
+        let dir = TempDir::new("git-graph-aliases");
+        let path = dir.path().join("aliases.toml");
+        std::fs::write(
+            &path,
+            "rel = '--svg  --model release'
+me = \"--author 'Jane Doe' -f \\\"%h %s\\\"\"\n",
+        )
+        .unwrap();
+        let aliases = read_aliases(&path, &["lint"]).unwrap();
+        assert_eq!(aliases.get("rel").unwrap(), ["--svg", "--model", "release"]);
+        assert_eq!(
+            aliases.get("me").unwrap(),
+            ["--author", "Jane Doe", "-f", "%h %s"]
+        );
+        assert!(aliases.get("lint").is_none());
+
+        std::fs::write(&path, "lint = '--svg'\n").unwrap();
+        assert!(read_aliases(&path, &["lint"])
+            .err()
+            .unwrap()
+            .contains("shadow"));
+        std::fs::write(&path, "rel = \"--format 'oneline\"\n").unwrap();
+        assert!(read_aliases(&path, &["lint"])
+            .err()
+            .unwrap()
+            .contains("Unclosed single quote in alias 'rel'"));
+    }
+
+    #[test]
+    fn read_filters_prefers_repo_settings() {
+        let repo = TestRepo::init("git-graph-filters");
+        let path = repo.workdir().unwrap();
+        assert!(read_filters(&repo, "git-graph.toml", ".git-graph.toml")
+            .unwrap()
+            .is_empty());
+
+        std::fs::write(
+            path.join(".git-graph.toml"),
+            "[filters]\naudit = 'branch:hotfix/*'\nmine = 'author:alice'\n",
+        )
+        .unwrap();
+        std::fs::write(
+            repo.path().join("git-graph.toml"),
+            "model = 'simple'\n[filters]\nmine = 'author:bob'\n",
+        )
+        .unwrap();
+        let filters = read_filters(&repo, "git-graph.toml", ".git-graph.toml").unwrap();
+        assert_eq!(filters["audit"], "branch:hotfix/*");
+        assert_eq!(filters["mine"], "author:bob");
+        assert_eq!(
+            get_model_name(&repo, "git-graph.toml").unwrap().as_deref(),
+            Some("simple")
+        );
+    }
+
+    #[test]
+    fn read_protected_branches_of_both_settings() {
+        let repo = TestRepo::init("git-graph-protected");
+        let path = repo.workdir().unwrap();
+        std::fs::write(
+            path.join(".git-graph.toml"),
+            "protected = ['main', 'release/*']\n",
+        )
+        .unwrap();
+        std::fs::write(
+            repo.path().join("git-graph.toml"),
+            "model = 'simple'\nprotected = ['develop']\n",
+        )
+        .unwrap();
+        let protected = read_protected_branches(&repo, "git-graph.toml", ".git-graph.toml");
+        assert_eq!(protected.unwrap(), ["main", "release/*", "develop"]);
+    }
+
+    #[test]
+    fn wip_patterns_of_own_settings_replace_shared_ones() {
+        let repo = TestRepo::init("git-graph-wip");
+        let path = repo.workdir().unwrap();
+        let read = || read_wip_patterns(&repo, "git-graph.toml", ".git-graph.toml").unwrap();
+        assert_eq!(read(), super::DEFAULT_WIP_PATTERNS);
+
+        std::fs::write(path.join(".git-graph.toml"), "wip = ['^draft:']\n").unwrap();
+        assert_eq!(read(), ["^draft:"]);
+        std::fs::write(repo.path().join("git-graph.toml"), "wip = []\n").unwrap();
+        assert!(read().is_empty());
+    }
+
+    #[test]
+    fn read_reviews_with_default_status() {
+        let dir = TempDir::new("git-graph-reviews");
+        let path = dir.path().join("reviews.txt");
+        let (a, b, c) = ("a".repeat(40), "b".repeat(40), "c".repeat(40));
+        let oid = |id: &str| git2::Oid::from_str(id).unwrap();
+        std::fs::write(
+            &path,
+            format!("# exported reviews\n{a}\n\n{b} unreviewed\n{c} reviewed alice\n"),
+        )
+        .unwrap();
+        let reviews = read_reviews(&path).unwrap();
+        assert_eq!(reviews.status(oid(&a)), ReviewStatus::Reviewed);
+        assert_eq!(reviews.status(oid(&b)), ReviewStatus::Unreviewed);
+        assert_eq!(reviews.status(oid(&c)), ReviewStatus::Reviewed);
+        assert_eq!(
+            reviews.status(oid(&"d".repeat(40))),
+            ReviewStatus::Unreviewed
+        );
+
+        std::fs::write(&path, format!("{a} approved\n")).unwrap();
+        assert!(read_reviews(&path)
+            .unwrap_err()
+            .contains("line 1: Unknown review status"));
+        std::fs::write(&path, "abc1234 reviewed\n").unwrap();
+        assert!(read_reviews(&path)
+            .unwrap_err()
+            .contains("expected a full commit id"));
+    }
+
+    #[test]
+    fn bookmarks_round_trip() {
+        let repo = TestRepo::bare("git-graph-bookmarks");
+        let first = repo.commit("first", &[]);
+        let second = repo.commit("second", &[first]);
+        let path = repo.path().join("bookmarks");
+        assert!(read_bookmarks(&repo, "bookmarks").unwrap().is_empty());
+
+        write_bookmarks(&repo, "bookmarks", &[second, first]).unwrap();
+        assert_eq!(
+            read_bookmarks(&repo, "bookmarks").unwrap(),
+            vec![second, first]
+        );
+
+        // Blank lines and surrounding whitespace are ignored, other lines must be ids
+        std::fs::write(&path, format!("\n  {}  \n\n", first)).unwrap();
+        assert_eq!(read_bookmarks(&repo, "bookmarks").unwrap(), vec![first]);
+        std::fs::write(&path, format!("{}\nnot-a-commit\n", first)).unwrap();
+        let err = read_bookmarks(&repo, "bookmarks").err().unwrap();
+        assert!(err.starts_with("Invalid bookmark 'not-a-commit' in"));
+
+        // Without bookmarks, the file is removed
+        write_bookmarks(&repo, "bookmarks", &[]).unwrap();
+        assert!(!path.exists());
+        write_bookmarks(&repo, "bookmarks", &[]).unwrap();
+        assert!(read_bookmarks(&repo, "bookmarks").unwrap().is_empty());
+    }
+
+    #[test]
+    fn notes_refs_include_display_refs() {
+        let repo = TestRepo::bare("git-graph-notes");
+        let sig = TestRepo::signature();
+        let commit = repo.commit("commit", &[]);
+        for name in ["refs/notes/build", "refs/notes/review"] {
+            repo.note(&sig, &sig, Some(name), commit, "note", false)
+                .unwrap();
+        }
+        let mut config = repo.config().unwrap();
+        config
+            .set_multivar("notes.displayRef", "^$", "refs/notes/*")
+            .unwrap();
+        config
+            .set_multivar("notes.displayRef", "^$", "notes/extra")
+            .unwrap();
+
+        let names = |names: &[&str], defaults| {
+            let names: Vec<_> = names.iter().map(|name| name.to_string()).collect();
+            notes_refs(&repo, &names, defaults).unwrap()
+        };
+        assert_eq!(
+            names(&[], true),
+            [
+                "refs/notes/commits",
+                "refs/notes/build",
+                "refs/notes/review",
+                "refs/notes/extra"
+            ]
+        );
+        assert_eq!(
+            names(&["review", "notes/other"], false),
+            ["refs/notes/review", "refs/notes/other"]
+        );
+        assert_eq!(names(&["build"], true).len(), 4);
+    }
+}
@@ -0,0 +1,283 @@
+//! Building blocks of the daemon mode: detecting ref changes, regenerating artifacts
+//! and notifying webhooks, for auto-updating dashboards.
+//!
+//! Remotes are fetched by the `git` executable and webhooks are posted by `curl`,
+//! so that all transports and credentials configured for them work.
+
+use crate::graph::GitGraph;
+use crate::print::json::print_json;
+use crate::print::summary::{print_summary, summarize};
+use crate::print::svg::print_svg;
+use crate::settings::Settings;
+use git2::{Oid, Repository};
+use serde_derive::Serialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+/// Files regenerated on ref changes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Artifact {
+    Svg,
+    Json,
+    /// A standalone HTML page showing the SVG graph and a summary
+    Html,
+}
+
+impl FromStr for Artifact {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "svg" => Ok(Artifact::Svg),
+            "json" => Ok(Artifact::Json),
+            "html" => Ok(Artifact::Html),
+            _ => Err(format!(
+                "Unknown artifact '{}'. Must be one of [svg|json|html]",
+                str
+            )),
+        }
+    }
+}
+
+impl Artifact {
+    /// The name of the artifact's file in the output directory.
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            Artifact::Svg => "graph.svg",
+            Artifact::Json => "graph.json",
+            Artifact::Html => "graph.html",
+        }
+    }
+}
+
+/// The targets of all refs and of HEAD, by name.
+pub fn ref_snapshot(repository: &Repository) -> Result<BTreeMap<String, Oid>, String> {
+    let mut refs = BTreeMap::new();
+    for reference in repository
+        .references()
+        .map_err(|err| err.message().to_string())?
+        .flatten()
+    {
+        if let (Some(name), Some(target)) = (reference.name(), reference.target()) {
+            refs.insert(name.to_string(), target);
+        }
+    }
+    if let Ok(head) = repository.head() {
+        refs.extend(head.target().map(|target| ("HEAD".to_string(), target)));
+    }
+    Ok(refs)
+}
+
+/// A ref created, moved or deleted between two snapshots.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct RefChange {
+    #[serde(rename = "ref")]
+    pub name: String,
+    /// The previous target, `None` if the ref was created
+    pub old: Option<String>,
+    /// The new target, `None` if the ref was deleted
+    pub new: Option<String>,
+}
+
+/// The refs differing between two snapshots, by name.
+pub fn ref_changes(old: &BTreeMap<String, Oid>, new: &BTreeMap<String, Oid>) -> Vec<RefChange> {
+    let mut names: Vec<&String> = old.keys().chain(new.keys()).collect();
+    names.sort();
+    names.dedup();
+    names
+        .into_iter()
+        .filter(|name| old.get(*name) != new.get(*name))
+        .map(|name| RefChange {
+            name: name.clone(),
+            old: old.get(name).map(|oid| oid.to_string()),
+            new: new.get(name).map(|oid| oid.to_string()),
+        })
+        .collect()
+}
+
+/// Writes the artifacts of a graph to a directory. Files are replaced atomically,
+/// so readers never see partially written files. Returns the paths written.
+pub fn write_artifacts(
+    graph: &GitGraph,
+    settings: &Settings,
+    bookmarks: &[Oid],
+    artifacts: &[Artifact],
+    dir: &Path,
+) -> Result<Vec<PathBuf>, String> {
+    std::fs::create_dir_all(dir)
+        .map_err(|err| format!("Can't create {}: {}", dir.display(), err))?;
+    let mut paths = vec![];
+    for artifact in artifacts {
+        let content = match artifact {
+            Artifact::Svg => print_svg(graph, settings)?,
+            Artifact::Json => print_json(graph, settings, bookmarks)?,
+            Artifact::Html => format!(
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+                 <title>git-graph</title>\n</head>\n<body>\n<p>{}</p>\n{}\n</body>\n</html>\n",
+                print_summary(&summarize(graph)?),
+                print_svg(graph, settings)?
+            ),
+        };
+        let path = dir.join(artifact.file_name());
+        let temp = dir.join(format!(".{}.tmp", artifact.file_name()));
+        std::fs::write(&temp, content)
+            .and_then(|_| std::fs::rename(&temp, &path))
+            .map_err(|err| format!("Can't write {}: {}", path.display(), err))?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Rejects a remote or URL passed to `git` or `curl` that starts with `-`,
+/// and would be read as an option.
+pub fn check_argument(kind: &str, value: &str) -> Result<(), String> {
+    if value.starts_with('-') {
+        Err(format!(
+            "Invalid {} '{}': must not start with '-'",
+            kind, value
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Fetches a remote, like `git fetch --prune <remote>`.
+pub fn fetch(repository: &Repository, remote: &str) -> Result<(), String> {
+    check_argument("remote", remote)?;
+    let output = Command::new("git")
+        .arg("--git-dir")
+        .arg(repository.path())
+        .args(["fetch", "--quiet", "--prune", "--", remote])
+        .output()
+        .map_err(|err| format!("Can't run git: {}", err))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        let message = String::from_utf8_lossy(&output.stderr);
+        Err(format!(
+            "Can't fetch {}: {}",
+            remote,
+            message.lines().last().unwrap_or_default()
+        ))
+    }
+}
+
+/// The payload of webhook notifications.
+#[derive(Serialize)]
+struct WebhookPayload<'a> {
+    repository: String,
+    changes: &'a [RefChange],
+    artifacts: Vec<String>,
+}
+
+/// Posts a notification about ref changes and regenerated artifacts as JSON to a URL.
+pub fn post_webhook(
+    url: &str,
+    repository: &Repository,
+    changes: &[RefChange],
+    artifacts: &[PathBuf],
+) -> Result<(), String> {
+    let payload = WebhookPayload {
+        repository: repository
+            .workdir()
+            .unwrap_or_else(|| repository.path())
+            .display()
+            .to_string(),
+        changes,
+        artifacts: artifacts
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect(),
+    };
+    check_argument("webhook URL", url)?;
+    let payload = serde_json::to_string(&payload).map_err(|err| err.to_string())?;
+    let mut process = Command::new("curl")
+        .args([
+            "--silent",
+            "--show-error",
+            "--fail",
+            "--max-time",
+            "30",
+            "--header",
+            "Content-Type: application/json",
+            "--data-binary",
+            "@-",
+            "--",
+            url,
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("Can't run curl: {}", err))?;
+    if let Some(mut stdin) = process.stdin.take() {
+        stdin
+            .write_all(payload.as_bytes())
+            .map_err(|err| err.to_string())?;
+    }
+    let output = process.wait_with_output().map_err(|err| err.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        let message = String::from_utf8_lossy(&output.stderr);
+        Err(format!(
+            "Webhook {} failed: {}",
+            url,
+            message.lines().last().unwrap_or_default()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_argument, fetch, post_webhook, ref_changes, RefChange};
+    use crate::testing::TestRepo;
+    use git2::Oid;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn ref_changes_between_snapshots() {
+        let oid = |n: u8| Oid::from_bytes(&[n; 20]).unwrap();
+        let old = BTreeMap::from([
+            ("refs/heads/main".to_string(), oid(1)),
+            ("refs/heads/old".to_string(), oid(2)),
+            ("refs/tags/v1".to_string(), oid(1)),
+        ]);
+        let new = BTreeMap::from([
+            ("refs/heads/main".to_string(), oid(3)),
+            ("refs/heads/new".to_string(), oid(3)),
+            ("refs/tags/v1".to_string(), oid(1)),
+        ]);
+        let change = |name: &str, old: Option<Oid>, new: Option<Oid>| RefChange {
+            name: name.to_string(),
+            old: old.map(|oid| oid.to_string()),
+            new: new.map(|oid| oid.to_string()),
+        };
+        assert_eq!(
+            ref_changes(&old, &new),
+            vec![
+                change("refs/heads/main", Some(oid(1)), Some(oid(3))),
+                change("refs/heads/new", None, Some(oid(3))),
+                change("refs/heads/old", Some(oid(2)), None),
+            ]
+        );
+        assert!(ref_changes(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn arguments_starting_with_dash_are_rejected() {
+        assert!(check_argument("remote", "origin").is_ok());
+        let repo = TestRepo::bare("git-graph-daemon");
+        assert_eq!(
+            fetch(&repo, "--upload-pack=touch x"),
+            Err("Invalid remote '--upload-pack=touch x': must not start with '-'".to_string())
+        );
+        assert_eq!(
+            post_webhook("-o/tmp/x", &repo, &[], &[]),
+            Err("Invalid webhook URL '-o/tmp/x': must not start with '-'".to_string())
+        );
+    }
+}
@@ -0,0 +1,249 @@
+//! Commit selection expressions, combining filters with `AND`, `OR` and `NOT`,
+//! like `author:alice AND branch:release/* AND NOT tag:*-rc*`.
+
+use crate::settings::{identity_patterns, parse_date};
+use chrono::{DateTime, Local};
+use regex::Regex;
+
+/// The keys of filter terms, in the order of the error message.
+const KEYS: [&str; 8] = [
+    "author",
+    "committer",
+    "message",
+    "branch",
+    "tag",
+    "path",
+    "since",
+    "until",
+];
+
+/// A filter expression, selecting commits.
+#[derive(Clone, Debug)]
+pub enum Filter {
+    /// Commits whose author's `Name <email>` matches
+    Author(Regex),
+    /// Commits whose committer's `Name <email>` matches
+    Committer(Regex),
+    /// Commits whose message matches
+    Message(Regex),
+    /// Commits reachable from a local or remote branch whose name matches
+    Branch(Regex),
+    /// Commits reachable from a tag whose name matches
+    Tag(Regex),
+    /// Commits changing files matching a pathspec, following renames
+    Path(String),
+    /// Commits committed at or after a time, in seconds since the epoch
+    Since(i64),
+    /// Commits committed at or before a time, in seconds since the epoch
+    Until(i64),
+    Not(Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Term(String, String),
+}
+
+impl Filter {
+    /// Parses a filter expression. `NOT` binds stronger than `AND`, and `AND` stronger
+    /// than `OR`. Adjacent terms are combined with `AND`. Relative dates of `since:`
+    /// and `until:` are relative to `now`.
+    pub fn parse(expr: &str, now: DateTime<Local>) -> Result<Self, String> {
+        let tokens = tokenize(expr)?;
+        if tokens.is_empty() {
+            return Err("Empty filter expression".to_string());
+        }
+        let mut pos = 0;
+        let filter = parse_or(&tokens, &mut pos, now)?;
+        // Only a closing parenthesis can be left over
+        if pos < tokens.len() {
+            return Err(format!("Unbalanced ')' in filter expression '{}'", expr));
+        }
+        Ok(filter)
+    }
+}
+
+/// Splits an expression into parentheses, operators and `key:value` terms.
+/// Values may be quoted with `"`, to include spaces or parentheses.
+fn tokenize(expr: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = expr.chars().peekable();
+    while let Some(&ch) = chars.peek() {
+        match ch {
+            _ if ch.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            _ => {
+                let mut word = String::new();
+                let mut quoted = false;
+                while let Some(&ch) = chars.peek() {
+                    if !quoted && (ch.is_whitespace() || ch == '(' || ch == ')') {
+                        break;
+                    }
+                    chars.next();
+                    if ch == '"' {
+                        quoted = !quoted;
+                    } else {
+                        word.push(ch);
+                    }
+                }
+                if quoted {
+                    return Err(format!(
+                        "Unterminated quote in filter expression '{}'",
+                        expr
+                    ));
+                }
+                tokens.push(match &word[..] {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => match word.split_once(':') {
+                        Some((key, value)) if !value.is_empty() => {
+                            Token::Term(key.to_string(), value.to_string())
+                        }
+                        _ => {
+                            return Err(format!(
+                                "Expected a term like 'author:alice' in filter expression, \
+                                 found '{}'",
+                                word
+                            ))
+                        }
+                    },
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_or(tokens: &[Token], pos: &mut usize, now: DateTime<Local>) -> Result<Filter, String> {
+    let mut filter = parse_and(tokens, pos, now)?;
+    while tokens.get(*pos) == Some(&Token::Or) {
+        *pos += 1;
+        filter = Filter::Or(Box::new(filter), Box::new(parse_and(tokens, pos, now)?));
+    }
+    Ok(filter)
+}
+
+fn parse_and(tokens: &[Token], pos: &mut usize, now: DateTime<Local>) -> Result<Filter, String> {
+    let mut filter = parse_not(tokens, pos, now)?;
+    loop {
+        match tokens.get(*pos) {
+            Some(Token::And) => *pos += 1,
+            // Implicit AND of adjacent terms
+            Some(Token::Not | Token::Open | Token::Term(..)) => {}
+            _ => return Ok(filter),
+        }
+        filter = Filter::And(Box::new(filter), Box::new(parse_not(tokens, pos, now)?));
+    }
+}
+
+fn parse_not(tokens: &[Token], pos: &mut usize, now: DateTime<Local>) -> Result<Filter, String> {
+    let token = tokens.get(*pos);
+    *pos += 1;
+    match token {
+        Some(Token::Not) => Ok(Filter::Not(Box::new(parse_not(tokens, pos, now)?))),
+        Some(Token::Open) => {
+            let filter = parse_or(tokens, pos, now)?;
+            if tokens.get(*pos) != Some(&Token::Close) {
+                return Err("Unbalanced '(' in filter expression".to_string());
+            }
+            *pos += 1;
+            Ok(filter)
+        }
+        Some(Token::Term(key, value)) => parse_term(key, value, now),
+        Some(_) => Err("Expected a term after an operator in filter expression".to_string()),
+        None => Err("Filter expression ends with an operator".to_string()),
+    }
+}
+
+fn parse_term(key: &str, value: &str, now: DateTime<Local>) -> Result<Filter, String> {
+    let regex =
+        |str: &str| Regex::new(str).map_err(|err| format!("Invalid pattern '{}': {}", str, err));
+    let identity = |str: &str| identity_patterns(&[str.to_string()]).map(|mut p| p.remove(0));
+    Ok(match key {
+        "author" => Filter::Author(identity(value)?),
+        "committer" => Filter::Committer(identity(value)?),
+        "message" => Filter::Message(regex(value)?),
+        "branch" => Filter::Branch(glob(value)?),
+        "tag" => Filter::Tag(glob(value)?),
+        "path" => Filter::Path(value.to_string()),
+        "since" => Filter::Since(parse_date(value, now)?),
+        "until" => Filter::Until(parse_date(value, now)?),
+        _ => {
+            return Err(format!(
+                "Unknown filter key '{}'. Must be one of [{}]",
+                key,
+                KEYS.join("|")
+            ))
+        }
+    })
+}
+
+/// Converts a glob like `release/*` to an anchored regex. `*` matches any characters,
+/// including `/`, and `?` matches a single character.
+pub fn glob(pattern: &str) -> Result<Regex, String> {
+    let regex = pattern
+        .split('*')
+        .map(|part| {
+            part.split('?')
+                .map(regex::escape)
+                .collect::<Vec<_>>()
+                .join(".")
+        })
+        .collect::<Vec<_>>()
+        .join(".*");
+    Regex::new(&format!("^{}$", regex)).map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{glob, Filter};
+    use chrono::Local;
+
+    #[test]
+    fn parse_filter_expressions() {
+        let parse = |str| Filter::parse(str, Local::now());
+        let filter = parse("author:alice AND branch:release/* AND NOT tag:*-rc*").unwrap();
+        let text = format!("{:?}", filter);
+        assert!(text.starts_with("And(And(Author("));
+        assert!(text.contains("Not(Tag("));
+
+        // NOT before AND before OR, adjacent terms are ANDed
+        let filter = parse("path:src OR message:fix NOT author:bot").unwrap();
+        assert!(matches!(filter, Filter::Or(_, ref rhs) if matches!(**rhs, Filter::And(..))));
+        let filter = parse("(path:src OR path:docs) since:2024-01-01").unwrap();
+        assert!(matches!(filter, Filter::And(ref lhs, _) if matches!(**lhs, Filter::Or(..))));
+        assert!(parse(r#"author:"Alice Smith""#).is_ok());
+
+        assert!(parse("").is_err());
+        assert!(parse("author:alice AND").is_err());
+        assert!(parse("(author:alice").is_err());
+        assert!(parse("author:alice)").is_err());
+        assert!(parse("alice").is_err());
+        assert!(parse("owner:alice").is_err());
+        assert!(parse(r#"author:"alice"#).is_err());
+
+        let release = glob("release/*").unwrap();
+        assert!(release.is_match("release/1.0"));
+        assert!(!release.is_match("origin/release/1.0"));
+        let rc = glob("v?.*-rc*").unwrap();
+        assert!(rc.is_match("v1.2-rc1"));
+        assert!(!rc.is_match("v1.2"));
+    }
+}
@@ -1,6 +1,7 @@
 //! A graph structure representing the history of a Git repository.
 //!
-//! To generate a graph, call [GitGraph::new()].
+//! To generate a graph, call [GitGraph::new()], or [GitGraph::from_provider()] to lay out
+//! commits from another source, see [crate::provider].
 //!
 //! ### Visualization of branches
 //! git-graph uses the term *branch* a little different from how git uses it.
@@ -17,46 +18,496 @@
 //! commit. Horizontal lines represent forks (multiple children) or
 //! merges (multiple parents), and show the remaining parent relations.
 
+use crate::filter::Filter;
 use crate::print::colors::to_terminal_color;
-use crate::settings::{BranchOrder, BranchSettings, MergePatterns, Settings};
-use git2::{BranchType, Commit, Error, Oid, Reference, Repository};
+use crate::provider::RepoProvider;
+use crate::settings::{
+    BranchOrder, BranchSettings, EmptyBranches, GraphView, MergePatterns, Pickaxe, RemoteColors,
+    Settings, StashMode, Symbol,
+};
+use git2::{
+    Branch, BranchType, Commit, Delta, Diff, DiffFindOptions, DiffOptions, Error, ErrorCode,
+    FileMode, ObjectType, Odb, Oid, Reference, Repository, RevparseMode, Revwalk, Signature, Time,
+    Tree,
+};
+use itertools::Itertools;
+use lazy_static::lazy_static;
 use regex::Regex;
-use std::collections::{HashMap, HashSet};
+#[cfg(feature = "serde")]
+use serde_derive::{Deserialize, Serialize};
+use std::cell::{OnceCell, RefCell};
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet};
+use std::hash::{BuildHasherDefault, Hasher};
+use std::io::Write;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
 
 const ORIGIN: &str = "origin/";
 const FORK: &str = "fork/";
 
+lazy_static! {
+    /// The branch merged into, in merge summaries like `Merge branch 'main' into feature`
+    static ref MERGE_INTO: Regex = Regex::new(r" into '?([^' ]+)'?$").unwrap();
+}
+/// Number of commits in a row older than `--since` after which the walk stops, like git
+const DATE_SLOP: usize = 5;
+/// Number of commits kept in the metadata cache of a [GitGraph]
+const METADATA_CACHE_SIZE: usize = 4096;
+/// Maximum number of threads computing diff stats
+const MAX_DIFF_WORKERS: usize = 8;
+/// Colors of branches and commits reachable only from reflogs, or dangling
+const REFLOG_TERM_COLOR: u8 = 8;
+const REFLOG_SVG_COLOR: &str = "darkgray";
+/// Number of commits between updates of progress reports
+const PROGRESS_STEP: usize = 100;
+
+/// Hash map keyed by object ids. As ids are already uniformly distributed hashes,
+/// their first bytes are used as hash instead of hashing them again.
+pub type OidMap<V> = HashMap<Oid, V, BuildHasherDefault<OidHasher>>;
+
+/// [Hasher] for [OidMap], taking the first 8 bytes of an object id as its hash.
+#[derive(Default)]
+pub struct OidHasher(u64);
+
+impl Hasher for OidHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        match bytes.first_chunk::<8>() {
+            Some(prefix) => self.0 = u64::from_le_bytes(*prefix),
+            None => {
+                for byte in bytes {
+                    self.0 = self.0.rotate_left(8) ^ *byte as u64;
+                }
+            }
+        }
+    }
+}
+
+/// Compact adjacency lists between commits, as indices into the commits.
+///
+/// All lists are stored back to back in a single array, which takes far less
+/// memory than a separate `Vec` per commit for huge graphs.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Default)]
+pub struct Adjacency {
+    /// Start of each commit's list in `targets`, plus the total length at the end
+    offsets: Vec<u32>,
+    targets: Vec<u32>,
+}
+
+impl Adjacency {
+    /// Collects the children of each commit from the commits' parents.
+    /// Children are listed in the order of `commits`.
+    pub fn children(commits: &[CommitInfo], indices: &OidMap<usize>) -> Self {
+        let parent_indices = |info: &CommitInfo| {
+            info.parents
+                .into_iter()
+                .filter_map(|par| par.and_then(|oid| indices.get(&oid)).copied())
+                .collect::<Vec<_>>()
+        };
+
+        let mut offsets = vec![0u32; commits.len() + 1];
+        for info in commits {
+            for par_idx in parent_indices(info) {
+                offsets[par_idx + 1] += 1;
+            }
+        }
+        for idx in 0..commits.len() {
+            offsets[idx + 1] += offsets[idx];
+        }
+
+        let mut fill: Vec<u32> = offsets[..commits.len()].to_vec();
+        let mut targets = vec![0u32; offsets[commits.len()] as usize];
+        for (idx, info) in commits.iter().enumerate() {
+            for par_idx in parent_indices(info) {
+                targets[fill[par_idx] as usize] = idx as u32;
+                fill[par_idx] += 1;
+            }
+        }
+        Adjacency { offsets, targets }
+    }
+
+    /// The indices adjacent to the commit at `index`.
+    pub fn get(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        let range = match (self.offsets.get(index), self.offsets.get(index + 1)) {
+            (Some(&start), Some(&end)) => start as usize..end as usize,
+            _ => 0..0,
+        };
+        self.targets[range].iter().map(|&idx| idx as usize)
+    }
+}
+
 /// Represents a git history graph.
+///
+/// Dereferences to its [GraphData], which holds everything computed from the repository.
+/// Commit metadata is not part of the layout, see [GitGraph::metadata]. Unless built with
+/// [GitGraph::retaining], a graph holds no handle to the repository for commits, diffs
+/// and refs. Graphs are `Send`.
 pub struct GitGraph {
-    pub repository: Repository,
+    repository: Option<Repository>,
+    /// Path of the repository to load metadata from, for graphs not retaining it
+    source: Option<PathBuf>,
+    /// Repository opened from `source` on the first metadata not yet loaded
+    loader: OnceCell<Repository>,
+    pub data: GraphData,
+    metadata: RefCell<MetadataCache>,
+}
+
+/// The computed part of a [GitGraph]: commits, branches and layout,
+/// without the [Repository]. Unlike the repository, this is `Send + Sync`,
+/// so a graph can be built on a worker thread and rendered elsewhere.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GraphData {
     pub commits: Vec<CommitInfo>,
     /// Mapping from commit id to index in `commits`
-    pub indices: HashMap<Oid, usize>,
+    #[cfg_attr(feature = "serde", serde(with = "oid_serde::map"))]
+    pub indices: OidMap<usize>,
+    /// Children of each commit, as indices into `commits`
+    pub children: Adjacency,
     /// All detected branches and tags, including merged and deleted
     pub all_branches: Vec<BranchInfo>,
     /// Indices of all real (still existing) branches in `all_branches`
     pub branches: Vec<usize>,
     /// Indices of all tags in `all_branches`
     pub tags: Vec<usize>,
-    /// The current HEAD
-    pub head: HeadInfo,
+    /// The current HEAD, if the source of the commits has one
+    pub head: Option<HeadInfo>,
+    /// The HEADs of the repository's other worktrees
+    pub worktrees: Vec<WorktreeInfo>,
+    /// Blobs above the large blob threshold added by commits relative to their first
+    /// parent. Only computed on demand, for the commits adding any.
+    #[cfg_attr(feature = "serde", serde(with = "oid_serde::map"))]
+    pub large_blobs: OidMap<Vec<LargeBlob>>,
+    /// Submodules whose commit commits change relative to their first parent.
+    /// Only computed on demand, for the commits changing any.
+    #[cfg_attr(feature = "serde", serde(with = "oid_serde::map"))]
+    pub submodule_bumps: OidMap<Vec<SubmoduleBump>>,
+    /// Text of the notes attached to commits, one per notes ref.
+    /// Only read if notes are shown, for the commits with any.
+    #[cfg_attr(feature = "serde", serde(with = "oid_serde::map"))]
+    pub notes: OidMap<Vec<String>>,
+    /// Indices of the branches in [GraphData::all_branches] whose history contains a
+    /// commit, of the visible branches. Only computed on demand, for the commits
+    /// contained in any.
+    #[cfg_attr(feature = "serde", serde(with = "oid_serde::map"))]
+    pub containing_branches: OidMap<Vec<usize>>,
 }
 
 impl GitGraph {
     /// Generate a branch graph for a repository
     pub fn new(
-        mut repository: Repository,
+        repository: &Repository,
         settings: &Settings,
         max_count: Option<usize>,
     ) -> Result<Self, String> {
         #![doc = include_str!("../docs/branch_assignment.md")]
-        let mut stashes = HashSet::new();
-        repository
-            .stash_foreach(|_, _, oid| {
-                stashes.insert(*oid);
-                true
+        Self::from_provider(repository, settings, max_count)
+    }
+
+    /// Generate a branch graph of only the history of the given commits.
+    /// Covers the history of all refs if `revisions` is empty.
+    ///
+    /// The graph holds no handle to the repository for commits, diffs and refs. Commit
+    /// metadata is loaded when rows are rendered, from the repository at the same path.
+    /// Use [GitGraph::retaining] to render formats that need more than the metadata.
+    pub fn from_revisions(
+        repository: &Repository,
+        settings: &Settings,
+        max_count: Option<usize>,
+        revisions: &[Oid],
+    ) -> Result<Self, String> {
+        let data = GraphData::new(repository, settings, max_count, revisions)?;
+        Ok(GitGraph {
+            repository: None,
+            source: Some(repository.path().to_path_buf()),
+            loader: OnceCell::new(),
+            data,
+            metadata: RefCell::new(MetadataCache::default()),
+        })
+    }
+
+    /// Like [GitGraph::from_revisions], but the graph keeps its own handle to the
+    /// repository. Metadata is then loaded when rows are rendered, and commits, diffs
+    /// and refs remain available, e.g. for custom formats, patches or bundles.
+    pub fn retaining(
+        repository: &Repository,
+        settings: &Settings,
+        max_count: Option<usize>,
+        revisions: &[Oid],
+    ) -> Result<Self, String> {
+        let data = GraphData::new(repository, settings, max_count, revisions)?;
+        let handle =
+            Repository::open(repository.path()).map_err(|err| err.message().to_string())?;
+        Ok(GitGraph {
+            repository: Some(handle),
+            source: None,
+            loader: OnceCell::new(),
+            data,
+            metadata: RefCell::new(MetadataCache::default()),
+        })
+    }
+
+    /// Lays out the refs and commits of a [RepoProvider]: a git repository, like
+    /// [GitGraph::new], or commits held in memory, like by a
+    /// [MemoryRepo](crate::provider::MemoryRepo). Without a repository, commit metadata
+    /// comes from the provider, and options that need the content of commits, like path
+    /// filters or diff stats, fail.
+    pub fn from_provider<P: RepoProvider>(
+        provider: &P,
+        settings: &Settings,
+        max_count: Option<usize>,
+    ) -> Result<Self, String> {
+        if let Some(repository) = provider.repository() {
+            return Self::from_revisions(repository, settings, max_count, &[]);
+        }
+        let mut commits = Vec::new();
+        let mut indices = OidMap::default();
+        let mut times = OidMap::default();
+        let mut metadata = MetadataCache::default();
+        let has_window = settings.since.is_some() || settings.until.is_some();
+        for (idx, oid) in provider.walk(max_count)?.into_iter().enumerate() {
+            let commit = provider.commit(oid)?;
+            commits.push(CommitInfo::new(oid, &commit.parents));
+            indices.insert(oid, idx);
+            if has_window {
+                times.insert(oid, commit.meta.time.seconds());
+            }
+            metadata.pinned.insert(oid, Arc::new(commit.meta));
+        }
+        let redirects = filter_history(
+            None,
+            &mut commits,
+            &mut indices,
+            &HashMap::new(),
+            &times,
+            settings,
+        )?;
+        let mut head = provider.head()?;
+        if let Some(head) = head.as_mut() {
+            head.redirect(&redirects);
+        }
+
+        let refs: Vec<GraphRef> = provider
+            .refs()?
+            .into_iter()
+            .filter_map(|(name, target)| {
+                let (name, is_remote, is_tag) = if let Some(name) = name.strip_prefix("refs/heads/")
+                {
+                    (name, false, false)
+                } else if let Some(name) = name.strip_prefix("refs/remotes/") {
+                    (name, true, false)
+                } else {
+                    (name.strip_prefix("refs/")?, false, true)
+                };
+                (!is_remote || settings.include_remote).then(|| GraphRef {
+                    name: name.to_string(),
+                    target,
+                    is_remote,
+                    is_tag,
+                    is_default: false,
+                    is_remote_head: false,
+                    is_wip: false,
+                    ahead_behind: None,
+                })
             })
+            .filter(|branch| branch.is_tag || settings.branch_filter.matches(&branch.name))
+            .collect();
+        let (mut all_branches, foxtrots) = branches_from_refs(
+            &refs,
+            &commits,
+            &indices,
+            &HashMap::new(),
+            settings,
+            |oid| {
+                let info = &commits[indices[&oid]];
+                let summary = &metadata.pinned[&oid].summary;
+                Ok(match (info.parents, summary.is_empty()) {
+                    ([Some(first), Some(second)], false) => Some((
+                        String::from_utf8_lossy(summary).to_string(),
+                        [first, second],
+                    )),
+                    _ => None,
+                })
+            },
+        )?;
+        redirect_branches(&mut all_branches, &indices, &redirects);
+
+        let data = GraphData::lay_out(
+            None,
+            commits,
+            indices,
+            all_branches,
+            &foxtrots,
+            head,
+            vec![],
+            settings,
+        )?;
+        Ok(GitGraph {
+            repository: None,
+            source: None,
+            loader: OnceCell::new(),
+            data,
+            metadata: RefCell::new(metadata),
+        })
+    }
+
+    /// Creates a graph from data laid out before, e.g. on another thread, with a handle
+    /// to the repository to load metadata from.
+    pub fn from_data(repository: Repository, data: GraphData) -> Self {
+        GitGraph {
+            repository: Some(repository),
+            source: None,
+            loader: OnceCell::new(),
+            data,
+            metadata: RefCell::new(MetadataCache::default()),
+        }
+    }
+
+    /// The repository, if the graph retains a handle to it.
+    pub fn repository(&self) -> Result<&Repository, String> {
+        self.repository
+            .as_ref()
+            .ok_or_else(|| "The graph holds no repository".to_string())
+    }
+
+    pub fn commit(&self, id: Oid) -> Result<Commit<'_>, Error> {
+        self.repository()
+            .map_err(|err| Error::from_str(&err))?
+            .find_commit(id)
+    }
+
+    /// Summary, author and dates of a commit. Loaded on first access and kept in a cache
+    /// of the most recently used commits, unless given by a [RepoProvider].
+    pub fn metadata(&self, id: Oid) -> Result<Arc<CommitMeta>, Error> {
+        if let Some(meta) = self.metadata.borrow_mut().get(id) {
+            return Ok(meta);
+        }
+        let replacement = self
+            .indices
+            .get(&id)
+            .and_then(|idx| self.commits[*idx].replaced_by);
+        let commit = self
+            .metadata_repository()?
+            .find_commit(replacement.unwrap_or(id))?;
+        let meta = Arc::new(CommitMeta::new(&commit));
+        self.metadata.borrow_mut().insert(id, meta.clone());
+        Ok(meta)
+    }
+
+    /// The repository to load metadata from: the retained one, or one opened from the
+    /// repository's path on first use.
+    fn metadata_repository(&self) -> Result<&Repository, Error> {
+        if let Some(repository) = self.repository.as_ref().or(self.loader.get()) {
+            return Ok(repository);
+        }
+        let path = self
+            .source
+            .as_ref()
+            .ok_or_else(|| Error::from_str("The graph holds no repository"))?;
+        let repository = Repository::open(path)?;
+        Ok(self.loader.get_or_init(|| repository))
+    }
+
+    /// The commits only reachable from the given local branch: those that would become
+    /// unreachable if the branch were deleted. All other refs and a detached HEAD keep
+    /// commits reachable, reflogs are not considered. Newest first.
+    pub fn exclusive_commits(&self, name: &str) -> Result<Vec<Oid>, String> {
+        let repository = self.repository()?;
+        let branch = repository
+            .find_branch(name, BranchType::Local)
+            .map_err(|_| format!("No local branch '{}'", name))?;
+        let branch_ref = branch.get().name().unwrap_or_default();
+        let tip = branch
+            .get()
+            .target()
+            .ok_or_else(|| format!("Branch '{}' has no target", name))?;
+
+        let mut walk = repository
+            .revwalk()
+            .map_err(|err| err.message().to_string())?;
+        walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)
+            .map_err(|err| err.message().to_string())?;
+        walk.push(tip).map_err(|err| err.message().to_string())?;
+        let references = repository
+            .references()
+            .map_err(|err| err.message().to_string())?;
+        let head = repository.head().ok();
+        for reference in references.flatten().chain(head) {
+            if reference.name() == Some(branch_ref) {
+                continue;
+            }
+            // HEAD resolves to its branch, if not detached
+            if let Ok(commit) = reference.peel_to_commit() {
+                walk.hide(commit.id())
+                    .map_err(|err| err.message().to_string())?;
+            }
+        }
+        walk.collect::<Result<Vec<_>, _>>()
+            .map_err(|err| err.message().to_string())
+    }
+
+    /// Resolves a row address to an index in `commits`.
+    /// Accepts `@N` for row `N`, or any revision understood by git.
+    pub fn resolve_row(&self, spec: &str) -> Result<usize, String> {
+        if let Some(row) = spec.strip_prefix('@') {
+            let row: usize = row
+                .parse()
+                .map_err(|_| format!("Invalid row address '{}'", spec))?;
+            return if row < self.commits.len() {
+                Ok(row)
+            } else {
+                Err(format!(
+                    "Row {} is out of range (0..{})",
+                    row,
+                    self.commits.len()
+                ))
+            };
+        }
+        let commit = self
+            .repository()?
+            .revparse_single(spec)
+            .and_then(|obj| obj.peel_to_commit())
+            .map_err(|err| err.message().to_string())?;
+        self.indices
+            .get(&commit.id())
+            .copied()
+            .ok_or_else(|| format!("Commit '{}' is not part of the graph", spec))
+    }
+}
+
+impl Deref for GitGraph {
+    type Target = GraphData;
+
+    fn deref(&self) -> &GraphData {
+        &self.data
+    }
+}
+
+impl GraphData {
+    /// Lays out the history of the given commits, or of all refs if `revisions` is empty.
+    /// The repository is only read here, the result holds no reference to it.
+    pub fn new(
+        repository: &Repository,
+        settings: &Settings,
+        max_count: Option<usize>,
+        revisions: &[Oid],
+    ) -> Result<Self, String> {
+        // Stash entries are the reflog of `refs/stash`, newest first
+        let mut stashes = HashMap::new();
+        let stash_log = repository
+            .reflog("refs/stash")
             .map_err(|err| err.message().to_string())?;
+        for (index, entry) in stash_log.iter().enumerate() {
+            stashes.insert(entry.id_new(), index);
+        }
 
         let mut walk = repository
             .revwalk()
@@ -64,193 +515,1131 @@ impl GitGraph {
 
         walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)
             .map_err(|err| err.message().to_string())?;
+        if settings.first_parent {
+            walk.simplify_first_parent()
+                .map_err(|err| err.message().to_string())?;
+        }
 
-        walk.push_glob("*")
-            .map_err(|err| err.message().to_string())?;
+        if revisions.is_empty() && !settings.revision_ranges.is_empty() {
+            for spec in &settings.revision_ranges {
+                push_revision_range(repository, &mut walk, spec)?;
+            }
+        } else if revisions.is_empty() && (!settings.branch_filter.is_empty() || settings.hide_wip)
+        {
+            // Only the history of the shown branches is walked
+            for branch in branch_refs(repository, settings)? {
+                walk.push(branch.target)
+                    .map_err(|err| err.message().to_string())?;
+            }
+            for oid in settings.commit_list.iter().flatten() {
+                walk.push(*oid).map_err(|err| err.message().to_string())?;
+            }
+        } else if revisions.is_empty() {
+            walk.push_glob("*")
+                .map_err(|err| err.message().to_string())?;
+            // Listed commits are attributed to branches as in the full history,
+            // and may not be reachable from any ref
+            for oid in settings.commit_list.iter().flatten() {
+                walk.push(*oid).map_err(|err| err.message().to_string())?;
+            }
+        }
+        for oid in revisions {
+            walk.push(*oid).map_err(|err| err.message().to_string())?;
+        }
+
+        // Commits rewritten or reset away are only reachable from reflogs
+        let reflog =
+            if settings.reflog && revisions.is_empty() && settings.revision_ranges.is_empty() {
+                reflog_entries(repository)?
+            } else {
+                vec![]
+            };
+        let dangling =
+            if settings.dangling && revisions.is_empty() && settings.revision_ranges.is_empty() {
+                dangling_commits(repository, &stashes)?
+            } else {
+                vec![]
+            };
+        for (_, oid) in reflog.iter().chain(&dangling) {
+            walk.push(*oid).map_err(|err| err.message().to_string())?;
+        }
 
-        if repository.is_shallow() {
-            return Err("ERROR: git-graph does not support shallow clones due to a missing feature in the underlying libgit2 library.".to_string());
+        // Only the latest stash is reachable from `refs/stash`, older ones live in its reflog
+        if settings.stash_mode != StashMode::Hide
+            && revisions.is_empty()
+            && settings.revision_ranges.is_empty()
+        {
+            for oid in stashes.keys() {
+                walk.push(*oid).map_err(|err| err.message().to_string())?;
+            }
         }
 
-        let head = HeadInfo::new(&repository.head().map_err(|err| err.message().to_string())?)?;
+        let mut head = HeadInfo::of_repository(repository)?;
+        let mut worktrees = other_worktrees(repository)?;
 
         // commits will hold the CommitInfo for all commits covered
         // indices maps git object id to an index into commits.
+        // Only ids and parents are read here, metadata is loaded when rendering.
+        let odb = repository.odb().map_err(|err| err.message().to_string())?;
+        // libgit2's revwalk ignores replace refs, their history is walked separately
+        let replacements = if settings.replace_refs {
+            replace_refs(repository)?
+        } else {
+            OidMap::default()
+        };
+        let grafts = info_grafts(repository)?;
+        let walk: Vec<Result<Oid, Error>> = if replacements.is_empty() {
+            walk.collect()
+        } else {
+            replaced_walk(&odb, walk, &replacements, &grafts, settings.first_parent)?
+                .into_iter()
+                .map(Ok)
+                .collect()
+        };
         let mut commits = Vec::new();
-        let mut indices = HashMap::new();
+        let mut indices = OidMap::default();
         let mut idx = 0;
+        // Commits outside the date window are read, to rewrite parents across them,
+        // but do not count for the maximum
+        let has_window = settings.since.is_some() || settings.until.is_some();
+        let mut times = OidMap::default();
+        let mut in_window = 0;
+        let mut older_in_row = 0;
         for oid in walk {
             if let Some(max) = max_count {
-                if idx >= max {
+                if in_window >= max {
                     break;
                 }
             }
+            if let Some(progress) = &settings.build_progress {
+                progress.advance()?;
+            }
             if let Ok(oid) = oid {
-                if !stashes.contains(&oid) {
-                    let commit = repository.find_commit(oid).unwrap();
-
-                    commits.push(CommitInfo::new(&commit));
+                let is_stash = stashes.contains_key(&oid);
+                if !is_stash || settings.stash_mode != StashMode::Hide {
+                    let (mut info, time) = match replacements.get(&oid) {
+                        Some(replacement) => CommitInfo::read_replaced(&odb, oid, *replacement),
+                        None => CommitInfo::read(&odb, oid),
+                    }
+                    .map_err(|err| err.message().to_string())?;
+                    if let Some(parents) = grafts.get(&oid) {
+                        info.graft(parents);
+                    }
+                    info.is_stash = is_stash;
+                    if is_stash && settings.stash_mode == StashMode::Show {
+                        // Detach the stash's index commit, so only the WIP commit is shown
+                        info.parents[1] = None;
+                        info.is_merge = false;
+                    }
+                    commits.push(info);
                     indices.insert(oid, idx);
                     idx += 1;
+
+                    let too_old = settings.since.is_some_and(|since| time < since);
+                    let too_new = settings.until.is_some_and(|until| time > until);
+                    if has_window {
+                        times.insert(oid, time);
+                    }
+                    if !too_old && !too_new {
+                        in_window += 1;
+                    }
+                    // Like git log, stop once several commits in a row are too old,
+                    // allowing for some clock skew
+                    older_in_row = if too_old { older_in_row + 1 } else { 0 };
+                    if older_in_row > DATE_SLOP {
+                        break;
+                    }
                 }
             }
         }
+        drop(odb);
+        // Index commits of stashes shown with their parents
+        let stash_indices: Vec<usize> = commits
+            .iter()
+            .filter(|info| info.is_stash)
+            .filter_map(|info| info.parents[1].and_then(|oid| indices.get(&oid)))
+            .copied()
+            .collect();
+        for idx in stash_indices {
+            commits[idx].is_stash = true;
+        }
+        // Parents beyond the boundary of a shallow clone are missing
+        for oid in shallow_boundaries(repository)? {
+            if let Some(&idx) = indices.get(&oid) {
+                let info = &mut commits[idx];
+                info.parents = [None, None];
+                info.is_merge = false;
+                info.shallow_boundary = true;
+            }
+        }
 
-        assign_children(&mut commits, &indices);
-
-        let mut all_branches = assign_branches(&repository, &mut commits, &indices, settings)?;
-        correct_fork_merges(&commits, &indices, &mut all_branches, settings)?;
-        assign_sources_targets(&commits, &indices, &mut all_branches);
-
-        let (shortest_first, forward) = match settings.branch_order {
-            BranchOrder::ShortestFirst(fwd) => (true, fwd),
-            BranchOrder::LongestFirst(fwd) => (false, fwd),
-        };
+        if !reflog.is_empty() || !dangling.is_empty() {
+            mark_unreachable(repository, &mut commits, &indices, &stashes)?;
+        }
+        let redirects = filter_history(
+            Some(repository),
+            &mut commits,
+            &mut indices,
+            &stashes,
+            &times,
+            settings,
+        )?;
+        // Branches and HEAD pointing to commits filtered out move to the nearest ancestor kept
+        head.redirect(&redirects);
+        for worktree in worktrees.iter_mut() {
+            worktree.head.redirect(&redirects);
+        }
 
-        assign_branch_columns(
+        let (mut all_branches, foxtrots) =
+            extract_branches(repository, &commits, &indices, &stashes, settings)?;
+        all_branches.extend(unreachable_branches(
+            &commits, &indices, &reflog, settings, None,
+        ));
+        // Dangling commits get a group of columns of their own, right of all others
+        all_branches.extend(unreachable_branches(
             &commits,
             &indices,
+            &dangling,
+            settings,
+            Some(settings.branches.order.len() + 1),
+        ));
+        redirect_branches(&mut all_branches, &indices, &redirects);
+        Self::lay_out(
+            Some(repository),
+            commits,
+            indices,
+            all_branches,
+            &foxtrots,
+            Some(head),
+            worktrees,
+            settings,
+        )
+    }
+
+    /// Traces and places the branches of the commits read and filtered, and computes the
+    /// stats of the options given. Options reading the content of commits need a
+    /// repository.
+    #[allow(clippy::too_many_arguments)]
+    fn lay_out(
+        repository: Option<&Repository>,
+        mut commits: Vec<CommitInfo>,
+        indices: OidMap<usize>,
+        mut all_branches: Vec<BranchInfo>,
+        foxtrots: &OidMap<String>,
+        head: Option<HeadInfo>,
+        worktrees: Vec<WorktreeInfo>,
+        settings: &Settings,
+    ) -> Result<Self, String> {
+        let children = Adjacency::children(&commits, &indices);
+        let linear = trace_branches(
+            &mut commits,
+            &indices,
+            &children,
             &mut all_branches,
-            &settings.branches,
-            shortest_first,
-            forward,
+            foxtrots,
+            settings,
         );
+        if settings.view == GraphView::SignedReleases {
+            restrict_to_signed_releases(
+                content_of(repository, "--view signed-releases")?,
+                &mut commits,
+                &indices,
+                &mut all_branches,
+                settings.release_levels,
+            );
+        }
+        drop(children);
+        let (mut filtered_commits, filtered_indices, filtered_children) =
+            place_branches(commits, indices, &mut all_branches, linear, settings)?;
+        if settings.impact_thresholds.is_some() {
+            let oids: Vec<Oid> = filtered_commits.iter().map(|info| info.oid).collect();
+            let repository = content_of(repository, "--impact")?;
+            let lines = per_commit_parallel(repository, &oids, None, count_changed_lines)?;
+            for (info, lines) in filtered_commits.iter_mut().zip(lines) {
+                info.lines_changed = Some(lines);
+            }
+        }
 
-        // Remove commits not on a branch. This will give all commits a new index.
-        let filtered_commits: Vec<CommitInfo> = commits
-            .into_iter()
-            .filter(|info| info.branch_trace.is_some())
-            .collect();
+        if let Some(pickaxe) = &settings.pickaxe {
+            let oids: Vec<Oid> = filtered_commits.iter().map(|info| info.oid).collect();
+            let progress = settings.progress.then_some("Searching diffs");
+            let matches = per_commit_parallel(
+                content_of(repository, "--pickaxe")?,
+                &oids,
+                progress,
+                |repository, oid| match pickaxe {
+                    Pickaxe::Occurrences(string) => changes_occurrences(repository, oid, string),
+                    Pickaxe::Lines(pattern) => touches_content(repository, oid, pattern),
+                },
+            )?;
+            for (info, is_match) in filtered_commits.iter_mut().zip(matches) {
+                info.pickaxe_match = is_match;
+            }
+        }
 
-        // Create indices from git object id into the filtered commits
-        let filtered_indices: HashMap<Oid, usize> = filtered_commits
-            .iter()
-            .enumerate()
-            .map(|(idx, info)| (info.oid, idx))
-            .collect();
+        if let Some([first, second]) = settings.merge_base {
+            mark_merge_bases(
+                content_of(repository, "--merge-base")?,
+                &mut filtered_commits,
+                first,
+                second,
+            )?;
+        }
 
-        // Map from old index to new index. None, if old index was removed
-        let index_map: HashMap<usize, Option<&usize>> = indices
-            .iter()
-            .map(|(oid, index)| (*index, filtered_indices.get(oid)))
-            .collect();
+        let mut large_blobs = OidMap::default();
+        if let Some(threshold) = settings.large_blob_threshold {
+            let oids: Vec<Oid> = filtered_commits.iter().map(|info| info.oid).collect();
+            let repository = content_of(repository, "--large-files")?;
+            let blobs = per_commit_parallel(repository, &oids, None, |repository, oid| {
+                find_large_blobs(repository, oid, threshold)
+            })?;
+            large_blobs = non_empty(oids.into_iter().zip(blobs));
+        }
 
-        // Update branch.range from old to new index. Shrink if endpoints were removed.
-        for branch in all_branches.iter_mut() {
-            if let Some(mut start_idx) = branch.range.0 {
-                let mut idx0 = index_map[&start_idx];
-                while idx0.is_none() {
-                    start_idx += 1;
-                    idx0 = index_map[&start_idx];
-                }
-                branch.range.0 = Some(*idx0.unwrap());
-            }
-            if let Some(mut end_idx) = branch.range.1 {
-                let mut idx0 = index_map[&end_idx];
-                while idx0.is_none() {
-                    end_idx -= 1;
-                    idx0 = index_map[&end_idx];
-                }
-                branch.range.1 = Some(*idx0.unwrap());
-            }
+        let mut submodule_bumps = OidMap::default();
+        if settings.submodules.is_some() {
+            let oids: Vec<Oid> = filtered_commits.iter().map(|info| info.oid).collect();
+            let repository = content_of(repository, "--submodules")?;
+            let bumps = per_commit_parallel(repository, &oids, None, find_submodule_bumps)?;
+            submodule_bumps = non_empty(oids.into_iter().zip(bumps));
         }
 
-        let branches = all_branches
-            .iter()
-            .enumerate()
-            .filter_map(|(idx, br)| {
-                if !br.is_merged && !br.is_tag {
-                    Some(idx)
-                } else {
-                    None
-                }
-            })
-            .collect();
+        let notes = if settings.notes_refs.is_empty() {
+            OidMap::default()
+        } else {
+            read_notes(
+                content_of(repository, "--notes")?,
+                &filtered_indices,
+                &settings.notes_refs,
+            )?
+        };
 
-        let tags = all_branches
-            .iter()
-            .enumerate()
-            .filter_map(|(idx, br)| {
-                if !br.is_merged && br.is_tag {
-                    Some(idx)
-                } else {
-                    None
-                }
-            })
-            .collect();
+        let (branches, tags) = visible_branches(&all_branches, settings);
+        if settings.autosquash {
+            let repository = content_of(repository, "--autosquash")?;
+            let summaries = filtered_commits
+                .iter()
+                .map(|info| {
+                    repository
+                        .find_commit(info.oid)
+                        .map(|commit| commit.summary().unwrap_or_default().to_string())
+                        .map_err(|err| err.message().to_string())
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            mark_fixup_targets(&mut filtered_commits, &filtered_indices, &summaries);
+        }
+        let containing_branches = if settings.branch_counts {
+            find_containing_branches(
+                &filtered_commits,
+                &filtered_indices,
+                &all_branches,
+                &branches,
+            )
+        } else {
+            OidMap::default()
+        };
 
-        Ok(GitGraph {
-            repository,
+        Ok(GraphData {
             commits: filtered_commits,
             indices: filtered_indices,
+            children: filtered_children,
             all_branches,
             branches,
             tags,
             head,
+            worktrees,
+            large_blobs,
+            submodule_bumps,
+            notes,
+            containing_branches,
         })
     }
 
-    pub fn take_repository(self) -> Repository {
-        self.repository
+    /// The large blobs added by a commit, if [Settings::large_blob_threshold] is set.
+    pub fn large_blobs_of(&self, oid: Oid) -> &[LargeBlob] {
+        self.large_blobs.get(&oid).map_or(&[], Vec::as_slice)
     }
 
-    pub fn commit(&self, id: Oid) -> Result<Commit<'_>, Error> {
-        self.repository.find_commit(id)
+    /// The submodules whose commit a commit changes, if [Settings::submodules] is set.
+    pub fn submodule_bumps_of(&self, oid: Oid) -> &[SubmoduleBump] {
+        self.submodule_bumps.get(&oid).map_or(&[], Vec::as_slice)
+    }
+
+    /// The notes attached to a commit, of the notes refs of [Settings::notes_refs].
+    pub fn notes_of(&self, oid: Oid) -> &[String] {
+        self.notes.get(&oid).map_or(&[], Vec::as_slice)
+    }
+
+    /// The indices of the visible branches containing a commit, if
+    /// [Settings::branch_counts] is set.
+    pub fn containing_branches_of(&self, oid: Oid) -> &[usize] {
+        self.containing_branches
+            .get(&oid)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// The branch a commit is attributed to, if the commit is part of the graph
+    /// and attributed to any branch.
+    pub fn branch_of(&self, oid: Oid) -> Option<&BranchInfo> {
+        self.indices
+            .get(&oid)
+            .and_then(|idx| self.commits[*idx].branch_trace)
+            .map(|trace| &self.all_branches[trace])
+    }
+
+    /// The commits attributed to the branch of the given name, newest first.
+    /// Includes branches derived from merge summaries, which may occur several times
+    /// under the same name. Empty for unknown names and tags.
+    pub fn commits_of_branch(&self, name: &str) -> Vec<&CommitInfo> {
+        self.commits
+            .iter()
+            .filter(|info| {
+                info.branch_trace
+                    .map(|trace| &self.all_branches[trace])
+                    .is_some_and(|branch| !branch.is_tag && branch.name == name)
+            })
+            .collect()
     }
 }
 
 /// Information about the current HEAD
-pub struct HeadInfo {
-    pub oid: Oid,
-    pub name: String,
-    pub is_branch: bool,
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum HeadInfo {
+    /// HEAD names a branch without commits yet, like in a freshly initialized repository
+    Unborn { name: String },
+    /// HEAD points to a commit directly
+    Detached {
+        #[cfg_attr(feature = "serde", serde(with = "oid_serde::single"))]
+        oid: Oid,
+    },
+    /// HEAD names a branch, which is checked out
+    Branch {
+        name: String,
+        #[cfg_attr(feature = "serde", serde(with = "oid_serde::single"))]
+        oid: Oid,
+    },
 }
 impl HeadInfo {
-    fn new(head: &Reference) -> Result<Self, String> {
-        let name = head.name().ok_or_else(|| "No name for HEAD".to_string())?;
-        let name = if name == "HEAD" {
-            name.to_string()
+    pub fn new(head: &Reference) -> Result<Self, String> {
+        let oid = head.target().ok_or_else(|| "No id for HEAD".to_string())?;
+        if head.is_branch() {
+            let name = head
+                .shorthand()
+                .ok_or_else(|| "No name for HEAD".to_string())?;
+            Ok(HeadInfo::Branch {
+                name: name.to_string(),
+                oid,
+            })
         } else {
-            name[11..].to_string()
-        };
-
-        let h = HeadInfo {
-            oid: head.target().ok_or_else(|| "No id for HEAD".to_string())?,
-            name,
-            is_branch: head.is_branch(),
-        };
-        Ok(h)
+            Ok(HeadInfo::Detached { oid })
+        }
     }
-}
 
-/// Represents a commit.
-pub struct CommitInfo {
-    pub oid: Oid,
-    pub is_merge: bool,
-    pub parents: [Option<Oid>; 2],
-    pub children: Vec<Oid>,
-    pub branches: Vec<usize>,
-    pub tags: Vec<usize>,
-    pub branch_trace: Option<usize>,
-}
+    /// The HEAD of a repository, which may be unborn.
+    pub fn of_repository(repository: &Repository) -> Result<Self, String> {
+        match repository.head() {
+            Ok(head) => HeadInfo::new(&head),
+            Err(err) if err.code() == ErrorCode::UnbornBranch => {
+                let head = repository
+                    .find_reference("HEAD")
+                    .map_err(|err| err.message().to_string())?;
+                let target = head
+                    .symbolic_target()
+                    .ok_or_else(|| "No name for HEAD".to_string())?;
+                Ok(HeadInfo::Unborn {
+                    name: target
+                        .strip_prefix("refs/heads/")
+                        .unwrap_or(target)
+                        .to_string(),
+                })
+            }
+            Err(err) => Err(err.message().to_string()),
+        }
+    }
 
-impl CommitInfo {
-    fn new(commit: &Commit) -> Self {
-        CommitInfo {
-            oid: commit.id(),
-            is_merge: commit.parent_count() > 1,
-            parents: [commit.parent_id(0).ok(), commit.parent_id(1).ok()],
-            children: Vec::new(),
-            branches: Vec::new(),
-            tags: Vec::new(),
-            branch_trace: None,
+    /// The commit HEAD points to, `None` if unborn.
+    pub fn oid(&self) -> Option<Oid> {
+        match self {
+            HeadInfo::Unborn { .. } => None,
+            HeadInfo::Detached { oid } | HeadInfo::Branch { oid, .. } => Some(*oid),
         }
     }
-}
 
-/// Represents a branch (real or derived from merge summary).
+    /// Whether HEAD points to the given commit.
+    pub fn is_at(&self, oid: Oid) -> bool {
+        self.oid() == Some(oid)
+    }
+
+    /// The name of the checked-out branch, `None` if detached or unborn.
+    pub fn branch(&self) -> Option<&str> {
+        match self {
+            HeadInfo::Branch { name, .. } => Some(name),
+            HeadInfo::Unborn { .. } | HeadInfo::Detached { .. } => None,
+        }
+    }
+
+    /// Moves HEAD to another commit, e.g. when its commit is filtered out.
+    fn redirect(&mut self, redirects: &OidMap<Oid>) {
+        if let HeadInfo::Detached { oid } | HeadInfo::Branch { oid, .. } = self {
+            if let Some(&target) = redirects.get(oid) {
+                *oid = target;
+            }
+        }
+    }
+}
+
+/// Information about the HEAD of another worktree of the repository
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct WorktreeInfo {
+    /// The name of a linked worktree, as given by `git worktree list`,
+    /// or the directory name of the main worktree
+    pub name: String,
+    /// The worktree's directory
+    pub path: String,
+    pub head: HeadInfo,
+}
+
+/// The worktrees of a repository other than the one it was opened from, with their HEADs:
+/// the linked worktrees, and the main worktree if opened from a linked one.
+/// Worktrees with a missing directory or an unborn HEAD are skipped.
+fn other_worktrees(repository: &Repository) -> Result<Vec<WorktreeInfo>, String> {
+    let to_string = |err: Error| err.message().to_string();
+    let mut repositories: Vec<(String, Repository)> = vec![];
+    if repository.is_worktree() {
+        let main = Repository::open(repository.commondir()).map_err(to_string)?;
+        let name = main
+            .workdir()
+            .and_then(|dir| dir.file_name())
+            .map(|name| name.to_string_lossy().to_string());
+        if let Some(name) = name {
+            repositories.push((name, main));
+        }
+    }
+    for name in repository.worktrees().map_err(to_string)?.iter().flatten() {
+        let Ok(worktree) = repository.find_worktree(name) else {
+            continue;
+        };
+        if worktree.validate().is_ok() {
+            if let Ok(linked) = Repository::open_from_worktree(&worktree) {
+                repositories.push((name.to_string(), linked));
+            }
+        }
+    }
+
+    let own_dir = repository.workdir().and_then(|dir| dir.canonicalize().ok());
+    let mut worktrees = vec![];
+    for (name, other) in repositories {
+        let Some(dir) = other.workdir() else {
+            continue;
+        };
+        if dir.canonicalize().ok() == own_dir {
+            continue;
+        }
+        if let Ok(head) = other.head() {
+            worktrees.push(WorktreeInfo {
+                name,
+                path: dir.components().as_path().display().to_string(),
+                head: HeadInfo::new(&head)?,
+            });
+        }
+    }
+    Ok(worktrees)
+}
+
+/// Represents a commit.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CommitInfo {
+    #[cfg_attr(feature = "serde", serde(with = "oid_serde::single"))]
+    pub oid: Oid,
+    pub is_merge: bool,
+    #[cfg_attr(feature = "serde", serde(with = "oid_serde::pair"))]
+    pub parents: [Option<Oid>; 2],
+    pub branches: Vec<usize>,
+    pub tags: Vec<usize>,
+    pub branch_trace: Option<usize>,
+    /// Inserted plus deleted lines relative to the first parent. Only computed on demand.
+    pub lines_changed: Option<usize>,
+    /// Whether the commit carries a valid signature. Only verified on demand.
+    pub signature: Option<SignatureStatus>,
+    /// The single commit brought in by this merge, if collapsed into it
+    #[cfg_attr(feature = "serde", serde(with = "oid_serde::optional"))]
+    pub collapsed: Option<Oid>,
+    /// The branch brought in by this merge, if only first parents are followed
+    pub merged_from: Option<String>,
+    /// Number of commits hidden between this commit and each of its parents,
+    /// if parents were rewritten across commits removed from the graph
+    pub hidden: [usize; 2],
+    /// Whether this is a stash entry, or the index commit of a stash entry
+    pub is_stash: bool,
+    /// Whether this commit matches the pickaxe search. Only computed on demand.
+    pub pickaxe_match: bool,
+    /// Whether this commit is reachable only from reflogs, or from no ref at all.
+    /// Only computed for the reflog lens and dangling commits.
+    pub is_unreachable: bool,
+    /// The commit replacing this one through `refs/replace`, whose parents and metadata
+    /// are shown instead
+    #[cfg_attr(feature = "serde", serde(with = "oid_serde::optional"))]
+    pub replaced_by: Option<Oid>,
+    /// Whether the parents of this commit differ from those recorded in it, grafted by
+    /// `info/grafts` or a replace ref
+    pub grafted: bool,
+    /// The commit a `fixup!`, `squash!` or `amend!` commit will be squashed into by
+    /// `git rebase --autosquash`. Only computed on demand.
+    #[cfg_attr(feature = "serde", serde(with = "oid_serde::optional"))]
+    pub fixup_of: Option<Oid>,
+    /// Whether this commit is a merge base of the two refs given by `--merge-base`
+    pub is_merge_base: bool,
+    /// Whether this commit is reachable from neither of the refs given by `--merge-base`
+    pub is_dimmed: bool,
+    /// Whether this commit is at the boundary of a shallow clone. Its parents are missing,
+    /// and removed from [CommitInfo::parents].
+    pub shallow_boundary: bool,
+}
+
+/// The result of verifying a commit's GPG, SSH or X.509 signature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SignatureStatus {
+    /// A good signature, even if by a key of unknown validity, or expired
+    Signed,
+    Unsigned,
+    /// A bad signature, or one by a revoked key
+    BadSignature,
+    /// A signature that can't be checked, like when the key is missing
+    Unverifiable,
+}
+
+impl SignatureStatus {
+    /// Status of a signature check code of `git log --format=%G?`.
+    fn from_code(code: &str) -> Self {
+        match code {
+            "G" | "U" | "X" | "Y" => SignatureStatus::Signed,
+            "N" => SignatureStatus::Unsigned,
+            "B" | "R" => SignatureStatus::BadSignature,
+            _ => SignatureStatus::Unverifiable,
+        }
+    }
+}
+
+/// A submodule's new commit, as set by a commit of the superproject.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SubmoduleBump {
+    /// The submodule's path in the superproject
+    pub path: String,
+    #[cfg_attr(feature = "serde", serde(with = "oid_serde::single"))]
+    pub oid: Oid,
+}
+
+/// A large blob added or modified by a commit.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LargeBlob {
+    pub path: String,
+    /// Size in bytes
+    pub size: u64,
+}
+
+impl CommitInfo {
+    /// Reads only the parents and the committer time (in seconds since the epoch) of a
+    /// commit from the raw object, without parsing (and caching) its message and signatures.
+    fn read(odb: &Odb, oid: Oid) -> Result<(Self, i64), Error> {
+        let (parents, time) = read_header(odb, oid)?;
+        Ok((CommitInfo::new(oid, &parents), time))
+    }
+
+    /// Like [CommitInfo::read], but with the parents of the commit replacing it.
+    fn read_replaced(odb: &Odb, oid: Oid, replacement: Oid) -> Result<(Self, i64), Error> {
+        let (parents, time) = read_header(odb, replacement)?;
+        let mut info = CommitInfo::new(oid, &parents);
+        info.replaced_by = Some(replacement);
+        info.grafted = read_header(odb, oid)?.0 != parents;
+        Ok((info, time))
+    }
+
+    /// Replaces the parents recorded in the commit by grafted ones.
+    fn graft(&mut self, parents: &[Oid]) {
+        self.parents = [parents.first().copied(), parents.get(1).copied()];
+        self.is_merge = parents.len() > 1;
+        self.grafted = true;
+    }
+
+    /// A commit with the given parents, not yet assigned to a branch.
+    fn new(oid: Oid, parents: &[Oid]) -> Self {
+        CommitInfo {
+            oid,
+            is_merge: parents.len() > 1,
+            parents: [parents.first().copied(), parents.get(1).copied()],
+            branches: Vec::new(),
+            tags: Vec::new(),
+            branch_trace: None,
+            lines_changed: None,
+            signature: None,
+            collapsed: None,
+            merged_from: None,
+            hidden: [0, 0],
+            is_stash: false,
+            pickaxe_match: false,
+            is_unreachable: false,
+            replaced_by: None,
+            grafted: false,
+            fixup_of: None,
+            is_merge_base: false,
+            is_dimmed: false,
+            shallow_boundary: false,
+        }
+    }
+}
+
+/// Summary, author and dates of a commit, loaded on demand by [GitGraph::metadata].
+#[derive(Clone)]
+pub struct CommitMeta {
+    /// Raw summary, to be sanitized for display
+    pub summary: Vec<u8>,
+    pub author_name: Vec<u8>,
+    pub author_email: Vec<u8>,
+    pub author_time: Time,
+    /// Committer time
+    pub time: Time,
+}
+
+impl CommitMeta {
+    pub fn new(commit: &Commit) -> Self {
+        let author = commit.author();
+        CommitMeta {
+            summary: commit.summary_bytes().unwrap_or(&[]).to_vec(),
+            author_name: author.name_bytes().to_vec(),
+            author_email: author.email_bytes().to_vec(),
+            author_time: author.when(),
+            time: commit.time(),
+        }
+    }
+}
+
+/// Least recently used cache of [CommitMeta].
+#[derive(Default)]
+struct MetadataCache {
+    /// Entries never evicted, for commits of a [RepoProvider]
+    pinned: OidMap<Arc<CommitMeta>>,
+    entries: OidMap<(u64, Arc<CommitMeta>)>,
+    /// Entries by time of last use
+    usage: BTreeMap<u64, Oid>,
+    clock: u64,
+}
+
+impl MetadataCache {
+    fn get(&mut self, id: Oid) -> Option<Arc<CommitMeta>> {
+        if let Some(meta) = self.pinned.get(&id) {
+            return Some(meta.clone());
+        }
+        let (used, meta) = self.entries.get_mut(&id)?;
+        self.usage.remove(used);
+        self.clock += 1;
+        *used = self.clock;
+        self.usage.insert(self.clock, id);
+        Some(meta.clone())
+    }
+
+    fn insert(&mut self, id: Oid, meta: Arc<CommitMeta>) {
+        if self.entries.len() >= METADATA_CACHE_SIZE {
+            if let Some((_, oldest)) = self.usage.pop_first() {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.clock += 1;
+        self.usage.insert(self.clock, id);
+        if let Some((used, _)) = self.entries.insert(id, (self.clock, meta)) {
+            self.usage.remove(&used);
+        }
+    }
+}
+
+/// The changes of a commit relative to its first parent.
+fn diff_to_first_parent(repository: &Repository, oid: Oid) -> Result<Diff<'_>, Error> {
+    let commit = repository.find_commit(oid)?;
+    let tree = commit.tree()?;
+    let parent_tree = match commit.parent(0) {
+        Ok(parent) => Some(parent.tree()?),
+        Err(_) => None,
+    };
+    repository.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)
+}
+
+/// Counts the inserted and deleted lines of a commit relative to its first parent.
+fn count_changed_lines(repository: &Repository, oid: Oid) -> Result<usize, Error> {
+    let stats = diff_to_first_parent(repository, oid)?.stats()?;
+    Ok(stats.insertions() + stats.deletions())
+}
+
+/// Whether a commit adds or removes lines matching the pattern, relative to its first
+/// parent, like `git log -G`. Merges are not searched.
+fn touches_content(repository: &Repository, oid: Oid, pattern: &Regex) -> Result<bool, Error> {
+    if repository.find_commit(oid)?.parent_count() > 1 {
+        return Ok(false);
+    }
+    let diff = diff_to_first_parent(repository, oid)?;
+    let mut found = false;
+    let result = diff.foreach(
+        &mut |_, _| true,
+        None,
+        None,
+        Some(&mut |_, _, line| {
+            found = matches!(line.origin(), '+' | '-')
+                && pattern.is_match(&String::from_utf8_lossy(line.content()));
+            // Stop at the first match
+            !found
+        }),
+    );
+    match result {
+        Err(_) if found => Ok(true),
+        result => result.map(|_| false),
+    }
+}
+
+/// The lines of a symbol's definition in a file's content, 1-based and inclusive:
+/// from the first line matching the definition pattern to the last line indented deeper,
+/// including a closing bracket or `end` at the definition's indentation. A closing bracket
+/// opening the body, like `) -> T {` of a multi-line signature, does not end the definition.
+fn symbol_range(content: &str, definition: &Regex) -> Option<(usize, usize)> {
+    let lines: Vec<&str> = content.lines().collect();
+    let start = lines.iter().position(|line| definition.is_match(line))?;
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let start_indent = indent(lines[start]);
+    let mut end = start;
+    for (idx, line) in lines.iter().enumerate().skip(start + 1) {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        if indent(line) > start_indent || trimmed.starts_with(['{', '(']) {
+            end = idx;
+            continue;
+        }
+        if trimmed.starts_with(['}', ')', ']']) || trimmed == "end" || trimmed.starts_with("end ") {
+            end = idx;
+            if trimmed.ends_with(['{', '(', ':']) {
+                continue;
+            }
+        }
+        break;
+    }
+    Some((start + 1, end + 1))
+}
+
+/// Whether a commit changes lines of a symbol's definition, relative to its first parent,
+/// approximating `git log -L :name:file`. Merges are not searched, and renames are not followed.
+fn changes_symbol(repository: &Repository, oid: Oid, symbol: &Symbol) -> Result<bool, Error> {
+    let commit = repository.find_commit(oid)?;
+    if commit.parent_count() > 1 {
+        return Ok(false);
+    }
+    let path = Path::new(&symbol.path);
+    let blob_id = |tree: &Tree| tree.get_path(path).ok().map(|entry| entry.id());
+    let new_id = blob_id(&commit.tree()?);
+    let old_id = match commit.parent(0) {
+        Ok(parent) => blob_id(&parent.tree()?),
+        Err(_) => None,
+    };
+    if new_id == old_id {
+        return Ok(false);
+    }
+    let blob = |id: Option<Oid>| id.map(|id| repository.find_blob(id)).transpose();
+    let (old_blob, new_blob) = (blob(old_id)?, blob(new_id)?);
+    let range = |blob: &Option<git2::Blob>| {
+        blob.as_ref().and_then(|blob| {
+            symbol_range(&String::from_utf8_lossy(blob.content()), &symbol.definition)
+        })
+    };
+    let (old_range, new_range) = (range(&old_blob), range(&new_blob));
+    if old_range.is_none() && new_range.is_none() {
+        return Ok(false);
+    }
+
+    let overlaps = |start: u32, lines: u32, range: Option<(usize, usize)>| {
+        let (start, lines) = (start as usize, lines as usize);
+        range.is_some_and(|(first, last)| lines > 0 && start <= last && start + lines > first)
+    };
+    let mut found = false;
+    // Without context, hunks span only the changed lines
+    let mut options = DiffOptions::new();
+    options.context_lines(0);
+    let result = repository.diff_blobs(
+        old_blob.as_ref(),
+        Some(&symbol.path),
+        new_blob.as_ref(),
+        Some(&symbol.path),
+        Some(&mut options),
+        None,
+        None,
+        Some(&mut |_, hunk| {
+            found = overlaps(hunk.old_start(), hunk.old_lines(), old_range)
+                || overlaps(hunk.new_start(), hunk.new_lines(), new_range);
+            // Stop at the first hunk changing the symbol
+            !found
+        }),
+        None,
+    );
+    match result {
+        Err(_) if found => Ok(true),
+        result => result.map(|_| false),
+    }
+}
+
+/// Whether a commit changes the number of occurrences of a string in any file,
+/// relative to its first parent, like `git log -S`. Merges and binary files are not searched.
+fn changes_occurrences(repository: &Repository, oid: Oid, string: &str) -> Result<bool, Error> {
+    if string.is_empty() || repository.find_commit(oid)?.parent_count() > 1 {
+        return Ok(false);
+    }
+    let count = |id: Oid| -> Result<usize, Error> {
+        if id.is_zero() {
+            return Ok(0);
+        }
+        let blob = repository.find_blob(id)?;
+        if blob.is_binary() {
+            return Ok(0);
+        }
+        Ok(String::from_utf8_lossy(blob.content())
+            .matches(string)
+            .count())
+    };
+    for delta in diff_to_first_parent(repository, oid)?.deltas() {
+        if count(delta.old_file().id())? != count(delta.new_file().id())? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// The blobs added or modified by a commit relative to its first parent,
+/// with a size of at least `threshold` bytes. For merges, blobs taken unchanged
+/// from another parent are not counted.
+fn find_large_blobs(
+    repository: &Repository,
+    oid: Oid,
+    threshold: u64,
+) -> Result<Vec<LargeBlob>, Error> {
+    let diff = diff_to_first_parent(repository, oid)?;
+    let merged_trees = repository
+        .find_commit(oid)?
+        .parents()
+        .skip(1)
+        .map(|parent| parent.tree())
+        .collect::<Result<Vec<_>, Error>>()?;
+    let odb = repository.odb()?;
+    let mut blobs = vec![];
+    for delta in diff.deltas() {
+        if !matches!(delta.status(), Delta::Added | Delta::Modified) {
+            continue;
+        }
+        let file = delta.new_file();
+        let (Some(path), (size, _)) = (file.path(), odb.read_header(file.id())?) else {
+            continue;
+        };
+        let merged = merged_trees.iter().any(|tree| {
+            tree.get_path(path)
+                .is_ok_and(|entry| entry.id() == file.id())
+        });
+        if size as u64 >= threshold && !merged {
+            blobs.push(LargeBlob {
+                path: path.to_string_lossy().to_string(),
+                size: size as u64,
+            });
+        }
+    }
+    Ok(blobs)
+}
+
+/// Finds the submodules added or moved to another commit, relative to the first parent.
+fn find_submodule_bumps(repository: &Repository, oid: Oid) -> Result<Vec<SubmoduleBump>, Error> {
+    let diff = diff_to_first_parent(repository, oid)?;
+    Ok(diff
+        .deltas()
+        .filter(|delta| matches!(delta.status(), Delta::Added | Delta::Modified))
+        .map(|delta| delta.new_file())
+        .filter(|file| file.mode() == FileMode::Commit)
+        .filter_map(|file| {
+            file.path().map(|path| SubmoduleBump {
+                path: path.to_string_lossy().to_string(),
+                oid: file.id(),
+            })
+        })
+        .collect())
+}
+
+/// The text of the notes of the given notes refs, of the commits in the graph.
+/// Notes refs that don't exist are skipped.
+fn read_notes(
+    repository: &Repository,
+    indices: &OidMap<usize>,
+    notes_refs: &[String],
+) -> Result<OidMap<Vec<String>>, String> {
+    let to_string = |err: Error| err.message().to_string();
+    let mut texts: OidMap<Vec<String>> = OidMap::default();
+    for notes_ref in notes_refs {
+        let notes = match repository.notes(Some(notes_ref)) {
+            Ok(notes) => notes,
+            Err(err) if err.code() == ErrorCode::NotFound => continue,
+            Err(err) => return Err(to_string(err)),
+        };
+        for note in notes {
+            let (note_id, oid) = note.map_err(to_string)?;
+            if indices.contains_key(&oid) {
+                let blob = repository.find_blob(note_id).map_err(to_string)?;
+                let text = String::from_utf8_lossy(blob.content());
+                texts
+                    .entry(oid)
+                    .or_default()
+                    .push(text.trim_end().to_string());
+            }
+        }
+    }
+    Ok(texts)
+}
+
+/// Verifies the signatures of commits like `git log --format=%G?`, by the `git`
+/// executable, which checks GPG, SSH and X.509 signatures with the user's configuration.
+/// Commits without a signature are recognized without running git.
+fn verify_signatures(repository: &Repository, commits: &mut [CommitInfo]) -> Result<(), String> {
+    let mut signed = vec![];
+    for (idx, info) in commits.iter_mut().enumerate() {
+        match repository.extract_signature(&info.oid, None) {
+            Ok(_) => signed.push(idx),
+            Err(err) if err.code() == ErrorCode::NotFound => {
+                info.signature = Some(SignatureStatus::Unsigned)
+            }
+            Err(err) => return Err(err.message().to_string()),
+        }
+    }
+    if signed.is_empty() {
+        return Ok(());
+    }
+
+    let mut child = Command::new("git")
+        .arg("--git-dir")
+        .arg(repository.path())
+        .args(["log", "--no-walk=unsorted", "--stdin", "--format=%H %G?"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("Can't run git: {}", err))?;
+    let input: String = signed
+        .iter()
+        .map(|idx| format!("{}\n", commits[*idx].oid))
+        .collect();
+    // Write on another thread, as git may block on a full output pipe
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child
+        .wait_with_output()
+        .map_err(|err| format!("Can't run git: {}", err))?;
+    writer
+        .join()
+        .unwrap()
+        .map_err(|err| format!("Can't run git: {}", err))?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr);
+        return Err(format!(
+            "Can't verify signatures: {}",
+            message.lines().last().unwrap_or_default()
+        ));
+    }
+
+    let mut statuses = OidMap::default();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        if let Some((oid, code)) = line.split_once(' ') {
+            let oid = Oid::from_str(oid).map_err(|err| err.message().to_string())?;
+            statuses.insert(oid, SignatureStatus::from_code(code));
+        }
+    }
+    for idx in signed {
+        let info = &mut commits[idx];
+        info.signature = Some(
+            statuses
+                .get(&info.oid)
+                .copied()
+                .unwrap_or(SignatureStatus::Unverifiable),
+        );
+    }
+    Ok(())
+}
+
+/// Computes a result for many commits on a bounded pool of worker threads,
+/// each with its own handle to the repository. Returns the results in the order of `oids`,
+/// once all are computed, as the layout needs them all. Reports the number of commits done on stderr, if a progress label is given.
+fn per_commit_parallel<T, F>(
+    repository: &Repository,
+    oids: &[Oid],
+    progress: Option<&str>,
+    f: F,
+) -> Result<Vec<T>, String>
+where
+    T: Default + Send,
+    F: Fn(&Repository, Oid) -> Result<T, Error> + Sync,
+{
+    let workers = thread::available_parallelism()
+        .map_or(1, |count| count.get())
+        .min(MAX_DIFF_WORKERS)
+        .min(oids.len());
+    let next = AtomicUsize::new(0);
+    let path = repository.path();
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let sender = sender.clone();
+            let next = &next;
+            let f = &f;
+            scope.spawn(move || {
+                let repository = match Repository::open(path) {
+                    Ok(repository) => repository,
+                    Err(err) => {
+                        let _ = sender.send((0, Err(err)));
+                        return;
+                    }
+                };
+                loop {
+                    let idx = next.fetch_add(1, Ordering::Relaxed);
+                    if idx >= oids.len() {
+                        break;
+                    }
+                    let result = f(&repository, oids[idx]);
+                    if sender.send((idx, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        // Collect results as they arrive, stopping all workers on the first error
+        let mut results: Vec<T> = oids.iter().map(|_| T::default()).collect();
+        for (done, (idx, result)) in receiver.into_iter().enumerate() {
+            if let Some(label) = progress.filter(|_| done % PROGRESS_STEP == 0) {
+                eprint!("\r{}: {}/{} commits", label, done, oids.len());
+            }
+            match result {
+                Ok(value) => results[idx] = value,
+                Err(err) => {
+                    next.store(oids.len(), Ordering::Relaxed);
+                    return Err(err.message().to_string());
+                }
+            }
+        }
+        if progress.is_some() {
+            // Clear the progress line
+            eprint!("\r\x1b[2K");
+        }
+        Ok(results)
+    })
+}
+
+/// Represents a branch (real or derived from merge summary).
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct BranchInfo {
+    #[cfg_attr(feature = "serde", serde(with = "oid_serde::single"))]
     pub target: Oid,
+    #[cfg_attr(feature = "serde", serde(with = "oid_serde::optional"))]
     pub merge_target: Option<Oid>,
     pub source_branch: Option<usize>,
     pub target_branch: Option<usize>,
@@ -259,6 +1648,16 @@ pub struct BranchInfo {
     pub is_remote: bool,
     pub is_merged: bool,
     pub is_tag: bool,
+    /// Whether this is the default branch named by `origin/HEAD`, or its local branch.
+    /// Default branches take the leftmost column, whatever the branching model
+    pub is_default: bool,
+    /// Whether its remote's HEAD points to this remote branch, e.g. `origin/HEAD`
+    pub is_remote_head: bool,
+    /// Whether the branch's tip commit subject marks it as work in progress
+    pub is_wip: bool,
+    /// Commits ahead of and behind the upstream branch, for local branches with an upstream,
+    /// if counted with [Settings::ahead_behind]
+    pub ahead_behind: Option<(usize, usize)>,
     pub visual: BranchVis,
     pub range: (Option<usize>, Option<usize>),
 }
@@ -285,13 +1684,43 @@ impl BranchInfo {
             is_remote,
             is_merged,
             is_tag,
+            is_default: false,
+            is_remote_head: false,
+            is_wip: false,
+            ahead_behind: None,
             visual,
             range: (end_index, None),
         }
     }
+
+    /// Whether this is a branch all of whose commits are claimed by other branches,
+    /// e.g. a branch pointing into `main`. Such branches occupy no column, except
+    /// for the shared lane of [EmptyBranches::Lane].
+    pub fn has_no_commits(&self) -> bool {
+        !self.is_tag && !self.is_merged && self.range == (None, None)
+    }
+
+    /// Whether this is a remote branch taking the color of another branch it runs into:
+    /// its local branch, or with [RemoteColors::All] another remote's branch of the same name.
+    fn inherits_color(&self, other: &BranchInfo, mode: RemoteColors) -> bool {
+        match mode {
+            RemoteColors::Origin => self.name.strip_prefix(ORIGIN) == Some(&other.name[..]),
+            RemoteColors::All => self.is_remote && self.local_name() == other.local_name(),
+            RemoteColors::Distinct => false,
+        }
+    }
+
+    /// The name without the remote, for remote branches.
+    fn local_name(&self) -> &str {
+        match self.name.split_once('/') {
+            Some((_, name)) if self.is_remote => name,
+            _ => &self.name,
+        }
+    }
 }
 
 /// Branch properties for visualization.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct BranchVis {
     /// The branch's column group (left to right)
     pub order_group: usize,
@@ -320,37 +1749,232 @@ impl BranchVis {
     }
 }
 
-/// Walks through the commits and adds each commit's Oid to the children of its parents.
-fn assign_children(commits: &mut [CommitInfo], indices: &HashMap<Oid, usize>) {
+/// Assigns commits to branches. Returns whether the history is linear, in which case
+/// all commits are on a single branch and columns are already assigned.
+fn trace_branches(
+    commits: &mut [CommitInfo],
+    indices: &OidMap<usize>,
+    children: &Adjacency,
+    all_branches: &mut Vec<BranchInfo>,
+    foxtrots: &OidMap<String>,
+    settings: &Settings,
+) -> bool {
+    let linear = settings.view != GraphView::SignedReleases
+        && assign_linear(commits, indices, all_branches, settings.remote_colors);
+    if !linear {
+        let options = TraceOptions {
+            foxtrots,
+            remote_colors: settings.remote_colors,
+        };
+        *all_branches = assign_branches(
+            commits,
+            indices,
+            children,
+            std::mem::take(all_branches),
+            &options,
+        );
+    }
+    linear
+}
+
+/// Places the traced branches in columns, and removes commits not on a branch.
+/// Returns the remaining commits, with their indices and children.
+fn place_branches(
+    mut commits: Vec<CommitInfo>,
+    indices: OidMap<usize>,
+    all_branches: &mut [BranchInfo],
+    linear: bool,
+    settings: &Settings,
+) -> Result<(Vec<CommitInfo>, OidMap<usize>, Adjacency), String> {
+    if let Some(list) = &settings.commit_list {
+        restrict_to_commits(&mut commits, &indices, all_branches, list);
+    }
+    correct_fork_merges(&commits, &indices, all_branches, settings)?;
+    assign_sources_targets(&commits, &indices, all_branches);
+
+    let (shortest_first, forward) = match settings.branch_order {
+        BranchOrder::ShortestFirst(fwd) => (true, fwd),
+        BranchOrder::LongestFirst(fwd) => (false, fwd),
+    };
+
+    if !linear {
+        assign_branch_columns(
+            &commits,
+            &indices,
+            all_branches,
+            &settings.branches,
+            shortest_first,
+            forward,
+        );
+    }
+
+    drop(indices);
+
+    // Remove commits not on a branch. This will give all commits a new index.
+    // Map from old index to new index. None, if old index was removed
+    let mut index_map: Vec<Option<usize>> = Vec::with_capacity(commits.len());
+    let mut filtered_commits: Vec<CommitInfo> = Vec::new();
+    for info in commits {
+        if info.branch_trace.is_some() {
+            index_map.push(Some(filtered_commits.len()));
+            filtered_commits.push(info);
+        } else {
+            index_map.push(None);
+        }
+    }
+
+    // Create indices from git object id into the filtered commits
+    let filtered_indices: OidMap<usize> = filtered_commits
+        .iter()
+        .enumerate()
+        .map(|(idx, info)| (info.oid, idx))
+        .collect();
+    let filtered_children = Adjacency::children(&filtered_commits, &filtered_indices);
+
+    // Update branch.range from old to new index. Shrink if endpoints were removed.
+    for branch in all_branches.iter_mut() {
+        branch.range = remap_range(branch.range, &index_map);
+    }
+
+    place_empty_branches(
+        &mut filtered_commits,
+        &filtered_indices,
+        all_branches,
+        settings.empty_branches,
+    );
+
+    Ok((filtered_commits, filtered_indices, filtered_children))
+}
+
+/// Lists the branches containing each commit, of the given indices into `all_branches`.
+/// Each branch is passed down from its tip to all ancestors, as a bit set per commit.
+/// Commits in none of the branches are left out.
+fn find_containing_branches(
+    commits: &[CommitInfo],
+    indices: &OidMap<usize>,
+    all_branches: &[BranchInfo],
+    branches: &[usize],
+) -> OidMap<Vec<usize>> {
+    let words = branches.len().div_ceil(64);
+    let mut sets = vec![0u64; commits.len() * words];
+    for (bit, branch) in branches.iter().enumerate() {
+        if let Some(&idx) = indices.get(&all_branches[*branch].target) {
+            sets[idx * words + bit / 64] |= 1 << (bit % 64);
+        }
+    }
+    // Children come before their parents
+    for idx in 0..commits.len() {
+        for parent in commits[idx].parents.iter().flatten() {
+            if let Some(&par_idx) = indices.get(parent) {
+                for word in 0..words {
+                    sets[par_idx * words + word] |= sets[idx * words + word];
+                }
+            }
+        }
+    }
+    non_empty(commits.iter().enumerate().map(|(idx, info)| {
+        let set = &sets[idx * words..(idx + 1) * words];
+        let containing = (0..branches.len())
+            .filter(|bit| set[bit / 64] & (1 << (bit % 64)) != 0)
+            .map(|bit| branches[bit])
+            .collect();
+        (info.oid, containing)
+    }))
+}
+
+/// A side table of the non-empty lists of the given commits.
+fn non_empty<T>(lists: impl Iterator<Item = (Oid, Vec<T>)>) -> OidMap<Vec<T>> {
+    lists.filter(|(_, list)| !list.is_empty()).collect()
+}
+
+/// Splits the subject of a commit made for `git rebase --autosquash`, like `fixup! Add parser`,
+/// into its kind (`fixup`, `squash` or `amend`) and the subject or id of its target.
+/// Nested prefixes, like `fixup! fixup! Add parser`, name the same target.
+pub fn autosquash_subject(summary: &str) -> Option<(&'static str, &str)> {
+    let mut kind = None;
+    let mut rest = summary;
+    while let Some((next_kind, next)) = ["fixup", "squash", "amend"].into_iter().find_map(|kind| {
+        rest.strip_prefix(kind)
+            .and_then(|rest| rest.strip_prefix("! "))
+            .map(|rest| (kind, rest))
+    }) {
+        kind.get_or_insert(next_kind);
+        rest = next;
+    }
+    kind.map(|kind| (kind, rest))
+}
+
+/// Finds the commits that `fixup!`, `squash!` and `amend!` commits will be squashed into,
+/// like `git rebase --autosquash`: the nearest first-parent ancestor whose id starts with
+/// the target, or whose subject does, skipping other autosquash commits. `summaries` are
+/// the subjects of the commits.
+fn mark_fixup_targets(commits: &mut [CommitInfo], indices: &OidMap<usize>, summaries: &[String]) {
     for idx in 0..commits.len() {
-        let (oid, parents) = {
-            let info = &commits[idx];
-            (info.oid, info.parents)
+        let Some((_, target)) = autosquash_subject(&summaries[idx]) else {
+            continue;
         };
-        for par_oid in &parents {
-            if let Some(par_idx) = par_oid.and_then(|oid| indices.get(&oid)) {
-                commits[*par_idx].children.push(oid);
+        if target.is_empty() {
+            continue;
+        }
+        let mut parent = commits[idx].parents[0];
+        while let Some(&par_idx) = parent.and_then(|oid| indices.get(&oid)) {
+            let summary = &summaries[par_idx];
+            let matches_id =
+                target.len() >= 4 && commits[par_idx].oid.to_string().starts_with(target);
+            let matches_subject =
+                autosquash_subject(summary).is_none() && summary.starts_with(target);
+            if matches_id || matches_subject {
+                commits[idx].fixup_of = Some(commits[par_idx].oid);
+                break;
             }
+            parent = commits[par_idx].parents[0];
         }
     }
 }
 
-/// Extracts branches from repository and merge summaries, assigns branches and branch traces to commits.
+/// Indices of the branches and of the tags to show in `all_branches`.
+fn visible_branches(all_branches: &[BranchInfo], settings: &Settings) -> (Vec<usize>, Vec<usize>) {
+    let branches = all_branches
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, br)| {
+            let hidden = settings.empty_branches == EmptyBranches::Hide && br.has_no_commits();
+            if !br.is_merged && !br.is_tag && !hidden {
+                Some(idx)
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    let tags = all_branches
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, br)| {
+            if !br.is_merged && br.is_tag {
+                Some(idx)
+            } else {
+                None
+            }
+        })
+        .collect();
+    (branches, tags)
+}
+
+/// Assigns branches and branch traces to commits, for the branches found by [extract_branches].
 ///
 /// Algorithm:
-/// * Find all actual branches (incl. target oid) and all extract branches from merge summaries (incl. parent oid)
-/// * Sort all branches by persistence
 /// * Iterating over all branches in persistence order, trace back over commit parents until a trace is already assigned
+/// * Drop merged branches that did not claim any commits
 fn assign_branches(
-    repository: &Repository,
     commits: &mut [CommitInfo],
-    indices: &HashMap<Oid, usize>,
-    settings: &Settings,
-) -> Result<Vec<BranchInfo>, String> {
+    indices: &OidMap<usize>,
+    children: &Adjacency,
+    mut branches: Vec<BranchInfo>,
+    options: &TraceOptions,
+) -> Vec<BranchInfo> {
     let mut branch_idx = 0;
 
-    let mut branches = extract_branches(repository, commits, indices, settings)?;
-
     let mut index_map: Vec<_> = (0..branches.len())
         .map(|old_idx| {
             let (target, is_tag, is_merged) = {
@@ -365,9 +1989,15 @@ fn assign_branches(
                     info.branches.push(old_idx);
                 }
                 let oid = info.oid;
-                let any_assigned =
-                    trace_branch(repository, commits, indices, &mut branches, oid, old_idx)
-                        .unwrap_or(false);
+                let any_assigned = trace_branch(
+                    commits,
+                    indices,
+                    children,
+                    &mut branches,
+                    oid,
+                    old_idx,
+                    options,
+                );
 
                 if any_assigned || !is_merged {
                     branch_idx += 1;
@@ -424,584 +2054,3488 @@ fn assign_branches(
         })
         .collect();
 
-    Ok(branches)
+    branches
 }
 
-fn correct_fork_merges(
-    commits: &[CommitInfo],
-    indices: &HashMap<Oid, usize>,
-    branches: &mut [BranchInfo],
-    settings: &Settings,
+/// Seeds a revision walk with a revision or range, like git log: `B` shows the history
+/// of B, `^A` excludes the history of A, `A..B` shows B's history excluding A's, and
+/// `A...B` the commits reachable from either but not from both.
+fn push_revision_range(
+    repository: &Repository,
+    walk: &mut Revwalk,
+    spec: &str,
 ) -> Result<(), String> {
-    for idx in 0..branches.len() {
-        if let Some(merge_target) = branches[idx]
-            .merge_target
-            .and_then(|oid| indices.get(&oid))
-            .and_then(|idx| commits.get(*idx))
-            .and_then(|info| info.branch_trace)
-            .and_then(|trace| branches.get(trace))
-        {
-            if branches[idx].name == merge_target.name {
-                let name = format!("{}{}", FORK, branches[idx].name);
-                let term_col = to_terminal_color(
-                    &branch_color(
-                        &name,
-                        &settings.branches.terminal_colors[..],
-                        &settings.branches.terminal_colors_unknown,
-                        idx,
-                    )[..],
-                )?;
-                let pos = branch_order(&name, &settings.branches.order);
-                let svg_col = branch_color(
-                    &name,
-                    &settings.branches.svg_colors,
-                    &settings.branches.svg_colors_unknown,
-                    idx,
-                );
-
-                branches[idx].name = format!("{}{}", FORK, branches[idx].name);
-                branches[idx].visual.order_group = pos;
-                branches[idx].visual.term_color = term_col;
-                branches[idx].visual.svg_color = svg_col;
+    let error = |err: Error| format!("Invalid revision range '{}': {}", spec, err.message());
+    if let Some(excluded) = spec.strip_prefix('^') {
+        let object = repository.revparse_single(excluded).map_err(error)?;
+        return walk.hide(object.id()).map_err(error);
+    }
+    let revspec = repository.revparse(spec).map_err(error)?;
+    let (from, to) = (
+        revspec.from().map(|obj| obj.id()),
+        revspec.to().map(|obj| obj.id()),
+    );
+    let mode = revspec.mode();
+    match (from, to) {
+        (Some(from), Some(to)) if mode.contains(RevparseMode::MERGE_BASE) => {
+            walk.push(from).map_err(error)?;
+            walk.push(to).map_err(error)?;
+            let bases = repository.merge_bases(from, to).map_err(error)?;
+            for base in bases.iter() {
+                walk.hide(*base).map_err(error)?;
             }
         }
+        (Some(from), Some(to)) if mode.contains(RevparseMode::RANGE) => {
+            walk.hide(from).map_err(error)?;
+            walk.push(to).map_err(error)?;
+        }
+        (Some(oid), _) | (None, Some(oid)) => walk.push(oid).map_err(error)?,
+        (None, None) => return Err(format!("Invalid revision range '{}'", spec)),
     }
     Ok(())
 }
-fn assign_sources_targets(
-    commits: &[CommitInfo],
-    indices: &HashMap<Oid, usize>,
-    branches: &mut [BranchInfo],
-) {
-    for idx in 0..branches.len() {
-        let target_branch_idx = branches[idx]
-            .merge_target
-            .and_then(|oid| indices.get(&oid))
-            .and_then(|idx| commits.get(*idx))
-            .and_then(|info| info.branch_trace);
 
-        branches[idx].target_branch = target_branch_idx;
+/// Fast path for purely linear histories, where every commit's only parent is the
+/// next commit: if the first branch in persistence order points to the newest commit,
+/// it claims all commits in a single column, and tracing and column assignment
+/// can be skipped. Produces the same layout as [assign_branches] and
+/// [assign_branch_columns] would. Returns `false`, without any changes, if the
+/// history is not linear or the fast path does not apply.
+fn assign_linear(
+    commits: &mut [CommitInfo],
+    indices: &OidMap<usize>,
+    branches: &mut Vec<BranchInfo>,
+    remote_colors: RemoteColors,
+) -> bool {
+    let is_linear = commits.iter().all(|info| info.parents[1].is_none())
+        && commits
+            .windows(2)
+            .all(|pair| pair[0].parents[0] == Some(pair[1].oid));
+    let first = branches
+        .iter()
+        .find(|branch| indices.contains_key(&branch.target));
+    match (commits.first(), first) {
+        (Some(tip), Some(first)) if is_linear && !first.is_tag && first.target == tip.oid => {}
+        _ => return false,
+    }
 
-        let group = target_branch_idx
-            .and_then(|trace| branches.get(trace))
-            .map(|br| br.visual.order_group);
+    branches.retain(|branch| indices.contains_key(&branch.target));
+    let (term_color, svg_color) = (
+        branches[0].visual.term_color,
+        branches[0].visual.svg_color.clone(),
+    );
+    let inherits: Vec<bool> = branches
+        .iter()
+        .map(|branch| branch.inherits_color(&branches[0], remote_colors))
+        .collect();
+    for (idx, branch) in branches.iter_mut().enumerate() {
+        let info = &mut commits[indices[&branch.target]];
+        if branch.is_tag {
+            info.tags.push(idx);
+        } else {
+            info.branches.push(idx);
+        }
+        if idx == 0 {
+            continue;
+        }
+        // Any other branch finds its commits already claimed
+        branch.range = (None, None);
+        if inherits[idx] {
+            branch.visual.term_color = term_color;
+            branch.visual.svg_color = svg_color.clone();
+        }
+    }
 
-        branches[idx].visual.target_order_group = group;
+    for info in commits.iter_mut() {
+        info.branch_trace = Some(0);
     }
-    for info in commits {
-        let mut max_par_order = None;
-        let mut source_branch_id = None;
-        for par_oid in info.parents.iter() {
-            let par_info = par_oid
-                .and_then(|oid| indices.get(&oid))
-                .and_then(|idx| commits.get(*idx));
-            if let Some(par_info) = par_info {
-                if par_info.branch_trace != info.branch_trace {
-                    if let Some(trace) = par_info.branch_trace {
-                        source_branch_id = Some(trace);
-                    }
+    // The trace ends at the root, or continues beyond the commits covered
+    let root = commits.last().filter(|info| info.parents[0].is_none());
+    branches[0].range.1 = root.map(|_| commits.len() - 1);
+    branches[0].visual.column = Some(0);
+    true
+}
 
-                    let group = par_info
-                        .branch_trace
-                        .and_then(|trace| branches.get(trace))
-                        .map(|br| br.visual.order_group);
-                    if let Some(gr) = max_par_order {
-                        if let Some(p_group) = group {
-                            if p_group > gr {
-                                max_par_order = group;
-                            }
-                        }
-                    } else {
-                        max_par_order = group;
-                    }
-                }
-            }
+/// The parents and the committer time of a commit, read from the raw object.
+fn read_header(odb: &Odb, oid: Oid) -> Result<(Vec<Oid>, i64), Error> {
+    let object = odb.read(oid)?;
+    let mut parents = vec![];
+    let mut time = 0;
+    for line in object.data().split(|byte| *byte == b'\n') {
+        if line.is_empty() {
+            break;
         }
-        let branch = info.branch_trace.and_then(|trace| branches.get_mut(trace));
-        if let Some(branch) = branch {
-            if let Some(order) = max_par_order {
-                branch.visual.source_order_group = Some(order);
-            }
-            if let Some(source_id) = source_branch_id {
-                branch.source_branch = Some(source_id);
-            }
+        if let Some(hex) = line.strip_prefix(b"parent ") {
+            parents.push(Oid::from_str(&String::from_utf8_lossy(hex))?);
+        } else if let Some(committer) = line.strip_prefix(b"committer ") {
+            // `Name <email> <time> <offset>`
+            time = String::from_utf8_lossy(committer)
+                .rsplit(' ')
+                .nth(1)
+                .and_then(|time| time.parse().ok())
+                .unwrap_or(0);
         }
     }
+    Ok((parents, time))
 }
 
-/// Extracts (real or derived from merge summary) and assigns basic properties.
-fn extract_branches(
-    repository: &Repository,
-    commits: &[CommitInfo],
-    indices: &HashMap<Oid, usize>,
-    settings: &Settings,
-) -> Result<Vec<BranchInfo>, String> {
-    let filter = if settings.include_remote {
-        None
-    } else {
-        Some(BranchType::Local)
-    };
-    let actual_branches = repository
-        .branches(filter)
+/// The commits replacing others, by the id of the commit they replace, as set by
+/// `git replace` in `refs/replace/<oid>`.
+fn replace_refs(repository: &Repository) -> Result<OidMap<Oid>, String> {
+    let mut replacements = OidMap::default();
+    for reference in repository
+        .references_glob("refs/replace/*")
         .map_err(|err| err.message().to_string())?
-        .collect::<Result<Vec<_>, Error>>()
-        .map_err(|err| err.message().to_string())?;
-
-    let mut counter = 0;
-
-    let mut valid_branches = actual_branches
-        .iter()
-        .filter_map(|(br, tp)| {
-            br.get().name().and_then(|n| {
-                br.get().target().map(|t| {
-                    counter += 1;
-                    let start_index = match tp {
-                        BranchType::Local => 11,
-                        BranchType::Remote => 13,
-                    };
-                    let name = &n[start_index..];
-                    let end_index = indices.get(&t).cloned();
-
-                    let term_color = match to_terminal_color(
-                        &branch_color(
-                            name,
-                            &settings.branches.terminal_colors[..],
-                            &settings.branches.terminal_colors_unknown,
-                            counter,
-                        )[..],
-                    ) {
-                        Ok(col) => col,
-                        Err(err) => return Err(err),
-                    };
-
-                    Ok(BranchInfo::new(
-                        t,
-                        None,
-                        name.to_string(),
-                        branch_order(name, &settings.branches.persistence) as u8,
-                        &BranchType::Remote == tp,
-                        false,
-                        false,
-                        BranchVis::new(
-                            branch_order(name, &settings.branches.order),
-                            term_color,
-                            branch_color(
-                                name,
-                                &settings.branches.svg_colors,
-                                &settings.branches.svg_colors_unknown,
-                                counter,
-                            ),
-                        ),
-                        end_index,
-                    ))
-                })
-            })
-        })
-        .collect::<Result<Vec<_>, String>>()?;
-
-    for (idx, info) in commits.iter().enumerate() {
-        let commit = repository
-            .find_commit(info.oid)
-            .map_err(|err| err.message().to_string())?;
-        if info.is_merge {
-            if let Some(summary) = commit.summary() {
-                counter += 1;
-
-                let parent_oid = commit
-                    .parent_id(1)
-                    .map_err(|err| err.message().to_string())?;
-
-                let branch_name = parse_merge_summary(summary, &settings.merge_patterns)
-                    .unwrap_or_else(|| "unknown".to_string());
+    {
+        let reference = reference.map_err(|err| err.message().to_string())?;
+        let (Some(name), Some(target)) = (reference.name(), reference.target()) else {
+            continue;
+        };
+        if let Ok(original) = Oid::from_str(name.trim_start_matches("refs/replace/")) {
+            replacements.insert(original, target);
+        }
+    }
+    Ok(replacements)
+}
 
-                let persistence = branch_order(&branch_name, &settings.branches.persistence) as u8;
+/// The grafted parents of commits, as listed in the deprecated `info/grafts` file,
+/// one commit per line followed by its parents. Empty if there is no such file.
+fn info_grafts(repository: &Repository) -> Result<OidMap<Vec<Oid>>, String> {
+    let path = repository.path().join("info").join("grafts");
+    if !path.exists() {
+        return Ok(OidMap::default());
+    }
+    let content = std::fs::read_to_string(&path)
+        .map_err(|err| format!("Can't read {}: {}", path.display(), err))?;
+    let mut grafts = OidMap::default();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut oids = line.split_whitespace().map(|oid| {
+            Oid::from_str(oid).map_err(|_| format!("Invalid graft in {}: {}", path.display(), line))
+        });
+        if let Some(oid) = oids.next() {
+            grafts.insert(oid?, oids.collect::<Result<_, _>>()?);
+        }
+    }
+    Ok(grafts)
+}
 
-                let pos = branch_order(&branch_name, &settings.branches.order);
+/// Walks the commits of a revwalk again, with the parents of the commits replacing them,
+/// and the parents of grafts like the revwalk. Starts from the walk's tips, and follows parents in the walk, and all ancestors of
+/// replacements: commits only reachable through replaced parents are left out, history
+/// joined by replacements is added. Like the revwalk, children come before their
+/// parents, and newer commits before older ones.
+fn replaced_walk(
+    odb: &Odb,
+    walk: Revwalk,
+    replacements: &OidMap<Oid>,
+    grafts: &OidMap<Vec<Oid>>,
+    first_parent: bool,
+) -> Result<Vec<Oid>, String> {
+    let to_string = |err: Error| err.message().to_string();
+    let walked: Vec<Oid> = walk.collect::<Result<_, _>>().map_err(to_string)?;
+    let in_walk: HashSet<Oid> = walked.iter().copied().collect();
+    let mut has_child = HashSet::new();
+    for oid in &walked {
+        let (parents, _) = read_header(odb, *oid).map_err(to_string)?;
+        let parents = grafts.get(oid).cloned().unwrap_or(parents);
+        let count = if first_parent { 1 } else { parents.len() };
+        has_child.extend(parents.into_iter().take(count));
+    }
 
-                let term_col = to_terminal_color(
-                    &branch_color(
-                        &branch_name,
-                        &settings.branches.terminal_colors[..],
-                        &settings.branches.terminal_colors_unknown,
-                        counter,
-                    )[..],
-                )?;
-                let svg_col = branch_color(
-                    &branch_name,
-                    &settings.branches.svg_colors,
-                    &settings.branches.svg_colors_unknown,
-                    counter,
-                );
+    let mut headers: OidMap<(Vec<Oid>, i64)> = OidMap::default();
+    let mut stack: Vec<Oid> = walked
+        .iter()
+        .filter(|oid| !has_child.contains(*oid))
+        .copied()
+        .collect();
+    while let Some(oid) = stack.pop() {
+        if headers.contains_key(&oid) {
+            continue;
+        }
+        let replacement = replacements.get(&oid).copied();
+        let (parents, time) = read_header(odb, replacement.unwrap_or(oid)).map_err(to_string)?;
+        let mut parents = grafts.get(&oid).cloned().unwrap_or(parents);
+        if first_parent {
+            parents.truncate(1);
+        }
+        // Commits hidden from the walk, like the excluded side of a range, stay hidden
+        let joined = replacement.is_some() || !in_walk.contains(&oid);
+        parents.retain(|parent| joined || in_walk.contains(parent));
+        stack.extend(&parents);
+        headers.insert(oid, (parents, time));
+    }
 
-                let branch_info = BranchInfo::new(
-                    parent_oid,
-                    Some(info.oid),
-                    branch_name,
-                    persistence,
-                    false,
-                    true,
-                    false,
-                    BranchVis::new(pos, term_col, svg_col),
-                    Some(idx + 1),
-                );
-                valid_branches.push(branch_info);
+    // Children first, newest first among commits whose children were all walked
+    let mut children: OidMap<usize> = OidMap::default();
+    for (parents, _) in headers.values() {
+        for parent in parents {
+            *children.entry(*parent).or_default() += 1;
+        }
+    }
+    let mut ready: BinaryHeap<(i64, Oid)> = headers
+        .iter()
+        .filter(|(oid, _)| !children.contains_key(*oid))
+        .map(|(oid, (_, time))| (*time, *oid))
+        .collect();
+    let mut order = Vec::with_capacity(headers.len());
+    while let Some((_, oid)) = ready.pop() {
+        order.push(oid);
+        for parent in &headers[&oid].0 {
+            let count = children.get_mut(parent).expect("Parent counted");
+            *count -= 1;
+            if *count == 0 {
+                ready.push((headers[parent].1, *parent));
             }
         }
     }
+    Ok(order)
+}
 
-    valid_branches.sort_by_cached_key(|branch| (branch.persistence, !branch.is_merged));
-
-    let mut tags = Vec::new();
-
-    repository
-        .tag_foreach(|oid, name| {
-            tags.push((oid, name.to_vec()));
-            true
+/// The commits at the boundary of a shallow clone, whose parents were not fetched,
+/// as listed in the repository's `shallow` file. Empty for complete repositories.
+fn shallow_boundaries(repository: &Repository) -> Result<Vec<Oid>, String> {
+    if !repository.is_shallow() {
+        return Ok(vec![]);
+    }
+    let path = repository.path().join("shallow");
+    let content = std::fs::read_to_string(&path)
+        .map_err(|err| format!("Can't read {}: {}", path.display(), err))?;
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            Oid::from_str(line.trim())
+                .map_err(|err| format!("Invalid commit in {}: {}", path.display(), err))
         })
-        .map_err(|err| err.message().to_string())?;
-
-    for (oid, name) in tags {
-        let name = std::str::from_utf8(&name[5..]).map_err(|err| err.to_string())?;
+        .collect()
+}
 
-        let target = repository
-            .find_tag(oid)
-            .map(|tag| tag.target_id())
-            .or_else(|_| repository.find_commit(oid).map(|_| oid));
+/// Commits in the reflogs of local branches and HEAD, with names like `main@{2}`.
+/// Each commit is listed once, preferring the names of branch reflogs.
+fn reflog_entries(repository: &Repository) -> Result<Vec<(String, Oid)>, String> {
+    let mut refs = vec![];
+    for branch in repository
+        .branches(Some(BranchType::Local))
+        .map_err(|err| err.message().to_string())?
+    {
+        let (branch, _) = branch.map_err(|err| err.message().to_string())?;
+        if let (Ok(Some(name)), Some(ref_name)) = (branch.name(), branch.get().name()) {
+            refs.push((name.to_string(), ref_name.to_string()));
+        }
+    }
+    refs.push(("HEAD".to_string(), "HEAD".to_string()));
 
-        if let Ok(target_oid) = target {
-            if let Some(target_index) = indices.get(&target_oid) {
-                counter += 1;
-                let term_col = to_terminal_color(
-                    &branch_color(
-                        name,
-                        &settings.branches.terminal_colors[..],
-                        &settings.branches.terminal_colors_unknown,
-                        counter,
-                    )[..],
-                )?;
-                let pos = branch_order(name, &settings.branches.order);
-                let svg_col = branch_color(
-                    name,
-                    &settings.branches.svg_colors,
-                    &settings.branches.svg_colors_unknown,
-                    counter,
-                );
-                let tag_info = BranchInfo::new(
-                    target_oid,
-                    None,
-                    name.to_string(),
-                    settings.branches.persistence.len() as u8 + 1,
-                    false,
-                    false,
-                    true,
-                    BranchVis::new(pos, term_col, svg_col),
-                    Some(*target_index),
-                );
-                valid_branches.push(tag_info);
+    let mut seen = HashSet::new();
+    let mut entries = vec![];
+    for (name, ref_name) in refs {
+        let Ok(reflog) = repository.reflog(&ref_name) else {
+            continue;
+        };
+        for (idx, entry) in reflog.iter().enumerate() {
+            let oid = entry.id_new();
+            // Commits may be gone already, if the reflog was not expired with them
+            if repository.find_commit(oid).is_ok() && seen.insert(oid) {
+                entries.push((format!("{}@{{{}}}", name, idx), oid));
             }
         }
     }
+    Ok(entries)
+}
 
-    Ok(valid_branches)
+/// Marks the commits not reachable from any ref, HEAD or stash as unreachable.
+fn mark_unreachable(
+    repository: &Repository,
+    commits: &mut [CommitInfo],
+    indices: &OidMap<usize>,
+    stashes: &HashMap<Oid, usize>,
+) -> Result<(), String> {
+    let targets = ref_targets(repository, true)?;
+    let mut reachable = vec![false; commits.len()];
+    // Commits are in topological order, children first
+    for idx in 0..commits.len() {
+        let info = &commits[idx];
+        reachable[idx] |= targets.contains(&info.oid) || stashes.contains_key(&info.oid);
+        if reachable[idx] {
+            for par_idx in info
+                .parents
+                .iter()
+                .filter_map(|par| par.and_then(|oid| indices.get(&oid)))
+            {
+                reachable[*par_idx] = true;
+            }
+        }
+    }
+    for (info, reachable) in commits.iter_mut().zip(reachable) {
+        info.is_unreachable = !reachable;
+    }
+    Ok(())
 }
 
-/// Traces back branches by following 1st commit parent,
-/// until a commit is reached that already has a trace.
-fn trace_branch(
+/// Marks the merge bases of two commits, and dims the commits reachable from neither.
+fn mark_merge_bases(
     repository: &Repository,
     commits: &mut [CommitInfo],
-    indices: &HashMap<Oid, usize>,
-    branches: &mut [BranchInfo],
-    oid: Oid,
-    branch_index: usize,
-) -> Result<bool, Error> {
-    let mut curr_oid = oid;
-    let mut prev_index: Option<usize> = None;
-    let mut start_index: Option<i32> = None;
-    let mut any_assigned = false;
-    while let Some(index) = indices.get(&curr_oid) {
-        let info = &mut commits[*index];
-        if let Some(old_trace) = info.branch_trace {
-            let (old_name, old_term, old_svg, old_range) = {
-                let old_branch = &branches[old_trace];
-                (
-                    &old_branch.name.clone(),
-                    old_branch.visual.term_color,
-                    old_branch.visual.svg_color.clone(),
-                    old_branch.range,
-                )
-            };
-            let new_name = &branches[branch_index].name;
-            let old_end = old_range.0.unwrap_or(0);
-            let new_end = branches[branch_index].range.0.unwrap_or(0);
-            if new_name == old_name && old_end >= new_end {
-                let old_branch = &mut branches[old_trace];
-                if let Some(old_end) = old_range.1 {
-                    if index > &old_end {
-                        old_branch.range = (None, None);
-                    } else {
-                        old_branch.range = (Some(*index), old_branch.range.1);
-                    }
-                } else {
-                    old_branch.range = (Some(*index), old_branch.range.1);
+    first: Oid,
+    second: Oid,
+) -> Result<(), String> {
+    let to_string = |err: git2::Error| err.message().to_string();
+    let bases: HashSet<Oid> = match repository.merge_bases(first, second) {
+        Ok(bases) => bases.iter().copied().collect(),
+        // Unrelated histories
+        Err(err) if err.code() == git2::ErrorCode::NotFound => HashSet::new(),
+        Err(err) => return Err(to_string(err)),
+    };
+    let mut walk = repository.revwalk().map_err(to_string)?;
+    walk.push(first).map_err(to_string)?;
+    walk.push(second).map_err(to_string)?;
+    let reachable = walk
+        .collect::<Result<HashSet<Oid>, _>>()
+        .map_err(to_string)?;
+    for info in commits {
+        info.is_merge_base = bases.contains(&info.oid);
+        info.is_dimmed = !reachable.contains(&info.oid);
+    }
+    Ok(())
+}
+
+/// Commits in the object database not reachable from any ref, HEAD, stash or reflog,
+/// like the dangling commits of `git fsck`, with names like `dangling/1a2b3c4`.
+/// Only the tips of unreachable histories are listed, newest first.
+fn dangling_commits(
+    repository: &Repository,
+    stashes: &HashMap<Oid, usize>,
+) -> Result<Vec<(String, Oid)>, String> {
+    let to_string = |err: Error| err.message().to_string();
+    let odb = repository.odb().map_err(to_string)?;
+    let mut all = vec![];
+    odb.foreach(|oid| {
+        if let Ok((_, ObjectType::Commit)) = odb.read_header(*oid) {
+            all.push(*oid);
+        }
+        true
+    })
+    .map_err(to_string)?;
+
+    let mut walk = repository.revwalk().map_err(to_string)?;
+    let roots = ref_targets(repository, true)?
+        .into_iter()
+        .chain(stashes.keys().copied())
+        .chain(reflog_entries(repository)?.into_iter().map(|(_, oid)| oid));
+    for oid in roots {
+        walk.push(oid).map_err(to_string)?;
+    }
+    let reachable: HashSet<Oid> = walk.flatten().collect();
+
+    let mut unreachable = vec![];
+    for oid in all.into_iter().filter(|oid| !reachable.contains(oid)) {
+        unreachable.push(repository.find_commit(oid).map_err(to_string)?);
+    }
+    let inner: HashSet<Oid> = unreachable
+        .iter()
+        .flat_map(|commit| commit.parent_ids())
+        .collect();
+    let mut tips: Vec<_> = unreachable
+        .into_iter()
+        .filter(|commit| !inner.contains(&commit.id()))
+        .collect();
+    tips.sort_by_key(|commit| std::cmp::Reverse(commit.time().seconds()));
+    Ok(tips
+        .into_iter()
+        .map(|commit| {
+            let oid = commit.id();
+            (format!("dangling/{}", &oid.to_string()[..7]), oid)
+        })
+        .collect())
+}
+
+/// Branches for the tips of unreachable histories, like reflog entries, drawn in gray.
+/// Branches are in the order group of their name, or in the given one.
+fn unreachable_branches(
+    commits: &[CommitInfo],
+    indices: &OidMap<usize>,
+    tips: &[(String, Oid)],
+    settings: &Settings,
+    order_group: Option<usize>,
+) -> Vec<BranchInfo> {
+    let unreachable = |oid: &Oid| {
+        indices
+            .get(oid)
+            .is_some_and(|idx| commits[*idx].is_unreachable)
+    };
+    let inner: HashSet<Oid> = commits
+        .iter()
+        .filter(|info| info.is_unreachable)
+        .flat_map(|info| info.parents.iter().flatten().copied())
+        .collect();
+    tips.iter()
+        .filter(|(_, oid)| unreachable(oid) && !inner.contains(oid))
+        .map(|(name, oid)| {
+            BranchInfo::new(
+                *oid,
+                None,
+                name.clone(),
+                branch_order(name, &settings.branches.persistence) as u8,
+                false,
+                false,
+                false,
+                BranchVis::new(
+                    order_group.unwrap_or_else(|| branch_order(name, &settings.branches.order)),
+                    REFLOG_TERM_COLOR,
+                    REFLOG_SVG_COLOR.to_string(),
+                ),
+                indices.get(oid).copied(),
+            )
+        })
+        .collect()
+}
+
+/// Summarizes the merged-in history of merges as the name of the merged branch,
+/// for first-parent mode. Merges lose their second parent, which is not walked.
+fn summarize_merges(
+    repository: &Repository,
+    commits: &mut [CommitInfo],
+    stashes: &HashMap<Oid, usize>,
+    patterns: &MergePatterns,
+) -> Result<(), String> {
+    for info in commits.iter_mut() {
+        let Some(merged) = info.parents[1] else {
+            continue;
+        };
+        let name = match stashes.get(&info.oid) {
+            Some(stash_index) => format!("stash@{{{}}}/index", stash_index),
+            None => {
+                let commit = repository
+                    .find_commit(info.oid)
+                    .map_err(|err| err.message().to_string())?;
+                commit
+                    .summary()
+                    .and_then(|summary| parse_merge_summary(summary, patterns))
+                    .unwrap_or_else(|| merged.to_string()[..7].to_string())
+            }
+        };
+        info.parents[1] = None;
+        info.is_merge = false;
+        info.merged_from = Some(name);
+    }
+    Ok(())
+}
+
+/// Collapses merges bringing in a single commit into one node: the merged commit is
+/// removed, and the merge loses its second parent and records the commit as collapsed.
+/// Merged commits with other children or refs pointing to them are kept, as are stashes.
+fn collapse_trivial_merges(
+    repository: &Repository,
+    commits: &mut Vec<CommitInfo>,
+    indices: &mut OidMap<usize>,
+    stashes: &HashMap<Oid, usize>,
+) -> Result<(), String> {
+    let mut child_counts: OidMap<usize> = OidMap::default();
+    for info in commits.iter() {
+        for parent in info.parents.iter().flatten() {
+            *child_counts.entry(*parent).or_default() += 1;
+        }
+    }
+    let ref_targets = ref_targets(repository, true)?;
+
+    let mut removed = HashSet::new();
+    for idx in 0..commits.len() {
+        let info = &commits[idx];
+        let (Some(first), Some(merged)) = (info.parents[0], info.parents[1]) else {
+            continue;
+        };
+        if stashes.contains_key(&info.oid)
+            || child_counts.get(&merged) != Some(&1)
+            || ref_targets.contains(&merged)
+        {
+            continue;
+        }
+        let base = match indices.get(&merged).map(|idx| commits[*idx].parents) {
+            Some([Some(base), None]) => base,
+            _ => continue,
+        };
+        let is_trivial = base == first
+            || repository
+                .graph_descendant_of(first, base)
+                .map_err(|err| err.message().to_string())?;
+        if is_trivial {
+            let info = &mut commits[idx];
+            info.parents[1] = None;
+            info.is_merge = false;
+            info.collapsed = Some(merged);
+            removed.insert(merged);
+        }
+    }
+
+    if !removed.is_empty() {
+        commits.retain(|info| !removed.contains(&info.oid));
+        *indices = commits
+            .iter()
+            .enumerate()
+            .map(|(idx, info)| (info.oid, idx))
+            .collect();
+    }
+    Ok(())
+}
+
+/// The commits pointed to by refs, including HEAD and, optionally, remote branches.
+fn ref_targets(repository: &Repository, include_remote: bool) -> Result<HashSet<Oid>, String> {
+    let mut targets: HashSet<Oid> = repository
+        .references()
+        .map_err(|err| err.message().to_string())?
+        .flatten()
+        .filter(|reference| include_remote || !reference.is_remote())
+        .filter_map(|reference| reference.peel_to_commit().ok())
+        .map(|commit| commit.id())
+        .collect();
+    if let Ok(head) = repository.head() {
+        targets.extend(head.target());
+    }
+    Ok(targets)
+}
+
+/// Removes the branch traces of all commits that are neither pointed to by a signed tag,
+/// nor on the path between two such commits along a persistent branch carrying them,
+/// i.e. a branch matching one of the persistence patterns of the first `levels` levels.
+/// Tags that cannot be resolved are skipped. Branches left without any commits lose
+/// their range.
+fn restrict_to_signed_releases(
+    repository: &Repository,
+    commits: &mut [CommitInfo],
+    indices: &OidMap<usize>,
+    branches: &mut [BranchInfo],
+    levels: usize,
+) {
+    let mut signed = vec![false; commits.len()];
+    for (idx, info) in commits.iter().enumerate() {
+        for tag_idx in &info.tags {
+            let tag_name = format!("refs/{}", branches[*tag_idx].name);
+            let Ok(reference) = repository.find_reference(&tag_name) else {
+                continue;
+            };
+            if let Ok(tag) = reference.peel_to_tag() {
+                if is_signed_tag_message(tag.message().unwrap_or("")) {
+                    signed[idx] = true;
                 }
+            }
+        }
+    }
+
+    // Commits are in topological order, children first.
+    let parent_indices = |info: &CommitInfo| {
+        info.parents
+            .iter()
+            .filter_map(|par| par.and_then(|oid| indices.get(&oid)).copied())
+            .collect::<Vec<_>>()
+    };
+    let mut ancestor = signed.clone();
+    for idx in 0..commits.len() {
+        if ancestor[idx] {
+            for par_idx in parent_indices(&commits[idx]) {
+                ancestor[par_idx] = true;
+            }
+        }
+    }
+    let mut descendant = signed.clone();
+    for idx in (0..commits.len()).rev() {
+        if !descendant[idx] {
+            descendant[idx] = parent_indices(&commits[idx])
+                .into_iter()
+                .any(|par_idx| descendant[par_idx]);
+        }
+    }
+
+    let mut release_branch = vec![false; branches.len()];
+    for (idx, info) in commits.iter().enumerate() {
+        if let (true, Some(trace)) = (signed[idx], info.branch_trace) {
+            release_branch[trace] = (branches[trace].persistence as usize) < levels;
+        }
+    }
+
+    let mut commit_count = vec![0; branches.len()];
+    for (idx, info) in commits.iter_mut().enumerate() {
+        if let Some(trace) = info.branch_trace {
+            if signed[idx] || (release_branch[trace] && ancestor[idx] && descendant[idx]) {
+                commit_count[trace] += 1;
             } else {
-                let branch = &mut branches[branch_index];
-                if branch.name.starts_with(ORIGIN) && branch.name[7..] == old_name[..] {
-                    branch.visual.term_color = old_term;
-                    branch.visual.svg_color = old_svg;
+                info.branch_trace = None;
+            }
+        }
+    }
+    for (branch, count) in branches.iter_mut().zip(commit_count) {
+        if count == 0 {
+            branch.range = (None, None);
+        }
+    }
+}
+
+/// Restricts the graph to the listed commits, which keep the branches they are
+/// attributed to in the full history. Parents are rewritten to the nearest listed
+/// ancestors, so that the graph stays connected.
+fn restrict_to_commits(
+    commits: &mut [CommitInfo],
+    indices: &OidMap<usize>,
+    branches: &mut [BranchInfo],
+    list: &[Oid],
+) {
+    let listed: HashSet<Oid> = list.iter().copied().collect();
+    let keep: Vec<bool> = commits
+        .iter()
+        .map(|info| listed.contains(&info.oid))
+        .collect();
+    rewrite_parents(commits, indices, &keep);
+
+    let mut commit_count = vec![0; branches.len()];
+    for info in commits.iter_mut() {
+        if let Some(trace) = info.branch_trace {
+            if listed.contains(&info.oid) {
+                commit_count[trace] += 1;
+            } else {
+                info.branch_trace = None;
+            }
+        }
+    }
+    for (branch, count) in branches.iter_mut().zip(commit_count) {
+        if count == 0 {
+            branch.range = (None, None);
+        }
+    }
+}
+
+/// Rewrites the parents of the commits to keep to their nearest ancestors to keep,
+/// recording the number of commits hidden in between.
+/// Returns the nearest commits to keep among each commit and its ancestors,
+/// with the number of commits not kept on the way, including the commit itself.
+fn rewrite_parents(
+    commits: &mut [CommitInfo],
+    indices: &OidMap<usize>,
+    keep: &[bool],
+) -> Vec<Vec<(Oid, usize)>> {
+    // Commits are in topological order, children first
+    let mut nearest: Vec<Vec<(Oid, usize)>> = vec![vec![]; commits.len()];
+    for idx in (0..commits.len()).rev() {
+        let mut ancestors: Vec<(Oid, usize)> = vec![];
+        for par_idx in commits[idx]
+            .parents
+            .iter()
+            .filter_map(|par| par.and_then(|oid| indices.get(&oid)))
+        {
+            for (oid, hidden) in &nearest[*par_idx] {
+                if !ancestors.iter().any(|(other, _)| other == oid) {
+                    ancestors.push((*oid, *hidden));
                 }
-                match prev_index {
-                    None => start_index = Some(*index as i32 - 1),
-                    Some(prev_index) => {
-                        // TODO: in cases where no crossings occur, the rule for merge commits can also be applied to normal commits
-                        // see also print::get_deviate_index()
-                        if commits[prev_index].is_merge {
-                            let mut temp_index = prev_index;
-                            for sibling_oid in &commits[*index].children {
-                                if sibling_oid != &curr_oid {
-                                    let sibling_index = indices[sibling_oid];
-                                    if sibling_index > temp_index {
-                                        temp_index = sibling_index;
-                                    }
-                                }
-                            }
-                            start_index = Some(temp_index as i32);
-                        } else {
-                            start_index = Some(*index as i32 - 1);
-                        }
-                    }
+            }
+        }
+        let info = &mut commits[idx];
+        if keep[idx] {
+            let parent = |idx: usize| ancestors.get(idx).map(|(oid, _)| *oid);
+            info.parents = [parent(0), parent(1)];
+            info.hidden = [0, 1].map(|idx| ancestors.get(idx).map_or(0, |(_, hidden)| *hidden));
+            info.is_merge = info.parents[1].is_some();
+            nearest[idx] = vec![(info.oid, 0)];
+        } else {
+            for (_, hidden) in ancestors.iter_mut() {
+                *hidden += 1;
+            }
+            // Two are enough, as commits have at most two parents in the graph
+            ancestors.truncate(2);
+            nearest[idx] = ancestors;
+        }
+    }
+    nearest
+}
+
+/// The repository to read the content of commits from, for an option that needs it.
+fn content_of<'a>(
+    repository: Option<&'a Repository>,
+    option: &str,
+) -> Result<&'a Repository, String> {
+    repository.ok_or_else(|| format!("{} needs a git repository", option))
+}
+
+/// Rewrites and filters the commits read, before branches are assigned.
+/// Returns the nearest ancestor kept for each commit removed, like [filter_commits].
+fn filter_history(
+    repository: Option<&Repository>,
+    commits: &mut Vec<CommitInfo>,
+    indices: &mut OidMap<usize>,
+    stashes: &HashMap<Oid, usize>,
+    times: &OidMap<i64>,
+    settings: &Settings,
+) -> Result<OidMap<Oid>, String> {
+    if settings.first_parent {
+        summarize_merges(
+            content_of(repository, "--first-parent")?,
+            commits,
+            stashes,
+            &settings.merge_patterns,
+        )?;
+    }
+    if settings.collapse_trivial_merges {
+        collapse_trivial_merges(
+            content_of(repository, "--collapse-merges")?,
+            commits,
+            indices,
+            stashes,
+        )?;
+    }
+    if settings.verify_signatures {
+        verify_signatures(content_of(repository, "--signatures")?, commits)?;
+    }
+    filter_commits(repository, commits, indices, times, settings)
+}
+
+/// Moves branches pointing to commits filtered out to the nearest ancestor kept.
+fn redirect_branches(
+    branches: &mut [BranchInfo],
+    indices: &OidMap<usize>,
+    redirects: &OidMap<Oid>,
+) {
+    for branch in branches.iter_mut().filter(|branch| !branch.is_tag) {
+        if let Some(&oid) = redirects.get(&branch.target) {
+            branch.target = oid;
+            branch.range.0 = indices.get(&oid).copied();
+        }
+    }
+}
+
+/// Removes the commits not matching the path, author, committer, date, signature and
+/// review filters of the settings, before branches are assigned, given the committer times of
+/// the commits for date filters. Returns the nearest ancestor kept for each commit removed, if there is one.
+fn filter_commits(
+    repository: Option<&Repository>,
+    commits: &mut Vec<CommitInfo>,
+    indices: &mut OidMap<usize>,
+    times: &OidMap<i64>,
+    settings: &Settings,
+) -> Result<OidMap<Oid>, String> {
+    let mut keep = vec![true; commits.len()];
+    if !times.is_empty() {
+        for (keep, info) in keep.iter_mut().zip(commits.iter()) {
+            let time = times.get(&info.oid).copied().unwrap_or_default();
+            *keep &= settings.since.is_none_or(|since| time >= since)
+                && settings.until.is_none_or(|until| time <= until);
+        }
+    }
+    if settings.unsigned_only {
+        for (keep, info) in keep.iter_mut().zip(commits.iter()) {
+            *keep &= info.signature == Some(SignatureStatus::Unsigned);
+        }
+    }
+    if let (Some(reviews), Some(status)) = (&settings.reviews, settings.review_filter) {
+        for (keep, info) in keep.iter_mut().zip(commits.iter()) {
+            *keep &= reviews.status(info.oid) == status;
+        }
+    }
+    if let Some(pattern) = &settings.message_pattern {
+        let repository = content_of(repository, "--grep")?;
+        for (keep, info) in keep.iter_mut().zip(commits.iter()) {
+            let commit = repository
+                .find_commit(info.oid)
+                .map_err(|err| err.message().to_string())?;
+            let message = String::from_utf8_lossy(commit.message_bytes());
+            *keep &= pattern.is_match(&message) != settings.invert_grep;
+        }
+    }
+    if !settings.path_filters.is_empty() {
+        let repository = content_of(repository, "--path-filter")?;
+        let changing =
+            commits_changing_paths(repository, commits, indices, &settings.path_filters)?;
+        for (keep, changing) in keep.iter_mut().zip(changing) {
+            *keep &= changing;
+        }
+    }
+    if !settings.author_filters.is_empty() || !settings.committer_filters.is_empty() {
+        let repository = content_of(repository, "--author")?;
+        for (keep, info) in keep.iter_mut().zip(commits.iter()) {
+            let commit = repository
+                .find_commit(info.oid)
+                .map_err(|err| err.message().to_string())?;
+            *keep &= matches_identity(&commit.author(), &settings.author_filters)
+                && matches_identity(&commit.committer(), &settings.committer_filters);
+        }
+    }
+    if let Some(selection) = &settings.selection {
+        let repository = content_of(repository, "--select")?;
+        let selected = select_commits(repository, commits, indices, selection)?;
+        for (keep, selected) in keep.iter_mut().zip(selected) {
+            *keep &= selected;
+        }
+    }
+    if !settings.contains_paths.is_empty() {
+        let repository = content_of(repository, "--contains-path")?;
+        for (keep, info) in keep.iter_mut().zip(commits.iter()) {
+            if *keep {
+                let tree = repository
+                    .find_commit(info.oid)
+                    .and_then(|commit| commit.tree())
+                    .map_err(|err| err.message().to_string())?;
+                *keep = settings
+                    .contains_paths
+                    .iter()
+                    .any(|path| tree.get_path(Path::new(path)).is_ok());
+            }
+        }
+    }
+    if let Some(pattern) = &settings.content_pattern {
+        // Diffs are expensive: only search the commits passing the other filters
+        let candidates: Vec<usize> = (0..commits.len()).filter(|idx| keep[*idx]).collect();
+        let oids: Vec<Oid> = candidates.iter().map(|idx| commits[*idx].oid).collect();
+        let progress = settings.progress.then_some("Searching diffs");
+        let repository = content_of(repository, "--touches-content")?;
+        let touching = per_commit_parallel(repository, &oids, progress, |repository, oid| {
+            touches_content(repository, oid, pattern)
+        })?;
+        for (idx, touching) in candidates.into_iter().zip(touching) {
+            keep[idx] = touching;
+        }
+    }
+    if let Some(symbol) = &settings.symbol {
+        let candidates: Vec<usize> = (0..commits.len()).filter(|idx| keep[*idx]).collect();
+        let oids: Vec<Oid> = candidates.iter().map(|idx| commits[*idx].oid).collect();
+        let progress = settings.progress.then_some("Tracing symbol");
+        let repository = content_of(repository, "trace-symbol")?;
+        let changing = per_commit_parallel(repository, &oids, progress, |repository, oid| {
+            changes_symbol(repository, oid, symbol)
+        })?;
+        for (idx, changing) in candidates.into_iter().zip(changing) {
+            keep[idx] = changing;
+        }
+    }
+    if settings.view == GraphView::Releases {
+        let tagged: HashSet<Oid> = tag_refs(content_of(repository, "--view releases")?, settings)?
+            .into_iter()
+            .map(|tag| tag.target)
+            .collect();
+        for (keep, info) in keep.iter_mut().zip(commits.iter()) {
+            *keep &= tagged.contains(&info.oid);
+        }
+    }
+    if settings.simplify_by_decoration {
+        let repository = content_of(repository, "--simplify-by-decoration")?;
+        let decorated = ref_targets(repository, settings.include_remote)?;
+        for (keep, topology) in keep
+            .iter_mut()
+            .zip(decoration_topology(commits, indices, &decorated))
+        {
+            *keep &= topology;
+        }
+    }
+    if keep.iter().all(|keep| *keep) {
+        return Ok(OidMap::default());
+    }
+    Ok(remove_commits(commits, indices, &keep))
+}
+
+/// The commits to keep for `--simplify-by-decoration`: the decorated commits, and the
+/// commits where the histories of different decorated commits fork. Merges are not
+/// kept, their sides are connected by rewriting parents.
+fn decoration_topology(
+    commits: &[CommitInfo],
+    indices: &OidMap<usize>,
+    decorated: &HashSet<Oid>,
+) -> Vec<bool> {
+    let mut keep = vec![false; commits.len()];
+    // The nearest decorated or fork commit each commit leads to, and whether several
+    let mut leads_to: Vec<Option<usize>> = vec![None; commits.len()];
+    let mut forks = vec![false; commits.len()];
+    // Commits are in topological order, children first
+    for idx in 0..commits.len() {
+        keep[idx] = forks[idx] || decorated.contains(&commits[idx].oid);
+        if keep[idx] {
+            leads_to[idx] = Some(idx);
+        }
+        let Some(target) = leads_to[idx] else {
+            continue;
+        };
+        for par_idx in commits[idx]
+            .parents
+            .iter()
+            .filter_map(|par| par.and_then(|oid| indices.get(&oid)))
+        {
+            match leads_to[*par_idx] {
+                None => leads_to[*par_idx] = Some(target),
+                Some(other) if other != target => forks[*par_idx] = true,
+                _ => {}
+            }
+        }
+    }
+    keep
+}
+
+/// Removes the commits not to keep, rewriting parents to the nearest ancestors kept.
+/// Returns the nearest ancestor kept for each commit removed, if there is one.
+fn remove_commits(
+    commits: &mut Vec<CommitInfo>,
+    indices: &mut OidMap<usize>,
+    keep: &[bool],
+) -> OidMap<Oid> {
+    let nearest = rewrite_parents(commits, indices, keep);
+    let redirects = commits
+        .iter()
+        .zip(&nearest)
+        .zip(keep)
+        .filter(|(_, keep)| !**keep)
+        .filter_map(|((info, nearest), _)| nearest.first().map(|(oid, _)| (info.oid, *oid)))
+        .collect();
+
+    let mut keep = keep.iter();
+    commits.retain(|_| *keep.next().unwrap_or(&false));
+    *indices = commits
+        .iter()
+        .enumerate()
+        .map(|(idx, info)| (info.oid, idx))
+        .collect();
+    redirects
+}
+
+/// Whether each commit matches a selection expression.
+fn select_commits(
+    repository: &Repository,
+    commits: &[CommitInfo],
+    indices: &OidMap<usize>,
+    filter: &Filter,
+) -> Result<Vec<bool>, String> {
+    let to_string = |err: Error| err.message().to_string();
+    let each = |predicate: &dyn Fn(&Commit) -> bool| {
+        commits
+            .iter()
+            .map(|info| {
+                repository
+                    .find_commit(info.oid)
+                    .map(|commit| predicate(&commit))
+            })
+            .collect::<Result<Vec<_>, Error>>()
+            .map_err(to_string)
+    };
+    let reachable = |is_match: &dyn Fn(&Reference) -> bool| -> Result<Vec<bool>, String> {
+        let mut walk = repository.revwalk().map_err(to_string)?;
+        for reference in repository.references().map_err(to_string)?.flatten() {
+            if is_match(&reference) {
+                if let Ok(commit) = reference.peel_to_commit() {
+                    walk.push(commit.id()).map_err(to_string)?;
                 }
-                break;
             }
         }
+        let reachable: HashSet<Oid> = walk.flatten().collect();
+        Ok(commits
+            .iter()
+            .map(|info| reachable.contains(&info.oid))
+            .collect())
+    };
+    let both = |lhs: &Filter, rhs: &Filter| -> Result<(Vec<bool>, Vec<bool>), String> {
+        Ok((
+            select_commits(repository, commits, indices, lhs)?,
+            select_commits(repository, commits, indices, rhs)?,
+        ))
+    };
+
+    match filter {
+        Filter::Author(pattern) => {
+            each(&|commit| matches_identity(&commit.author(), std::slice::from_ref(pattern)))
+        }
+        Filter::Committer(pattern) => {
+            each(&|commit| matches_identity(&commit.committer(), std::slice::from_ref(pattern)))
+        }
+        Filter::Message(pattern) => {
+            each(&|commit| pattern.is_match(&String::from_utf8_lossy(commit.message_bytes())))
+        }
+        Filter::Since(since) => each(&|commit| commit.time().seconds() >= *since),
+        Filter::Until(until) => each(&|commit| commit.time().seconds() <= *until),
+        Filter::Branch(pattern) => reachable(&|reference| {
+            (reference.is_branch() || reference.is_remote())
+                && reference
+                    .shorthand()
+                    .is_some_and(|name| pattern.is_match(name))
+        }),
+        Filter::Tag(pattern) => reachable(&|reference| {
+            reference.is_tag()
+                && reference
+                    .shorthand()
+                    .is_some_and(|name| pattern.is_match(name))
+        }),
+        Filter::Path(pathspec) => {
+            commits_changing_paths(repository, commits, indices, std::slice::from_ref(pathspec))
+        }
+        Filter::Not(filter) => Ok(select_commits(repository, commits, indices, filter)?
+            .into_iter()
+            .map(|selected| !selected)
+            .collect()),
+        Filter::And(lhs, rhs) => {
+            let (lhs, rhs) = both(lhs, rhs)?;
+            Ok(lhs.into_iter().zip(rhs).map(|(l, r)| l && r).collect())
+        }
+        Filter::Or(lhs, rhs) => {
+            let (lhs, rhs) = both(lhs, rhs)?;
+            Ok(lhs.into_iter().zip(rhs).map(|(l, r)| l || r).collect())
+        }
+    }
+}
+
+/// Whether a signature's `Name <email>` matches any of the patterns, like
+/// `git log --author`. Any signature matches if there are no patterns.
+fn matches_identity(signature: &Signature, patterns: &[Regex]) -> bool {
+    if patterns.is_empty() {
+        return true;
+    }
+    let identity = format!(
+        "{} <{}>",
+        String::from_utf8_lossy(signature.name_bytes()),
+        String::from_utf8_lossy(signature.email_bytes())
+    );
+    patterns.iter().any(|pattern| pattern.is_match(&identity))
+}
+
+/// Whether each commit changes files matching the pathspecs, like `git log -- <paths>`.
+/// Merges only count if they differ from all their parents. Renames to a matching
+/// path are followed, like `git log --follow`: beyond the rename, the file's old path
+/// is matched as well.
+fn commits_changing_paths(
+    repository: &Repository,
+    commits: &[CommitInfo],
+    indices: &OidMap<usize>,
+    pathspecs: &[String],
+) -> Result<Vec<bool>, String> {
+    let to_string = |err: Error| err.message().to_string();
+    // The paths matched at each commit, extended by renames in its descendants
+    let mut matched: Vec<Option<Rc<BTreeSet<String>>>> = vec![None; commits.len()];
+    let initial = Rc::new(pathspecs.iter().cloned().collect::<BTreeSet<_>>());
+    let mut keep = vec![false; commits.len()];
+    for idx in 0..commits.len() {
+        let paths = matched[idx].take().unwrap_or_else(|| initial.clone());
+        let commit = repository
+            .find_commit(commits[idx].oid)
+            .map_err(to_string)?;
+        let tree = commit.tree().map_err(to_string)?;
+        let parent_trees = commit
+            .parents()
+            .take(if commits[idx].is_merge { usize::MAX } else { 1 })
+            .map(|parent| parent.tree())
+            .collect::<Result<Vec<_>, Error>>()
+            .map_err(to_string)?;
+        if parent_trees.is_empty() {
+            keep[idx] = path_changes(repository, None, &tree, &paths)
+                .map_err(to_string)?
+                .0;
+            continue;
+        }
+
+        keep[idx] = true;
+        for (parent_tree, parent) in parent_trees.iter().zip(commits[idx].parents) {
+            let (changed, renamed) =
+                path_changes(repository, Some(parent_tree), &tree, &paths).map_err(to_string)?;
+            keep[idx] &= changed;
+            let Some(&par_idx) = parent.and_then(|oid| indices.get(&oid)) else {
+                continue;
+            };
+            let mut parent_paths = match &matched[par_idx] {
+                Some(other) if *other != paths => other.union(&paths).cloned().collect(),
+                _ => BTreeSet::new(),
+            };
+            parent_paths.extend(renamed);
+            matched[par_idx] = if parent_paths.is_empty() {
+                Some(paths.clone())
+            } else {
+                parent_paths.extend(paths.iter().cloned());
+                Some(Rc::new(parent_paths))
+            };
+        }
+    }
+
+    Ok(keep)
+}
+
+/// Whether files matching the paths differ between a commit's tree and a parent's tree,
+/// and the old paths of files renamed to a matching path.
+fn path_changes(
+    repository: &Repository,
+    parent_tree: Option<&Tree>,
+    tree: &Tree,
+    paths: &BTreeSet<String>,
+) -> Result<(bool, Vec<String>), Error> {
+    let mut options = DiffOptions::new();
+    for path in paths {
+        options.pathspec(path);
+    }
+    let diff = repository.diff_tree_to_tree(parent_tree, Some(tree), Some(&mut options))?;
+    let added: Vec<&Path> = diff
+        .deltas()
+        .filter(|delta| delta.status() == Delta::Added)
+        .filter_map(|delta| delta.new_file().path())
+        .collect();
+
+    let mut renamed = vec![];
+    if !added.is_empty() && parent_tree.is_some() {
+        // Renames are only detected with both paths in the diff
+        let mut full = repository.diff_tree_to_tree(parent_tree, Some(tree), None)?;
+        full.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+        for delta in full.deltas() {
+            if let (Delta::Renamed, Some(old), Some(new)) = (
+                delta.status(),
+                delta.old_file().path(),
+                delta.new_file().path(),
+            ) {
+                if added.contains(&new) {
+                    renamed.push(old.to_string_lossy().into_owned());
+                }
+            }
+        }
+    }
+    Ok((diff.deltas().len() > 0, renamed))
+}
+
+/// Checks whether an annotated tag's message carries a PGP, SSH or X.509 signature.
+fn is_signed_tag_message(message: &str) -> bool {
+    [
+        "-----BEGIN PGP SIGNATURE-----",
+        "-----BEGIN SSH SIGNATURE-----",
+        "-----BEGIN SIGNED MESSAGE-----",
+    ]
+    .iter()
+    .any(|marker| message.contains(marker))
+}
+
+fn correct_fork_merges(
+    commits: &[CommitInfo],
+    indices: &OidMap<usize>,
+    branches: &mut [BranchInfo],
+    settings: &Settings,
+) -> Result<(), String> {
+    for idx in 0..branches.len() {
+        if let Some(merge_target) = branches[idx]
+            .merge_target
+            .and_then(|oid| indices.get(&oid))
+            .and_then(|idx| commits.get(*idx))
+            .and_then(|info| info.branch_trace)
+            .and_then(|trace| branches.get(trace))
+        {
+            if branches[idx].name == merge_target.name {
+                let name = format!("{}{}", FORK, branches[idx].name);
+                let term_col = to_terminal_color(
+                    &branch_color(
+                        &name,
+                        &settings.branches.terminal_colors[..],
+                        &settings.branches.terminal_colors_unknown,
+                        idx,
+                        settings.color_seed,
+                    )[..],
+                )?;
+                let pos = branch_order(&name, &settings.branches.order);
+                let svg_col = branch_color(
+                    &name,
+                    &settings.branches.svg_colors,
+                    &settings.branches.svg_colors_unknown,
+                    idx,
+                    settings.color_seed,
+                );
+
+                branches[idx].name = format!("{}{}", FORK, branches[idx].name);
+                branches[idx].visual.order_group = pos;
+                branches[idx].visual.term_color = term_col;
+                branches[idx].visual.svg_color = svg_col;
+            }
+        }
+    }
+    Ok(())
+}
+fn assign_sources_targets(
+    commits: &[CommitInfo],
+    indices: &OidMap<usize>,
+    branches: &mut [BranchInfo],
+) {
+    for idx in 0..branches.len() {
+        let target_branch_idx = branches[idx]
+            .merge_target
+            .and_then(|oid| indices.get(&oid))
+            .and_then(|idx| commits.get(*idx))
+            .and_then(|info| info.branch_trace);
+
+        branches[idx].target_branch = target_branch_idx;
+
+        let group = target_branch_idx
+            .and_then(|trace| branches.get(trace))
+            .map(|br| br.visual.order_group);
+
+        branches[idx].visual.target_order_group = group;
+    }
+    for info in commits {
+        let mut max_par_order = None;
+        let mut source_branch_id = None;
+        for par_oid in info.parents.iter() {
+            let par_info = par_oid
+                .and_then(|oid| indices.get(&oid))
+                .and_then(|idx| commits.get(*idx));
+            if let Some(par_info) = par_info {
+                if par_info.branch_trace != info.branch_trace {
+                    if let Some(trace) = par_info.branch_trace {
+                        source_branch_id = Some(trace);
+                    }
+
+                    let group = par_info
+                        .branch_trace
+                        .and_then(|trace| branches.get(trace))
+                        .map(|br| br.visual.order_group);
+                    if let Some(gr) = max_par_order {
+                        if let Some(p_group) = group {
+                            if p_group > gr {
+                                max_par_order = group;
+                            }
+                        }
+                    } else {
+                        max_par_order = group;
+                    }
+                }
+            }
+        }
+        let branch = info.branch_trace.and_then(|trace| branches.get_mut(trace));
+        if let Some(branch) = branch {
+            if let Some(order) = max_par_order {
+                branch.visual.source_order_group = Some(order);
+            }
+            if let Some(source_id) = source_branch_id {
+                branch.source_branch = Some(source_id);
+            }
+        }
+    }
+}
+
+/// A branch or tag, with the commit it points to.
+struct GraphRef {
+    /// The name as shown, like `main`, `origin/main` or `tags/v1.0`
+    name: String,
+    target: Oid,
+    is_remote: bool,
+    is_tag: bool,
+    /// Whether this is origin's default branch, or its local branch
+    is_default: bool,
+    /// Whether its remote's HEAD points to this remote branch
+    is_remote_head: bool,
+    /// Whether the tip commit subject marks the branch as work in progress
+    is_wip: bool,
+    /// Commits ahead of and behind the upstream branch, if counted
+    ahead_behind: Option<(usize, usize)>,
+}
+
+/// The summary and the two parents of a merge commit, if it has a summary.
+type MergeSummary = Option<(String, [Oid; 2])>;
+
+/// Extracts (real or derived from merge summary) and assigns basic properties.
+fn extract_branches(
+    repository: &Repository,
+    commits: &[CommitInfo],
+    indices: &OidMap<usize>,
+    stashes: &HashMap<Oid, usize>,
+    settings: &Settings,
+) -> Result<(Vec<BranchInfo>, OidMap<String>), String> {
+    let mut refs = branch_refs(repository, settings)?;
+    refs.extend(tag_refs(repository, settings)?);
+
+    branches_from_refs(&refs, commits, indices, stashes, settings, |oid| {
+        let to_string = |err: Error| err.message().to_string();
+        let commit = repository.find_commit(oid).map_err(to_string)?;
+        // Commits below a filtered-out merge inherit its parents, without being merges
+        if commit.parent_count() < 2 {
+            return Ok(None);
+        }
+        let parents = [
+            commit.parent_id(0).map_err(to_string)?,
+            commit.parent_id(1).map_err(to_string)?,
+        ];
+        Ok(commit
+            .summary()
+            .map(|summary| (summary.to_string(), parents)))
+    })
+}
+
+/// The refs of the branches shown: local, and remote if enabled, matching the branch filter.
+fn branch_refs(repository: &Repository, settings: &Settings) -> Result<Vec<GraphRef>, String> {
+    let filter = if settings.include_remote {
+        None
+    } else {
+        Some(BranchType::Local)
+    };
+    let actual_branches = repository
+        .branches(filter)
+        .map_err(|err| err.message().to_string())?
+        .collect::<Result<Vec<_>, Error>>()
+        .map_err(|err| err.message().to_string())?;
+    let remote_heads = remote_heads(repository)?;
+    let default = remote_heads
+        .iter()
+        .find_map(|head| head.strip_prefix(ORIGIN));
+
+    Ok(actual_branches
+        .iter()
+        .filter_map(|(br, tp)| {
+            br.get().name().and_then(|n| {
+                br.get().target().map(|t| {
+                    let start_index = match tp {
+                        BranchType::Local => 11,
+                        BranchType::Remote => 13,
+                    };
+                    let name = &n[start_index..];
+                    let is_remote = &BranchType::Remote == tp;
+                    let local_name = if is_remote {
+                        name.strip_prefix(ORIGIN)
+                    } else {
+                        Some(name)
+                    };
+                    GraphRef {
+                        name: name.to_string(),
+                        target: t,
+                        is_remote,
+                        is_tag: false,
+                        is_default: default.is_some() && local_name == default,
+                        is_remote_head: is_remote && remote_heads.contains(name),
+                        is_wip: !settings.wip_patterns.is_empty()
+                            && is_wip(repository, t, settings),
+                        ahead_behind: if settings.ahead_behind {
+                            upstream_ahead_behind(repository, br, t)
+                        } else {
+                            None
+                        },
+                    }
+                })
+            })
+        })
+        .filter(|branch| settings.branch_filter.matches(&branch.name))
+        .filter(|branch| !(settings.hide_wip && branch.is_wip))
+        .collect())
+}
+
+/// The commits a local branch is ahead of and behind its upstream branch, like
+/// `git status`. `None` for branches without an upstream, or whose upstream is gone.
+fn upstream_ahead_behind(
+    repository: &Repository,
+    branch: &Branch,
+    tip: Oid,
+) -> Option<(usize, usize)> {
+    let upstream = branch.upstream().ok()?.get().target()?;
+    repository.graph_ahead_behind(tip, upstream).ok()
+}
+
+/// Whether the subject of a branch's tip commit matches a work-in-progress pattern.
+fn is_wip(repository: &Repository, tip: Oid, settings: &Settings) -> bool {
+    repository
+        .find_commit(tip)
+        .ok()
+        .and_then(|commit| commit.summary().map(str::to_string))
+        .is_some_and(|summary| matches_wip(&summary, &settings.wip_patterns))
+}
+
+/// Whether a commit subject matches any of the work-in-progress patterns.
+fn matches_wip(summary: &str, patterns: &[Regex]) -> bool {
+    patterns.iter().any(|pattern| pattern.is_match(summary))
+}
+
+/// The remote branches the HEADs of remotes point to, like `origin/main` for
+/// `refs/remotes/origin/HEAD`: the default branches of the remotes, as of the last clone
+/// or `git remote set-head`.
+fn remote_heads(repository: &Repository) -> Result<HashSet<String>, String> {
+    let to_string = |err: Error| err.message().to_string();
+    let mut heads = HashSet::new();
+    for reference in repository
+        .references_glob("refs/remotes/*/HEAD")
+        .map_err(to_string)?
+    {
+        let reference = reference.map_err(to_string)?;
+        if let Some(target) = reference
+            .symbolic_target()
+            .and_then(|target| target.strip_prefix("refs/remotes/"))
+        {
+            heads.insert(target.to_string());
+        }
+    }
+    Ok(heads)
+}
+
+/// The refs of the tags shown, matching the tag filter, named like `tags/v1.0`.
+fn tag_refs(repository: &Repository, settings: &Settings) -> Result<Vec<GraphRef>, String> {
+    let mut tags = Vec::new();
+
+    repository
+        .tag_foreach(|oid, name| {
+            tags.push((oid, name.to_vec()));
+            true
+        })
+        .map_err(|err| err.message().to_string())?;
+
+    // Targets, and whether the tags are annotated
+    let mut refs = vec![];
+    let mut annotated = vec![];
+    for (oid, name) in tags {
+        let name = std::str::from_utf8(&name[5..]).map_err(|err| err.to_string())?;
+
+        let target = repository
+            .find_tag(oid)
+            .map(|tag| (tag.target_id(), true))
+            .or_else(|_| repository.find_commit(oid).map(|_| (oid, false)));
+
+        if let Ok((target, is_annotated)) = target {
+            refs.push(GraphRef {
+                name: name.to_string(),
+                target,
+                is_remote: false,
+                is_tag: true,
+                is_default: false,
+                is_remote_head: false,
+                is_wip: false,
+                ahead_behind: None,
+            });
+            annotated.push(is_annotated);
+        }
+    }
+    if settings.tag_filter.is_empty() {
+        return Ok(refs);
+    }
+
+    let tags: Vec<(&str, bool, i64)> = refs
+        .iter()
+        .zip(annotated)
+        .map(|(tag, is_annotated)| {
+            let time = repository
+                .find_commit(tag.target)
+                .map(|commit| commit.time().seconds())
+                .unwrap_or_default();
+            (&tag.name["tags/".len()..], is_annotated, time)
+        })
+        .collect();
+    let keep = settings.tag_filter.select(&tags);
+    let mut keep = keep.into_iter();
+    refs.retain(|_| keep.next().unwrap_or(false));
+    Ok(refs)
+}
+
+/// Creates the branches of branch and tag refs, of stashes, and of the branches
+/// named in merge summaries. Tags pointing to commits not in the graph are skipped.
+fn branches_from_refs<F>(
+    refs: &[GraphRef],
+    commits: &[CommitInfo],
+    indices: &OidMap<usize>,
+    stashes: &HashMap<Oid, usize>,
+    settings: &Settings,
+    merge_summary: F,
+) -> Result<(Vec<BranchInfo>, OidMap<String>), String>
+where
+    F: Fn(Oid) -> Result<MergeSummary, String>,
+{
+    let mut counter = 0;
+
+    let mut valid_branches = Vec::new();
+    for branch in refs.iter().filter(|branch| !branch.is_tag) {
+        counter += 1;
+        let name = &branch.name[..];
+        let end_index = indices.get(&branch.target).cloned();
+
+        // Distinct remote branches match no branch type's colors
+        let distinct = settings.remote_colors == RemoteColors::Distinct && branch.is_remote;
+        let (terminal_colors, svg_colors) = if distinct {
+            (&[][..], &[][..])
+        } else {
+            (
+                &settings.branches.terminal_colors[..],
+                &settings.branches.svg_colors[..],
+            )
+        };
+
+        let term_color = to_terminal_color(
+            &branch_color(
+                name,
+                terminal_colors,
+                &settings.branches.terminal_colors_unknown,
+                counter,
+                settings.color_seed,
+            )[..],
+        )?;
+
+        // The default branch is the most persistent, in the leftmost group
+        let (persistence, order_group) = if branch.is_default {
+            (0, 0)
+        } else {
+            (
+                branch_order(name, &settings.branches.persistence),
+                branch_order(name, &settings.branches.order),
+            )
+        };
+        let mut branch_info = BranchInfo::new(
+            branch.target,
+            None,
+            name.to_string(),
+            persistence as u8,
+            branch.is_remote,
+            false,
+            false,
+            BranchVis::new(
+                order_group,
+                term_color,
+                branch_color(
+                    name,
+                    svg_colors,
+                    &settings.branches.svg_colors_unknown,
+                    counter,
+                    settings.color_seed,
+                ),
+            ),
+            end_index,
+        );
+        branch_info.is_default = branch.is_default;
+        branch_info.is_remote_head = branch.is_remote_head;
+        branch_info.is_wip = branch.is_wip;
+        branch_info.ahead_behind = branch.ahead_behind;
+        valid_branches.push(branch_info);
+    }
+
+    if settings.stash_mode != StashMode::Hide {
+        for (oid, stash_index) in stashes.iter().sorted_by_key(|(_, index)| **index) {
+            if let Some(&end_index) = indices.get(oid) {
+                counter += 1;
+                let name = format!("stash@{{{}}}", stash_index);
+                let term_color = to_terminal_color(
+                    &branch_color(
+                        &name,
+                        &settings.branches.terminal_colors[..],
+                        &settings.branches.terminal_colors_unknown,
+                        counter,
+                        settings.color_seed,
+                    )[..],
+                )?;
+                let svg_color = branch_color(
+                    &name,
+                    &settings.branches.svg_colors,
+                    &settings.branches.svg_colors_unknown,
+                    counter,
+                    settings.color_seed,
+                );
+                valid_branches.push(BranchInfo::new(
+                    *oid,
+                    None,
+                    name.clone(),
+                    branch_order(&name, &settings.branches.persistence) as u8,
+                    false,
+                    false,
+                    false,
+                    BranchVis::new(
+                        branch_order(&name, &settings.branches.order),
+                        term_color,
+                        svg_color,
+                    ),
+                    Some(end_index),
+                ));
+            }
+        }
+    }
+
+    let mut foxtrots = OidMap::default();
+    for (idx, info) in commits.iter().enumerate() {
+        if info.is_merge {
+            if let Some((summary, parents)) = merge_summary(info.oid)? {
+                let summary = &summary[..];
+                counter += 1;
+
+                let mut parent_oid = parents[1];
+
+                let mut branch_name = match stashes.get(&info.oid) {
+                    Some(stash_index) => format!("stash@{{{}}}/index", stash_index),
+                    None => parse_merge_summary(summary, &settings.merge_patterns)
+                        .unwrap_or_else(|| "unknown".to_string()),
+                };
+
+                // A foxtrot merge: the merged-in branch follows the 2nd parent,
+                // the branch merged into ends at the 1st parent.
+                let follows_second_parent = settings
+                    .branches
+                    .follow_second_parents
+                    .iter()
+                    .any(|pattern| pattern.is_match(branch_name.trim_start_matches(ORIGIN)));
+                if follows_second_parent {
+                    parent_oid = parents[0];
+                    let into = MERGE_INTO
+                        .captures(summary)
+                        .and_then(|captures| captures.get(1))
+                        .map_or("unknown", |into| into.as_str())
+                        .to_string();
+                    foxtrots.insert(info.oid, std::mem::replace(&mut branch_name, into));
+                }
+
+                let persistence = branch_order(&branch_name, &settings.branches.persistence) as u8;
+
+                let pos = branch_order(&branch_name, &settings.branches.order);
+
+                let term_col = to_terminal_color(
+                    &branch_color(
+                        &branch_name,
+                        &settings.branches.terminal_colors[..],
+                        &settings.branches.terminal_colors_unknown,
+                        counter,
+                        settings.color_seed,
+                    )[..],
+                )?;
+                let svg_col = branch_color(
+                    &branch_name,
+                    &settings.branches.svg_colors,
+                    &settings.branches.svg_colors_unknown,
+                    counter,
+                    settings.color_seed,
+                );
+
+                let branch_info = BranchInfo::new(
+                    parent_oid,
+                    Some(info.oid),
+                    branch_name,
+                    persistence,
+                    false,
+                    true,
+                    false,
+                    BranchVis::new(pos, term_col, svg_col),
+                    Some(idx + 1),
+                );
+                valid_branches.push(branch_info);
+            }
+        }
+    }
+
+    valid_branches
+        .sort_by_cached_key(|branch| (branch.persistence, !branch.is_default, !branch.is_merged));
+
+    for tag in refs.iter().filter(|tag| tag.is_tag) {
+        let name = &tag.name[..];
+        let target_oid = tag.target;
+        if let Some(target_index) = indices.get(&target_oid) {
+            counter += 1;
+            let term_col = to_terminal_color(
+                &branch_color(
+                    name,
+                    &settings.branches.terminal_colors[..],
+                    &settings.branches.terminal_colors_unknown,
+                    counter,
+                    settings.color_seed,
+                )[..],
+            )?;
+            let pos = branch_order(name, &settings.branches.order);
+            let svg_col = branch_color(
+                name,
+                &settings.branches.svg_colors,
+                &settings.branches.svg_colors_unknown,
+                counter,
+                settings.color_seed,
+            );
+            let tag_info = BranchInfo::new(
+                target_oid,
+                None,
+                name.to_string(),
+                settings.branches.persistence.len() as u8 + 1,
+                false,
+                false,
+                true,
+                BranchVis::new(pos, term_col, svg_col),
+                Some(*target_index),
+            );
+            valid_branches.push(tag_info);
+        }
+    }
+
+    Ok((valid_branches, foxtrots))
+}
+
+/// Options of [trace_branch], beyond the branch to trace.
+struct TraceOptions<'a> {
+    /// The merged-in branch's name of foxtrot merges, by merge commit
+    foxtrots: &'a OidMap<String>,
+    /// Which remote branches take the color of the local branch they run into
+    remote_colors: RemoteColors,
+}
+
+/// Traces back branches by following 1st commit parent,
+/// until a commit is reached that already has a trace.
+/// At foxtrot merges of the branch itself into another branch, the 2nd parent
+/// is followed instead.
+fn trace_branch(
+    commits: &mut [CommitInfo],
+    indices: &OidMap<usize>,
+    children: &Adjacency,
+    branches: &mut [BranchInfo],
+    oid: Oid,
+    branch_index: usize,
+    options: &TraceOptions,
+) -> bool {
+    let mut curr_oid = oid;
+    let mut prev_index: Option<usize> = None;
+    let mut start_index: Option<i32> = None;
+    let mut any_assigned = false;
+    while let Some(index) = indices.get(&curr_oid) {
+        let info = &mut commits[*index];
+        if let Some(old_trace) = info.branch_trace {
+            let (old_name, old_term, old_svg, old_range) = {
+                let old_branch = &branches[old_trace];
+                (
+                    &old_branch.name.clone(),
+                    old_branch.visual.term_color,
+                    old_branch.visual.svg_color.clone(),
+                    old_branch.range,
+                )
+            };
+            let new_name = &branches[branch_index].name;
+            let old_end = old_range.0.unwrap_or(0);
+            let new_end = branches[branch_index].range.0.unwrap_or(0);
+            if new_name == old_name && old_end >= new_end {
+                let old_branch = &mut branches[old_trace];
+                if let Some(old_end) = old_range.1 {
+                    if index > &old_end {
+                        old_branch.range = (None, None);
+                    } else {
+                        old_branch.range = (Some(*index), old_branch.range.1);
+                    }
+                } else {
+                    old_branch.range = (Some(*index), old_branch.range.1);
+                }
+            } else {
+                let inherits = branches[branch_index]
+                    .inherits_color(&branches[old_trace], options.remote_colors);
+                let branch = &mut branches[branch_index];
+                if inherits {
+                    branch.visual.term_color = old_term;
+                    branch.visual.svg_color = old_svg;
+                }
+                match prev_index {
+                    None => start_index = Some(*index as i32 - 1),
+                    Some(prev_index) => {
+                        // TODO: in cases where no crossings occur, the rule for merge commits can also be applied to normal commits
+                        // see also print::get_deviate_index()
+                        if commits[prev_index].is_merge {
+                            let mut temp_index = prev_index;
+                            for sibling_index in children.get(*index) {
+                                if sibling_index > temp_index {
+                                    temp_index = sibling_index;
+                                }
+                            }
+                            start_index = Some(temp_index as i32);
+                        } else {
+                            start_index = Some(*index as i32 - 1);
+                        }
+                    }
+                }
+                break;
+            }
+        }
+
+        info.branch_trace = Some(branch_index);
+        any_assigned = true;
+
+        let is_foxtrot = |merged: &String| {
+            merged.trim_start_matches(ORIGIN)
+                == branches[branch_index].name.trim_start_matches(ORIGIN)
+        };
+        let parent = match (info.parents, options.foxtrots.get(&info.oid)) {
+            ([_, Some(second)], Some(merged)) if is_foxtrot(merged) => Some(second),
+            ([first, _], _) => first,
+        };
+        match parent {
+            None => {
+                // If no parents, this is the root commit, set `start_index` and break.
+                start_index = Some(*index as i32);
+                break;
+            }
+            Some(parent) => {
+                // Set `prev_index` to the current commit's index and move to the first parent.
+                prev_index = Some(*index);
+                curr_oid = parent;
+            }
+        }
+    }
+
+    let branch = &mut branches[branch_index];
+    if let Some(end) = branch.range.0 {
+        if let Some(start_index) = start_index {
+            if start_index < end as i32 {
+                // TODO: find a better solution (bool field?) to identify non-deleted branches that were not assigned to any commits, and thus should not occupy a column.
+                branch.range = (None, None);
+            } else {
+                branch.range = (branch.range.0, Some(start_index as usize));
+            }
+        } else {
+            branch.range = (branch.range.0, None);
+        }
+    } else {
+        branch.range = (branch.range.0, start_index.map(|si| si as usize));
+    }
+    any_assigned
+}
+
+/// Maps a branch's range of commit indices to the indices after removing the commits
+/// not on a branch, given the new index of each old index. Endpoints removed move inwards
+/// to the nearest commits kept within the range; open ends stay open. A range without
+/// any commits kept, or with an out-of-order or out-of-bounds start, becomes `(None, None)`.
+fn remap_range(
+    range: (Option<usize>, Option<usize>),
+    index_map: &[Option<usize>],
+) -> (Option<usize>, Option<usize>) {
+    let lower = range.0.unwrap_or(0);
+    let upper = range
+        .1
+        .map_or(index_map.len(), |end| (end + 1).min(index_map.len()));
+    let window = index_map.get(lower..upper).unwrap_or(&[]);
+    match (
+        window.iter().find_map(|idx| *idx),
+        window.iter().rev().find_map(|idx| *idx),
+    ) {
+        (Some(first), Some(last)) => (range.0.map(|_| first), range.1.map(|_| last)),
+        _ => (None, None),
+    }
+}
+
+/// Applies the [EmptyBranches] mode to branches that have no commits of their own, as
+/// all their commits are claimed by other branches. By default, only their labels are
+/// shown. Hidden branches are removed from their target commit, and branches in the
+/// lane mode all get a shared column right of all other columns.
+fn place_empty_branches(
+    commits: &mut [CommitInfo],
+    indices: &OidMap<usize>,
+    branches: &mut [BranchInfo],
+    mode: EmptyBranches,
+) {
+    if mode == EmptyBranches::Label {
+        return;
+    }
+    let lane = branches
+        .iter()
+        .filter_map(|branch| branch.visual.column)
+        .max()
+        .map_or(0, |column| column + 1);
+    for (idx, branch) in branches.iter_mut().enumerate() {
+        if !branch.has_no_commits() {
+            continue;
+        }
+        let Some(&target_idx) = indices.get(&branch.target) else {
+            continue;
+        };
+        match mode {
+            EmptyBranches::Label => {}
+            EmptyBranches::Hide => commits[target_idx].branches.retain(|&br| br != idx),
+            EmptyBranches::Lane => branch.visual.column = Some(lane),
+        }
+    }
+}
+
+/// Sorts branches into columns for visualization, that all branches can be
+/// visualizes linearly and without overlaps. Uses Shortest-First scheduling.
+fn assign_branch_columns(
+    commits: &[CommitInfo],
+    indices: &OidMap<usize>,
+    branches: &mut [BranchInfo],
+    settings: &BranchSettings,
+    shortest_first: bool,
+    forward: bool,
+) {
+    // Groups of the branch types in order, unknown branches, and dangling commits
+    let mut occupied: Vec<Vec<Vec<(usize, usize)>>> = vec![vec![]; settings.order.len() + 2];
+
+    let length_sort_factor = if shortest_first { 1 } else { -1 };
+    let start_sort_factor = if forward { 1 } else { -1 };
+
+    let mut branches_sort: Vec<_> = branches
+        .iter()
+        .enumerate()
+        .filter(|(_idx, br)| br.range.0.is_some() || br.range.1.is_some())
+        .map(|(idx, br)| {
+            (
+                idx,
+                br.range.0.unwrap_or(0),
+                br.range.1.unwrap_or(branches.len() - 1),
+                br.visual
+                    .source_order_group
+                    .unwrap_or(settings.order.len() + 1),
+                br.visual
+                    .target_order_group
+                    .unwrap_or(settings.order.len() + 1),
+            )
+        })
+        .collect();
+
+    // The default branch takes the first column of its group
+    branches_sort.sort_by_cached_key(|tup| {
+        (
+            !branches[tup.0].is_default,
+            std::cmp::max(tup.3, tup.4),
+            (tup.2 as i32 - tup.1 as i32) * length_sort_factor,
+            tup.1 as i32 * start_sort_factor,
+        )
+    });
+
+    for (branch_idx, start, end, _, _) in branches_sort {
+        let branch = &branches[branch_idx];
+        let group = branch.visual.order_group;
+        let group_occ = &mut occupied[group];
+
+        let align_right = branch
+            .source_branch
+            .map(|src| branches[src].visual.order_group > branch.visual.order_group)
+            .unwrap_or(false)
+            || branch
+                .target_branch
+                .map(|trg| branches[trg].visual.order_group > branch.visual.order_group)
+                .unwrap_or(false);
+
+        let len = group_occ.len();
+        let mut found = len;
+        for i in 0..len {
+            let index = if align_right { len - i - 1 } else { i };
+            let column_occ = &group_occ[index];
+            let mut occ = false;
+            for (s, e) in column_occ {
+                if start <= *e && end >= *s {
+                    occ = true;
+                    break;
+                }
+            }
+            if !occ {
+                if let Some(merge_trace) = branch
+                    .merge_target
+                    .and_then(|t| indices.get(&t))
+                    .and_then(|t_idx| commits[*t_idx].branch_trace)
+                {
+                    let merge_branch = &branches[merge_trace];
+                    if merge_branch.visual.order_group == branch.visual.order_group {
+                        if let Some(merge_column) = merge_branch.visual.column {
+                            if merge_column == index {
+                                occ = true;
+                            }
+                        }
+                    }
+                }
+            }
+            if !occ {
+                found = index;
+                break;
+            }
+        }
+
+        let branch = &mut branches[branch_idx];
+        branch.visual.column = Some(found);
+        if found == group_occ.len() {
+            group_occ.push(vec![]);
+        }
+        group_occ[found].push((start, end));
+    }
+
+    // Compute start column of each group
+    let mut group_offset: Vec<usize> = vec![];
+    let mut acc = 0;
+    for group in occupied {
+        group_offset.push(acc);
+        acc += group.len();
+    }
+
+    // Compute branch column. Up till now we have computed the branch group
+    // and the column offset within that group. This was to make it easy to
+    // insert columns between groups. Now it is time to convert offset relative
+    // to the group the final column.
+    for branch in branches {
+        if let Some(column) = branch.visual.column {
+            let offset = group_offset[branch.visual.order_group];
+            branch.visual.column = Some(column + offset);
+        }
+    }
+}
+
+/// Finds the index for a branch name from a slice of prefixes
+fn branch_order(name: &str, order: &[Regex]) -> usize {
+    order
+        .iter()
+        .position(|b| (name.starts_with(ORIGIN) && b.is_match(&name[7..])) || b.is_match(name))
+        .unwrap_or(order.len())
+}
+
+/// Finds the color for a branch name. Colors are picked from the branch type's colors
+/// by a running counter, or by a hash of the name if a color seed is given.
+fn branch_color<T: Clone>(
+    name: &str,
+    order: &[(Regex, Vec<T>)],
+    unknown: &[T],
+    counter: usize,
+    seed: Option<u64>,
+) -> T {
+    let stripped_name = name.strip_prefix(ORIGIN).unwrap_or(name);
+    let index = match seed {
+        Some(seed) => name_hash(stripped_name, seed) as usize,
+        None => counter,
+    };
+
+    for (regex, colors) in order {
+        if regex.is_match(stripped_name) {
+            return colors[index % colors.len()].clone();
+        }
+    }
+
+    unknown[index % unknown.len()].clone()
+}
+
+/// A hash of a branch name, stable across platforms, runs and versions (64-bit FNV-1a).
+fn name_hash(name: &str, seed: u64) -> u64 {
+    seed.to_le_bytes()
+        .iter()
+        .chain(name.as_bytes())
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+/// Tries to extract the name of a merged-in branch from the merge commit summary.
+pub fn parse_merge_summary(summary: &str, patterns: &MergePatterns) -> Option<String> {
+    for regex in &patterns.patterns {
+        if let Some(captures) = regex.captures(summary) {
+            if captures.len() == 2 && captures.get(1).is_some() {
+                return captures.get(1).map(|m| m.as_str().to_string());
+            }
+        }
+    }
+    None
+}
+
+/// (De)serialization of object ids as hex strings.
+#[cfg(feature = "serde")]
+mod oid_serde {
+    use git2::Oid;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    fn parse<E: Error>(str: &str) -> Result<Oid, E> {
+        Oid::from_str(str).map_err(|err| E::custom(err.message()))
+    }
+
+    pub mod single {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(oid: &Oid, serializer: S) -> Result<S::Ok, S::Error> {
+            oid.to_string().serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Oid, D::Error> {
+            parse(&String::deserialize(deserializer)?)
+        }
+    }
+
+    pub mod optional {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            oid: &Option<Oid>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            oid.map(|oid| oid.to_string()).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<Oid>, D::Error> {
+            Option::<String>::deserialize(deserializer)?
+                .map(|str| parse(&str))
+                .transpose()
+        }
+    }
+
+    pub mod pair {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            oids: &[Option<Oid>; 2],
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            oids.map(|oid| oid.map(|oid| oid.to_string()))
+                .serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<[Option<Oid>; 2], D::Error> {
+            let [first, second] = <[Option<String>; 2]>::deserialize(deserializer)?;
+            Ok([
+                first.map(|str| parse(&str)).transpose()?,
+                second.map(|str| parse(&str)).transpose()?,
+            ])
+        }
+    }
+
+    pub mod map {
+        use super::*;
+        use crate::graph::OidMap;
+        use std::collections::HashMap;
+
+        pub fn serialize<S: Serializer, V: Serialize>(
+            map: &OidMap<V>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            map.iter()
+                .map(|(oid, value)| (oid.to_string(), value))
+                .collect::<HashMap<_, _>>()
+                .serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>, V: Deserialize<'de>>(
+            deserializer: D,
+        ) -> Result<OidMap<V>, D::Error> {
+            HashMap::<String, V>::deserialize(deserializer)?
+                .into_iter()
+                .map(|(str, value)| parse(&str).map(|oid| (oid, value)))
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::settings::MergePatterns;
+
+    #[test]
+    fn parse_merge_summary() {
+        let patterns = MergePatterns::default();
+
+        let gitlab_pull = "Merge branch 'feature/my-feature' into 'master'";
+        let git_default = "Merge branch 'feature/my-feature' into dev";
+        let git_master = "Merge branch 'feature/my-feature'";
+        let github_pull = "Merge pull request #1 from user-x/feature/my-feature";
+        let github_pull_2 = "Merge branch 'feature/my-feature' of github.com:user-x/repo";
+        let bitbucket_pull = "Merged in feature/my-feature (pull request #1)";
+
+        assert_eq!(
+            super::parse_merge_summary(gitlab_pull, &patterns),
+            Some("feature/my-feature".to_string()),
+        );
+        assert_eq!(
+            super::parse_merge_summary(git_default, &patterns),
+            Some("feature/my-feature".to_string()),
+        );
+        assert_eq!(
+            super::parse_merge_summary(git_master, &patterns),
+            Some("feature/my-feature".to_string()),
+        );
+        assert_eq!(
+            super::parse_merge_summary(github_pull, &patterns),
+            Some("feature/my-feature".to_string()),
+        );
+        assert_eq!(
+            super::parse_merge_summary(github_pull_2, &patterns),
+            Some("feature/my-feature".to_string()),
+        );
+        assert_eq!(
+            super::parse_merge_summary(bitbucket_pull, &patterns),
+            Some("feature/my-feature".to_string()),
+        );
+    }
+
+    #[test]
+    fn merge_into_branch() {
+        let into = |summary: &str| {
+            super::MERGE_INTO
+                .captures(summary)
+                .map(|captures| captures[1].to_string())
+        };
+        assert_eq!(
+            into("Merge branch 'main' into feature/x"),
+            Some("feature/x".to_string())
+        );
+        assert_eq!(
+            into("Merge branch 'main' into 'feature/x'"),
+            Some("feature/x".to_string())
+        );
+        assert_eq!(
+            into("Merge remote-tracking branch 'origin/main' into dev"),
+            Some("dev".to_string())
+        );
+        assert_eq!(into("Merge branch 'feature/x'"), None);
+    }
+
+    #[test]
+    fn parse_merge_summary_with_extra_patterns() {
+        let patterns =
+            MergePatterns::with_patterns(&[r"^Merge remote-tracking branch .origin/(.+).$".into()])
+                .unwrap();
+        assert_eq!(
+            super::parse_merge_summary("Merge remote-tracking branch 'origin/fix'", &patterns),
+            Some("fix".to_string()),
+        );
+        assert_eq!(
+            super::parse_merge_summary("Merge branch 'feature/a'", &patterns),
+            Some("feature/a".to_string()),
+        );
+        assert!(MergePatterns::with_patterns(&["^Merge .+$".into()]).is_err());
+        assert!(MergePatterns::with_patterns(&["^Merge (.+$".into()]).is_err());
+    }
+
+    #[test]
+    fn push_revision_ranges() {
+        use crate::testing::TestRepo;
+        use git2::Oid;
+
+        let repo = TestRepo::bare("git-graph-ranges");
+        let root = repo.commit("root", &[]);
+        let a = repo.commit("a", &[root]);
+        let b = repo.commit("b", &[root]);
+        let walk = |specs: &[String]| {
+            let mut walk = repo.revwalk().unwrap();
+            for spec in specs {
+                super::push_revision_range(&repo, &mut walk, spec).unwrap();
+            }
+            let mut oids: Vec<Oid> = walk.map(|oid| oid.unwrap()).collect();
+            oids.sort();
+            oids
+        };
+        let sorted = |mut oids: Vec<Oid>| {
+            oids.sort();
+            oids
+        };
+        assert_eq!(walk(&[format!("{}..{}", a, b)]), vec![b]);
+        assert_eq!(walk(&[format!("{}...{}", a, b)]), sorted(vec![a, b]));
+        assert_eq!(walk(&[b.to_string(), format!("^{}", a)]), vec![b]);
+        assert_eq!(walk(&[a.to_string()]), sorted(vec![root, a]));
+        assert!(super::push_revision_range(&repo, &mut repo.revwalk().unwrap(), "x..y").is_err());
+    }
+
+    #[test]
+    fn path_filters_follow_renames() {
+        use super::{CommitInfo, OidMap};
+        use crate::testing::TestRepo;
+        use git2::Oid;
+
+        let repo = TestRepo::bare("git-graph-paths");
+        let commit =
+            |parent: &[Oid], files: &[(&str, &str)]| repo.commit_files("commit", parent, files);
+        let root = commit(&[], &[("old.txt", "a\nb\nc\n")]);
+        let other = commit(&[root], &[("old.txt", "a\nb\nc\n"), ("other", "x")]);
+        let renamed = commit(&[other], &[("new.txt", "a\nb\nc\n"), ("other", "x")]);
+        let edited = commit(&[renamed], &[("new.txt", "a\nb\nd\n"), ("other", "x")]);
+
+        let mut commits: Vec<CommitInfo> = [edited, renamed, other, root]
+            .iter()
+            .map(|oid| CommitInfo::read(&repo.odb().unwrap(), *oid).unwrap().0)
+            .collect();
+        let mut indices: OidMap<usize> = commits
+            .iter()
+            .enumerate()
+            .map(|(idx, info)| (info.oid, idx))
+            .collect();
+        let paths = ["new.txt".to_string()];
+        let changing = super::commits_changing_paths(&repo, &commits, &indices, &paths).unwrap();
+        assert_eq!(changing, vec![true, true, false, true]);
+
+        let redirects = super::remove_commits(&mut commits, &mut indices, &changing);
+
+        let oids: Vec<Oid> = commits.iter().map(|info| info.oid).collect();
+        assert_eq!(oids, vec![edited, renamed, root]);
+        assert_eq!(commits[1].parents, [Some(root), None]);
+        assert_eq!(redirects.get(&other), Some(&root));
+    }
+
+    #[test]
+    fn content_search_of_diffs() {
+        use super::{changes_occurrences, touches_content};
+        use crate::settings::content_pattern;
+        use crate::testing::TestRepo;
+        use git2::Oid;
+
+        let repo = TestRepo::bare("git-graph-content");
+        let commit = |parent: &[Oid], content: &str| {
+            repo.commit_files("commit", parent, &[("lib.rs", content)])
+        };
+        let root = commit(&[], "fn parse() {}\n");
+        let added = commit(&[root], "fn parse() {}\nfn render() {}\n");
+        let removed = commit(&[added], "fn render() {}\n");
+
+        let pattern = content_pattern("fn parse").unwrap();
+        let touching = |oid| touches_content(&repo, oid, &pattern).unwrap();
+        assert!(touching(root));
+        assert!(!touching(added));
+        assert!(touching(removed));
+        assert!(content_pattern("fn (").is_err());
+
+        let moved = commit(&[removed], "fn render() {}\n\nfn render2() {}\n");
+        let occurrences = |oid| changes_occurrences(&repo, oid, "fn render").unwrap();
+        assert!(occurrences(added));
+        assert!(!occurrences(removed));
+        assert!(occurrences(moved));
+    }
+
+    #[test]
+    fn symbol_history_of_diff_hunks() {
+        use super::{changes_symbol, symbol_range};
+        use crate::settings::Symbol;
+        use crate::testing::TestRepo;
+        use git2::Oid;
+
+        let rust = "use x;\n\nfn parse(\n    a: u8,\n) -> u8 {\n    a\n}\n\nfn render() {}\n";
+        let parse = Symbol::new("lib.rs", "parse").unwrap();
+        assert_eq!(symbol_range(rust, &parse.definition), Some((3, 7)));
+        let python = "class A:\n    def parse(self):\n        pass\n\n    def b(self):\n";
+        assert_eq!(symbol_range(python, &parse.definition), Some((2, 3)));
+        let c = "int parse(char *s);\n\nint parse(char *s)\n{\n  return 0;\n}\n";
+        assert_eq!(symbol_range(c, &parse.definition), Some((3, 6)));
+        assert_eq!(symbol_range("fn parser() {}\n", &parse.definition), None);
+
+        let repo = TestRepo::bare("git-graph-symbol");
+        let commit = |parent: &[Oid], content: &str| {
+            repo.commit_files("commit", parent, &[("lib.rs", content)])
+        };
+        let root = commit(&[], rust);
+        let other = commit(&[root], &rust.replace("render() {}", "render() { 1 }"));
+        let body = commit(&[other], &rust.replace("    a\n", "    a + 1\n"));
+        let changing = |oid| changes_symbol(&repo, oid, &parse).unwrap();
+        assert!(changing(root));
+        assert!(!changing(other));
+        assert!(changing(body));
+    }
+
+    #[test]
+    fn identity_filters_match_name_and_email() {
+        use super::matches_identity;
+        use crate::settings::identity_patterns;
+        use git2::Signature;
+
+        let sig = Signature::now("Alice Doe", "alice@example.com").unwrap();
+        let patterns = |patterns: &[&str]| {
+            identity_patterns(&patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>()).unwrap()
+        };
+        assert!(matches_identity(&sig, &[]));
+        assert!(matches_identity(&sig, &patterns(&["Alice"])));
+        assert!(matches_identity(
+            &sig,
+            &patterns(&["bob@x", "<alice@example.com>"])
+        ));
+        assert!(!matches_identity(&sig, &patterns(&["^Doe"])));
+    }
+
+    #[test]
+    fn seeded_branch_colors_ignore_order() {
+        use super::branch_color;
+
+        let colors: Vec<usize> = (0..8).collect();
+        let color = |name: &str, counter: usize, seed: Option<u64>| {
+            branch_color(name, &[], &colors, counter, seed)
+        };
+        assert_ne!(color("feature/a", 1, None), color("feature/a", 2, None));
+        assert_eq!(
+            color("feature/a", 1, Some(0)),
+            color("feature/a", 2, Some(0))
+        );
+        assert_eq!(
+            color("feature/a", 1, Some(0)),
+            color("origin/feature/a", 5, Some(0))
+        );
+        let seeds: Vec<usize> = (0..16)
+            .map(|seed| color("feature/a", 0, Some(seed)))
+            .collect();
+        assert!(seeds.iter().any(|color| *color != seeds[0]));
+    }
+
+    #[test]
+    fn linear_fast_path_matches_tracing() {
+        use super::{
+            assign_branch_columns, assign_branches, assign_linear, extract_branches, Adjacency,
+            BranchInfo, CommitInfo, OidMap, TraceOptions,
+        };
+        use crate::testing::{settings, TestRepo};
+        use std::collections::HashMap;
+
+        let repo = TestRepo::bare("git-graph-linear");
+        let mut oids = vec![];
+        for idx in 0..5 {
+            let parents: Vec<_> = oids.last().copied().into_iter().collect();
+            oids.push(repo.commit(&format!("commit {}", idx), &parents));
+        }
+        repo.reference("refs/heads/main", oids[4], true, "")
+            .unwrap();
+        repo.reference("refs/heads/feature/a", oids[2], true, "")
+            .unwrap();
+        repo.reference("refs/heads/develop", oids[3], true, "")
+            .unwrap();
+        repo.reference("refs/remotes/origin/main", oids[3], true, "")
+            .unwrap();
+        repo.reference("refs/tags/v1", oids[1], true, "").unwrap();
+
+        let settings = settings();
+        // Newest first, as walked
+        let new_commits = || -> Vec<CommitInfo> {
+            (0..oids.len())
+                .rev()
+                .map(|idx| CommitInfo::new(oids[idx], &oids[idx.saturating_sub(1)..idx]))
+                .collect()
+        };
+        let commits = new_commits();
+        let indices: OidMap<usize> = commits
+            .iter()
+            .enumerate()
+            .map(|(idx, info)| (info.oid, idx))
+            .collect();
+        let children = Adjacency::children(&commits, &indices);
+        let stashes = HashMap::new();
+
+        let mut fast = new_commits();
+        let (mut fast_branches, _) =
+            extract_branches(&repo, &fast, &indices, &stashes, &settings).unwrap();
+        assert!(assign_linear(
+            &mut fast,
+            &indices,
+            &mut fast_branches,
+            settings.remote_colors
+        ));
+
+        let mut traced = commits;
+        let (branches, foxtrots) =
+            extract_branches(&repo, &traced, &indices, &stashes, &settings).unwrap();
+        let options = TraceOptions {
+            foxtrots: &foxtrots,
+            remote_colors: settings.remote_colors,
+        };
+        let mut traced_branches =
+            assign_branches(&mut traced, &indices, &children, branches, &options);
+        assign_branch_columns(
+            &traced,
+            &indices,
+            &mut traced_branches,
+            &settings.branches,
+            true,
+            true,
+        );
+
+        let layout = |branches: &[BranchInfo]| -> Vec<_> {
+            branches
+                .iter()
+                .map(|branch| {
+                    (
+                        branch.name.clone(),
+                        branch.range,
+                        branch.visual.column,
+                        branch.visual.term_color,
+                        branch.visual.svg_color.clone(),
+                    )
+                })
+                .collect()
+        };
+        assert_eq!(fast_branches.len(), 5);
+        assert_eq!(layout(&fast_branches), layout(&traced_branches));
+        for (fast, traced) in fast.iter().zip(&traced) {
+            assert_eq!(fast.branch_trace, traced.branch_trace);
+            assert_eq!(fast.branches, traced.branches);
+            assert_eq!(fast.tags, traced.tags);
+        }
+    }
+
+    #[test]
+    fn remote_branches_inherit_colors() {
+        use super::{BranchInfo, BranchVis};
+        use crate::settings::RemoteColors;
+        use git2::Oid;
+
+        let branch = |name: &str, is_remote: bool| {
+            let visual = BranchVis::new(0, 0, String::new());
+            BranchInfo::new(
+                Oid::zero(),
+                None,
+                name.to_string(),
+                0,
+                is_remote,
+                false,
+                false,
+                visual,
+                None,
+            )
+        };
+        let main = branch("main", false);
+        let origin = branch("origin/main", true);
+        let upstream = branch("upstream/main", true);
+
+        assert!(origin.inherits_color(&main, RemoteColors::Origin));
+        assert!(!upstream.inherits_color(&main, RemoteColors::Origin));
+        assert!(upstream.inherits_color(&main, RemoteColors::All));
+        assert!(upstream.inherits_color(&origin, RemoteColors::All));
+        assert!(!main.inherits_color(&origin, RemoteColors::All));
+        assert!(!origin.inherits_color(&main, RemoteColors::Distinct));
+    }
+
+    #[test]
+    fn simplify_by_decoration_keeps_forks() {
+        use super::{decoration_topology, remove_commits, CommitInfo, OidMap};
+        use git2::Oid;
+        use std::collections::HashSet;
+
+        let oid = |n: u8| Oid::from_bytes(&[n; 20]).unwrap();
+        // Two decorated tips 0 and 1, forking at 4, with a merge 2 bringing in 3
+        let parents: [&[u8]; 6] = [&[2], &[4], &[4, 3], &[4], &[5], &[]];
+        let mut commits: Vec<CommitInfo> = parents
+            .iter()
+            .enumerate()
+            .map(|(n, parents)| CommitInfo {
+                oid: oid(n as u8),
+                is_merge: parents.len() > 1,
+                parents: [
+                    parents.first().map(|p| oid(*p)),
+                    parents.get(1).map(|p| oid(*p)),
+                ],
+                branches: vec![],
+                tags: vec![],
+                branch_trace: None,
+                lines_changed: None,
+                signature: None,
+                collapsed: None,
+                merged_from: None,
+                hidden: [0, 0],
+                is_stash: false,
+                pickaxe_match: false,
+                is_unreachable: false,
+                replaced_by: None,
+                grafted: false,
+                fixup_of: None,
+                is_merge_base: false,
+                is_dimmed: false,
+                shallow_boundary: false,
+            })
+            .collect();
+        let mut indices: OidMap<usize> = commits
+            .iter()
+            .enumerate()
+            .map(|(idx, info)| (info.oid, idx))
+            .collect();
+
+        let decorated: HashSet<Oid> = [oid(0), oid(1)].into_iter().collect();
+        let keep = decoration_topology(&commits, &indices, &decorated);
+        assert_eq!(keep, [true, true, false, false, true, false]);
+
+        let redirects = remove_commits(&mut commits, &mut indices, &keep);
+        assert_eq!(redirects.get(&oid(2)), Some(&oid(4)));
+        assert_eq!(commits[0].parents, [Some(oid(4)), None]);
+        assert_eq!(commits[0].hidden, [1, 0]);
+        assert_eq!(commits[1].hidden, [0, 0]);
+        assert_eq!(commits[2].parents, [None, None]);
+    }
+
+    #[test]
+    fn reflog_finds_amended_commits() {
+        use super::{mark_unreachable, reflog_entries, CommitInfo, OidMap};
+        use crate::testing::TestRepo;
+        use std::collections::HashMap;
+
+        let repo = TestRepo::init("git-graph-reflog");
+        let root = repo.commit("root", &[]);
+        let original = repo.commit("original", &[root]);
+        let amended = repo.commit("amended", &[root]);
+        repo.reference("refs/heads/main", root, true, "commit")
+            .unwrap();
+        repo.reference("refs/heads/main", original, true, "commit")
+            .unwrap();
+        repo.reference("refs/heads/main", amended, true, "amend")
+            .unwrap();
+        repo.set_head("refs/heads/main").unwrap();
+
+        let entries = reflog_entries(&repo).unwrap();
+        assert_eq!(
+            entries,
+            [
+                ("main@{0}".to_string(), amended),
+                ("main@{1}".to_string(), original),
+                ("main@{2}".to_string(), root),
+            ]
+        );
+
+        let mut commits: Vec<CommitInfo> =
+            [(amended, Some(root)), (original, Some(root)), (root, None)]
+                .into_iter()
+                .map(|(oid, parent)| CommitInfo {
+                    oid,
+                    is_merge: false,
+                    parents: [parent, None],
+                    branches: vec![],
+                    tags: vec![],
+                    branch_trace: None,
+                    lines_changed: None,
+                    signature: None,
+                    collapsed: None,
+                    merged_from: None,
+                    hidden: [0, 0],
+                    is_stash: false,
+                    pickaxe_match: false,
+                    is_unreachable: false,
+                    replaced_by: None,
+                    grafted: false,
+                    fixup_of: None,
+                    is_merge_base: false,
+                    is_dimmed: false,
+                    shallow_boundary: false,
+                })
+                .collect();
+        let indices: OidMap<usize> = commits
+            .iter()
+            .enumerate()
+            .map(|(idx, info)| (info.oid, idx))
+            .collect();
+        mark_unreachable(&repo, &mut commits, &indices, &HashMap::new()).unwrap();
+        let unreachable: Vec<bool> = commits.iter().map(|info| info.is_unreachable).collect();
+        assert_eq!(unreachable, [false, true, false]);
+    }
+
+    #[test]
+    fn shallow_boundaries_from_shallow_file() {
+        use super::shallow_boundaries;
+        use crate::testing::TestRepo;
+
+        let repo = TestRepo::bare("git-graph-shallow");
+        assert!(shallow_boundaries(&repo).unwrap().is_empty());
+
+        let boundary = repo.commit("boundary", &[]);
+        std::fs::write(repo.path().join("shallow"), format!("{}\n", boundary)).unwrap();
+        assert_eq!(shallow_boundaries(&repo).unwrap(), [boundary]);
+    }
+
+    #[test]
+    fn unsigned_commits_and_signature_codes() {
+        use super::{verify_signatures, CommitInfo, SignatureStatus};
+        use crate::testing::TestRepo;
+
+        let repo = TestRepo::bare("git-graph-signatures");
+        let oid = repo.commit("commit", &[]);
+        // Without signed commits, git is not run
+        let mut commits = vec![CommitInfo::new(oid, &[])];
+        verify_signatures(&repo, &mut commits).unwrap();
+        assert_eq!(commits[0].signature, Some(SignatureStatus::Unsigned));
+
+        let status = |code| SignatureStatus::from_code(code);
+        assert_eq!(status("G"), SignatureStatus::Signed);
+        assert_eq!(status("U"), SignatureStatus::Signed);
+        assert_eq!(status("N"), SignatureStatus::Unsigned);
+        assert_eq!(status("R"), SignatureStatus::BadSignature);
+        assert_eq!(status("E"), SignatureStatus::Unverifiable);
+    }
+
+    #[test]
+    fn merge_bases_and_dimmed_commits() {
+        use super::{mark_merge_bases, CommitInfo};
+        use crate::testing::TestRepo;
+
+        let repo = TestRepo::bare("git-graph-merge-base");
+        let root = repo.commit("root", &[]);
+        let base = repo.commit("base", &[root]);
+        let left = repo.commit("left", &[base]);
+        let right = repo.commit("right", &[base]);
+        let other = repo.commit("other", &[root]);
+
+        let mut commits: Vec<_> = [left, right, other, base, root]
+            .iter()
+            .map(|oid| CommitInfo::new(*oid, &[]))
+            .collect();
+        mark_merge_bases(&repo, &mut commits, left, right).unwrap();
+        let bases: Vec<bool> = commits.iter().map(|info| info.is_merge_base).collect();
+        let dimmed: Vec<bool> = commits.iter().map(|info| info.is_dimmed).collect();
+        assert_eq!(bases, [false, false, false, true, false]);
+        assert_eq!(dimmed, [false, false, true, false, false]);
+    }
+
+    #[test]
+    fn replace_refs_join_histories() {
+        use super::{replace_refs, replaced_walk};
+        use crate::testing::TestRepo;
+        use git2::{Oid, Repository, Signature};
+
+        let repo = TestRepo::bare("git-graph-replace");
+        let tree = repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        let commit = |message: &str, time: i64, parent: Option<Oid>| {
+            let sig = Signature::new("a", "a@x", &git2::Time::new(time, 0)).unwrap();
+            let parent = parent.map(|oid| repo.find_commit(oid).unwrap());
+            let parents: Vec<_> = parent.iter().collect();
+            Repository::commit(&repo, None, &sig, &sig, message, &tree, &parents).unwrap()
+        };
+        // An old history, and a new one starting from scratch
+        let old_root = commit("old root", 0, None);
+        let old_tip = commit("old tip", 1, Some(old_root));
+        let new_root = commit("new root", 2, None);
+        let new_tip = commit("new tip", 3, Some(new_root));
+        // The new root grafted onto the old history, like `git replace --graft`
+        let graft = commit("new root", 2, Some(old_tip));
+        repo.reference(&format!("refs/replace/{}", new_root), graft, false, "")
+            .unwrap();
+
+        let replacements = replace_refs(&repo).unwrap();
+        assert_eq!(replacements.len(), 1);
+        assert_eq!(replacements[&new_root], graft);
+
+        let odb = repo.odb().unwrap();
+        let mut walk = repo.revwalk().unwrap();
+        walk.push(new_tip).unwrap();
+        let order = replaced_walk(&odb, walk, &replacements, &Default::default(), false).unwrap();
+        assert_eq!(order, [new_tip, new_root, old_tip, old_root]);
+    }
+
+    #[test]
+    fn grafts_of_info_grafts_file() {
+        use super::{info_grafts, CommitInfo};
+        use crate::testing::TestRepo;
+        use git2::Oid;
+
+        let repo = TestRepo::bare("git-graph-grafts");
+        let path = repo.path();
+        assert!(info_grafts(&repo).unwrap().is_empty());
+
+        let [commit, first, second] = ["1", "2", "3"].map(|hex| Oid::from_str(hex).unwrap());
+        let content = format!("# grafts\n{} {} {}\n\n{}\n", commit, first, second, first);
+        std::fs::write(path.join("info").join("grafts"), content).unwrap();
+        let grafts = info_grafts(&repo).unwrap();
+        assert_eq!(grafts.len(), 2);
+        assert_eq!(grafts[&commit], [first, second]);
+        assert!(grafts[&first].is_empty());
+
+        let mut info = CommitInfo::new(commit, &[first]);
+        info.graft(&grafts[&commit]);
+        assert!(info.grafted && info.is_merge);
+        assert_eq!(info.parents, [Some(first), Some(second)]);
+
+        std::fs::write(path.join("info").join("grafts"), "xyz\n").unwrap();
+        assert!(info_grafts(&repo).is_err());
+    }
+
+    #[test]
+    fn branches_containing_commits() {
+        use super::{find_containing_branches, BranchInfo, BranchVis, CommitInfo, OidMap};
+        use git2::Oid;
+
+        // root <- base <- (main, left), base <- right, base <- unreferenced
+        let [root, base, left, right, unreferenced] =
+            ["1", "2", "3", "4", "5"].map(|hex| Oid::from_str(hex).unwrap());
+        let commits = vec![
+            CommitInfo::new(unreferenced, &[base]),
+            CommitInfo::new(right, &[base]),
+            CommitInfo::new(left, &[base]),
+            CommitInfo::new(base, &[root]),
+            CommitInfo::new(root, &[]),
+        ];
+        let indices: OidMap<usize> = commits
+            .iter()
+            .enumerate()
+            .map(|(idx, info)| (info.oid, idx))
+            .collect();
+        let branch = |name: &str, target| {
+            let visual = BranchVis::new(0, 7, String::new());
+            BranchInfo::new(
+                target,
+                None,
+                name.to_string(),
+                0,
+                false,
+                false,
+                false,
+                visual,
+                None,
+            )
+        };
+        let all_branches = vec![
+            branch("main", left),
+            branch("tags/v1", root),
+            branch("left", left),
+            branch("right", right),
+        ];
+        let containing = find_containing_branches(&commits, &indices, &all_branches, &[0, 2, 3]);
+        assert!(!containing.contains_key(&unreferenced));
+        let containing: Vec<&[usize]> = [right, left, base, root]
+            .iter()
+            .map(|oid| &containing[oid][..])
+            .collect();
+        assert_eq!(containing, [&[3][..], &[0, 2], &[0, 2, 3], &[0, 2, 3]]);
+    }
+
+    #[test]
+    fn autosquash_targets_of_fixup_commits() {
+        use super::{autosquash_subject, mark_fixup_targets, CommitInfo, OidMap};
+        use git2::Oid;
+
+        assert_eq!(
+            autosquash_subject("fixup! squash! Add parser"),
+            Some(("fixup", "Add parser"))
+        );
+        assert_eq!(autosquash_subject("Fix fixup! handling"), None);
+
+        let oids = ["a1", "a2", "a3", "a4", "a5", "a6"].map(|hex| Oid::from_str(hex).unwrap());
+        let summaries = [
+            "fixup! a200000",
+            "squash! fixup! Add parser",
+            "fixup! Add parser",
+            "Add parser tests",
+            "Add parser",
+            "fixup! Unknown",
+        ]
+        .map(str::to_string);
+        let mut commits: Vec<_> = (0..oids.len())
+            .map(|idx| CommitInfo::new(oids[idx], &oids[idx + 1..(idx + 2).min(oids.len())]))
+            .collect();
+        let indices: OidMap<usize> = commits
+            .iter()
+            .enumerate()
+            .map(|(idx, info)| (info.oid, idx))
+            .collect();
+        mark_fixup_targets(&mut commits, &indices, &summaries);
+        let targets: Vec<_> = commits.iter().map(|info| info.fixup_of).collect();
+        // Ids match any commit, subjects match other than autosquash commits by prefix
+        assert_eq!(
+            targets,
+            [
+                Some(oids[1]),
+                Some(oids[3]),
+                Some(oids[3]),
+                None,
+                None,
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn submodule_bumps_of_gitlinks() {
+        use super::find_submodule_bumps;
+        use crate::testing::TestRepo;
+        use git2::Oid;
+
+        let repo = TestRepo::bare("git-graph-submodules");
+        // Submodule commits are not in the superproject's repository
+        let commit = |parent: &[Oid], lib: u8, file: &[u8]| {
+            let mut builder = repo.treebuilder(None).unwrap();
+            let lib = Oid::from_bytes(&[lib; 20]).unwrap();
+            builder.insert("lib", lib, 0o160000).unwrap();
+            builder
+                .insert("file", repo.blob(file).unwrap(), 0o100644)
+                .unwrap();
+            let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+            repo.commit_tree("commit", &tree, parent)
+        };
+        let added = commit(&[], 1, b"a");
+        let unchanged = commit(&[added], 1, b"b");
+        let bumped = commit(&[unchanged], 2, b"b");
+
+        let bumps = |oid| {
+            find_submodule_bumps(&repo, oid)
+                .unwrap()
+                .into_iter()
+                .map(|bump| (bump.path, bump.oid))
+                .collect::<Vec<_>>()
+        };
+        let lib = |n: u8| Oid::from_bytes(&[n; 20]).unwrap();
+        assert_eq!(bumps(added), [("lib".to_string(), lib(1))]);
+        assert!(bumps(unchanged).is_empty());
+        assert_eq!(bumps(bumped), [("lib".to_string(), lib(2))]);
+    }
+
+    #[test]
+    fn other_worktrees_with_heads() {
+        use super::other_worktrees;
+        use crate::testing::{TempDir, TestRepo};
+        use git2::Repository;
+
+        let dir = TempDir::new("git-graph-worktrees");
+        let base = dir.path();
+        let repo = Repository::init(base.join("main")).unwrap();
+        let sig = TestRepo::signature();
+        let tree = repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        let root = repo
+            .commit(Some("HEAD"), &sig, &sig, "root", &tree, &[])
+            .unwrap();
+        assert!(other_worktrees(&repo).unwrap().is_empty());
+
+        let worktree = repo.worktree("linked", &base.join("linked"), None).unwrap();
+        let worktrees = other_worktrees(&repo).unwrap();
+        assert_eq!(worktrees.len(), 1);
+        assert_eq!(worktrees[0].name, "linked");
+        assert_eq!(worktrees[0].head.branch(), Some("linked"));
+        assert_eq!(worktrees[0].head.oid(), Some(root));
+
+        // From the linked worktree, the main worktree is the other one
+        let linked = Repository::open_from_worktree(&worktree).unwrap();
+        let worktrees = other_worktrees(&linked).unwrap();
+        assert_eq!(worktrees.len(), 1);
+        assert_eq!(worktrees[0].name, "main");
+    }
+
+    #[test]
+    fn dangling_commits_are_unreachable_tips() {
+        use super::dangling_commits;
+        use crate::testing::TestRepo;
+        use std::collections::HashMap;
+
+        let repo = TestRepo::init("git-graph-dangling");
+        let root = repo.commit("root", &[]);
+        let lost = repo.commit("lost", &[root]);
+        let lost_tip = repo.commit("lost tip", &[lost]);
+        repo.reference("refs/heads/main", root, true, "commit")
+            .unwrap();
+        repo.set_head("refs/heads/main").unwrap();
+
+        let dangling = dangling_commits(&repo, &HashMap::new()).unwrap();
+        let name = format!("dangling/{}", &lost_tip.to_string()[..7]);
+        assert_eq!(dangling, [(name, lost_tip)]);
+
+        repo.reference("refs/heads/lost", lost_tip, true, "recover")
+            .unwrap();
+        assert!(dangling_commits(&repo, &HashMap::new()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn empty_branches_share_a_lane_or_are_hidden() {
+        use super::{place_empty_branches, BranchInfo, BranchVis, CommitInfo, OidMap};
+        use crate::settings::EmptyBranches;
+        use git2::Oid;
+
+        let oid = |n: u8| Oid::from_bytes(&[n; 20]).unwrap();
+        let commits = || {
+            (0..2)
+                .map(|n| CommitInfo {
+                    oid: oid(n),
+                    is_merge: false,
+                    parents: [(n == 0).then(|| oid(1)), None],
+                    branches: if n == 0 { vec![0, 1] } else { vec![] },
+                    tags: vec![],
+                    branch_trace: Some(0),
+                    lines_changed: None,
+                    signature: None,
+                    collapsed: None,
+                    merged_from: None,
+                    hidden: [0, 0],
+                    is_stash: false,
+                    pickaxe_match: false,
+                    is_unreachable: false,
+                    replaced_by: None,
+                    grafted: false,
+                    fixup_of: None,
+                    is_merge_base: false,
+                    is_dimmed: false,
+                    shallow_boundary: false,
+                })
+                .collect::<Vec<_>>()
+        };
+        let branches = || {
+            let branch = |name: &str, range| {
+                let mut info = BranchInfo::new(
+                    oid(0),
+                    None,
+                    name.to_string(),
+                    0,
+                    false,
+                    false,
+                    false,
+                    BranchVis::new(0, 0, String::new()),
+                    None,
+                );
+                info.range = range;
+                info
+            };
+            let mut main = branch("main", (Some(0), Some(1)));
+            main.visual.column = Some(0);
+            vec![main, branch("feature", (None, None))]
+        };
+        let indices: OidMap<usize> = [(oid(0), 0), (oid(1), 1)].into_iter().collect();
+
+        let (mut lane_commits, mut lane_branches) = (commits(), branches());
+        place_empty_branches(
+            &mut lane_commits,
+            &indices,
+            &mut lane_branches,
+            EmptyBranches::Lane,
+        );
+        assert!(lane_branches[1].has_no_commits());
+        assert_eq!(lane_branches[1].visual.column, Some(1));
+        assert_eq!(lane_commits[0].branches, vec![0, 1]);
 
-        info.branch_trace = Some(branch_index);
-        any_assigned = true;
+        let (mut hide_commits, mut hide_branches) = (commits(), branches());
+        place_empty_branches(
+            &mut hide_commits,
+            &indices,
+            &mut hide_branches,
+            EmptyBranches::Hide,
+        );
+        assert_eq!(hide_branches[1].visual.column, None);
+        assert_eq!(hide_commits[0].branches, vec![0]);
+    }
 
-        let commit = repository.find_commit(curr_oid)?;
-        if commit.parent_count() == 0 {
-            // If no parents, this is the root commit, set `start_index` and break.
-            start_index = Some(*index as i32);
-            break;
+    #[test]
+    fn remap_ranges_to_filtered_indices() {
+        use super::remap_range;
+
+        // Old indices 1 and 3 to 5 were removed
+        let index_map = [Some(0), None, Some(1), None, None, None];
+        assert_eq!(
+            remap_range((Some(0), Some(2)), &index_map),
+            (Some(0), Some(1))
+        );
+        // Traces starting or ending at removed commits shrink to the commits kept
+        assert_eq!(
+            remap_range((Some(1), Some(4)), &index_map),
+            (Some(1), Some(1))
+        );
+        assert_eq!(
+            remap_range((Some(0), Some(3)), &index_map),
+            (Some(0), Some(1))
+        );
+        // Traces ending at the last commit, removed, used to walk out of bounds
+        assert_eq!(remap_range((Some(3), Some(5)), &index_map), (None, None));
+        assert_eq!(remap_range((Some(3), None), &index_map), (None, None));
+        assert_eq!(remap_range((Some(1), None), &index_map), (Some(1), None));
+        assert_eq!(remap_range((None, Some(1)), &index_map), (None, Some(0)));
+        // Open, inverted and out-of-bounds ranges
+        assert_eq!(remap_range((None, None), &index_map), (None, None));
+        assert_eq!(remap_range((Some(2), Some(0)), &index_map), (None, None));
+        assert_eq!(remap_range((Some(9), Some(12)), &index_map), (None, None));
+        assert_eq!(
+            remap_range((Some(2), Some(12)), &index_map),
+            (Some(1), Some(1))
+        );
+        assert_eq!(remap_range((Some(0), None), &[]), (None, None));
+    }
+
+    #[test]
+    fn graph_data_is_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        fn assert_send<T: Send>() {}
+        assert_send_sync::<super::GraphData>();
+        // Graphs can be moved between threads, e.g. across awaits of async servers
+        assert_send::<super::GitGraph>();
+    }
+
+    #[test]
+    fn wip_subjects_match_default_patterns() {
+        use super::matches_wip;
+        use crate::settings::{wip_pattern, DEFAULT_WIP_PATTERNS};
+
+        let patterns: Vec<_> = DEFAULT_WIP_PATTERNS
+            .iter()
+            .map(|pattern| wip_pattern(pattern).unwrap())
+            .collect();
+        for summary in [
+            "WIP",
+            "wip: parser",
+            "WIP login form",
+            "fixup! Add parser",
+            "squash! Fix",
+        ] {
+            assert!(matches_wip(summary, &patterns), "{}", summary);
+        }
+        for summary in [
+            "Wipe cache",
+            "Add fixup! support",
+            "fixup!",
+            "Fix WIP detection",
+        ] {
+            assert!(!matches_wip(summary, &patterns), "{}", summary);
         }
-        // Set `prev_index` to the current commit's index and move to the first parent.
-        prev_index = Some(*index);
-        curr_oid = commit.parent_id(0)?;
+        assert!(!matches_wip("WIP", &[]));
     }
 
-    let branch = &mut branches[branch_index];
-    if let Some(end) = branch.range.0 {
-        if let Some(start_index) = start_index {
-            if start_index < end as i32 {
-                // TODO: find a better solution (bool field?) to identify non-deleted branches that were not assigned to any commits, and thus should not occupy a column.
-                branch.range = (None, None);
-            } else {
-                branch.range = (branch.range.0, Some(start_index as usize));
-            }
-        } else {
-            branch.range = (branch.range.0, None);
+    #[test]
+    fn collapse_merges_of_single_commits() {
+        use super::GitGraph;
+        use crate::print::svg::print_svg;
+        use crate::print::unicode::print_unicode;
+        use crate::testing::{settings, TestRepo};
+
+        let repo = TestRepo::bare("git-graph-collapse");
+        let root = repo.commit("root", &[]);
+        // Trivial: a single commit on top of the merge's first parent
+        let single = repo.commit("single", &[root]);
+        let trivial = repo.commit("Merge single", &[root, single]);
+        // Not trivial: two commits merged
+        let first = repo.commit("first of two", &[root]);
+        let second = repo.commit("second of two", &[first]);
+        let two = repo.commit("Merge two", &[trivial, second]);
+        // A ref points to the merged commit
+        let pointed = repo.commit("pointed", &[two]);
+        let ref_merge = repo.commit("Merge pointed", &[two, pointed]);
+        // The merged commit has another child
+        let shared = repo.commit("shared", &[ref_merge]);
+        let child = repo.commit("child", &[shared]);
+        let multi = repo.commit("Merge shared", &[ref_merge, shared]);
+        repo.reference("refs/heads/main", multi, true, "").unwrap();
+        repo.reference("refs/heads/keep", pointed, true, "")
+            .unwrap();
+        repo.reference("refs/heads/other", child, true, "").unwrap();
+
+        let mut settings = settings();
+        settings.collapse_trivial_merges = true;
+        let graph = GitGraph::new(&repo, &settings, None).unwrap();
+        let info = |oid| &graph.commits[graph.indices[&oid]];
+
+        assert!(!graph.indices.contains_key(&single));
+        assert_eq!(info(trivial).collapsed, Some(single));
+        assert_eq!(info(trivial).parents, [Some(root), None]);
+        assert!(!info(trivial).is_merge);
+        for (merge, merged) in [(two, second), (ref_merge, pointed), (multi, shared)] {
+            assert_eq!(info(merge).collapsed, None);
+            assert!(info(merge).is_merge);
+            assert!(graph.indices.contains_key(&merged));
         }
-    } else {
-        branch.range = (branch.range.0, start_index.map(|si| si as usize));
+
+        let badge = format!("[merged {}]", &single.to_string()[..7]);
+        let (_, t_lines, _) = print_unicode(&graph, &settings).unwrap();
+        assert_eq!(t_lines.iter().filter(|l| l.contains(&badge)).count(), 1);
+        assert!(print_svg(&graph, &settings).unwrap().contains(&badge));
     }
-    Ok(any_assigned)
-}
 
-/// Sorts branches into columns for visualization, that all branches can be
-/// visualizes linearly and without overlaps. Uses Shortest-First scheduling.
-fn assign_branch_columns(
-    commits: &[CommitInfo],
-    indices: &HashMap<Oid, usize>,
-    branches: &mut [BranchInfo],
-    settings: &BranchSettings,
-    shortest_first: bool,
-    forward: bool,
-) {
-    let mut occupied: Vec<Vec<Vec<(usize, usize)>>> = vec![vec![]; settings.order.len() + 1];
+    #[test]
+    fn stash_display_modes() {
+        use super::GitGraph;
+        use crate::settings::StashMode;
+        use crate::testing::{settings, TestRepo};
+        use git2::Repository;
+        use std::path::Path;
 
-    let length_sort_factor = if shortest_first { 1 } else { -1 };
-    let start_sort_factor = if forward { 1 } else { -1 };
+        let repo = TestRepo::init("git-graph-stash");
+        let workdir = repo.workdir().unwrap();
+        std::fs::write(workdir.join("file"), "committed\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("file")).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let root = repo.commit_tree("root", &tree, &[]);
+        repo.reference("refs/heads/main", root, true, "").unwrap();
+        repo.set_head("refs/heads/main").unwrap();
+        std::fs::write(workdir.join("file"), "changed\n").unwrap();
+        let stash = Repository::open(workdir)
+            .unwrap()
+            .stash_save(&TestRepo::signature(), "wip", None)
+            .unwrap();
+        let stash_index = repo.find_commit(stash).unwrap().parent_id(1).unwrap();
 
-    let mut branches_sort: Vec<_> = branches
-        .iter()
-        .enumerate()
-        .filter(|(_idx, br)| br.range.0.is_some() || br.range.1.is_some())
-        .map(|(idx, br)| {
-            (
-                idx,
-                br.range.0.unwrap_or(0),
-                br.range.1.unwrap_or(branches.len() - 1),
-                br.visual
-                    .source_order_group
-                    .unwrap_or(settings.order.len() + 1),
-                br.visual
-                    .target_order_group
-                    .unwrap_or(settings.order.len() + 1),
-            )
-        })
-        .collect();
+        let mut settings = settings();
+        let graph = GitGraph::new(&repo, &settings, None).unwrap();
+        assert!(!graph.indices.contains_key(&stash));
+        assert_eq!(graph.commits.len(), 1);
 
-    branches_sort.sort_by_cached_key(|tup| {
-        (
-            std::cmp::max(tup.3, tup.4),
-            (tup.2 as i32 - tup.1 as i32) * length_sort_factor,
-            tup.1 as i32 * start_sort_factor,
-        )
-    });
+        // The stash is shown, its index commit is not
+        settings.stash_mode = StashMode::Show;
+        let graph = GitGraph::new(&repo, &settings, None).unwrap();
+        let info = &graph.commits[graph.indices[&stash]];
+        assert!(!info.is_merge);
+        assert_eq!(info.parents, [Some(root), None]);
+        assert_eq!(graph.branch_of(stash).unwrap().name, "stash@{0}");
+        assert!(!graph.indices.contains_key(&stash_index));
 
-    for (branch_idx, start, end, _, _) in branches_sort {
-        let branch = &branches[branch_idx];
-        let group = branch.visual.order_group;
-        let group_occ = &mut occupied[group];
+        settings.stash_mode = StashMode::ShowWithParents;
+        let graph = GitGraph::new(&repo, &settings, None).unwrap();
+        assert!(graph.commits[graph.indices[&stash]].is_merge);
+        assert_eq!(
+            graph.branch_of(stash_index).unwrap().name,
+            "stash@{0}/index"
+        );
+    }
 
-        let align_right = branch
-            .source_branch
-            .map(|src| branches[src].visual.order_group > branch.visual.order_group)
-            .unwrap_or(false)
-            || branch
-                .target_branch
-                .map(|trg| branches[trg].visual.order_group > branch.visual.order_group)
-                .unwrap_or(false);
+    #[test]
+    fn parallel_results_in_commit_order() {
+        use super::{count_changed_lines, per_commit_parallel};
+        use crate::testing::TestRepo;
+        use git2::Oid;
 
-        let len = group_occ.len();
-        let mut found = len;
-        for i in 0..len {
-            let index = if align_right { len - i - 1 } else { i };
-            let column_occ = &group_occ[index];
-            let mut occ = false;
-            for (s, e) in column_occ {
-                if start <= *e && end >= *s {
-                    occ = true;
-                    break;
-                }
-            }
-            if !occ {
-                if let Some(merge_trace) = branch
-                    .merge_target
-                    .and_then(|t| indices.get(&t))
-                    .and_then(|t_idx| commits[*t_idx].branch_trace)
-                {
-                    let merge_branch = &branches[merge_trace];
-                    if merge_branch.visual.order_group == branch.visual.order_group {
-                        if let Some(merge_column) = merge_branch.visual.column {
-                            if merge_column == index {
-                                occ = true;
-                            }
-                        }
-                    }
-                }
-            }
-            if !occ {
-                found = index;
-                break;
-            }
+        let repo = TestRepo::bare("git-graph-parallel");
+        let mut oids = vec![];
+        let mut content = String::new();
+        for idx in 0..40 {
+            // Each commit changes a different number of lines
+            content.push_str(&format!("{}\n", idx).repeat(idx % 7 + 1));
+            let parents: Vec<Oid> = oids.last().copied().into_iter().collect();
+            oids.push(repo.commit_files("change", &parents, &[("file", &content)]));
         }
 
-        let branch = &mut branches[branch_idx];
-        branch.visual.column = Some(found);
-        if found == group_occ.len() {
-            group_occ.push(vec![]);
+        let sequential: Vec<usize> = oids
+            .iter()
+            .map(|oid| count_changed_lines(&repo, *oid).unwrap())
+            .collect();
+        let parallel = per_commit_parallel(&repo, &oids, None, count_changed_lines).unwrap();
+        assert_eq!(parallel, sequential);
+        assert_eq!(&parallel[..3], &[1, 2, 3]);
+
+        let missing = Oid::from_str("0123456789012345678901234567890123456789").unwrap();
+        oids.push(missing);
+        assert!(per_commit_parallel(&repo, &oids, None, count_changed_lines).is_err());
+    }
+
+    #[test]
+    fn signed_releases_along_persistent_branches() {
+        use super::GitGraph;
+        use crate::settings::GraphView;
+        use crate::testing::{settings, TestRepo};
+        use git2::Oid;
+
+        let repo = TestRepo::bare("git-graph-signed-releases");
+        let sign = |name: &str, oid: Oid| {
+            let message = "Release\n-----BEGIN PGP SIGNATURE-----\n-----END PGP SIGNATURE-----\n";
+            let target = repo.find_object(oid, None).unwrap();
+            repo.tag(name, &target, &TestRepo::signature(), message, false)
+                .unwrap();
+        };
+        let m1 = repo.commit("main 1", &[]);
+        let m2 = repo.commit("main 2", &[m1]);
+        let m3 = repo.commit("main 3", &[m2]);
+        let d1 = repo.commit("develop 1", &[m1]);
+        let d2 = repo.commit("develop 2", &[d1]);
+        let d3 = repo.commit("develop 3", &[d2]);
+        let t1 = repo.commit("topic 1", &[m1]);
+        let t2 = repo.commit("topic 2", &[t1]);
+        let t3 = repo.commit("topic 3", &[t2]);
+        repo.reference("refs/heads/main", m3, true, "").unwrap();
+        repo.reference("refs/heads/develop", d3, true, "").unwrap();
+        repo.reference("refs/heads/feature/topic", t3, true, "")
+            .unwrap();
+        for (name, oid) in [
+            ("v1", m1),
+            ("v2", m3),
+            ("dev-1", d1),
+            ("dev-2", d3),
+            ("topic-1", t1),
+            ("topic-2", t3),
+        ] {
+            sign(name, oid);
         }
-        group_occ[found].push((start, end));
+
+        let mut settings = settings();
+        settings.view = GraphView::SignedReleases;
+        let graph = GitGraph::new(&repo, &settings, None).unwrap();
+        // The path between releases is kept on main and develop, but not on the
+        // feature branch, though it matches a persistence pattern of the model
+        for oid in [m1, m2, m3, d1, d2, d3, t1, t3] {
+            assert!(graph.indices.contains_key(&oid));
+        }
+        assert!(!graph.indices.contains_key(&t2));
+
+        settings.release_levels = 1;
+        let graph = GitGraph::new(&repo, &settings, None).unwrap();
+        assert!(graph.indices.contains_key(&m2));
+        assert!(!graph.indices.contains_key(&d2));
     }
 
-    // Compute start column of each group
-    let mut group_offset: Vec<usize> = vec![];
-    let mut acc = 0;
-    for group in occupied {
-        group_offset.push(acc);
-        acc += group.len();
+    #[test]
+    fn ahead_behind_upstream_branches() {
+        use super::{upstream_ahead_behind, GitGraph};
+        use crate::settings::Settings;
+        use crate::testing::{settings, TestRepo};
+        use git2::BranchType;
+
+        let repo = TestRepo::bare("git-graph-upstream");
+        let root = repo.commit("root", &[]);
+        let local = repo.commit("local 1", &[root]);
+        let local = repo.commit("local 2", &[local]);
+        let remote = repo.commit("remote", &[root]);
+        repo.reference("refs/heads/main", local, false, "").unwrap();
+        repo.reference("refs/heads/topic", root, false, "").unwrap();
+        repo.reference("refs/remotes/origin/main", remote, false, "")
+            .unwrap();
+        repo.remote("origin", "https://example.com/repo.git")
+            .unwrap();
+
+        let main = repo.find_branch("main", BranchType::Local).unwrap();
+        assert_eq!(upstream_ahead_behind(&repo, &main, local), None);
+        repo.find_branch("main", BranchType::Local)
+            .unwrap()
+            .set_upstream(Some("origin/main"))
+            .unwrap();
+        let main = repo.find_branch("main", BranchType::Local).unwrap();
+        assert_eq!(upstream_ahead_behind(&repo, &main, local), Some((2, 1)));
+        let topic = repo.find_branch("topic", BranchType::Local).unwrap();
+        assert_eq!(upstream_ahead_behind(&repo, &topic, root), None);
+
+        // Only counted on request
+        let mut settings = settings();
+        let counts = |settings: &Settings| {
+            let graph = GitGraph::new(&repo, settings, None).unwrap();
+            let main = graph.all_branches.iter().find(|br| br.name == "main");
+            main.unwrap().ahead_behind
+        };
+        assert_eq!(counts(&settings), None);
+        settings.ahead_behind = true;
+        assert_eq!(counts(&settings), Some((2, 1)));
     }
 
-    // Compute branch column. Up till now we have computed the branch group
-    // and the column offset within that group. This was to make it easy to
-    // insert columns between groups. Now it is time to convert offset relative
-    // to the group the final column.
-    for branch in branches {
-        if let Some(column) = branch.visual.column {
-            let offset = group_offset[branch.visual.order_group];
-            branch.visual.column = Some(column + offset);
+    #[test]
+    fn remote_heads_name_default_branches() {
+        use super::remote_heads;
+        use crate::testing::TestRepo;
+
+        let repo = TestRepo::bare("git-graph-remote-head");
+        let root = repo.commit("root", &[]);
+        for name in ["origin/stable", "origin/main", "upstream/trunk"] {
+            repo.reference(&format!("refs/remotes/{}", name), root, false, "")
+                .unwrap();
         }
+        repo.reference_symbolic(
+            "refs/remotes/origin/HEAD",
+            "refs/remotes/origin/stable",
+            false,
+            "",
+        )
+        .unwrap();
+        repo.reference_symbolic(
+            "refs/remotes/upstream/HEAD",
+            "refs/remotes/upstream/trunk",
+            false,
+            "",
+        )
+        .unwrap();
+
+        let mut heads: Vec<_> = remote_heads(&repo).unwrap().into_iter().collect();
+        heads.sort();
+        assert_eq!(heads, ["origin/stable", "upstream/trunk"]);
     }
-}
 
-/// Finds the index for a branch name from a slice of prefixes
-fn branch_order(name: &str, order: &[Regex]) -> usize {
-    order
-        .iter()
-        .position(|b| (name.starts_with(ORIGIN) && b.is_match(&name[7..])) || b.is_match(name))
-        .unwrap_or(order.len())
-}
+    #[test]
+    fn head_of_repositories_without_commits() {
+        use super::HeadInfo;
+        use crate::testing::TestRepo;
 
-/// Finds the svg color for a branch name.
-fn branch_color<T: Clone>(
-    name: &str,
-    order: &[(Regex, Vec<T>)],
-    unknown: &[T],
-    counter: usize,
-) -> T {
-    let stripped_name = name.strip_prefix(ORIGIN).unwrap_or(name);
+        let repo = TestRepo::bare("git-graph-unborn");
+        repo.set_head("refs/heads/trunk").unwrap();
+        let head = HeadInfo::of_repository(&repo).unwrap();
+        assert_eq!(
+            head,
+            HeadInfo::Unborn {
+                name: "trunk".to_string()
+            }
+        );
+        assert_eq!((head.oid(), head.branch()), (None, None));
 
-    for (regex, colors) in order {
-        if regex.is_match(stripped_name) {
-            return colors[counter % colors.len()].clone();
+        let root = repo.commit("root", &[]);
+        repo.reference("refs/heads/trunk", root, false, "").unwrap();
+        let head = HeadInfo::of_repository(&repo).unwrap();
+        assert_eq!((head.oid(), head.branch()), (Some(root), Some("trunk")));
+
+        repo.set_head_detached(root).unwrap();
+        let head = HeadInfo::of_repository(&repo).unwrap();
+        assert_eq!(head, HeadInfo::Detached { oid: root });
+    }
+
+    #[test]
+    fn metadata_cache_evicts_least_recently_used() {
+        use super::{CommitMeta, MetadataCache, METADATA_CACHE_SIZE};
+        use git2::{Oid, Time};
+        use std::sync::Arc;
+
+        let meta = Arc::new(CommitMeta {
+            summary: vec![],
+            author_name: vec![],
+            author_email: vec![],
+            author_time: Time::new(0, 0),
+            time: Time::new(0, 0),
+        });
+        let oid = |idx: usize| Oid::from_str(&format!("{:08x}{:032x}", idx, 0)).unwrap();
+
+        let mut cache = MetadataCache::default();
+        for idx in 0..METADATA_CACHE_SIZE {
+            cache.insert(oid(idx), meta.clone());
         }
+        assert!(cache.get(oid(0)).is_some());
+        cache.insert(oid(METADATA_CACHE_SIZE), meta.clone());
+
+        assert_eq!(cache.entries.len(), METADATA_CACHE_SIZE);
+        assert!(cache.get(oid(0)).is_some());
+        assert!(cache.get(oid(1)).is_none());
     }
 
-    unknown[counter % unknown.len()].clone()
-}
+    #[test]
+    fn memory_provider_lays_out_like_repository() {
+        use super::GitGraph;
+        use crate::provider::{MemoryRepo, RepoProvider};
+        use crate::testing::{settings, TestRepo};
 
-/// Tries to extract the name of a merged-in branch from the merge commit summary.
-pub fn parse_merge_summary(summary: &str, patterns: &MergePatterns) -> Option<String> {
-    for regex in &patterns.patterns {
-        if let Some(captures) = regex.captures(summary) {
-            if captures.len() == 2 && captures.get(1).is_some() {
-                return captures.get(1).map(|m| m.as_str().to_string());
-            }
+        let repo = TestRepo::bare("git-graph-memory-provider");
+        let root = repo.commit("root", &[]);
+        let feature = repo.commit("feature", &[root]);
+        let main = repo.commit("main", &[root]);
+        let merge = repo.commit("Merge branch 'feature/x'", &[main, feature]);
+        let develop = repo.commit("develop", &[merge]);
+        repo.reference("refs/heads/main", merge, true, "").unwrap();
+        repo.reference("refs/heads/develop", develop, true, "")
+            .unwrap();
+
+        let mut memory = MemoryRepo::new();
+        for oid in RepoProvider::walk(&*repo, None).unwrap() {
+            let commit = RepoProvider::commit(&*repo, oid).unwrap();
+            memory.add_commit(oid, &commit.parents, commit.meta);
+        }
+        for (name, target) in RepoProvider::refs(&*repo).unwrap() {
+            memory.set_ref(&name, target);
         }
+
+        let mut settings = settings();
+        settings.branch_counts = true;
+        let expected = GitGraph::new(&repo, &settings, None).unwrap();
+        let graph = GitGraph::from_provider(&memory, &settings, None).unwrap();
+        let layout = |graph: &GitGraph| {
+            let layout: Vec<_> = graph
+                .commits
+                .iter()
+                .map(|info| {
+                    let branch = &graph.all_branches[info.branch_trace.unwrap()];
+                    (info.oid, branch.name.clone(), branch.visual.column)
+                })
+                .collect();
+            layout
+        };
+        assert_eq!(layout(&graph), layout(&expected));
+        assert_eq!(graph.containing_branches, expected.containing_branches);
+        assert_eq!(graph.head, None);
+
+        // Options reading the content of commits fail instead of being ignored
+        settings.path_filters = vec!["src".to_string()];
+        let err = GitGraph::from_provider(&memory, &settings, None).err();
+        assert_eq!(err.as_deref(), Some("--path-filter needs a git repository"));
     }
-    None
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::settings::MergePatterns;
+    #[test]
+    fn metadata_loaded_on_first_access() {
+        use super::GitGraph;
+        use crate::testing::{settings, TestRepo};
 
+        let repo = TestRepo::bare("git-graph-lazy-metadata");
+        let root = repo.commit("root", &[]);
+        let tip = repo.commit("tip", &[root]);
+        repo.reference("refs/heads/main", tip, true, "").unwrap();
+
+        let graph = GitGraph::new(&repo, &settings(), None).unwrap();
+        assert!(graph.metadata.borrow().entries.is_empty());
+        assert!(graph.metadata.borrow().pinned.is_empty());
+        assert_eq!(graph.metadata(tip).unwrap().summary, b"tip");
+        assert_eq!(graph.metadata.borrow().entries.len(), 1);
+        // Loading metadata does not make the repository available
+        assert!(graph.repository().is_err());
+    }
+
+    #[cfg(feature = "serde")]
     #[test]
-    fn parse_merge_summary() {
-        let patterns = MergePatterns::default();
+    fn serde_commit_info() {
+        use super::CommitInfo;
+        use git2::Oid;
 
-        let gitlab_pull = "Merge branch 'feature/my-feature' into 'master'";
-        let git_default = "Merge branch 'feature/my-feature' into dev";
-        let git_master = "Merge branch 'feature/my-feature'";
-        let github_pull = "Merge pull request #1 from user-x/feature/my-feature";
-        let github_pull_2 = "Merge branch 'feature/my-feature' of github.com:user-x/repo";
-        let bitbucket_pull = "Merged in feature/my-feature (pull request #1)";
+        let oid = Oid::from_str("91f3bb2a514e842fa88eb3fda50a889e95eb81d4").unwrap();
+        let info = CommitInfo {
+            oid,
+            is_merge: false,
+            parents: [Some(oid), None],
+            branches: vec![1],
+            tags: vec![],
+            branch_trace: Some(1),
+            lines_changed: None,
+            signature: None,
+            collapsed: None,
+            merged_from: None,
+            hidden: [0, 0],
+            is_stash: false,
+            pickaxe_match: false,
+            is_unreachable: false,
+            replaced_by: None,
+            grafted: false,
+            fixup_of: None,
+            is_merge_base: false,
+            is_dimmed: false,
+            shallow_boundary: false,
+        };
+        let json = serde_json::to_string(&info).unwrap();
+        assert!(json.contains("\"parents\":[\"91f3bb2a514e842fa88eb3fda50a889e95eb81d4\",null]"));
 
-        assert_eq!(
-            super::parse_merge_summary(gitlab_pull, &patterns),
-            Some("feature/my-feature".to_string()),
-        );
-        assert_eq!(
-            super::parse_merge_summary(git_default, &patterns),
-            Some("feature/my-feature".to_string()),
-        );
-        assert_eq!(
-            super::parse_merge_summary(git_master, &patterns),
-            Some("feature/my-feature".to_string()),
-        );
-        assert_eq!(
-            super::parse_merge_summary(github_pull, &patterns),
-            Some("feature/my-feature".to_string()),
-        );
-        assert_eq!(
-            super::parse_merge_summary(github_pull_2, &patterns),
-            Some("feature/my-feature".to_string()),
-        );
-        assert_eq!(
-            super::parse_merge_summary(bitbucket_pull, &patterns),
-            Some("feature/my-feature".to_string()),
-        );
+        let back: CommitInfo = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.oid, oid);
+        assert_eq!(back.parents, [Some(oid), None]);
+        assert_eq!(back.branch_trace, Some(1));
     }
 }
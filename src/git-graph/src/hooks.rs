@@ -0,0 +1,121 @@
+//! Git hooks printing a short graph excerpt around HEAD after commits and merges,
+//! for immediate visual feedback during normal work.
+
+use git2::Repository;
+use std::path::{Path, PathBuf};
+
+/// The hooks installed: after commits (including amends) and after merges (including pulls).
+pub const HOOKS: [&str; 2] = ["post-commit", "post-merge"];
+
+/// Line identifying hooks written by git-graph, so that they can be replaced or removed.
+const MARKER: &str = "# Installed by 'git-graph install-hooks'";
+
+/// The directory git runs hooks from: `core.hooksPath` if set, `.git/hooks` otherwise.
+pub fn hooks_dir(repository: &Repository) -> PathBuf {
+    let configured = repository
+        .config()
+        .and_then(|config| config.get_path("core.hooksPath"))
+        .ok();
+    match configured {
+        // Relative paths are relative to the directory hooks are run in
+        Some(path) if path.is_relative() => repository
+            .workdir()
+            .unwrap_or_else(|| repository.path())
+            .join(path),
+        Some(path) => path,
+        None => repository.path().join("hooks"),
+    }
+}
+
+/// The script of a hook, running git-graph with the given arguments (if installed)
+/// to print `context` lines before and after HEAD. Hooks never fail.
+pub fn hook_script(context: usize, args: &[String]) -> String {
+    let args: String = args
+        .iter()
+        .map(|arg| format!(" '{}'", arg.replace('\'', "'\\''")))
+        .collect();
+    format!(
+        "#!/bin/sh\n\
+         {}. Remove with 'git-graph install-hooks --uninstall'.\n\
+         command -v git-graph >/dev/null 2>&1 || exit 0\n\
+         git-graph --no-pager --focus HEAD --context {}{} || true\n",
+        MARKER, context, args
+    )
+}
+
+/// Writes the [HOOKS]. Existing hooks not written by git-graph are only replaced
+/// with `force`. Returns the paths written.
+pub fn install_hooks(
+    repository: &Repository,
+    context: usize,
+    args: &[String],
+    force: bool,
+) -> Result<Vec<PathBuf>, String> {
+    let dir = hooks_dir(repository);
+    let paths: Vec<PathBuf> = HOOKS.iter().map(|hook| dir.join(hook)).collect();
+    if !force {
+        if let Some(path) = paths
+            .iter()
+            .find(|path| path.exists() && !is_own_hook(path))
+        {
+            return Err(format!(
+                "Hook {} exists and was not written by git-graph. Use --force to replace it",
+                path.display()
+            ));
+        }
+    }
+    std::fs::create_dir_all(&dir)
+        .map_err(|err| format!("Can't create {}: {}", dir.display(), err))?;
+    let script = hook_script(context, args);
+    for path in &paths {
+        std::fs::write(path, &script)
+            .and_then(|_| make_executable(path))
+            .map_err(|err| format!("Can't write {}: {}", path.display(), err))?;
+    }
+    Ok(paths)
+}
+
+/// Removes the [HOOKS] written by git-graph, leaving other hooks untouched.
+/// Returns the paths removed.
+pub fn uninstall_hooks(repository: &Repository) -> Result<Vec<PathBuf>, String> {
+    let dir = hooks_dir(repository);
+    let mut removed = vec![];
+    for path in HOOKS.iter().map(|hook| dir.join(hook)) {
+        if path.exists() && is_own_hook(&path) {
+            std::fs::remove_file(&path)
+                .map_err(|err| format!("Can't remove {}: {}", path.display(), err))?;
+            removed.push(path);
+        }
+    }
+    Ok(removed)
+}
+
+fn is_own_hook(path: &Path) -> bool {
+    std::fs::read_to_string(path)
+        .is_ok_and(|script| script.lines().any(|line| line.starts_with(MARKER)))
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::hook_script;
+
+    #[test]
+    fn hook_script_quotes_arguments() {
+        let script = hook_script(3, &["--style".to_string(), "it's".to_string()]);
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(script.contains(
+            "git-graph --no-pager --focus HEAD --context 3 '--style' 'it'\\''s' || true"
+        ));
+    }
+}
@@ -0,0 +1,229 @@
+//! Commit lists for laying out graphs without a repository, as read by `--layout-from`.
+//!
+//! Other tools, like server-side indexes, already know the commits and refs of a
+//! repository. They can use git-graph as a layout engine by passing them as JSON:
+//!
+//! ```json
+//! {
+//!   "commits": [
+//!     { "oid": "<id>", "parents": ["<id>", "<id>"], "summary": "Merge branch 'feature'" }
+//!   ],
+//!   "refs": { "HEAD": "refs/heads/main", "refs/heads/main": "<id>" }
+//! }
+//! ```
+
+use crate::graph::{CommitMeta, HeadInfo};
+use crate::provider::MemoryRepo;
+use git2::{Oid, Time};
+use serde_derive::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// A commit of a [LayoutInput].
+pub struct LayoutCommit {
+    pub oid: Oid,
+    pub parents: Vec<Oid>,
+    pub summary: String,
+    pub author_name: String,
+    pub author_email: String,
+    /// Commit time, in seconds since the epoch
+    pub time: i64,
+}
+
+/// Commits and refs to lay out, instead of the history of a repository.
+pub struct LayoutInput {
+    /// The commits, children before their parents
+    pub commits: Vec<LayoutCommit>,
+    /// Full names of branches and tags, like `refs/heads/main`, with their targets
+    pub refs: BTreeMap<String, Oid>,
+    pub head: Option<HeadInfo>,
+}
+
+#[derive(Deserialize)]
+struct RawCommit {
+    oid: String,
+    #[serde(default)]
+    parents: Vec<String>,
+    #[serde(default)]
+    summary: String,
+    #[serde(default)]
+    author: String,
+    #[serde(default)]
+    email: String,
+    #[serde(default)]
+    time: i64,
+}
+
+#[derive(Deserialize)]
+struct RawInput {
+    commits: Vec<RawCommit>,
+    #[serde(default)]
+    refs: BTreeMap<String, String>,
+}
+
+impl LayoutInput {
+    /// Parses a JSON commit list. Commits must be listed before their parents, like
+    /// by `git rev-list --topo-order`. Parents not listed are cut off, as if the history
+    /// was truncated. `HEAD` may name a branch, or a commit for a detached HEAD.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let raw: RawInput = serde_json::from_str(source).map_err(|err| err.to_string())?;
+
+        let mut positions = HashMap::new();
+        for (idx, commit) in raw.commits.iter().enumerate() {
+            if positions.insert(commit.oid.as_str(), idx).is_some() {
+                return Err(format!("Commit {} is listed twice", commit.oid));
+            }
+        }
+        let mut commits = Vec::with_capacity(raw.commits.len());
+        for (idx, commit) in raw.commits.iter().enumerate() {
+            for parent in &commit.parents {
+                if positions.get(parent.as_str()).is_some_and(|pos| *pos < idx) {
+                    return Err(format!(
+                        "Commit {} is listed before its child {}. \
+                         Commits must be listed before their parents",
+                        parent, commit.oid
+                    ));
+                }
+            }
+            commits.push(LayoutCommit {
+                oid: parse_oid(&commit.oid)?,
+                parents: commit
+                    .parents
+                    .iter()
+                    .map(|parent| parse_oid(parent))
+                    .collect::<Result<_, _>>()?,
+                summary: commit.summary.clone(),
+                author_name: commit.author.clone(),
+                author_email: commit.email.clone(),
+                time: commit.time,
+            });
+        }
+
+        let mut refs = BTreeMap::new();
+        for (name, target) in &raw.refs {
+            if name == "HEAD" {
+                continue;
+            }
+            let known = ["refs/heads/", "refs/remotes/", "refs/tags/"];
+            if !known.iter().any(|prefix| name.starts_with(prefix)) {
+                return Err(format!(
+                    "Unsupported ref '{}'. Must be one of [{}]",
+                    name,
+                    known.map(|prefix| format!("{}*", prefix)).join("|")
+                ));
+            }
+            refs.insert(name.clone(), parse_oid(target)?);
+        }
+        let head = match raw.refs.get("HEAD") {
+            None => None,
+            Some(target) => Some(match target.strip_prefix("refs/heads/") {
+                Some(name) => HeadInfo::Branch {
+                    name: name.to_string(),
+                    oid: *refs
+                        .get(target)
+                        .ok_or_else(|| format!("HEAD points to unknown ref '{}'", target))?,
+                },
+                None => HeadInfo::Detached {
+                    oid: parse_oid(target)?,
+                },
+            }),
+        };
+        Ok(LayoutInput {
+            commits,
+            refs,
+            head,
+        })
+    }
+}
+
+impl From<LayoutInput> for MemoryRepo {
+    fn from(input: LayoutInput) -> Self {
+        let mut repo = MemoryRepo::new();
+        for commit in input.commits {
+            let time = Time::new(commit.time, 0);
+            let meta = CommitMeta {
+                summary: commit.summary.into_bytes(),
+                author_name: commit.author_name.into_bytes(),
+                author_email: commit.author_email.into_bytes(),
+                author_time: time,
+                time,
+            };
+            repo.add_commit(commit.oid, &commit.parents, meta);
+        }
+        for (name, target) in &input.refs {
+            repo.set_ref(name, *target);
+        }
+        if let Some(head) = input.head {
+            repo.set_head(head);
+        }
+        repo
+    }
+}
+
+/// Reads a JSON commit list, see [LayoutInput::parse].
+pub fn read_layout_input(path: &Path) -> Result<LayoutInput, String> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|err| format!("Can't read commit list {}: {}", path.display(), err))?;
+    LayoutInput::parse(&source)
+        .map_err(|err| format!("Error in commit list {}: {}", path.display(), err))
+}
+
+/// Parses a full object id, as abbreviated ids are padded with zeros.
+fn parse_oid(hex: &str) -> Result<Oid, String> {
+    Some(hex)
+        .filter(|hex| hex.len() == 40)
+        .and_then(|hex| Oid::from_str(hex).ok())
+        .ok_or_else(|| format!("Expected a full commit id, found '{}'", hex))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LayoutInput;
+
+    #[test]
+    fn parse_commit_lists() {
+        let a = "a".repeat(40);
+        let b = "b".repeat(40);
+        let input = LayoutInput::parse(&format!(
+            r#"{{
+                "commits": [
+                    {{ "oid": "{b}", "parents": ["{a}"], "summary": "Second", "time": 20 }},
+                    {{ "oid": "{a}" }}
+                ],
+                "refs": {{ "HEAD": "refs/heads/main", "refs/heads/main": "{b}", "refs/tags/v1": "{a}" }}
+            }}"#
+        ))
+        .unwrap();
+        assert_eq!(input.commits.len(), 2);
+        assert_eq!(input.commits[0].parents[0].to_string(), a);
+        assert_eq!(input.commits[0].summary, "Second");
+        assert_eq!(input.commits[1].summary, "");
+        assert_eq!(input.refs.len(), 2);
+        let head = input.head.unwrap();
+        assert_eq!(
+            (head.branch(), head.oid().map(|oid| oid.to_string())),
+            (Some("main"), Some(b.clone()))
+        );
+
+        let detached = LayoutInput::parse(&format!(
+            r#"{{ "commits": [], "refs": {{ "HEAD": "{a}" }} }}"#
+        ))
+        .unwrap();
+        assert!(detached.head.unwrap().branch().is_none());
+
+        // Parents before children, duplicates, short ids, unknown refs
+        let parse = |commits: &str, refs: &str| {
+            LayoutInput::parse(&format!(
+                r#"{{ "commits": [{}], "refs": {{ {} }} }}"#,
+                commits, refs
+            ))
+        };
+        let first = format!(r#"{{ "oid": "{a}" }}"#);
+        let second = format!(r#"{{ "oid": "{b}", "parents": ["{a}"] }}"#);
+        assert!(parse(&format!("{},{}", first, second), "").is_err());
+        assert!(parse(&format!("{},{}", first, first), "").is_err());
+        assert!(parse(r#"{ "oid": "abc123" }"#, "").is_err());
+        assert!(parse("", &format!(r#""refs/notes/x": "{a}""#)).is_err());
+        assert!(parse("", r#""HEAD": "refs/heads/gone""#).is_err());
+    }
+}
@@ -10,10 +10,24 @@
 use git2::Repository;
 use std::path::Path;
 
+pub mod build;
+pub mod bundle;
+pub mod completions;
 pub mod config;
+pub mod daemon;
+pub mod filter;
 pub mod graph;
+pub mod hooks;
+pub mod layout;
 pub mod print;
+pub mod provider;
+pub mod rebase;
+pub mod search;
 pub mod settings;
+pub mod snapshot;
+#[cfg(test)]
+mod testing;
+pub mod verify;
 
 pub fn get_repo<P: AsRef<Path>>(
     path: P,
@@ -1,27 +1,179 @@
 //! Command line tool to show clear git graphs arranged for your branching model.
 
+use chrono::Local;
 use clap::{crate_version, Arg, Command};
-use crossterm::cursor::MoveToRow;
-use crossterm::event::{Event, KeyCode, KeyModifiers};
+use crossterm::cursor::{MoveTo, MoveToRow};
+use crossterm::event::{
+    DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers, MouseButton,
+    MouseEventKind,
+};
 use crossterm::style::Print;
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType};
 use crossterm::{ErrorKind, ExecutableCommand};
-use git2::Repository;
+use git2::{BranchType, Oid, Repository};
+use git_graph::bundle::{create_bundle, selection_refs};
+use git_graph::completions::{completions, ref_names, CompletionShell};
 use git_graph::config::{
-    create_config, get_available_models, get_model, get_model_name, set_model,
+    check_model_file, create_config, describe_models, get_available_models, get_model,
+    get_model_files, get_model_name, init_model, notes_refs, read_actions, read_aliases,
+    read_bookmarks, read_commit_list, read_commit_map, read_filters, read_key_bindings,
+    read_lint_rules, read_protected_branches, read_reviews, read_wip_patterns, set_model,
+    write_bookmarks,
+};
+use git_graph::daemon::{
+    check_argument, fetch, post_webhook, ref_changes, ref_snapshot, write_artifacts, Artifact,
 };
+use git_graph::filter::Filter;
 use git_graph::get_repo;
 use git_graph::graph::GitGraph;
-use git_graph::print::format::CommitFormat;
+use git_graph::hooks::{install_hooks, uninstall_hooks};
+use git_graph::layout::read_layout_input;
+use git_graph::print::compare::{side_by_side, Pane};
+use git_graph::print::explain::{explain, unparsed_merges};
+use git_graph::print::first_commits::{first_commits, print_first_commits};
+use git_graph::print::format::{CommitFormat, ControlChars};
+use git_graph::print::heatmap::{heatmap, print_heatmap, Bucket, HeatmapFormat, HeatmapRows};
+use git_graph::print::json::print_json;
+use git_graph::print::large_files::{large_files_report, parse_size};
+use git_graph::print::lint::lint;
+use git_graph::print::patches::export_patches;
+use git_graph::print::screen::Screen;
+use git_graph::print::summary::{print_summary, summarize};
 use git_graph::print::svg::print_svg;
-use git_graph::print::unicode::print_unicode;
-use git_graph::settings::{BranchOrder, BranchSettings, Characters, MergePatterns, Settings};
+use git_graph::print::unicode::{
+    parse_rows, print_legend, print_unborn_header, print_unicode, print_unicode_rows,
+    UnicodeGraphInfo,
+};
+use git_graph::provider::MemoryRepo;
+use git_graph::rebase::{is_bundle, simulate, ScratchRepository, StepKind};
+use git_graph::search::{
+    matching_rows, origin_lines, restricted_settings, search_text, BRANCH_FILTER,
+};
+use git_graph::settings::{
+    content_pattern, identity_patterns, message_pattern, parse_date, wip_pattern, Actions, Aliases,
+    BranchFilter, BranchOrder, BranchSettings, Characters, EmptyBranches, GraphView, KeyBindings,
+    LabelColors, MergePatterns, OutputParts, PagerAction, Pickaxe, RemoteColors, ReviewStatus,
+    Settings, StashMode, Symbol, TagFilter, Theme,
+};
+use git_graph::snapshot::{store_snapshot, Snapshot, SNAPSHOT_NOTES_REF};
+use git_graph::verify::verify_against_git_log;
+use itertools::Itertools;
 use platform_dirs::AppDirs;
-use std::io::stdout;
+use std::cell::OnceCell;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::io::{stdout, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+use yansi::Paint;
 
 const REPO_CONFIG_FILE: &str = "git-graph.toml";
+const SHARED_CONFIG_FILE: &str = ".git-graph.toml";
+const BOOKMARKS_FILE: &str = "git-graph-bookmarks";
+const KEYS_FILE: &str = "keys.toml";
+const ACTIONS_FILE: &str = "actions.toml";
+const LINT_FILE: &str = "lint.toml";
+const ALIASES_FILE: &str = "aliases.toml";
+/// Default minimum size of large blobs, 1 MiB
+const DEFAULT_LARGE_BLOB_SIZE: u64 = 1 << 20;
+/// Default number of recent commits shown of each submodule
+const DEFAULT_SUBMODULE_COMMITS: usize = 20;
+/// Lines printed before and after HEAD by the hooks of `install-hooks`.
+const DEFAULT_HOOK_CONTEXT: usize = 5;
+/// Lines scrolled per step of the mouse wheel
+const SCROLL_LINES: usize = 3;
+
+/// Settings of the interactive pager.
+struct Pager {
+    keys: KeyBindings,
+    actions: Actions,
+}
+
+/// Callbacks of the pager, operating on lines of the graph.
+struct PagerHooks<'a> {
+    /// Resolves a revision or `@N` to its line
+    jump: &'a dyn Fn(&str) -> Result<usize, String>,
+    /// The lines shown for a filter query
+    filter: &'a dyn Fn(&str) -> Result<FilterView, String>,
+    /// Runs an external action (by name) on the commit at a line
+    action: &'a dyn Fn(&str, usize) -> Result<(), String>,
+    /// Writes the commits at the given lines to a bundle file, returns a status message
+    bundle: &'a dyn Fn(&str, &[usize]) -> String,
+    /// Writes the graph between the first and last of the given lines to a text file,
+    /// returns a status message
+    slice: &'a dyn Fn(&str, &[usize]) -> String,
+}
+
+/// The lines shown by the pager for a filter query.
+#[derive(Default)]
+struct FilterView {
+    graph_lines: Vec<String>,
+    text_lines: Vec<String>,
+    /// The line of the unfiltered graph shown by each line, for lines of commits
+    origins: Vec<Option<usize>>,
+}
+
+impl FilterView {
+    /// Some of the unfiltered lines, as they are.
+    fn select(graph_lines: &[String], text_lines: &[String], lines: Vec<usize>) -> Self {
+        FilterView {
+            graph_lines: lines.iter().map(|l| graph_lines[*l].clone()).collect(),
+            text_lines: lines.iter().map(|l| text_lines[*l].clone()).collect(),
+            origins: lines.into_iter().map(Some).collect(),
+        }
+    }
+}
+
+/// The lines shown by the pager: all lines, or the lines for a filter query.
+struct PagerView<'a> {
+    graph_lines: &'a [String],
+    text_lines: &'a [String],
+    filtered: Option<FilterView>,
+}
+
+impl PagerView<'_> {
+    fn len(&self) -> usize {
+        match &self.filtered {
+            Some(filtered) => filtered.origins.len(),
+            None => self.graph_lines.len(),
+        }
+    }
+
+    /// The graph and text of a shown line, and the unfiltered line it shows, if any.
+    fn line(&self, idx: usize) -> Option<(&str, &str, Option<usize>)> {
+        match &self.filtered {
+            Some(filtered) => Some((
+                filtered.graph_lines.get(idx)?,
+                &filtered.text_lines[idx],
+                filtered.origins[idx],
+            )),
+            None => Some((self.graph_lines.get(idx)?, &self.text_lines[idx], Some(idx))),
+        }
+    }
+
+    /// The unfiltered lines shown from the given index on.
+    fn origins_from(&self, start: usize) -> Vec<usize> {
+        (start..self.len())
+            .filter_map(|idx| self.line(idx)?.2)
+            .collect()
+    }
+
+    /// The index showing an unfiltered line, if shown.
+    fn position(&self, line: usize) -> Option<usize> {
+        match &self.filtered {
+            Some(filtered) => filtered.origins.iter().position(|l| *l == Some(line)),
+            None => (line < self.graph_lines.len()).then_some(line),
+        }
+    }
+}
+
+/// Bookmarked lines of a pager session, and the lines of all commits that can be bookmarked.
+struct Bookmarks<'a> {
+    lines: Vec<usize>,
+    commit_lines: &'a [usize],
+}
 
 fn main() {
     std::process::exit(match from_args() {
@@ -35,12 +187,12 @@ fn main() {
 
 fn from_args() -> Result<(), String> {
     let app_dir = AppDirs::new(Some("git-graph"), false).unwrap().config_dir;
-    let mut models_dir = app_dir;
+    let mut models_dir = app_dir.clone();
     models_dir.push("models");
 
     create_config(&models_dir)?;
 
-    let app = Command::new("git-graph")
+    let mut app = Command::new("git-graph")
         .version(crate_version!())
         .about(
             "Structured Git graphs for your branching model.\n    \
@@ -52,7 +204,62 @@ fn from_args() -> Result<(), String> {
                  git-graph --model <model>   -> Show graph using a certain <model>\n    \
                  git-graph model --list      -> List available branching models\n    \
                  git-graph model             -> Show repo's current branching models\n    \
-                 git-graph model <model>     -> Permanently set model <model> for this repo",
+                 git-graph model <model>     -> Permanently set model <model> for this repo\n    \
+                 git-graph model check       -> Validate the repo's branching model\n    \
+                 git-graph model list        -> List available branching models with descriptions\n    \
+                 git-graph model init <name> -> Write a commented model file for editing\n    \
+                 git-graph explain <commit>  -> Explain a commit's branch attribution\n    \
+                 git-graph delete-preview <branch> -> Show commits lost by deleting a branch\n    \
+                 git-graph export-patches <branch> -> Write a branch's commits as patch series\n    \
+                 git-graph bundle <file> <refs>... -> Bundle the selected branches and commits\n    \
+                 git-graph lint              -> Check commit messages against the lint rules\n    \
+                 git-graph heatmap --by author -> Commit counts per author and week as CSV\n    \
+                 git-graph first-commits     -> Timeline of each author's first commit\n    \
+                 git-graph large-files       -> Report commits adding blobs of 1 MiB or more\n    \
+                 git-graph snapshot --store-notes -> Store the layout as a note of HEAD\n    \
+                 git-graph trace-symbol src/graph.rs filter_commits -> History of a function\n    \
+                 git-graph daemon -o <dir>   -> Regenerate SVG, JSON and HTML on ref changes\n    \
+                 git-graph --remote-url <url> -> Show a repository without cloning it\n    \
+                 git-graph --path repo.bundle -> Show the history in a bundle file\n    \
+                 git-graph --watch --append  -> Append new commits below the graph, like tail -f\n    \
+                 git-graph install-hooks     -> Print the graph around HEAD after commits\n    \
+                 git-graph --print-oids      -> Print the ids of the commits shown, like git rev-list\n    \
+                 git-graph --layout-from commits.json --svg -> Lay out a commit list without a repository\n    \
+                 git-graph --rows 100..200   -> Print only rows 100 to 199 of the graph\n    \
+                 git-graph --color-seed      -> Pick branch colors by name, stable across runs\n    \
+                 git-graph --theme deuteranopia -> Use a color-blind safe palette\n    \
+                 git-graph --type-badges     -> Show branch types like [feature] next to labels\n    \
+                 git-graph --merge-names     -> Show the merged-in branch on merge commits\n    \
+                 git-graph --persistence-weight -> Draw main and develop in heavier lines\n    \
+                 git-graph --range v1.0..main -> Show only the commits of main since v1.0\n    \
+                 git-graph --stash show      -> Show where stash entries hang off the history\n    \
+                 git-graph --submodules      -> Show each submodule's history next to the graph\n    \
+                 git-graph --branch-counts   -> Mark commits contained in several branches\n    \
+                 git-graph --autosquash      -> Show which commits fixup! commits will be squashed into\n    \
+                 git-graph --hide-wip        -> Hide branches ending in WIP or fixup! commits\n    \
+                 git-graph --notes build     -> Mark commits with notes in refs/notes/build\n    \
+                 git-graph --reflog          -> Also show rebased-away and amended commits\n    \
+                 git-graph --dangling        -> Also show dangling commits, to recover lost work\n    \
+                 git-graph --first-parent    -> Show main's first parents, with merged branches as arrows\n    \
+                 git-graph --simplify-by-decoration -> Show only branch and tag commits, and forks\n    \
+                 git-graph --path-filter src/lib.rs -> Show only commits changing src/lib.rs\n    \
+                 git-graph --author alice@example.com -> Show only commits authored by Alice\n    \
+                 git-graph --unsigned        -> Show only commits without a signature\n    \
+                 git-graph --reviews reviews.txt --review-status unreviewed -> Show commits still to review\n    \
+                 git-graph --exclude 'dependabot/*' -> Hide dependabot branches and their commits\n    \
+                 git-graph --view releases --latest-tags 5 -> The last five releases of each series\n    \
+                 git-graph --grep \"^fix\"     -> Show only commits whose message starts with 'fix'\n    \
+                 git-graph --select \"author:alice AND NOT tag:*-rc*\" -> Combine filters\n    \
+                 git-graph --filter hotfix-audit -> Apply a filter saved in .git-graph.toml\n    \
+                 git-graph --since \"2 weeks ago\" -> Show only commits of the last two weeks\n    \
+                 git-graph --touches-content parse_date -> Show only commits adding or removing 'parse_date'\n    \
+                 git-graph --pickaxe TODO    -> Highlight commits adding or removing a 'TODO'\n    \
+                 git-graph --merge-base main release -> Highlight where release diverged from main\n    \
+                 git-graph --show-unparsed-merges -> List merges matching no merge pattern\n    \
+                 git-graph --empty-branches lane -> Show branches pointing into others in an extra column\n    \
+                 git-graph --remote-colors distinct -> Draw remote branches in their own colors\n    \
+                 git-graph completions bash  -> Print the completion script for bash\n    \
+                 git-graph run-action <name> <commit> -> Run an external action on a commit",
         )
         .arg(
             Arg::new("reverse")
@@ -66,10 +273,45 @@ fn from_args() -> Result<(), String> {
             Arg::new("path")
                 .long("path")
                 .short('p')
-                .help("Open repository from this path or above. Default '.'")
+                .help("Open repository from this path or above, or a bundle file\n\
+                       created by 'git bundle', read-only. Default '.'")
                 .required(false)
                 .num_args(1),
         )
+        .arg(
+            Arg::new("remote-url")
+                .long("remote-url")
+                .help("Show a repository that was not cloned, by its URL. Commits and refs are\n\
+                       fetched into a temporary repository, deleted on exit. File contents\n\
+                       are not fetched, so options reading them are not available.\n\
+                       Requires git.")
+                .required(false)
+                .num_args(1)
+                .value_name("url")
+                .conflicts_with_all([
+                    "path",
+                    "layout-from",
+                    "large-files",
+                    "path-filter",
+                    "touches-content",
+                    "pickaxe",
+                    "pickaxe-regex",
+                    "impact",
+                ]),
+        )
+        .arg(
+            Arg::new("layout-from")
+                .long("layout-from")
+                .help("Lay out the commits and refs of a JSON commit list instead of a\n\
+                       repository, without repository access. Commits are listed children\n\
+                       first, with 'oid', 'parents' and optional 'summary', 'author', 'email'\n\
+                       and 'time'. 'refs' maps full ref names and 'HEAD' to their targets.\n\
+                       Only the graph is computed: options reading commit contents fail.")
+                .required(false)
+                .num_args(1)
+                .value_name("file")
+                .conflicts_with_all(["path", "stdin"]),
+        )
         .arg(
             Arg::new("max-count")
                 .long("max-count")
@@ -98,6 +340,408 @@ fn from_args() -> Result<(), String> {
                 .required(false)
                 .num_args(0),
         )
+        .arg(
+            Arg::new("branches")
+                .long("branches")
+                .help("Show only branches matching a glob, like 'release/*' or 'origin/main',\n\
+                       and only their history. Can be repeated to match any.")
+                .required(false)
+                .num_args(1)
+                .action(clap::ArgAction::Append)
+                .value_name("glob"),
+        )
+        .arg(
+            Arg::new("exclude")
+                .long("exclude")
+                .help("Hide branches matching a glob, like 'dependabot/*', and the history\n\
+                       only they reach. Can be repeated.")
+                .required(false)
+                .num_args(1)
+                .action(clap::ArgAction::Append)
+                .value_name("glob"),
+        )
+        .arg(
+            Arg::new("branch-counts")
+                .long("branch-counts")
+                .help("Count the branches containing each commit, and mark the tips of several\n\
+                       branches and the commits where their histories join, like '\u{2442} 3 branches'.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("ahead-behind")
+                .long("ahead-behind")
+                .help("Label local branches with the commits they are ahead of and behind their\n\
+                       upstream branches, like 'main \u{2191}2 \u{2193}1'.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("autosquash")
+                .long("autosquash")
+                .help("Group fixup!, squash! and amend! commits with the commits they will be\n\
+                       squashed into by 'git rebase --autosquash': indented, with a badge naming\n\
+                       their target. SVG output connects them by a dotted arc.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("protected")
+                .long("protected")
+                .help("Mark branches matching a glob as protected, with a lock badge. Adds to\n\
+                       the 'protected' globs of the repository settings. Can be repeated.")
+                .required(false)
+                .num_args(1)
+                .action(clap::ArgAction::Append)
+                .value_name("glob"),
+        )
+        .arg(
+            Arg::new("wip-pattern")
+                .long("wip-pattern")
+                .help("Mark branches whose tip commit subject matches a regex as work in progress.\n\
+                       Adds to the 'wip' patterns of the repository settings, by default\n\
+                       subjects starting with 'WIP', 'fixup! ' or 'squash! '. Can be repeated.")
+                .required(false)
+                .num_args(1)
+                .action(clap::ArgAction::Append)
+                .value_name("regex"),
+        )
+        .arg(
+            Arg::new("hide-wip")
+                .long("hide-wip")
+                .help("Hide work-in-progress branches, and the commits only they contain.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("stash")
+                .long("stash")
+                .help("How to handle stashes. One of [hide|show|show-with-parents].\n  \
+                         show: stash entries as nodes marked '◇', show-with-parents:\n  \
+                         also their index commits.\n\
+                       Default: hide.")
+                .required(false)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("view")
+                .long("view")
+                .help("Restrict the graph to a view of the history.\n\
+                       One of [all|signed-releases|releases].\n  \
+                         signed-releases: only commits with signed tags and the paths\n  \
+                         between them on persistent branches (see --release-levels).\n  \
+                         releases: only commits with tags shown, connected by edges\n  \
+                         labeled with the number of commits in between.\n\
+                       Default: all.")
+                .required(false)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("release-levels")
+                .long("release-levels")
+                .help("Number of the most persistent levels of the branching model whose\n\
+                       branches count as persistent for '--view signed-releases'. Default: 2")
+                .required(false)
+                .num_args(1)
+                .value_name("levels"),
+        )
+        .arg(
+            Arg::new("tags")
+                .long("tags")
+                .help("Show only tags matching a glob, like 'v*'. Can be repeated to match any.")
+                .required(false)
+                .num_args(1)
+                .action(clap::ArgAction::Append)
+                .value_name("glob"),
+        )
+        .arg(
+            Arg::new("annotated-tags")
+                .long("annotated-tags")
+                .help("Show only annotated tags, no lightweight ones.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("latest-tags")
+                .long("latest-tags")
+                .help("Show only the latest <n> tags of each prefix, the part of the name before\n\
+                       the first digit, like 'v' of 'v1.2.0'.")
+                .required(false)
+                .num_args(1)
+                .value_name("n"),
+        )
+        .arg(
+            Arg::new("impact")
+                .long("impact")
+                .help("Visualize commit size by lines changed: a magnitude glyph in text output,\n\
+                       dot size in SVG. Optional ascending thresholds, e.g. '10,100,1000'.\n\
+                       Default thresholds: 10,100,1000")
+                .required(false)
+                .num_args(0..=1)
+                .value_name("thresholds"),
+        )
+        .arg(
+            Arg::new("collapse-merges")
+                .long("collapse-merges")
+                .help("Collapse merges bringing in a single commit into one node, with a badge\n\
+                       naming the merged commit.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("first-parent")
+                .long("first-parent")
+                .help("Follow only the first parent of merges. Merged-in branches are\n\
+                       summarized by an incoming arrow and the merged branch's name.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("no-replace-objects")
+                .long("no-replace-objects")
+                .help("Ignore replace refs created by `git replace`, showing the history\n\
+                       as recorded instead of with the replacement commits.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("dangling")
+                .long("dangling")
+                .help("Also show dangling commits, reachable from no ref, stash or reflog,\n\
+                       like 'git fsck' finds them, e.g. to recover lost work. Dangling\n\
+                       commits are grayed out, in columns right of all branches.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("reflog")
+                .long("reflog")
+                .help("Also show commits reachable only from the reflogs of HEAD and local\n\
+                       branches, like rebased-away and amended commits, grayed out\n\
+                       and labeled with their reflog entry, like 'main@{2}'.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("simplify-by-decoration")
+                .long("simplify-by-decoration")
+                .help("Show only commits pointed to by branches, tags or HEAD, and the commits\n\
+                       where their histories fork. Edges are annotated with the number\n\
+                       of commits hidden.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("large-files")
+                .long("large-files")
+                .help("Mark commits adding blobs of at least the given size with a size badge,\n\
+                       e.g. '500K', '1M' or '2G'. Default size: 1M")
+                .required(false)
+                .num_args(0..=1)
+                .value_name("size"),
+        )
+        .arg(
+            Arg::new("submodules")
+                .long("submodules")
+                .help("Show the recent history of each submodule next to the graph. Commits\n\
+                       moving a submodule to another commit get a badge, and the submodule\n\
+                       commits they set are labeled with them. Optional number of commits\n\
+                       per submodule. Default: 20")
+                .required(false)
+                .num_args(0..=1)
+                .value_name("n"),
+        )
+        .arg(
+            Arg::new("commit-map")
+                .long("commit-map")
+                .help("Annotate commits of a history rewrite with their old or new ids, from a\n\
+                       file of '<old-id> <new-id>' lines like the commit-map of git filter-repo\n\
+                       or the rewritten-list of git rebase. Rewritten commits whose parents do\n\
+                       not correspond to the old commit's parents are flagged.")
+                .required(false)
+                .num_args(1)
+                .value_name("file"),
+        )
+        .arg(
+            Arg::new("reviews")
+                .long("reviews")
+                .help("Mark commits as reviewed or unreviewed, from a file of\n\
+                       '<commit-id> [reviewed|unreviewed]' lines, e.g. exported from a forge.\n\
+                       The status defaults to reviewed, commits not listed are unreviewed.")
+                .required(false)
+                .num_args(1)
+                .value_name("file"),
+        )
+        .arg(
+            Arg::new("review-status")
+                .long("review-status")
+                .help("Show only commits of a review status, from --reviews.\n\
+                       Possible values: [reviewed|unreviewed]")
+                .required(false)
+                .num_args(1)
+                .value_name("status")
+                .requires("reviews"),
+        )
+        .arg(
+            Arg::new("range")
+                .long("range")
+                .help("Show only the history of a revision or range instead of all refs, like\n\
+                       git log: 'A..B' for B's commits not in A, 'A...B' for commits in either\n\
+                       but not both, '^A' to exclude A's history, or a single revision.\n\
+                       Can be repeated, e.g. '--range main --range ^release/1.2'.")
+                .required(false)
+                .num_args(1)
+                .action(clap::ArgAction::Append)
+                .allow_hyphen_values(false)
+                .value_name("revisions")
+                .conflicts_with("stdin"),
+        )
+        .arg(
+            Arg::new("path-filter")
+                .long("path-filter")
+                .help("Show only commits changing files matching a pathspec, like\n\
+                       'git log -- <path>', following renames like 'git log --follow'.\n\
+                       Can be repeated, e.g. '--path-filter src --path-filter Cargo.toml'.")
+                .required(false)
+                .num_args(1)
+                .action(clap::ArgAction::Append)
+                .value_name("path"),
+        )
+        .arg(
+            Arg::new("contains-path")
+                .long("contains-path")
+                .help("Show only commits whose tree contains a file or directory at the path.\n\
+                       Can be repeated to show commits containing any of the paths.")
+                .required(false)
+                .num_args(1)
+                .action(clap::ArgAction::Append)
+                .value_name("path"),
+        )
+        .arg(
+            Arg::new("touches-content")
+                .long("touches-content")
+                .help("Show only commits adding or removing lines matching a regex,\n\
+                       like 'git log -G'. Merges are not searched.")
+                .required(false)
+                .num_args(1)
+                .value_name("regex"),
+        )
+        .arg(
+            Arg::new("pickaxe")
+                .long("pickaxe")
+                .help("Highlight commits changing the number of occurrences of a string,\n\
+                       like 'git log -S', in place in the graph. Merges are not searched.")
+                .required(false)
+                .num_args(1)
+                .conflicts_with("pickaxe-regex")
+                .value_name("string"),
+        )
+        .arg(
+            Arg::new("pickaxe-regex")
+                .short('G')
+                .long("pickaxe-regex")
+                .help("Highlight commits adding or removing lines matching a regex,\n\
+                       like 'git log -G', in place in the graph. Merges are not searched.")
+                .required(false)
+                .num_args(1)
+                .value_name("regex"),
+        )
+        .arg(
+            Arg::new("author")
+                .long("author")
+                .help("Show only commits whose author's 'Name <email>' matches a regex, like\n\
+                       'git log --author'. Can be repeated to match any, e.g. for a list of emails.")
+                .required(false)
+                .num_args(1)
+                .action(clap::ArgAction::Append)
+                .value_name("pattern"),
+        )
+        .arg(
+            Arg::new("grep")
+                .long("grep")
+                .help("Show only commits whose message matches a regex, like 'git log --grep'.\n\
+                       Matches are highlighted in the commit text.")
+                .required(false)
+                .num_args(1)
+                .value_name("regex"),
+        )
+        .arg(
+            Arg::new("invert-grep")
+                .long("invert-grep")
+                .help("Show only commits whose message does not match --grep.")
+                .required(false)
+                .num_args(0)
+                .requires("grep"),
+        )
+        .arg(
+            Arg::new("unsigned")
+                .long("unsigned")
+                .help("Show only commits without a signature, e.g. to audit a branch that\n\
+                       requires signed commits. Implies --signatures.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("select")
+                .long("select")
+                .help("Show only commits matching an expression of filter terms, combined with\n\
+                       AND, OR, NOT and parentheses, like\n\
+                       'author:alice AND branch:release/* AND NOT tag:*-rc*'. Terms are\n\
+                       author:, committer: and message: with a regex, branch: and tag: with\n\
+                       a glob, matching commits reachable from the refs, path:, since: and until:.")
+                .required(false)
+                .num_args(1)
+                .value_name("expr"),
+        )
+        .arg(
+            Arg::new("filter")
+                .long("filter")
+                .help("Show only commits matching a saved selection expression, by name, from\n\
+                       the [filters] table of the repository's settings: .git-graph.toml in the\n\
+                       working tree, shared with the team, or .git/git-graph.toml.\n\
+                       Combined with --select, both must match.")
+                .required(false)
+                .num_args(1)
+                .value_name("name"),
+        )
+        .arg(
+            Arg::new("committer")
+                .long("committer")
+                .help("Show only commits whose committer's 'Name <email>' matches a regex, like\n\
+                       'git log --committer'. Can be repeated to match any.")
+                .required(false)
+                .num_args(1)
+                .action(clap::ArgAction::Append)
+                .value_name("pattern"),
+        )
+        .arg(
+            Arg::new("since")
+                .long("since")
+                .help("Show only commits committed at or after a date, like 'git log --since':\n\
+                       e.g. '2024-01-31', '2024-01-31 12:00' or '2 weeks ago'.")
+                .required(false)
+                .num_args(1)
+                .value_name("date"),
+        )
+        .arg(
+            Arg::new("until")
+                .long("until")
+                .help("Show only commits committed at or before a date, like 'git log --until'.\n\
+                       Branches are shown at their last commit before the date.")
+                .required(false)
+                .num_args(1)
+                .value_name("date"),
+        )
+        .arg(
+            Arg::new("stdin")
+                .long("stdin")
+                .help("Show only the commits read from stdin, one per line, like the output of\n\
+                       'git rev-list'. Commits keep the branches they are attributed to, and are\n\
+                       connected to their nearest listed ancestors.")
+                .required(false)
+                .num_args(0)
+                .conflicts_with("max-count"),
+        )
         .arg(
             Arg::new("svg")
                 .long("svg")
@@ -105,6 +749,106 @@ fn from_args() -> Result<(), String> {
                 .required(false)
                 .num_args(0),
         )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .help("Print graph and commits as JSON instead of text-based.")
+                .required(false)
+                .num_args(0)
+                .conflicts_with("svg"),
+        )
+        .arg(
+            Arg::new("row-numbers")
+                .long("row-numbers")
+                .help("Prefix each commit with its row index, as used by '@N' addresses.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("summary")
+                .long("summary")
+                .help("Append a footer with totals: commits, branches, tags, merges,\n\
+                       authors and time span.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("focus")
+                .long("focus")
+                .help("Commit to mark and scroll the pager to. A revision, or '@N' for row N.\n\
+                       In the pager, press ':' to jump to another commit.")
+                .required(false)
+                .num_args(1)
+                .value_name("commit"),
+        )
+        .arg(
+            Arg::new("context")
+                .long("context")
+                .help("Print only <lines> lines before and after the commit given by --focus,\n\
+                       without the pager. Used by the hooks of 'git-graph install-hooks'.")
+                .required(false)
+                .num_args(1)
+                .value_name("lines")
+                .requires("focus")
+                .conflicts_with_all(["svg", "json", "watch"]),
+        )
+        .arg(
+            Arg::new("rows")
+                .long("rows")
+                .help("Print only a range of rows of the laid-out graph, without the pager.\n\
+                       Rows are counted from 0, like '@N', e.g. '100..200', '100..' or '..200'.\n\
+                       Lanes continuing beyond the range are marked at the cuts.\n\
+                       In the pager, press 's' to write the rows between bookmarks to a file.")
+                .required(false)
+                .num_args(1)
+                .value_name("range")
+                .conflicts_with_all(["svg", "json", "watch"]),
+        )
+        .arg(
+            Arg::new("compare")
+                .long("compare")
+                .help("Show the histories of two refs side by side, e.g. a feature branch\n\
+                       against trunk. Both start aligned at the refs' merge base.")
+                .required(false)
+                .num_args(2)
+                .value_names(["ref1", "ref2"])
+                .conflicts_with_all(["svg", "json", "watch", "focus"]),
+        )
+        .arg(
+            Arg::new("merge-base")
+                .long("merge-base")
+                .help("Highlight the merge base(s) of two refs in the graph, and dim the\n\
+                       commits reachable from neither.")
+                .required(false)
+                .num_args(2)
+                .value_names(["ref1", "ref2"]),
+        )
+        .arg(
+            Arg::new("onto")
+                .long("onto")
+                .help("Preview rebasing <branch> onto <base>: which commits would be replayed,\n\
+                       which are dropped as already upstream (by patch-id), and the graphs\n\
+                       before and after side by side. The repository is not changed.")
+                .required(false)
+                .num_args(2)
+                .value_names(["base", "branch"])
+                .conflicts_with_all(["svg", "json", "watch", "focus", "compare"]),
+        )
+        .arg(
+            Arg::new("graph-only")
+                .long("graph-only")
+                .help("Print only the graph, without commit metadata.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("no-graph")
+                .long("no-graph")
+                .help("Print only commit metadata, with branch names in place of the graph.")
+                .required(false)
+                .num_args(0)
+                .conflicts_with("graph-only"),
+        )
         .arg(
             Arg::new("debug")
                 .long("debug")
@@ -140,25 +884,202 @@ fn from_args() -> Result<(), String> {
                 .num_args(0),
         )
         .arg(
-            Arg::new("no-pager")
-                .long("no-pager")
-                .help("Use no pager (print everything at once without prompt).")
+            Arg::new("legend")
+                .long("legend")
+                .help("Show a legend of branch colors and columns above the graph\n\
+                       (or as a box next to the graph in SVG output).")
                 .required(false)
                 .num_args(0),
         )
         .arg(
-            Arg::new("style")
-                .long("style")
-                .short('s')
-                .help("Output style. One of [normal/thin|round|bold|double|ascii].\n  \
-                         (First character can be used as abbreviation, e.g. '-s r')")
+            Arg::new("label-colors")
+                .long("label-colors")
+                .help("Coloring of ref labels. One of [branch|fixed|ref-type].\n  \
+                         branch: color of the label's branch, fixed: one decoration color,\n  \
+                         ref-type: by local/remote/tag/HEAD.\n\
+                       Default: branch.")
                 .required(false)
                 .num_args(1),
         )
         .arg(
-            Arg::new("wrap")
-                .long("wrap")
-                .short('w')
+            Arg::new("empty-branches")
+                .long("empty-branches")
+                .help("Showing of branches without commits of their own, e.g. pointing into main.\n\
+                       One of [label|hide|lane].\n  \
+                         label: only a label at the target commit, hide: not shown,\n  \
+                         lane: a marker at the target commit in a shared extra column.\n\
+                       Default: label.")
+                .required(false)
+                .num_args(1)
+                .value_name("mode"),
+        )
+        .arg(
+            Arg::new("remote-colors")
+                .long("remote-colors")
+                .help("Coloring of remote branches. One of [origin|all|distinct].\n  \
+                         origin: branches of 'origin' take the color of their local branch,\n  \
+                         all: branches of any remote, distinct: remote branches take the\n  \
+                         branching model's colors for unknown branches.\n\
+                       Default: origin.")
+                .required(false)
+                .num_args(1)
+                .value_name("mode"),
+        )
+        .arg(
+            Arg::new("persistence-weight")
+                .long("persistence-weight")
+                .help("Draw the most persistent branches, like main and develop, in heavier lines:\n\
+                       bold glyphs in text output, thicker lines in SVG. Optionally the number\n\
+                       of persistence levels of the branching model emphasized. Default: 2")
+                .required(false)
+                .num_args(0..=1)
+                .value_name("levels"),
+        )
+        .arg(
+            Arg::new("notes")
+                .long("notes")
+                .help("Mark commits with git notes by a '[note]' badge. Optional notes ref, like\n\
+                       'build' for refs/notes/build. Can be repeated. Default: core.notesRef or\n\
+                       refs/notes/commits, and the refs of notes.displayRef.")
+                .required(false)
+                .num_args(0..=1)
+                .default_missing_value("")
+                .action(clap::ArgAction::Append)
+                .value_name("ref"),
+        )
+        .arg(
+            Arg::new("notes-inline")
+                .long("notes-inline")
+                .help("Show the text of notes below their commits instead of a badge.\n\
+                       Implies --notes.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("signatures")
+                .long("signatures")
+                .help("Verify commit signatures with git, and mark commits by a badge:\n\
+                       '[signed]', '[bad signature]' or '[unverifiable signature]'.\n\
+                       GPG, SSH and X.509 signatures are checked as configured for git.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("merge-names")
+                .long("merge-names")
+                .help("Show the name of the merged-in branch on merge commits, like '\u{2190} feature/x',\n\
+                       as parsed from the merge summary. Shown even if the branch was deleted.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("merge-pattern")
+                .long("merge-pattern")
+                .help("Additional regex for parsing the merged-in branch's name from merge\n\
+                       summaries, with one capture group for the name, e.g.\n\
+                       '^Merge remote-tracking branch .origin/(.+).$'. Can be repeated.\n\
+                       Tried before the built-in patterns.")
+                .required(false)
+                .num_args(1)
+                .action(clap::ArgAction::Append)
+                .value_name("regex"),
+        )
+        .arg(
+            Arg::new("type-badges")
+                .long("type-badges")
+                .help("Show the type of each branch and tag next to its label, like [feature],\n\
+                       [remote] or [tag]. Branch types are named by the branching model.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("theme")
+                .long("theme")
+                .help("Color theme for branches, replacing the branching model's colors.\n\
+                       One of [default|deuteranopia|protanopia|tritanopia].\n  \
+                         deuteranopia, protanopia, tritanopia: color-blind safe palettes\n\
+                       Default: default.")
+                .required(false)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("color-seed")
+                .long("color-seed")
+                .help("Pick branch colors by a hash of the branch name instead of the order\n\
+                       in which branches are found, so that colors don't shift when branches\n\
+                       appear or disappear. An optional seed gives a different set of colors.\n\
+                       Default seed: 0")
+                .required(false)
+                .num_args(0..=1)
+                .value_name("seed"),
+        )
+        .arg(
+            Arg::new("watch")
+                .long("watch")
+                .help("Re-render the graph whenever the branching model or the\n\
+                       repository's model setting changes.")
+                .required(false)
+                .num_args(0)
+                .conflicts_with_all(["svg", "json"]),
+        )
+        .arg(
+            Arg::new("append")
+                .long("append")
+                .help("With --watch, print the graph oldest first and append commits arriving\n\
+                       in the repository as new rows, like 'tail -f', instead of redrawing.")
+                .required(false)
+                .num_args(0)
+                .requires("watch"),
+        )
+        .arg(
+            Arg::new("print-oids")
+                .long("print-oids")
+                .help("Instead of the graph, print the ids of the commits it shows, one per line\n\
+                       like 'git rev-list', e.g. to pipe them into other git commands.")
+                .required(false)
+                .num_args(0)
+                .conflicts_with_all(["svg", "json", "watch", "verify-against-git-log"]),
+        )
+        .arg(
+            Arg::new("show-unparsed-merges")
+                .long("show-unparsed-merges")
+                .help("Instead of the graph, list the merge commits whose summaries match none of\n\
+                       the merge patterns, and whose merged-in branch is named 'unknown'.\n\
+                       Helps extending the patterns to improve the layout.")
+                .required(false)
+                .num_args(0)
+                .conflicts_with_all(["svg", "json", "watch", "print-oids"]),
+        )
+        .arg(
+            Arg::new("verify-against-git-log")
+                .long("verify-against-git-log")
+                .help("Instead of the graph, compare its commits and their parents with the\n\
+                       output of 'git log' for the same refs, and report missing commits,\n\
+                       different parents or parent order, and commits shown below a parent.")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("no-pager")
+                .long("no-pager")
+                .help("Use no pager (print everything at once without prompt).")
+                .required(false)
+                .num_args(0),
+        )
+        .arg(
+            Arg::new("style")
+                .long("style")
+                .short('s')
+                .help("Output style. One of [normal/thin|round|bold|double|ascii|braille].\n  \
+                         (First character can be used as abbreviation, e.g. '-s r';\n  \
+                         'br' for braille)")
+                .required(false)
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("wrap")
+                .long("wrap")
+                .short('w')
                 .help("Line wrapping for formatted commit text. Default: 'auto 0 8'\n\
                        Argument format: [<width>|auto|none[ <indent1>[ <indent2>]]]\n\
                        For examples, consult 'git-graph --help'")
@@ -217,6 +1138,15 @@ fn from_args() -> Result<(), String> {
                 .required(false)
                 .num_args(1),
         )
+        .arg(
+            Arg::new("control-chars")
+                .long("control-chars")
+                .help("Handling of control characters and escape sequences in commit texts.\n\
+                       One of [raw|strip|replace:<string>]. Tabs are printed as spaces.\n\
+                       Default: replace:\u{fffd}")
+                .required(false)
+                .num_args(1),
+        )
         .arg(
             Arg::new("skip-repo-owner-validation")
                 .long("skip-repo-owner-validation")
@@ -226,6 +1156,247 @@ fn from_args() -> Result<(), String> {
                 .required(false)
                 .num_args(0)
         )
+        .subcommand(Command::new("explain")
+            .about("Explains why a commit is attributed to its branch, or how a branch is traced:\n\
+                    origin, persistence tier, order group and matching merge pattern.")
+            .arg(
+                Arg::new("target")
+                    .help("A branch name, a revision, or '@N' for row N.")
+                    .value_name("branch|commit")
+                    .num_args(1)
+                    .required(true)
+                    .index(1)))
+        .subcommand(Command::new("delete-preview")
+            .about("Shows the commits that would become unreachable if a branch were deleted,\n\
+                    i.e. commits not reachable from any other ref, highlighted in the graph.")
+            .arg(
+                Arg::new("branch")
+                    .help("The name of a local branch.")
+                    .num_args(1)
+                    .required(true)
+                    .index(1)))
+        .subcommand(Command::new("export-patches")
+            .about("Writes the commits attributed to a branch as a numbered patch series,\n\
+                    like 'git format-patch', e.g. for mailing-list workflows. Merges are skipped.")
+            .arg(
+                Arg::new("branch")
+                    .help("The name of a branch, as shown in the graph.")
+                    .num_args(1)
+                    .required(true)
+                    .index(1))
+            .arg(
+                Arg::new("output-directory")
+                    .long("output-directory")
+                    .short('o')
+                    .help("Directory to write the patch files to. Default: '.'")
+                    .num_args(1)
+                    .value_name("dir"))
+            .arg(
+                Arg::new("stdout")
+                    .long("stdout")
+                    .help("Print all patches to stdout as a single mbox, instead of writing files.")
+                    .num_args(0)
+                    .conflicts_with("output-directory"))
+            .arg(
+                Arg::new("subject-prefix")
+                    .long("subject-prefix")
+                    .help("Prefix of the subjects, instead of 'PATCH'.")
+                    .num_args(1)
+                    .value_name("prefix")))
+        .subcommand(Command::new("bundle")
+            .about("Writes a git bundle containing exactly the selected branches, tags and\n\
+                    commits with their history, to share a slice of the graph offline.\n\
+                    Commits without a ref are bundled as 'refs/heads/selected/<short-id>'.")
+            .arg(
+                Arg::new("file")
+                    .help("The bundle file to write.")
+                    .num_args(1)
+                    .required(true)
+                    .index(1))
+            .arg(
+                Arg::new("selection")
+                    .help("Branch names, tags, revisions, or '@N' for row N.")
+                    .value_name("ref|commit")
+                    .num_args(1..)
+                    .required(true)
+                    .index(2)))
+        .subcommand(Command::new("heatmap")
+            .about("Prints a matrix of commit counts per author or branch and time bucket.\n\
+                    Commits are credited to the branch they are attributed to in the graph,\n\
+                    also after being merged.")
+            .arg(
+                Arg::new("by")
+                    .long("by")
+                    .help("Rows of the matrix. One of [author|branch]. Default: branch.")
+                    .num_args(1)
+                    .value_name("rows"))
+            .arg(
+                Arg::new("bucket")
+                    .long("bucket")
+                    .help("Time span of the columns, by author date. One of [day|week|month].\n\
+                           Default: week.")
+                    .num_args(1)
+                    .value_name("bucket"))
+            .arg(
+                Arg::new("format")
+                    .long("format")
+                    .help("Output format. One of [csv|json|html]. Default: csv.")
+                    .num_args(1)
+                    .value_name("format")))
+        .subcommand(Command::new("first-commits")
+            .about("Lists each author's first commit as a timeline, with its branch and the\n\
+                    top-level directories and files it changed.")
+            .arg(
+                Arg::new("format")
+                    .long("format")
+                    .help("Output format. One of [text|json]. Default: text.")
+                    .num_args(1)
+                    .value_name("format")))
+        .subcommand(Command::new("snapshot")
+            .about("Prints a compact JSON snapshot of the layout: HEAD, the branches with their\n\
+                    columns, and the branch of each commit.")
+            .arg(
+                Arg::new("store-notes")
+                    .long("store-notes")
+                    .help("Store the snapshot as the note of HEAD in 'refs/notes/commitlens'\n\
+                           instead of printing it, replacing an earlier snapshot of HEAD.")
+                    .num_args(0)))
+        .subcommand(Command::new("large-files")
+            .about("Reports the commits adding large blobs, largest first, with totals per branch,\n\
+                    to find where and on which branch repository bloat entered.")
+            .arg(
+                Arg::new("threshold")
+                    .long("threshold")
+                    .help("Minimum blob size, e.g. '500K', '1M' or '2G'. Default: 1M.")
+                    .num_args(1)
+                    .value_name("size")))
+        .subcommand(Command::new("trace-symbol")
+            .about("Shows only the commits changing a function or other symbol of a file, with\n\
+                    their branches, approximating 'git log -L :<symbol>:<file>'. The symbol's\n\
+                    definition is found by keywords like 'fn' or 'def', and extends over the\n\
+                    lines indented deeper.")
+            .arg(
+                Arg::new("file")
+                    .help("The file's path, relative to the repository root.")
+                    .num_args(1)
+                    .required(true)
+                    .index(1))
+            .arg(
+                Arg::new("symbol")
+                    .help("The name of the function, type or other symbol.")
+                    .value_name("name")
+                    .num_args(1)
+                    .required(true)
+                    .index(2)))
+        .subcommand(Command::new("daemon")
+            .about("Watches the repository, and regenerates graph artifacts in a directory\n\
+                    whenever refs change, e.g. for auto-updating dashboards.\n\
+                    Optionally fetches remotes and notifies a webhook. ^C: quit")
+            .arg(
+                Arg::new("output-directory")
+                    .long("output-directory")
+                    .short('o')
+                    .help("Directory to write the artifacts to.")
+                    .num_args(1)
+                    .required(true)
+                    .value_name("dir"))
+            .arg(
+                Arg::new("artifacts")
+                    .long("artifacts")
+                    .help("Comma-separated artifacts to write: graph.svg, graph.json and a\n\
+                           graph.html page. Any of [svg|json|html]. Default: svg,json,html")
+                    .num_args(1)
+                    .value_name("list"))
+            .arg(
+                Arg::new("interval")
+                    .long("interval")
+                    .help("Seconds between checks for ref changes. Default: 5")
+                    .num_args(1)
+                    .value_name("seconds"))
+            .arg(
+                Arg::new("fetch")
+                    .long("fetch")
+                    .help("Fetch the given remote before each check, to follow its branches.")
+                    .num_args(1)
+                    .action(clap::ArgAction::Append)
+                    .value_name("remote"))
+            .arg(
+                Arg::new("webhook")
+                    .long("webhook")
+                    .help("POST the changed refs and written artifacts as JSON to this URL\n\
+                           after each update. Requires curl.")
+                    .num_args(1)
+                    .value_name("url")))
+        .subcommand(Command::new("install-hooks")
+            .about("Installs post-commit and post-merge git hooks, printing the graph around\n\
+                    HEAD after commits and merges. Requires git-graph on the PATH.")
+            .arg(
+                Arg::new("context")
+                    .long("context")
+                    .help("Lines to print before and after HEAD. Default: 5")
+                    .num_args(1)
+                    .value_name("lines"))
+            .arg(
+                Arg::new("force")
+                    .long("force")
+                    .help("Replace existing hooks not written by git-graph.")
+                    .num_args(0))
+            .arg(
+                Arg::new("uninstall")
+                    .long("uninstall")
+                    .help("Remove the hooks written by git-graph instead.")
+                    .num_args(0)
+                    .conflicts_with_all(["context", "force", "args"]))
+            .arg(
+                Arg::new("args")
+                    .help("Further options for git-graph in the hooks, after '--',\n\
+                           e.g. '-- --style round --max-count 200'.")
+                    .num_args(0..)
+                    .last(true)
+                    .value_name("options")))
+        .subcommand(Command::new("completions")
+            .about("Prints a shell completion script, completing options, subcommands,\n\
+                    and the repository's branches and tags and the branching models.")
+            .arg(
+                Arg::new("shell")
+                    .help("The shell. One of [bash|zsh|fish]")
+                    .num_args(1)
+                    .required(true)
+                    .index(1)))
+        .subcommand(Command::new("complete-refs")
+            .about("Prints the names of branches and tags, for shell completions.")
+            .hide(true)
+            .arg(
+                Arg::new("models")
+                    .long("models")
+                    .help("Print the names of the available branching models instead.")
+                    .num_args(0)))
+        .subcommand(Command::new("lint")
+            .about("Checks commit messages against rules for subject length, subject pattern\n\
+                    (e.g. a conventional commit type) and ticket references, as configured in\n\
+                    the git-graph config directory's lint.toml. Reports violations per branch and author.")
+            .arg(
+                Arg::new("rules")
+                    .long("rules")
+                    .help("Read the rules from this TOML file instead of lint.toml.")
+                    .num_args(1)
+                    .value_name("file")))
+        .subcommand(Command::new("run-action")
+            .about("Runs an external action, defined in the git-graph config directory's\n\
+                    actions.toml, on a commit.")
+            .arg(
+                Arg::new("name")
+                    .help("The name of the action.")
+                    .num_args(1)
+                    .required(true)
+                    .index(1))
+            .arg(
+                Arg::new("revision")
+                    .help("A revision, or '@N' for row N.")
+                    .value_name("commit")
+                    .num_args(1)
+                    .required(true)
+                    .index(2)))
         .subcommand(Command::new("model")
             .about("Prints or permanently sets the branching model for a repository.")
             .arg(
@@ -243,9 +1414,70 @@ fn from_args() -> Result<(), String> {
                     .help("List all available branching models.")
                     .required(false)
                     .num_args(0),
+            )
+            .args_conflicts_with_subcommands(true)
+            .subcommand(Command::new("list")
+                .about("Lists all available branching models, with descriptions."))
+            .subcommand(Command::new("init")
+                .about("Writes a new, commented model file to the models directory, ready for editing.")
+                .arg(
+                    Arg::new("name")
+                        .help("Name of the new model.")
+                        .value_name("name")
+                        .num_args(1)
+                        .required(true)
+                        .index(1))
+                .arg(
+                    Arg::new("from")
+                        .long("from")
+                        .help("Model to start from. Default: git-flow.")
+                        .value_name("model")
+                        .num_args(1)
+                        .required(false)))
+            .subcommand(Command::new("check")
+                .about("Validates a branching model, reporting unknown keys, invalid regexes and colors.")
+                .arg(
+                    Arg::new("model")
+                        .help("Name of the model, or path to a model file.\n\
+                               When not given, checks the model set for the repository.")
+                        .value_name("model")
+                        .num_args(1)
+                        .required(false)
+                        .index(1))
         ));
 
-    let matches = app.get_matches();
+    app.build();
+    let subcommands: Vec<String> = app
+        .get_subcommands()
+        .map(|sub| sub.get_name().to_string())
+        .collect();
+    let subcommands: Vec<&str> = subcommands.iter().map(|sub| &sub[..]).collect();
+    let aliases = read_aliases(&app_dir.join(ALIASES_FILE), &subcommands)?;
+    let matches =
+        app.clone()
+            .get_matches_from(expand_alias(&app, &aliases, std::env::args_os().collect()));
+
+    if let Some(matches) = matches.subcommand_matches("completions") {
+        let shell = CompletionShell::from_str(matches.get_one::<String>("shell").unwrap())?;
+        print!("{}", completions(&mut app, shell));
+        return Ok(());
+    }
+    if let Some(sub_matches) = matches.subcommand_matches("complete-refs") {
+        // Completions fail silently
+        let names = if sub_matches.get_flag("models") {
+            get_available_models(&models_dir).unwrap_or_default()
+        } else {
+            let dot = ".".to_string();
+            let path = matches.get_one::<String>("path").unwrap_or(&dot);
+            get_repo(path, false)
+                .and_then(|repository| ref_names(&repository))
+                .unwrap_or_default()
+        };
+        for name in names.into_iter().sorted() {
+            println!("{}", name);
+        }
+        return Ok(());
+    }
 
     if let Some(matches) = matches.subcommand_matches("model") {
         if matches.get_flag("list") {
@@ -255,6 +1487,29 @@ fn from_args() -> Result<(), String> {
             );
             return Ok(());
         }
+        if matches.subcommand_matches("list").is_some() {
+            for (name, description) in describe_models(&models_dir)? {
+                println!("{:<16} {}", name, description);
+            }
+            return Ok(());
+        }
+        if let Some(matches) = matches.subcommand_matches("init") {
+            let name = matches.get_one::<String>("name").unwrap();
+            let from = matches
+                .get_one::<String>("from")
+                .map(|s| &s[..])
+                .unwrap_or("git-flow");
+            let path = init_model(name, from, &models_dir)?;
+            println!("Branching model written to {}", path.display());
+            return Ok(());
+        }
+        if let Some(matches) = matches.subcommand_matches("check") {
+            if let Some(model) = matches.get_one::<String>("model") {
+                let path = check_model_file(model, &models_dir)?;
+                println!("Branching model {} is valid", path.display());
+                return Ok(());
+            }
+        }
     }
 
     let skip_repo_owner_validation = matches.get_flag("skip-repo-owner-validation");
@@ -263,10 +1518,47 @@ fn from_args() -> Result<(), String> {
     }
     let dot = ".".to_string();
     let path = matches.get_one::<String>("path").unwrap_or(&dot);
-    let repository = get_repo(path, skip_repo_owner_validation)
-        .map_err(|err| format!("ERROR: {}\n       Navigate into a repository before running git-graph, or use option --path", err.message()))?;
+    let layout_input = match matches.get_one::<String>("layout-from") {
+        None => None,
+        Some(_) if matches.subcommand_name().is_some() => {
+            return Err("Option --layout-from can't be combined with subcommands".to_string())
+        }
+        Some(file) => Some(read_layout_input(Path::new(file))?),
+    };
+    let reads_contents = ["large-files", "trace-symbol", "export-patches"];
+    if let Some(name) = matches
+        .subcommand_name()
+        .filter(|name| reads_contents.contains(name) && matches.contains_id("remote-url"))
+    {
+        return Err(format!(
+            "Subcommand {} can't be combined with --remote-url, which fetches no file contents",
+            name
+        ));
+    }
+    // Commit lists are laid out without a repository, an empty one stands in for it.
+    // Remotes and bundles are read into a temporary repository
+    let scratch = match (&layout_input, matches.get_one::<String>("remote-url")) {
+        (Some(_), _) => Some(ScratchRepository::empty()?),
+        (None, Some(url)) => Some(ScratchRepository::clone_remote(url)?),
+        (None, None) if is_bundle(Path::new(path)) => {
+            Some(ScratchRepository::from_bundle(Path::new(path))?)
+        }
+        (None, None) => None,
+    };
+    let repository = match &scratch {
+        Some(scratch) => scratch.open()?,
+        None => get_repo(path, skip_repo_owner_validation)
+            .map_err(|err| format!("ERROR: {}\n       Navigate into a repository before running git-graph, or use option --path", err.message()))?,
+    };
 
     if let Some(matches) = matches.subcommand_matches("model") {
+        if matches.subcommand_matches("check").is_some() {
+            let model = get_model_name(&repository, REPO_CONFIG_FILE)?
+                .unwrap_or_else(|| "git-flow".to_string());
+            let path = check_model_file(&model, &models_dir)?;
+            println!("Branching model {} is valid", path.display());
+            return Ok(());
+        }
         match matches.get_one::<String>("model") {
             None => {
                 let curr_model = get_model_name(&repository, REPO_CONFIG_FILE)?;
@@ -283,6 +1575,35 @@ fn from_args() -> Result<(), String> {
         return Ok(());
     }
 
+    if let Some(matches) = matches.subcommand_matches("install-hooks") {
+        if matches.get_flag("uninstall") {
+            let removed = uninstall_hooks(&repository)?;
+            if removed.is_empty() {
+                println!("No hooks written by git-graph found");
+            }
+            for path in removed {
+                println!("Removed {}", path.display());
+            }
+            return Ok(());
+        }
+        let context = match matches.get_one::<String>("context") {
+            None => DEFAULT_HOOK_CONTEXT,
+            Some(str) => str.parse::<usize>().map_err(|_| {
+                format!(
+                    "Option context must be a positive number, but got '{}'",
+                    str
+                )
+            })?,
+        };
+        let args: Vec<String> = matches
+            .get_many::<String>("args")
+            .map_or(vec![], |args| args.cloned().collect());
+        for path in install_hooks(&repository, context, &args, matches.get_flag("force"))? {
+            println!("Wrote {}", path.display());
+        }
+        return Ok(());
+    }
+
     let commit_limit = match matches.get_one::<String>("max-count") {
         None => None,
         Some(str) => match str.parse::<usize>() {
@@ -297,13 +1618,336 @@ fn from_args() -> Result<(), String> {
     };
 
     let include_remote = !matches.get_flag("local");
+    let globs = |id: &str| {
+        matches
+            .get_many::<String>(id)
+            .map(|globs| globs.cloned().collect::<Vec<_>>())
+            .unwrap_or_default()
+    };
+    let branch_filter = BranchFilter::new(&globs("branches"), &globs("exclude"))?;
+    let latest_tags = match matches.get_one::<String>("latest-tags") {
+        None => None,
+        Some(str) => Some(str.parse::<usize>().map_err(|_| {
+            format!(
+                "Option latest-tags must be a positive number, but got '{}'",
+                str
+            )
+        })?),
+    };
+    let tag_filter = TagFilter::new(
+        &globs("tags"),
+        matches.get_flag("annotated-tags"),
+        latest_tags,
+    )?;
+
+    let mut protected = read_protected_branches(&repository, REPO_CONFIG_FILE, SHARED_CONFIG_FILE)?;
+    protected.extend(globs("protected"));
+    let protected_branches = BranchFilter::new(&protected, &[])?.include;
+    let mut wip = read_wip_patterns(&repository, REPO_CONFIG_FILE, SHARED_CONFIG_FILE)?;
+    wip.extend(globs("wip-pattern"));
+    let wip_patterns = wip
+        .iter()
+        .map(|pattern| wip_pattern(pattern))
+        .collect::<Result<Vec<_>, _>>()?;
+    let hide_wip = matches.get_flag("hide-wip");
+
+    let stash_mode = match matches.get_one::<String>("stash") {
+        None => StashMode::Hide,
+        Some(str) => StashMode::from_str(str)?,
+    };
+
+    let view = match matches.get_one::<String>("view") {
+        None => GraphView::All,
+        Some(str) => GraphView::from_str(str)?,
+    };
+    let release_levels = match matches.get_one::<String>("release-levels") {
+        None => 2,
+        Some(str) => str.parse::<usize>().map_err(|_| {
+            format!(
+                "Option release-levels must be a positive number, but got '{}'",
+                str
+            )
+        })?,
+    };
+
+    let commit_map = matches
+        .get_one::<String>("commit-map")
+        .map(|path| read_commit_map(Path::new(path)))
+        .transpose()?;
+    let reviews = matches
+        .get_one::<String>("reviews")
+        .map(|path| read_reviews(Path::new(path)))
+        .transpose()?;
+    let review_filter = matches
+        .get_one::<String>("review-status")
+        .map(|str| ReviewStatus::from_str(str))
+        .transpose()?;
+
+    let commit_list = if matches.get_flag("stdin") {
+        let mut source = String::new();
+        std::io::stdin()
+            .read_to_string(&mut source)
+            .map_err(|err| format!("Can't read commits from stdin: {}", err))?;
+        Some(read_commit_list(&repository, &source)?)
+    } else {
+        None
+    };
+
+    let merge_base = match matches.get_many::<String>("merge-base") {
+        Some(refs) => {
+            let refs: Vec<&String> = refs.collect();
+            Some([
+                resolve_commit(&repository, refs[0])?,
+                resolve_commit(&repository, refs[1])?,
+            ])
+        }
+        None => None,
+    };
 
-    let reverse_commit_order = matches.get_flag("reverse");
+    let persistence_weight = if matches.contains_id("persistence-weight") {
+        match matches.get_one::<String>("persistence-weight") {
+            None => Some(2),
+            Some(str) => Some(str.parse::<u8>().map_err(|_| {
+                format!(
+                    "Option persistence-weight must be a positive number, but got '{}'",
+                    str
+                )
+            })?),
+        }
+    } else {
+        None
+    };
+
+    let revision_ranges: Vec<String> = matches
+        .get_many::<String>("range")
+        .map(|ranges| ranges.cloned().collect())
+        .unwrap_or_default();
+    let path_filters: Vec<String> = matches
+        .get_many::<String>("path-filter")
+        .map(|paths| paths.cloned().collect())
+        .unwrap_or_default();
+    let patterns = |id: &str| {
+        identity_patterns(
+            &matches
+                .get_many::<String>(id)
+                .map(|patterns| patterns.cloned().collect::<Vec<_>>())
+                .unwrap_or_default(),
+        )
+    };
+    let author_filters = patterns("author")?;
+    let committer_filters = patterns("committer")?;
+    let date = |id: &str| {
+        matches
+            .get_one::<String>(id)
+            .map(|str| parse_date(str, Local::now()))
+            .transpose()
+    };
+    let since = date("since")?;
+    let until = date("until")?;
+
+    let selection = matches
+        .get_one::<String>("select")
+        .map(|str| Filter::parse(str, Local::now()))
+        .transpose()?;
+    let saved_filter = match matches.get_one::<String>("filter") {
+        None => None,
+        Some(name) => {
+            let filters = read_filters(&repository, REPO_CONFIG_FILE, SHARED_CONFIG_FILE)?;
+            let expr = filters.get(name).ok_or_else(|| {
+                format!(
+                    "Unknown filter '{}'. Must be one of [{}]",
+                    name,
+                    filters.keys().join("|")
+                )
+            })?;
+            Some(
+                Filter::parse(expr, Local::now())
+                    .map_err(|err| format!("{} in saved filter '{}'", err, name))?,
+            )
+        }
+    };
+    // A saved filter and an expression given ad hoc must both match
+    let selection = match (saved_filter, selection) {
+        (Some(saved), Some(selection)) => Some(Filter::And(Box::new(saved), Box::new(selection))),
+        (saved, selection) => saved.or(selection),
+    };
+
+    let pickaxe = match (
+        matches.get_one::<String>("pickaxe"),
+        matches.get_one::<String>("pickaxe-regex"),
+    ) {
+        (Some(string), _) => Some(Pickaxe::Occurrences(string.clone())),
+        (None, Some(regex)) => Some(Pickaxe::Lines(content_pattern(regex)?)),
+        (None, None) => None,
+    };
+    let contains_paths: Vec<String> = matches
+        .get_many::<String>("contains-path")
+        .map(|paths| paths.cloned().collect())
+        .unwrap_or_default();
+    let message_pattern = matches
+        .get_one::<String>("grep")
+        .map(|str| message_pattern(str))
+        .transpose()?;
+    let invert_grep = matches.get_flag("invert-grep");
+    let content_pattern = matches
+        .get_one::<String>("touches-content")
+        .map(|str| content_pattern(str))
+        .transpose()?;
+    let symbol = matches
+        .subcommand_matches("trace-symbol")
+        .map(|matches| {
+            Symbol::new(
+                matches.get_one::<String>("file").unwrap(),
+                matches.get_one::<String>("symbol").unwrap(),
+            )
+        })
+        .transpose()?;
+    let progress = atty::is(atty::Stream::Stderr);
+
+    let color_seed = if matches.contains_id("color-seed") {
+        match matches.get_one::<String>("color-seed") {
+            None => Some(0),
+            Some(str) => Some(str.parse::<u64>().map_err(|_| {
+                format!(
+                    "Option color-seed must be a positive number, but got '{}'",
+                    str
+                )
+            })?),
+        }
+    } else {
+        None
+    };
+
+    let impact_thresholds = if matches.contains_id("impact") {
+        match matches.get_one::<String>("impact") {
+            None => Some(vec![10, 100, 1000]),
+            Some(str) => Some(
+                str.split(',')
+                    .map(|t| t.trim().parse::<usize>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| {
+                        format!("ERROR: Can't parse option --impact '{}' to integers.", str)
+                    })?,
+            ),
+        }
+    } else {
+        None
+    };
+
+    let large_blob_threshold = match matches.subcommand_matches("large-files") {
+        Some(matches) => Some(
+            matches
+                .get_one::<String>("threshold")
+                .map_or(Ok(DEFAULT_LARGE_BLOB_SIZE), |str| parse_size(str))?,
+        ),
+        None if matches.contains_id("large-files") => Some(
+            matches
+                .get_one::<String>("large-files")
+                .map_or(Ok(DEFAULT_LARGE_BLOB_SIZE), |str| parse_size(str))?,
+        ),
+        None => None,
+    };
+
+    let submodules = if matches.contains_id("submodules") {
+        match matches.get_one::<String>("submodules") {
+            None => Some(DEFAULT_SUBMODULE_COMMITS),
+            Some(str) => Some(str.parse::<usize>().map_err(|_| {
+                format!(
+                    "Option submodules must be a positive number, but got '{}'",
+                    str
+                )
+            })?),
+        }
+    } else {
+        None
+    };
+    let branch_counts = matches.get_flag("branch-counts");
+    let ahead_behind = matches.get_flag("ahead-behind");
+    let autosquash = matches.get_flag("autosquash");
+
+    let inline_notes = matches.get_flag("notes-inline");
+    let notes_refs = if matches.contains_id("notes") || inline_notes {
+        let names: Vec<String> = matches
+            .get_many::<String>("notes")
+            .map(|names| names.cloned().collect())
+            .unwrap_or_default();
+        // '--notes' without a ref adds the default refs
+        let defaults = names.is_empty() || names.iter().any(String::is_empty);
+        let names: Vec<String> = names.into_iter().filter(|name| !name.is_empty()).collect();
+        notes_refs(&repository, &names, defaults)?
+    } else {
+        vec![]
+    };
+
+    let unsigned_only = matches.get_flag("unsigned");
+    let verify_signatures = matches.get_flag("signatures") || unsigned_only;
+
+    let collapse_trivial_merges = matches.get_flag("collapse-merges");
+    let first_parent = matches.get_flag("first-parent");
+    // Like git, replace refs are also ignored if `GIT_NO_REPLACE_OBJECTS` is set
+    let replace_refs = !matches.get_flag("no-replace-objects")
+        && std::env::var_os("GIT_NO_REPLACE_OBJECTS").is_none();
+    let simplify_by_decoration = matches.get_flag("simplify-by-decoration");
+    let reflog = matches.get_flag("reflog");
+    let dangling = matches.get_flag("dangling");
+
+    // Appended commits go below the graph, oldest first
+    let reverse_commit_order = matches.get_flag("reverse") || matches.get_flag("append");
 
     let svg = matches.get_flag("svg");
-    let pager = !matches.get_flag("no-pager");
+    let json = matches.get_flag("json");
+    let focus = match matches.get_one::<String>("focus") {
+        None => None,
+        Some(spec) => Some(Focus {
+            spec: spec.clone(),
+            context: match matches.get_one::<String>("context") {
+                None => None,
+                Some(str) => Some(str.parse::<usize>().map_err(|_| {
+                    format!(
+                        "Option context must be a positive number, but got '{}'",
+                        str
+                    )
+                })?),
+            },
+        }),
+    };
+    let rows = matches
+        .get_one::<String>("rows")
+        .map(|str| parse_rows(str))
+        .transpose()?;
+    let row_numbers = matches.get_flag("row-numbers");
+    let summary = matches.get_flag("summary");
+    let paged = !matches.get_flag("no-pager");
     let compact = !matches.get_flag("sparse");
     let debug = matches.get_flag("debug");
+    let legend = matches.get_flag("legend");
+    let output_parts = if matches.get_flag("graph-only") {
+        OutputParts::GraphOnly
+    } else if matches.get_flag("no-graph") {
+        OutputParts::NoGraph
+    } else {
+        OutputParts::Full
+    };
+    let type_badges = matches.get_flag("type-badges");
+    let merge_names = matches.get_flag("merge-names");
+    let merge_patterns = MergePatterns::with_patterns(
+        &matches
+            .get_many::<String>("merge-pattern")
+            .map(|patterns| patterns.cloned().collect::<Vec<_>>())
+            .unwrap_or_default(),
+    )?;
+    let remote_colors = match matches.get_one::<String>("remote-colors") {
+        None => RemoteColors::Origin,
+        Some(str) => RemoteColors::from_str(str)?,
+    };
+    let empty_branches = match matches.get_one::<String>("empty-branches") {
+        None => EmptyBranches::Label,
+        Some(str) => EmptyBranches::from_str(str)?,
+    };
+    let label_colors = match matches.get_one::<String>("label-colors") {
+        None => LabelColors::Branch,
+        Some(str) => LabelColors::from_str(str)?,
+    };
     let style = matches
         .get_one::<String>("style")
         .map(|s| Characters::from_str(s))
@@ -315,18 +1959,28 @@ fn from_args() -> Result<(), String> {
         style
     };
 
+    let theme = match matches.get_one::<String>("theme") {
+        None => Theme::Default,
+        Some(str) => Theme::from_str(str)?,
+    };
     let model = get_model(
         &repository,
         matches.get_one::<String>("model").map(|s| &s[..]),
         REPO_CONFIG_FILE,
         &models_dir,
-    )?;
+    )?
+    .with_theme(theme);
 
     let format = match matches.get_one::<String>("format") {
         None => CommitFormat::OneLine,
         Some(str) => CommitFormat::from_str(str)?,
     };
 
+    let control_chars = match matches.get_one::<String>("control-chars") {
+        None => ControlChars::Replace("\u{fffd}".to_string()),
+        Some(str) => ControlChars::from_str(str)?,
+    };
+
     let colored = if matches.get_flag("no-color") {
         false
     } else if let Some(mode) = matches.get_one::<String>("color") {
@@ -401,33 +2055,589 @@ fn from_args() -> Result<(), String> {
         reverse_commit_order,
         debug,
         colored,
+        legend,
+        output_parts,
+        row_numbers,
+        summary,
+        label_colors,
         compact,
         include_remote,
+        remote_colors,
+        stash_mode,
+        view,
+        release_levels,
+        impact_thresholds,
+        large_blob_threshold,
+        submodules,
+        branch_counts,
+        ahead_behind,
+        autosquash,
+        notes_refs,
+        inline_notes,
+        verify_signatures,
+        collapse_trivial_merges,
+        first_parent,
+        replace_refs,
+        simplify_by_decoration,
+        commit_map,
+        commit_list,
+        revision_ranges,
+        path_filters,
+        author_filters,
+        committer_filters,
+        unsigned_only,
+        reviews,
+        review_filter,
+        branch_filter,
+        tag_filter,
+        protected_branches,
+        wip_patterns,
+        hide_wip,
+        message_pattern,
+        invert_grep,
+        since,
+        until,
+        selection,
+        contains_paths,
+        content_pattern,
+        symbol,
+        progress,
+        build_progress: None,
+        pickaxe,
+        merge_base,
+        reflog,
+        dangling,
+        persistence_weight,
+        merge_names,
+        type_badges,
+        color_seed,
+        rows,
         format,
+        control_chars,
         wrapping,
         characters: style,
         branch_order: BranchOrder::ShortestFirst(true),
+        empty_branches,
         branches: BranchSettings::from(model).map_err(|err| err.to_string())?,
-        merge_patterns: MergePatterns::default(),
+        merge_patterns,
     };
 
-    run(repository, &settings, svg, commit_limit, pager)
-}
-
-fn run(
-    repository: Repository,
-    settings: &Settings,
-    svg: bool,
-    max_commits: Option<usize>,
-    pager: bool,
-) -> Result<(), String> {
-    let now = Instant::now();
-    let graph = GitGraph::new(repository, settings, max_commits)?;
-
-    let duration_graph = now.elapsed().as_micros();
+    if let Some(matches) = matches.subcommand_matches("explain") {
+        let graph = GitGraph::retaining(&repository, &settings, commit_limit, &[])?;
+        let target = matches.get_one::<String>("target").unwrap();
+        print!("{}", explain(&graph, &settings, target)?);
+        return Ok(());
+    }
 
-    if settings.debug {
-        for branch in &graph.all_branches {
+    if let Some(matches) = matches.subcommand_matches("export-patches") {
+        let graph = GitGraph::retaining(&repository, &settings, commit_limit, &[])?;
+        let branch = matches.get_one::<String>("branch").unwrap();
+        let prefix = matches
+            .get_one::<String>("subject-prefix")
+            .map_or("PATCH", |prefix| &prefix[..]);
+        let (patches, merges) = export_patches(&graph, branch, prefix)?;
+        if merges > 0 {
+            eprintln!("Skipped {} merge commit(s)", merges);
+        }
+        if matches.get_flag("stdout") {
+            let mut out = stdout();
+            for patch in &patches {
+                out.write_all(&patch.content)
+                    .map_err(|err| err.to_string())?;
+            }
+        } else {
+            let dir = matches
+                .get_one::<String>("output-directory")
+                .map_or(Path::new("."), Path::new);
+            std::fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+            for patch in &patches {
+                let path = dir.join(&patch.file_name);
+                std::fs::write(&path, &patch.content)
+                    .map_err(|err| format!("Can't write {}: {}", path.display(), err))?;
+                println!("{}", path.display());
+            }
+        }
+        return Ok(());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("bundle") {
+        let graph = GitGraph::retaining(&repository, &settings, commit_limit, &[])?;
+        let file = matches.get_one::<String>("file").unwrap();
+        let specs: Vec<String> = matches
+            .get_many::<String>("selection")
+            .unwrap()
+            .cloned()
+            .collect();
+        let refs = selection_refs(&graph, &specs)?;
+        create_bundle(&graph, Path::new(file), &refs)?;
+        for (name, oid) in &refs {
+            println!("{} {}", &oid.to_string()[..7], name);
+        }
+        return Ok(());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("heatmap") {
+        let rows = match matches.get_one::<String>("by") {
+            None => HeatmapRows::Branch,
+            Some(str) => HeatmapRows::from_str(str)?,
+        };
+        let bucket = match matches.get_one::<String>("bucket") {
+            None => Bucket::Week,
+            Some(str) => Bucket::from_str(str)?,
+        };
+        let format = match matches.get_one::<String>("format") {
+            None => HeatmapFormat::Csv,
+            Some(str) => HeatmapFormat::from_str(str)?,
+        };
+        let graph = GitGraph::new(&repository, &settings, commit_limit)?;
+        let heatmap = heatmap(&graph, &settings, rows, bucket)?;
+        print!("{}", print_heatmap(&heatmap, rows, format)?);
+        return Ok(());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("first-commits") {
+        let graph = GitGraph::retaining(&repository, &settings, commit_limit, &[])?;
+        let first_commits = first_commits(&graph, &settings)?;
+        match matches.get_one::<String>("format").map(|s| &s[..]) {
+            None | Some("text") => print!("{}", print_first_commits(&first_commits)),
+            Some("json") => println!(
+                "{}",
+                serde_json::to_string_pretty(&first_commits).map_err(|err| err.to_string())?
+            ),
+            Some(format) => {
+                return Err(format!(
+                    "Unknown format '{}'. Must be one of [text|json]",
+                    format
+                ))
+            }
+        }
+        return Ok(());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("snapshot") {
+        let graph = GitGraph::new(&repository, &settings, commit_limit)?;
+        let snapshot = Snapshot::new(&graph, Local::now().timestamp());
+        if matches.get_flag("store-notes") {
+            let note = store_snapshot(&repository, &snapshot)?;
+            println!(
+                "Stored snapshot of {} commits as note {} of {} in {}",
+                snapshot.commits.len(),
+                &note.to_string()[..7],
+                snapshot.head.as_deref().map_or("", |head| &head[..7]),
+                SNAPSHOT_NOTES_REF
+            );
+        } else {
+            println!("{}", snapshot.to_json()?);
+        }
+        return Ok(());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("daemon") {
+        let artifacts = matches
+            .get_one::<String>("artifacts")
+            .map_or("svg,json,html", |s| &s[..])
+            .split(',')
+            .map(|str| Artifact::from_str(str.trim()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let interval = match matches.get_one::<String>("interval") {
+            None => 5,
+            Some(str) => str.parse::<u64>().map_err(|_| {
+                format!(
+                    "Option interval must be a positive number, but got '{}'",
+                    str
+                )
+            })?,
+        };
+        let options = DaemonOptions {
+            dir: PathBuf::from(matches.get_one::<String>("output-directory").unwrap()),
+            artifacts,
+            interval: Duration::from_secs(interval.max(1)),
+            remotes: matches
+                .get_many::<String>("fetch")
+                .map_or(vec![], |remotes| remotes.cloned().collect()),
+            webhook: matches.get_one::<String>("webhook").cloned(),
+        };
+        for remote in &options.remotes {
+            check_argument("remote", remote)?;
+        }
+        if let Some(url) = &options.webhook {
+            check_argument("webhook URL", url)?;
+        }
+        return daemon(repository, &settings, &options, commit_limit);
+    }
+
+    if matches.subcommand_matches("large-files").is_some() {
+        let graph = GitGraph::new(&repository, &settings, commit_limit)?;
+        print!("{}", large_files_report(&graph, &settings)?);
+        return Ok(());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("lint") {
+        let path = matches
+            .get_one::<String>("rules")
+            .map_or_else(|| app_dir.join(LINT_FILE), PathBuf::from);
+        if matches.contains_id("rules") && !path.exists() {
+            return Err(format!("Lint rules file {} not found", path.display()));
+        }
+        let rules = read_lint_rules(&path)?;
+        let graph = GitGraph::retaining(&repository, &settings, commit_limit, &[])?;
+        print!("{}", lint(&graph, &settings, &rules)?);
+        return Ok(());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("delete-preview") {
+        let branch = matches.get_one::<String>("branch").unwrap();
+        return delete_preview(repository, &settings, branch, commit_limit);
+    }
+
+    let actions = read_actions(&app_dir.join(ACTIONS_FILE))?;
+    if let Some(matches) = matches.subcommand_matches("run-action") {
+        let graph = GitGraph::retaining(&repository, &settings, commit_limit, &[])?;
+        let name = matches.get_one::<String>("name").unwrap();
+        let row = graph.resolve_row(matches.get_one::<String>("revision").unwrap())?;
+        return run_action(&graph, &actions, name, row);
+    }
+
+    let pager = if paged && !matches.contains_id("context") && !matches.contains_id("rows") {
+        let keys = read_key_bindings(&app_dir.join(KEYS_FILE))?;
+        Some(Pager { keys, actions })
+    } else {
+        None
+    };
+
+    if let Some(input) = layout_input {
+        let provider = MemoryRepo::from(input);
+        let layout =
+            |settings: &Settings| GitGraph::from_provider(&provider, settings, commit_limit);
+        return run(layout, &repository, &settings, svg, json, focus, pager);
+    }
+
+    if let Some(refs) = matches.get_many::<String>("onto") {
+        let refs: Vec<&str> = refs.map(|r| &r[..]).collect();
+        return rebase_preview(
+            repository,
+            &settings,
+            [refs[0], refs[1]],
+            commit_limit,
+            pager,
+        );
+    }
+
+    if let Some(refs) = matches.get_many::<String>("compare") {
+        let refs: Vec<&str> = refs.map(|r| &r[..]).collect();
+        return compare(
+            repository,
+            &settings,
+            [refs[0], refs[1]],
+            commit_limit,
+            pager,
+        );
+    }
+
+    if matches.get_flag("print-oids") {
+        let graph = GitGraph::new(&repository, &settings, commit_limit)?;
+        let mut oids: Vec<String> = graph
+            .commits
+            .iter()
+            .map(|info| info.oid.to_string())
+            .collect();
+        if settings.reverse_commit_order {
+            oids.reverse();
+        }
+        for oid in oids {
+            println!("{}", oid);
+        }
+        return Ok(());
+    }
+
+    if matches.get_flag("show-unparsed-merges") {
+        let graph = GitGraph::new(&repository, &settings, commit_limit)?;
+        print!("{}", unparsed_merges(&graph, &settings)?);
+        return Ok(());
+    }
+
+    if matches.get_flag("verify-against-git-log") {
+        let graph = GitGraph::retaining(&repository, &settings, commit_limit, &[])?;
+        let mismatches = verify_against_git_log(&graph, &settings, commit_limit.is_some())?;
+        for mismatch in &mismatches {
+            println!("{}", mismatch);
+        }
+        if !mismatches.is_empty() {
+            return Err(format!(
+                "{} mismatch(es) with git log in {} commit(s)",
+                mismatches.len(),
+                graph.commits.len()
+            ));
+        }
+        println!("{} commit(s) match git log", graph.commits.len());
+        return Ok(());
+    }
+
+    if matches.get_flag("watch") && matches.get_flag("append") {
+        return watch_append(repository, settings, commit_limit);
+    }
+
+    if matches.get_flag("watch") {
+        let model = matches.get_one::<String>("model").map(|s| &s[..]);
+        return watch(
+            repository,
+            settings,
+            model,
+            theme,
+            &models_dir,
+            commit_limit,
+        );
+    }
+
+    let graph = |settings: &Settings| GitGraph::retaining(&repository, settings, commit_limit, &[]);
+    run(graph, &repository, &settings, svg, json, focus, pager)
+}
+
+/// Replaces the first subcommand argument by the arguments of the alias of that name,
+/// if there is one. Options before it, like `--path`, are kept.
+fn expand_alias(app: &Command, aliases: &Aliases, mut args: Vec<OsString>) -> Vec<OsString> {
+    let mut idx = 1;
+    while let Some(arg) = args.get(idx).and_then(|arg| arg.to_str()) {
+        if arg == "--" {
+            break;
+        }
+        if !arg.starts_with('-') {
+            if let Some(expansion) = aliases.get(arg) {
+                let expansion: Vec<OsString> = expansion.iter().map(OsString::from).collect();
+                args.splice(idx..idx + 1, expansion);
+            }
+            break;
+        }
+        // Skip the values of options, unless attached like `--max-count=10` or `-n10`
+        let option = match arg.strip_prefix("--") {
+            Some(long) if !long.contains('=') => {
+                app.get_opts().find(|opt| opt.get_long() == Some(long))
+            }
+            Some(_) => None,
+            None => arg
+                .strip_prefix('-')
+                .filter(|short| short.chars().count() == 1)
+                .and_then(|short| {
+                    app.get_opts()
+                        .find(|opt| opt.get_short().map(String::from).as_deref() == Some(short))
+                }),
+        };
+        idx += 1 + option
+            .and_then(|opt| opt.get_num_args())
+            .map_or(0, |range| range.min_values());
+    }
+    args
+}
+
+/// Render the graph, and re-render with reloaded settings whenever
+/// the branching model or the repo's config file changes.
+fn watch(
+    repository: Repository,
+    mut settings: Settings,
+    model: Option<&str>,
+    theme: Theme,
+    models_dir: &Path,
+    max_commits: Option<usize>,
+) -> Result<(), String> {
+    let modified = |files: &[PathBuf]| {
+        files
+            .iter()
+            .map(|file| std::fs::metadata(file).and_then(|m| m.modified()).ok())
+            .collect::<Vec<_>>()
+    };
+    let mut files = get_model_files(&repository, model, REPO_CONFIG_FILE, &models_dir);
+    let mut stamps = modified(&files);
+    let mut status = String::new();
+    // Only changed rows are redrawn, so refreshes of large graphs don't flicker
+    let mut screen = Screen::new();
+    loop {
+        let graph = GitGraph::retaining(&repository, &settings, max_commits, &[])?;
+        let (g_lines, t_lines, _) = print_lines(&graph, &settings)?;
+
+        let size = crossterm::terminal::size().unwrap_or((80, 25));
+        let visible_lines = (size.1 as usize).saturating_sub(2);
+        let mut rows: Vec<String> = g_lines
+            .iter()
+            .zip(&t_lines)
+            .take(visible_lines)
+            .map(|(graph, text)| format!(" {}  {}", graph, text))
+            .collect();
+        let watching = format!(
+            " >>> Watching {} for changes, ^C: quit {}",
+            itertools::join(
+                files.iter().filter(|f| f.exists()).map(|f| f.display()),
+                ", "
+            ),
+            status
+        );
+        rows.extend(watching.lines().map(str::to_string));
+        screen
+            .draw(&mut stdout(), rows, size)
+            .map_err(|err| err.to_string())?;
+
+        // Wait for changes and reload the branching model
+        loop {
+            std::thread::sleep(Duration::from_millis(500));
+            let new_files = get_model_files(&repository, model, REPO_CONFIG_FILE, &models_dir);
+            let new_stamps = modified(&new_files);
+            if new_files != files || new_stamps != stamps {
+                files = new_files;
+                stamps = new_stamps;
+                break;
+            }
+        }
+        status =
+            match get_model(&repository, model, REPO_CONFIG_FILE, &models_dir).and_then(|model| {
+                BranchSettings::from(model.with_theme(theme)).map_err(|err| err.to_string())
+            }) {
+                Ok(branches) => {
+                    settings.branches = branches;
+                    String::new()
+                }
+                Err(err) => format!("\n{}\n >>> Keeping the previous model", err),
+            };
+    }
+}
+
+/// Prints the graph oldest first, then appends the commits arriving in the repository
+/// as new rows below, like `tail -f`, until interrupted. Lanes continuing from the rows
+/// printed before are marked by an extra line above the new rows.
+fn watch_append(
+    repository: Repository,
+    settings: Settings,
+    max_commits: Option<usize>,
+) -> Result<(), String> {
+    let mut refs = ref_snapshot(&repository)?;
+    let graph = GitGraph::retaining(&repository, &settings, max_commits, &[])?;
+    let (g_lines, t_lines, _) = print_lines(&graph, &settings)?;
+    print_unpaged(&g_lines, &t_lines, 0..g_lines.len(), None, &[]);
+    let mut shown: HashSet<Oid> = graph.commits.iter().map(|info| info.oid).collect();
+    loop {
+        std::thread::sleep(Duration::from_secs(1));
+        let new_refs = ref_snapshot(&repository)?;
+        if new_refs == refs {
+            continue;
+        }
+        refs = new_refs;
+        let graph = GitGraph::retaining(&repository, &settings, max_commits, &[])?;
+        // Commits are listed children first, new ones are usually at the start
+        let Some(last) = graph
+            .commits
+            .iter()
+            .rposition(|info| !shown.contains(&info.oid))
+        else {
+            continue;
+        };
+        let (g_lines, t_lines, _) = print_unicode_rows(&graph, &settings, Some(0..last + 1))?;
+        print_unpaged(&g_lines, &t_lines, 0..g_lines.len(), None, &[]);
+        shown.extend(graph.commits[..=last].iter().map(|info| info.oid));
+    }
+}
+
+/// What the daemon does on ref changes.
+struct DaemonOptions {
+    dir: PathBuf,
+    artifacts: Vec<Artifact>,
+    interval: Duration,
+    /// Remotes to fetch before each check
+    remotes: Vec<String>,
+    webhook: Option<String>,
+}
+
+/// Regenerates artifacts whenever refs change, until interrupted. Errors after the
+/// first update are reported, but don't stop the daemon.
+fn daemon(
+    repository: Repository,
+    settings: &Settings,
+    options: &DaemonOptions,
+    max_commits: Option<usize>,
+) -> Result<(), String> {
+    let mut refs = None;
+    println!(
+        "Writing {} to {}, ^C: quit",
+        options
+            .artifacts
+            .iter()
+            .map(|artifact| artifact.file_name())
+            .join(", "),
+        options.dir.display()
+    );
+    loop {
+        let first = refs.is_none();
+        let mut update = || -> Result<Option<String>, String> {
+            for remote in &options.remotes {
+                if let Err(err) = fetch(&repository, remote) {
+                    eprintln!("{}", err);
+                }
+            }
+            let new_refs = ref_snapshot(&repository)?;
+            let changes = refs.as_ref().map(|refs| ref_changes(refs, &new_refs));
+            if changes.as_ref().is_some_and(|changes| changes.is_empty()) {
+                return Ok(None);
+            }
+            let bookmarks = read_bookmarks(&repository, BOOKMARKS_FILE)?;
+            let graph = GitGraph::retaining(&repository, settings, max_commits, &[])?;
+            let paths = write_artifacts(
+                &graph,
+                settings,
+                &bookmarks,
+                &options.artifacts,
+                &options.dir,
+            )?;
+            refs = Some(new_refs);
+            let mut message = match &changes {
+                Some(changes) => format!("{} ref(s) changed, ", changes.len()),
+                None => String::new(),
+            };
+            message.push_str(&format!("wrote {} artifact(s)", paths.len()));
+            if let (Some(url), Some(changes)) = (&options.webhook, &changes) {
+                match post_webhook(url, &repository, changes, &paths) {
+                    Ok(()) => message.push_str(", notified webhook"),
+                    Err(err) => message.push_str(&format!("; {}", err)),
+                }
+            }
+            Ok(Some(message))
+        };
+        match update() {
+            Ok(Some(message)) => println!("[{}] {}", Local::now().format("%H:%M:%S"), message),
+            Ok(None) => {}
+            Err(err) if first => return Err(err),
+            Err(err) => eprintln!("[{}] {}", Local::now().format("%H:%M:%S"), err),
+        }
+        std::thread::sleep(options.interval);
+    }
+}
+
+/// The commit to mark, and optionally the number of lines to print around it.
+struct Focus {
+    spec: String,
+    context: Option<usize>,
+}
+
+/// Builds the graph with `build`, and prints it.
+fn run<F>(
+    build: F,
+    repository: &Repository,
+    settings: &Settings,
+    svg: bool,
+    json: bool,
+    focus: Option<Focus>,
+    pager: Option<Pager>,
+) -> Result<(), String>
+where
+    F: Fn(&Settings) -> Result<GitGraph, String>,
+{
+    let now = Instant::now();
+    let graph = build(settings)?;
+    let context = focus.as_ref().and_then(|focus| focus.context);
+    let focus = focus
+        .map(|focus| graph.resolve_row(&focus.spec))
+        .transpose()?;
+
+    let duration_graph = now.elapsed().as_micros();
+
+    if settings.debug {
+        for branch in &graph.all_branches {
             eprintln!(
                 "{} (col {}) ({:?}) {} s: {:?}, t: {:?}",
                 branch.name,
@@ -441,15 +2651,135 @@ fn run(
     }
 
     let now = Instant::now();
+    let bookmarked = read_bookmarks(repository, BOOKMARKS_FILE)?;
 
     if svg {
         println!("{}", print_svg(&graph, settings)?);
+    } else if json {
+        println!("{}", print_json(&graph, settings, &bookmarked)?);
+    } else if let Some((left, right)) = submodule_panes(&graph, repository, settings)? {
+        print_panes(&[], left, right, pager)?;
     } else {
-        let (g_lines, t_lines, _indices) = print_unicode(&graph, settings)?;
-        if pager && atty::is(atty::Stream::Stdout) {
-            print_paged(&g_lines, &t_lines).map_err(|err| err.to_string())?;
+        let (g_lines, t_lines, commit_lines) = print_lines(&graph, settings)?;
+        let line_of = |row: usize| commit_lines[row];
+        let focus = focus.map(line_of);
+        let mut bookmarks = Bookmarks {
+            lines: bookmarked
+                .iter()
+                .filter_map(|oid| graph.indices.get(oid))
+                .filter(|row| settings.rows.as_ref().is_none_or(|rows| rows.contains(row)))
+                .map(|row| line_of(*row))
+                .sorted()
+                .collect(),
+            commit_lines: &commit_lines,
+        };
+        if let Some(pager) = pager.filter(|_| atty::is(atty::Stream::Stdout)) {
+            let jump = |spec: &str| graph.resolve_row(spec).map(line_of);
+            let texts = OnceCell::new();
+            let filter = |query: &str| {
+                let texts = texts.get_or_init(|| {
+                    graph
+                        .commits
+                        .iter()
+                        .map(|info| search_text(&graph, info))
+                        .collect::<Vec<_>>()
+                });
+                let rows = matching_rows(&graph, texts, query);
+                if rows.is_empty() {
+                    return Ok(FilterView::default());
+                }
+                // Lay out the matches again, connected through the hidden commits
+                let settings = restricted_settings(settings, &graph, &rows);
+                let filtered = build(&settings)?;
+                let (graph_lines, text_lines, lines) = print_lines(&filtered, &settings)?;
+                let num_lines = graph_lines.len();
+                Ok(FilterView {
+                    origins: origin_lines(&graph, &commit_lines, &filtered, &lines, num_lines),
+                    graph_lines,
+                    text_lines,
+                })
+            };
+            let action = |name: &str, line: usize| {
+                let row = commit_lines.iter().position(|l| *l == line).unwrap();
+                run_action(&graph, &pager.actions, name, row)
+            };
+            let bundle = |file: &str, lines: &[usize]| {
+                // Bundle the refs of bookmarked branch tips, the commits otherwise
+                let mut specs = vec![];
+                for row in lines
+                    .iter()
+                    .filter_map(|line| commit_lines.iter().position(|l| l == line))
+                {
+                    let info = &graph.commits[row];
+                    let names = info.branches.iter().chain(&info.tags).map(|idx| {
+                        let name = &graph.all_branches[*idx].name;
+                        name.strip_prefix("tags/").unwrap_or(name).to_string()
+                    });
+                    let count = specs.len();
+                    specs.extend(names);
+                    if specs.len() == count {
+                        specs.push(format!("@{}", row));
+                    }
+                }
+                selection_refs(&graph, &specs)
+                    .and_then(|refs| {
+                        create_bundle(&graph, Path::new(file), &refs)?;
+                        Ok(format!("Wrote {} with {} ref(s)", file, refs.len()))
+                    })
+                    .unwrap_or_else(|err| err)
+            };
+            let slice = |file: &str, lines: &[usize]| {
+                let rows: Vec<usize> = lines
+                    .iter()
+                    .filter_map(|line| commit_lines.iter().position(|l| l == line))
+                    .collect();
+                let (Some(first), Some(last)) = (rows.iter().min(), rows.iter().max()) else {
+                    return "No commits bookmarked".to_string();
+                };
+                print_unicode_rows(&graph, settings, Some(*first..*last + 1))
+                    .and_then(|(g_lines, t_lines, _)| {
+                        let text: String = g_lines
+                            .iter()
+                            .zip(&t_lines)
+                            .map(|(g, t)| {
+                                let line = format!("{}  {}", g, t);
+                                ControlChars::Strip.apply(line.as_bytes()) + "\n"
+                            })
+                            .collect();
+                        std::fs::write(file, text)
+                            .map_err(|err| format!("Can't write {}: {}", file, err))?;
+                        Ok(format!("Wrote {} with rows {}..{}", file, first, last + 1))
+                    })
+                    .unwrap_or_else(|err| err)
+            };
+            let hooks = PagerHooks {
+                jump: &jump,
+                filter: &filter,
+                action: &action,
+                bundle: &bundle,
+                slice: &slice,
+            };
+            print_paged(&g_lines, &t_lines, focus, &hooks, &mut bookmarks, &pager)
+                .map_err(|err| err.to_string())?;
+
+            // Keep bookmarks of commits not shown in this session
+            let mut new_bookmarks: Vec<_> = bookmarked
+                .into_iter()
+                .filter(|oid| !graph.indices.contains_key(oid))
+                .collect();
+            new_bookmarks.extend(bookmarks.lines.iter().filter_map(|line| {
+                let row = commit_lines.iter().position(|l| l == line)?;
+                Some(graph.commits[row].oid)
+            }));
+            write_bookmarks(repository, BOOKMARKS_FILE, &new_bookmarks)?;
         } else {
-            print_unpaged(&g_lines, &t_lines);
+            let lines = match (focus, context) {
+                (Some(line), Some(context)) => {
+                    line.saturating_sub(context)..(line + context + 1).min(g_lines.len())
+                }
+                _ => 0..g_lines.len(),
+            };
+            print_unpaged(&g_lines, &t_lines, lines, focus, &bookmarks.lines);
         }
     };
 
@@ -466,21 +2796,444 @@ fn run(
     Ok(())
 }
 
+/// Show the subgraphs of two refs side by side, aligned at their merge base.
+fn compare(
+    repository: Repository,
+    settings: &Settings,
+    refs: [&str; 2],
+    max_commits: Option<usize>,
+    pager: Option<Pager>,
+) -> Result<(), String> {
+    let oids = [
+        resolve_commit(&repository, refs[0])?,
+        resolve_commit(&repository, refs[1])?,
+    ];
+    let base = repository.merge_base(oids[0], oids[1]).ok();
+
+    let mut panes = vec![];
+    for (name, oid) in refs.iter().zip(&oids) {
+        let graph = GitGraph::retaining(&repository, settings, max_commits, &[*oid])?;
+        panes.push(graph_pane(&graph, settings, format!(" {}", name), base)?);
+    }
+
+    let right = panes.pop().unwrap();
+    let left = panes.pop().unwrap();
+    print_panes(&[], left, right, pager)
+}
+
+/// Preview rebasing a branch onto a base: the commits replayed or dropped, and the
+/// graphs before and after side by side. The repository is not changed.
+fn rebase_preview(
+    repository: Repository,
+    settings: &Settings,
+    refs: [&str; 2],
+    max_commits: Option<usize>,
+    pager: Option<Pager>,
+) -> Result<(), String> {
+    let onto = resolve_commit(&repository, refs[0])?;
+    let branch = resolve_commit(&repository, refs[1])?;
+    let scratch = ScratchRepository::new(&repository)?;
+    let scratch_repo = scratch.open()?;
+    let plan = simulate(&scratch_repo, onto, branch)?;
+
+    // Name the refs of the result after the given branches
+    let branch_name = |name: &str, fallback: &'static str| match repository
+        .find_branch(name, BranchType::Local)
+    {
+        Ok(_) => name.to_string(),
+        Err(_) => fallback.to_string(),
+    };
+    let onto_ref = format!("refs/heads/{}", branch_name(refs[0], "onto"));
+    let branch_ref = format!("refs/heads/{}", branch_name(refs[1], "rebased"));
+    let set_refs = |repository: &Repository, tip: Oid| {
+        repository
+            .reference(&onto_ref, onto, true, "")
+            .and_then(|_| repository.reference(&branch_ref, tip, true, ""))
+            .and_then(|_| repository.set_head(&branch_ref))
+            .map_err(|err| err.message().to_string())
+    };
+
+    let replayed = plan
+        .steps
+        .iter()
+        .filter(|step| matches!(step.kind, StepKind::Pick(_)))
+        .count();
+    let mut header = vec![format!(
+        " Rebase {} onto {}: {} of {} commit(s) replayed",
+        refs[1],
+        refs[0],
+        replayed,
+        plan.steps.len()
+    )];
+    for step in &plan.steps {
+        let commit = repository
+            .find_commit(step.oid)
+            .map_err(|err| err.message().to_string())?;
+        let (action, detail) = match &step.kind {
+            StepKind::Pick(oid) => ("pick", format!("-> {}", &oid.to_string()[..7])),
+            StepKind::Upstream(oid) => ("upstream", format!("== {}", &oid.to_string()[..7])),
+            StepKind::Empty => ("empty", String::new()),
+            StepKind::Conflict(_) => ("conflict", String::new()),
+            StepKind::Pending => ("pending", String::new()),
+        };
+        let mut line = format!(
+            "   {:<9}{} {:<10} {}",
+            action,
+            &step.oid.to_string()[..7],
+            detail,
+            settings
+                .control_chars
+                .apply(commit.summary_bytes().unwrap_or(b""))
+        );
+        if let StepKind::Conflict(paths) = &step.kind {
+            line.push_str(&format!(" (conflicts in {})", paths.join(", ")));
+        }
+        header.push(line);
+    }
+    header.push(String::new());
+
+    // Both graphs show only the two branches, before and after moving the rebased one
+    set_refs(&scratch_repo, branch)?;
+    let before = GitGraph::retaining(&scratch_repo, settings, max_commits, &[onto, branch])?;
+    let left = graph_pane(&before, settings, " before".to_string(), Some(onto))?;
+    set_refs(&scratch_repo, plan.tip)?;
+    let after = GitGraph::retaining(&scratch_repo, settings, max_commits, &[onto, plan.tip])?;
+    let right = graph_pane(&after, settings, " after".to_string(), Some(onto))?;
+    print_panes(&header, left, right, pager)
+}
+
+/// Print the graph with the commits highlighted that would become unreachable
+/// if a branch were deleted.
+fn delete_preview(
+    repository: Repository,
+    settings: &Settings,
+    branch: &str,
+    max_commits: Option<usize>,
+) -> Result<(), String> {
+    let graph = GitGraph::retaining(&repository, settings, max_commits, &[])?;
+    let lost = graph.exclusive_commits(branch)?;
+    if lost.is_empty() {
+        println!(
+            "Deleting branch '{}' loses no commits, all are reachable from other refs.",
+            branch
+        );
+    } else {
+        println!(
+            "Deleting branch '{}' would make {} commit(s) unreachable:",
+            branch,
+            lost.len()
+        );
+    }
+    if graph.head.as_ref().and_then(|head| head.branch()) == Some(branch) {
+        println!("The branch is checked out, switch to another branch before deleting it.");
+    }
+    println!();
+
+    let (g_lines, mut t_lines, commit_lines) = print_lines(&graph, settings)?;
+    let label = if settings.colored {
+        Paint::red("[unreachable]").bold().to_string()
+    } else {
+        "[unreachable]".to_string()
+    };
+    for oid in &lost {
+        if let Some(row) = graph.indices.get(oid) {
+            t_lines[commit_lines[*row]].insert_str(0, &format!("{} ", label));
+        }
+    }
+    let missing = lost
+        .iter()
+        .filter(|oid| !graph.indices.contains_key(oid))
+        .count();
+    print_unpaged(&g_lines, &t_lines, 0..g_lines.len(), None, &[]);
+    if missing > 0 {
+        println!("... and {} more beyond the commits shown", missing);
+    }
+    Ok(())
+}
+
+/// Resolves a revision to a commit.
+fn resolve_commit(repository: &Repository, name: &str) -> Result<Oid, String> {
+    repository
+        .revparse_single(name)
+        .and_then(|obj| obj.peel_to_commit())
+        .map(|commit| commit.id())
+        .map_err(|err| format!("Can't resolve '{}': {}", name, err.message()))
+}
+
+/// A graph as one side of a side-by-side view, anchored at the given commit.
+fn graph_pane(
+    graph: &GitGraph,
+    settings: &Settings,
+    title: String,
+    anchor: Option<Oid>,
+) -> Result<Pane, String> {
+    let (g_lines, t_lines, commit_lines) = print_lines(graph, settings)?;
+    let anchor = anchor
+        .and_then(|oid| graph.indices.get(&oid))
+        .map(|row| commit_lines[*row]);
+    Ok(Pane {
+        title,
+        lines: g_lines
+            .iter()
+            .zip(&t_lines)
+            .map(|(g_line, t_line)| format!(" {}  {}", g_line, t_line))
+            .collect(),
+        anchor,
+    })
+}
+
+/// The graph, and the recent history of its submodules below each other, if submodules
+/// are shown and there are any. Submodule commits set by commits of the graph are
+/// labeled with those, and the newest of them is aligned with its superproject commit.
+fn submodule_panes(
+    graph: &GitGraph,
+    repository: &Repository,
+    settings: &Settings,
+) -> Result<Option<(Pane, Pane)>, String> {
+    let Some(count) = settings.submodules else {
+        return Ok(None);
+    };
+    let submodules = repository
+        .submodules()
+        .map_err(|err| err.message().to_string())?;
+    if submodules.is_empty() {
+        return Ok(None);
+    }
+
+    let mut lines = vec![];
+    let mut anchors = None;
+    for submodule in &submodules {
+        let path = submodule.path().to_string_lossy().to_string();
+        lines.push(format!(" {}", path));
+        let Ok(repository) = submodule.open() else {
+            lines.push("   (not checked out)".to_string());
+            lines.push(String::new());
+            continue;
+        };
+        // Superproject and submodule commit of each bump, newest first
+        let bumps: Vec<(Oid, Oid)> = graph
+            .commits
+            .iter()
+            .flat_map(|info| {
+                graph
+                    .submodule_bumps_of(info.oid)
+                    .iter()
+                    .filter(|bump| bump.path == path)
+                    .map(|bump| (info.oid, bump.oid))
+            })
+            .collect();
+        let mut revisions: Vec<Oid> = bumps
+            .iter()
+            .map(|(_, oid)| *oid)
+            .filter(|oid| repository.find_commit(*oid).is_ok())
+            .collect();
+        if let Ok(head) = repository.head().and_then(|head| head.peel_to_commit()) {
+            revisions.push(head.id());
+        }
+        let sub_graph = GitGraph::retaining(&repository, settings, Some(count), &revisions)?;
+        let (g_lines, mut t_lines, commit_lines) = print_lines(&sub_graph, settings)?;
+        for (oid, sub_oid) in &bumps {
+            if let Some(row) = sub_graph.indices.get(sub_oid) {
+                let label = format!(" <- {}", &oid.to_string()[..7]);
+                t_lines[commit_lines[*row]].push_str(&label);
+                if anchors.is_none() {
+                    anchors = Some((*oid, lines.len() + commit_lines[*row]));
+                }
+            }
+        }
+        lines.extend(
+            g_lines
+                .iter()
+                .zip(&t_lines)
+                .map(|(g_line, t_line)| format!(" {}  {}", g_line, t_line)),
+        );
+        lines.push(String::new());
+    }
+
+    let left = graph_pane(
+        graph,
+        settings,
+        " superproject".to_string(),
+        anchors.map(|(oid, _)| oid),
+    )?;
+    let right = Pane {
+        title: " submodules".to_string(),
+        lines,
+        anchor: anchors.map(|(_, line)| line),
+    };
+    Ok(Some((left, right)))
+}
+
+/// Print two panes side by side, below the given header lines.
+fn print_panes(
+    header: &[String],
+    left: Pane,
+    right: Pane,
+    pager: Option<Pager>,
+) -> Result<(), String> {
+    let is_tty = atty::is(atty::Stream::Stdout);
+    let width = match crossterm::terminal::size() {
+        Ok((width, _)) if is_tty => width as usize,
+        _ => 160,
+    };
+    // Leave room for the focus marker and the separator added by the pager
+    let (panes, base_line) = side_by_side(left, right, width.saturating_sub(3));
+    let mut lines = header.to_vec();
+    lines.extend(panes);
+    let base_line = base_line.map(|line| line + header.len());
+    let text_lines = vec![String::new(); lines.len()];
+    if let Some(pager) = pager.filter(|_| is_tty) {
+        let jump = |_: &str| Err("Jumping is not supported in the side-by-side view".to_string());
+        let mut bookmarks = Bookmarks {
+            lines: vec![],
+            commit_lines: &[],
+        };
+        let filter = |query: &str| {
+            let query = query.to_lowercase();
+            let selected = (header.len() + 1..lines.len())
+                .filter(|idx| lines[*idx].to_lowercase().contains(&query))
+                .collect();
+            Ok(FilterView::select(&lines, &text_lines, selected))
+        };
+        let action = |_: &str, _: usize| {
+            Err("Actions are not supported in the side-by-side view".to_string())
+        };
+        let bundle =
+            |_: &str, _: &[usize]| "Bundles are not supported in the side-by-side view".to_string();
+        let slice = |_: &str, _: &[usize]| {
+            "Writing rows is not supported in the side-by-side view".to_string()
+        };
+        let hooks = PagerHooks {
+            jump: &jump,
+            filter: &filter,
+            action: &action,
+            bundle: &bundle,
+            slice: &slice,
+        };
+        print_paged(
+            &lines,
+            &text_lines,
+            base_line,
+            &hooks,
+            &mut bookmarks,
+            &pager,
+        )
+        .map_err(|err| err.to_string())?;
+    } else {
+        print_unpaged(&lines, &text_lines, 0..lines.len(), base_line, &[]);
+    }
+    Ok(())
+}
+
+/// Create the graph-lines and text-lines of text-based output, including legend and summary.
+///
+/// Also returns the line of each commit.
+fn print_lines(graph: &GitGraph, settings: &Settings) -> Result<UnicodeGraphInfo, String> {
+    let (mut g_lines, mut t_lines, indices) = print_unicode(graph, settings)?;
+    let mut offset = 0;
+    if settings.legend {
+        let (legend_g, legend_t) = print_legend(graph, settings);
+        offset = legend_g.len();
+        g_lines.splice(0..0, legend_g);
+        t_lines.splice(0..0, legend_t);
+    }
+    if let Some(header) = print_unborn_header(graph, settings) {
+        offset += 1;
+        g_lines.insert(0, String::new());
+        t_lines.insert(0, header);
+    }
+    let num_lines = g_lines.len() - offset;
+    if settings.summary {
+        g_lines.push(String::new());
+        t_lines.push(print_summary(&summarize(graph)?));
+    }
+    let commit_lines = indices
+        .iter()
+        .map(|line| {
+            if settings.reverse_commit_order {
+                offset + num_lines - 1 - line
+            } else {
+                offset + line
+            }
+        })
+        .collect();
+    Ok((g_lines, t_lines, commit_lines))
+}
+
 /// Print the graph, paged (i.e. wait for user input once the terminal is filled).
-fn print_paged(graph_lines: &[String], text_lines: &[String]) -> Result<(), ErrorKind> {
+///
+/// `focus` is the line to mark and start at, `jump` resolves addresses typed after ':' to lines.
+/// Bookmarks are toggled with 'm' for the marked commit, or the first commit on screen.
+fn print_paged(
+    graph_lines: &[String],
+    text_lines: &[String],
+    focus: Option<usize>,
+    hooks: &PagerHooks,
+    bookmarks: &mut Bookmarks,
+    pager: &Pager,
+) -> Result<(), ErrorKind> {
+    let keys = &pager.keys;
     let (width, height) = crossterm::terminal::size()?;
-    let mut start_idx: usize = 0;
-    let mut should_update: bool = true;
     let visible_lines: usize = height as usize - 1;
-    let help = "\r >>> Down/Up: line, PgDown/Enter: page, End: all, Esc/Q/^C: quit\r";
-    let help = if help.len() > width as usize {
-        &help[0..width as usize]
-    } else {
-        help
+    let mut view = PagerView {
+        graph_lines,
+        text_lines,
+        filtered: None,
+    };
+    let max_start = view.len().saturating_sub(visible_lines);
+    let mut focus = focus;
+    let mut start_idx: usize = focus.unwrap_or(0).min(max_start);
+    let mut should_update: bool = true;
+    // The action prompting for input, jump or run-action, and the input
+    let mut prompt: Option<(PagerAction, String)> = None;
+    let mut query: Option<String> = None;
+    let mut editing_query = false;
+    let mut message: Option<String> = None;
+    let mut show_help = false;
+    let first_key = |action| keys.keys(action).first().map_or("-", |key| &key[..]);
+    let help = format!(
+        " >>> {}/{}: line, {}: page, {}: jump, {}: filter, {}: bookmark, {}: help, {}: quit",
+        first_key(PagerAction::Down),
+        first_key(PagerAction::Up),
+        first_key(PagerAction::PageDown),
+        first_key(PagerAction::Jump),
+        first_key(PagerAction::Filter),
+        first_key(PagerAction::Bookmark),
+        first_key(PagerAction::Help),
+        first_key(PagerAction::Quit),
+    );
+    let help = format!(
+        "\r{}\r",
+        help.chars().take(width as usize).collect::<String>()
+    );
+    let key_lists: Vec<String> = PagerAction::ALL
+        .iter()
+        .map(|action| keys.keys(*action).join(", "))
+        .collect();
+    let key_width = key_lists
+        .iter()
+        .map(|k| k.chars().count())
+        .max()
+        .unwrap_or(0);
+    let help_lines: Vec<String> = PagerAction::ALL
+        .iter()
+        .zip(&key_lists)
+        .map(|(action, keys)| format!("  {:<key_width$}  {}", keys, action.description()))
+        .collect();
+
+    // Shows a line, clearing the filter if it hides the line
+    let scroll_to = |line: usize, view: &mut PagerView, query: &mut Option<String>| {
+        if view.position(line).is_none() {
+            view.filtered = None;
+            *query = None;
+        }
+        view.position(line).unwrap_or(0)
     };
 
     enable_raw_mode()?;
-    while start_idx + visible_lines < graph_lines.len() {
+    stdout().execute(EnableMouseCapture)?;
+    while start_idx + visible_lines < view.len() || prompt.is_some() || query.is_some() || show_help
+    {
         // Print commits
         if should_update {
             should_update = false;
@@ -488,64 +3241,416 @@ fn print_paged(graph_lines: &[String], text_lines: &[String]) -> Result<(), Erro
                 .execute(MoveToRow(0))?
                 .execute(Clear(ClearType::CurrentLine))?;
             for curr_idx in 0..visible_lines {
-                stdout()
-                    .execute(Clear(ClearType::CurrentLine))?
-                    .execute(Print(format!(
-                        " {}  {}\r\n",
-                        graph_lines[start_idx + curr_idx],
-                        text_lines[start_idx + curr_idx]
+                stdout().execute(Clear(ClearType::CurrentLine))?;
+                if show_help {
+                    if let Some(line) = help_lines.get(curr_idx) {
+                        stdout().execute(Print(line))?;
+                    }
+                } else if let Some((graph_line, text_line, line)) = view.line(start_idx + curr_idx)
+                {
+                    stdout().execute(Print(format!(
+                        "{}{}  {}",
+                        line.map_or(" ", |line| marker(line, focus, &bookmarks.lines)),
+                        graph_line,
+                        text_line
                     )))?;
+                }
+                stdout().execute(Print("\r\n"))?;
+            }
+            // Print help, jump prompt, filter or message at the end
+            stdout().execute(Clear(ClearType::CurrentLine))?;
+            if show_help {
+                stdout().execute(Print("\r >>> Press any key to close the help\r"))?;
+            } else if let Some((PagerAction::RunAction, text)) = &prompt {
+                let names = pager.actions.names().join("|");
+                stdout().execute(Print(format!("\r Action [{}]: {}", names, text)))?;
+            } else if let Some((PagerAction::Bundle, text)) = &prompt {
+                stdout().execute(Print(format!("\r Bundle file: {}", text)))?;
+            } else if let Some((PagerAction::Slice, text)) = &prompt {
+                stdout().execute(Print(format!("\r Graph file: {}", text)))?;
+            } else if let Some((_, text)) = &prompt {
+                stdout().execute(Print(format!("\r :{}", text)))?;
+            } else if let Some(message) = message.take() {
+                stdout().execute(Print(format!("\r {}\r", message)))?;
+            } else if let Some(query) = &query {
+                let status = if editing_query {
+                    format!("\r /{}", query)
+                } else {
+                    format!("\r >>> Filter '{}', /: edit, Esc: clear\r", query)
+                };
+                stdout().execute(Print(status))?;
+            } else {
+                stdout().execute(Print(&help))?;
             }
-            // Print help at the end
-            stdout().execute(Print(help))?;
         } else {
             let input = crossterm::event::read()?;
             if let Event::Key(evt) = input {
-                match evt.code {
-                    KeyCode::Down => {
-                        start_idx += 1;
-                        should_update = true;
+                if show_help {
+                    show_help = false;
+                    should_update = true;
+                    continue;
+                }
+                if let Some((prompt_action, text)) = &mut prompt {
+                    match evt.code {
+                        KeyCode::Char(c) => text.push(c),
+                        KeyCode::Backspace => {
+                            text.pop();
+                        }
+                        KeyCode::Enter if *prompt_action == PagerAction::RunAction => {
+                            let shown = view.origins_from(start_idx);
+                            match marked_line(&shown, focus, bookmarks.commit_lines) {
+                                Some(line) => {
+                                    focus = Some(line);
+                                    stdout()
+                                        .execute(DisableMouseCapture)?
+                                        .execute(Clear(ClearType::All))?
+                                        .execute(MoveTo(0, 0))?;
+                                    disable_raw_mode()?;
+                                    let result = (hooks.action)(text.trim(), line);
+                                    enable_raw_mode()?;
+                                    stdout().execute(EnableMouseCapture)?;
+                                    match result {
+                                        Ok(()) => {
+                                            stdout().execute(Print(
+                                                "\r\n >>> Press any key to return to the graph",
+                                            ))?;
+                                            while !matches!(
+                                                crossterm::event::read()?,
+                                                Event::Key(_)
+                                            ) {}
+                                        }
+                                        Err(err) => message = Some(err),
+                                    }
+                                }
+                                None => message = Some("No commit marked".to_string()),
+                            }
+                            prompt = None;
+                        }
+                        KeyCode::Enter if *prompt_action == PagerAction::Bundle => {
+                            let file = text.trim();
+                            if !file.is_empty() {
+                                message = Some((hooks.bundle)(file, &bookmarks.lines));
+                            }
+                            prompt = None;
+                        }
+                        KeyCode::Enter if *prompt_action == PagerAction::Slice => {
+                            let file = text.trim();
+                            if !file.is_empty() {
+                                message = Some((hooks.slice)(file, &bookmarks.lines));
+                            }
+                            prompt = None;
+                        }
+                        KeyCode::Enter => {
+                            match (hooks.jump)(text.trim()) {
+                                Ok(line) => {
+                                    focus = Some(line);
+                                    let pos = scroll_to(line, &mut view, &mut query);
+                                    start_idx = pos.min(view.len().saturating_sub(visible_lines));
+                                }
+                                Err(err) => message = Some(err),
+                            }
+                            prompt = None;
+                        }
+                        KeyCode::Esc => prompt = None,
+                        _ => {}
                     }
-                    KeyCode::Up => {
-                        if start_idx > 0 {
-                            start_idx -= 1;
-                            should_update = true;
+                    should_update = true;
+                    continue;
+                }
+                if editing_query {
+                    let text = query.get_or_insert_with(String::new);
+                    match evt.code {
+                        KeyCode::Char(c) => text.push(c),
+                        KeyCode::Backspace => {
+                            text.pop();
+                        }
+                        KeyCode::Enter => editing_query = false,
+                        KeyCode::Esc => {
+                            editing_query = false;
+                            query = None;
                         }
+                        _ => {}
                     }
-                    KeyCode::Enter | KeyCode::PageDown => {
-                        start_idx += height as usize - 2;
-                        should_update = true;
+                    // Filter live while typing
+                    view.filtered = match query.as_deref().map(str::trim) {
+                        Some(text) if !text.is_empty() => match (hooks.filter)(text) {
+                            Ok(filtered) => Some(filtered),
+                            Err(err) => {
+                                message = Some(err);
+                                None
+                            }
+                        },
+                        _ => None,
+                    };
+                    if !editing_query && query.as_deref().is_some_and(|q| q.trim().is_empty()) {
+                        query = None;
                     }
-                    KeyCode::End => {
-                        start_idx = graph_lines.len() - height as usize - 2;
-                        should_update = true;
+                    start_idx = 0;
+                    should_update = true;
+                    continue;
+                }
+                if evt.code == KeyCode::Esc && query.is_some() {
+                    query = None;
+                    view.filtered = None;
+                    start_idx = 0;
+                    should_update = true;
+                    continue;
+                }
+                let action = match key_name(&evt).and_then(|key| keys.action(&key)) {
+                    Some(action) => action,
+                    None => continue,
+                };
+                should_update = true;
+                match action {
+                    PagerAction::Down => start_idx += 1,
+                    PagerAction::Up => start_idx = start_idx.saturating_sub(1),
+                    PagerAction::PageDown => start_idx += height as usize - 2,
+                    PagerAction::PageUp => {
+                        start_idx = start_idx.saturating_sub(height as usize - 2)
+                    }
+                    PagerAction::Top => start_idx = 0,
+                    PagerAction::End => {
+                        start_idx = view.len().saturating_sub(height as usize + 2);
                         // TODO: maybe make this better
                     }
-                    KeyCode::Char(c) => match c {
-                        'q' => {
-                            break;
+                    PagerAction::Quit => break,
+                    PagerAction::Help => show_help = true,
+                    PagerAction::Jump => prompt = Some((action, String::new())),
+                    PagerAction::RunAction if pager.actions.names().is_empty() => {
+                        message = Some(format!("No actions defined in {}", ACTIONS_FILE))
+                    }
+                    PagerAction::RunAction => prompt = Some((action, String::new())),
+                    PagerAction::Bundle if bookmarks.lines.is_empty() => {
+                        message = Some("Bookmark commits to select them for a bundle".to_string())
+                    }
+                    PagerAction::Bundle => prompt = Some((action, String::new())),
+                    PagerAction::Slice if bookmarks.lines.is_empty() => {
+                        message = Some("Bookmark commits to select the rows to write".to_string())
+                    }
+                    PagerAction::Slice => prompt = Some((action, String::new())),
+                    PagerAction::Filter => {
+                        editing_query = true;
+                        query.get_or_insert_with(String::new);
+                    }
+                    PagerAction::Bookmark => {
+                        let shown = view.origins_from(start_idx);
+                        if let Some(line) = marked_line(&shown, focus, bookmarks.commit_lines) {
+                            match bookmarks.lines.binary_search(&line) {
+                                Ok(pos) => {
+                                    bookmarks.lines.remove(pos);
+                                }
+                                Err(pos) => bookmarks.lines.insert(pos, line),
+                            }
+                            focus = Some(line);
+                        }
+                    }
+                    PagerAction::NextBookmark | PagerAction::PrevBookmark => {
+                        let current = focus
+                            .or_else(|| view.origins_from(start_idx).first().copied())
+                            .unwrap_or(0);
+                        let next = if action == PagerAction::NextBookmark {
+                            let next = bookmarks.lines.iter().find(|line| **line > current);
+                            next.or(bookmarks.lines.first())
+                        } else {
+                            let prev = bookmarks.lines.iter().rev().find(|l| **l < current);
+                            prev.or(bookmarks.lines.last())
+                        };
+                        match next {
+                            Some(&line) => {
+                                focus = Some(line);
+                                let pos = scroll_to(line, &mut view, &mut query);
+                                start_idx = pos.min(view.len().saturating_sub(visible_lines));
+                            }
+                            None => message = Some("No bookmarks".to_string()),
                         }
-                        'c' if evt.modifiers == KeyModifiers::CONTROL => {
-                            break;
+                    }
+                }
+                // While filtering, stay in the pager at the end of the matches
+                if query.is_some() {
+                    start_idx = start_idx.min(view.len().saturating_sub(visible_lines));
+                }
+            } else if let Event::Mouse(evt) = input {
+                if show_help || prompt.is_some() || editing_query {
+                    continue;
+                }
+                match evt.kind {
+                    MouseEventKind::ScrollDown => start_idx += SCROLL_LINES,
+                    MouseEventKind::ScrollUp => start_idx = start_idx.saturating_sub(SCROLL_LINES),
+                    MouseEventKind::Down(MouseButton::Left) => {
+                        let row = evt.row as usize;
+                        let (label, line) = match view.line(start_idx + row) {
+                            Some((graph_line, text_line, line)) if row < visible_lines => {
+                                (label_at(graph_line, text_line, evt.column), line)
+                            }
+                            _ => continue,
+                        };
+                        // Clicking a branch label filters to the branch's commits
+                        if let Some(label) = label {
+                            let branch_query = format!("{}{}", BRANCH_FILTER, label);
+                            match (hooks.filter)(&branch_query) {
+                                Ok(filtered) if !filtered.origins.is_empty() => {
+                                    view.filtered = Some(filtered);
+                                    query = Some(branch_query);
+                                    start_idx = 0;
+                                }
+                                Ok(_) => {}
+                                Err(err) => message = Some(err),
+                            }
+                        }
+                        if let Some(line) = line.filter(|l| bookmarks.commit_lines.contains(l)) {
+                            focus = Some(line);
                         }
-                        _ => {}
-                    },
-                    KeyCode::Esc => {
-                        break;
                     }
-                    _ => {}
+                    _ => continue,
+                }
+                should_update = true;
+                if query.is_some() {
+                    start_idx = start_idx.min(view.len().saturating_sub(visible_lines));
                 }
             }
         }
     }
 
+    stdout().execute(DisableMouseCapture)?;
     disable_raw_mode()?;
     Ok(())
 }
 
+/// The commit line that bookmarks and actions apply to: the focused line if shown,
+/// otherwise the first commit shown.
+fn marked_line(shown: &[usize], focus: Option<usize>, commit_lines: &[usize]) -> Option<usize> {
+    focus.filter(|line| shown.contains(line)).or_else(|| {
+        shown
+            .iter()
+            .copied()
+            .find(|line| commit_lines.contains(line))
+    })
+}
+
+/// Runs an external action on the commit at `row`, in the repository's work tree.
+fn run_action(graph: &GitGraph, actions: &Actions, name: &str, row: usize) -> Result<(), String> {
+    let info = &graph.commits[row];
+    let oid = info.oid.to_string();
+    let branch = graph
+        .branch_of(info.oid)
+        .map_or("", |branch| &branch.name[..]);
+    let values = [("oid", &oid[..]), ("short", &oid[..7]), ("branch", branch)];
+    let command = actions.command(name, &values)?;
+
+    let mut process = if cfg!(windows) {
+        let mut process = std::process::Command::new("cmd");
+        process.arg("/C");
+        process
+    } else {
+        let mut process = std::process::Command::new("sh");
+        process.arg("-c");
+        process
+    };
+    let repository = graph.repository()?;
+    let status = process
+        .arg(&command)
+        .current_dir(repository.workdir().unwrap_or(repository.path()))
+        .status()
+        .map_err(|err| format!("Can't run action '{}': {}", name, err))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("Action '{}' failed: {}", name, status))
+    }
+}
+
+/// The ref label under a clicked column of a pager line, if any.
+/// Labels are the comma-separated names in parentheses or brackets, like `(HEAD -> main, origin/main)`.
+fn label_at(graph_line: &str, text_line: &str, column: u16) -> Option<String> {
+    // The marker, the graph and two spaces precede the text
+    let graph_width = visible_text(graph_line).chars().count();
+    let offset = (column as usize).checked_sub(graph_width + 3)?;
+    let text: Vec<char> = visible_text(text_line).chars().collect();
+    let is_delimiter = |c: &char| c.is_whitespace() || ",()[]".contains(*c);
+    if offset >= text.len() || is_delimiter(&text[offset]) {
+        return None;
+    }
+    let start = text[..offset]
+        .iter()
+        .rposition(is_delimiter)
+        .map_or(0, |pos| pos + 1);
+    let end = text[offset..]
+        .iter()
+        .position(is_delimiter)
+        .map_or(text.len(), |pos| offset + pos);
+    let depth = text[..start].iter().fold(0i32, |depth, c| match c {
+        '(' | '[' => depth + 1,
+        ')' | ']' => depth - 1,
+        _ => depth,
+    });
+    (depth > 0).then(|| text[start..end].iter().collect())
+}
+
+/// A line without terminal color codes.
+fn visible_text(line: &str) -> String {
+    let mut text = String::new();
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' && chars.peek() == Some(&'[') {
+            chars.find(|c| c.is_ascii_alphabetic());
+        } else {
+            text.push(c);
+        }
+    }
+    text
+}
+
+/// Name of a pressed key, as used in [KeyBindings].
+fn key_name(evt: &KeyEvent) -> Option<String> {
+    let name = match evt.code {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Down => "Down".to_string(),
+        KeyCode::Up => "Up".to_string(),
+        KeyCode::Left => "Left".to_string(),
+        KeyCode::Right => "Right".to_string(),
+        KeyCode::PageDown => "PageDown".to_string(),
+        KeyCode::PageUp => "PageUp".to_string(),
+        KeyCode::Home => "Home".to_string(),
+        KeyCode::End => "End".to_string(),
+        KeyCode::Enter => "Enter".to_string(),
+        KeyCode::Esc => "Esc".to_string(),
+        KeyCode::Tab => "Tab".to_string(),
+        KeyCode::Backspace => "Backspace".to_string(),
+        _ => return None,
+    };
+    if evt.modifiers.contains(KeyModifiers::CONTROL) {
+        Some(format!("Ctrl-{}", name))
+    } else {
+        Some(name)
+    }
+}
+
 /// Print the graph, un-paged.
-fn print_unpaged(graph_lines: &[String], text_lines: &[String]) {
-    for (g_line, t_line) in graph_lines.iter().zip(text_lines.iter()) {
-        println!(" {}  {}", g_line, t_line);
+fn print_unpaged(
+    graph_lines: &[String],
+    text_lines: &[String],
+    lines: Range<usize>,
+    focus: Option<usize>,
+    bookmarks: &[usize],
+) {
+    for idx in lines {
+        println!(
+            "{}{}  {}",
+            marker(idx, focus, bookmarks),
+            graph_lines[idx],
+            text_lines[idx]
+        );
+    }
+}
+
+/// The marker in front of a line: '>' for the focus, '*' for bookmarks.
+fn marker(line: usize, focus: Option<usize>, bookmarks: &[usize]) -> &'static str {
+    if focus == Some(line) {
+        ">"
+    } else if bookmarks.binary_search(&line).is_ok() {
+        "*"
+    } else {
+        " "
     }
 }
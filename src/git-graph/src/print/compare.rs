@@ -0,0 +1,118 @@
+//! Side-by-side view of two refs' subgraphs, aligned at their merge base.
+
+/// One side of a side-by-side view.
+pub struct Pane {
+    pub title: String,
+    /// Rendered lines, possibly containing terminal color codes
+    pub lines: Vec<String>,
+    /// The line to align with the other pane, usually the merge base
+    pub anchor: Option<usize>,
+}
+
+/// Joins two panes into lines of at most `width` characters, separated by a
+/// vertical bar. Blank lines are inserted above one of the panes, so that the
+/// anchors of both end up on the same line.
+///
+/// Returns the lines and the line of the aligned anchors, if any.
+pub fn side_by_side(left: Pane, right: Pane, width: usize) -> (Vec<String>, Option<usize>) {
+    const SEPARATOR: &str = " │ ";
+    let pane_width = width.saturating_sub(SEPARATOR.chars().count()) / 2;
+
+    let (left_pad, right_pad) = match (left.anchor, right.anchor) {
+        (Some(l), Some(r)) if l < r => (r - l, 0),
+        (Some(l), Some(r)) => (0, l - r),
+        _ => (0, 0),
+    };
+    let anchor = left.anchor.zip(right.anchor).map(|(l, r)| 1 + l.max(r));
+
+    let left_lines = pad_top(&left.lines, left_pad);
+    let right_lines = pad_top(&right.lines, right_pad);
+    let height = left_lines.len().max(right_lines.len());
+
+    let title = format!(
+        "{}{}{}",
+        fit(&left.title, pane_width),
+        SEPARATOR,
+        fit(&right.title, pane_width)
+    );
+    let mut lines = vec![title.trim_end().to_string()];
+    for idx in 0..height {
+        let left = left_lines.get(idx).map_or("", |line| &line[..]);
+        let right = right_lines.get(idx).map_or("", |line| &line[..]);
+        let line = format!(
+            "{}{}{}",
+            fit(left, pane_width),
+            SEPARATOR,
+            fit(right, pane_width)
+        );
+        lines.push(line.trim_end().to_string());
+    }
+    (lines, anchor)
+}
+
+fn pad_top(lines: &[String], count: usize) -> Vec<&str> {
+    std::iter::repeat_n("", count)
+        .chain(lines.iter().map(|line| &line[..]))
+        .collect()
+}
+
+/// Cuts or pads a line to exactly `width` visible characters.
+/// Terminal escape sequences are kept, but not counted.
+fn fit(line: &str, width: usize) -> String {
+    let mut out = String::new();
+    let mut visible = 0;
+    let mut escaped = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' && chars.peek() == Some(&'[') {
+            out.push(c);
+            for c in chars.by_ref() {
+                out.push(c);
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+            escaped = true;
+            continue;
+        }
+        if visible == width {
+            continue;
+        }
+        out.push(c);
+        visible += 1;
+    }
+    if escaped {
+        out.push_str("\x1b[0m");
+    }
+    out.push_str(&" ".repeat(width - visible));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fit, side_by_side, Pane};
+
+    #[test]
+    fn side_by_side_aligns_anchors() {
+        let pane = |title: &str, lines: &[&str], anchor| Pane {
+            title: title.to_string(),
+            lines: lines.iter().map(|line| line.to_string()).collect(),
+            anchor: Some(anchor),
+        };
+        let left = pane("a", &["a1", "base"], 1);
+        let right = pane("b", &["b1", "b2", "b3", "base"], 3);
+
+        let (lines, anchor) = side_by_side(left, right, 13);
+        assert_eq!(anchor, Some(4));
+        assert_eq!(lines[0], "a     │ b");
+        assert_eq!(lines[1], "      │ b1");
+        assert_eq!(lines[3], "a1    │ b3");
+        assert_eq!(lines[4], "base  │ base");
+    }
+
+    #[test]
+    fn fit_ignores_escape_sequences() {
+        assert_eq!(fit("\x1b[38;5;1mabc", 2), "\x1b[38;5;1mab\x1b[0m");
+        assert_eq!(fit("ab", 4), "ab  ");
+    }
+}
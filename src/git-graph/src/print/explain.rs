@@ -0,0 +1,303 @@
+//! Explain why commits are attributed to branches, without rendering a graph.
+
+use crate::graph::{parse_merge_summary, BranchInfo, GitGraph};
+use crate::settings::Settings;
+use git2::Oid;
+use regex::Regex;
+use std::fmt::Write;
+
+/// Explains the attribution of a branch (given by name) or commit (given by
+/// revision or `@N` row address): which branch claimed it, from which origin,
+/// and which persistence and order rules matched.
+pub fn explain(graph: &GitGraph, settings: &Settings, spec: &str) -> Result<String, String> {
+    let mut out = String::new();
+    if let Some(index) = graph
+        .all_branches
+        .iter()
+        .position(|branch| !branch.is_tag && branch.name == spec)
+    {
+        explain_branch(graph, settings, index, &mut out)?;
+        return Ok(out);
+    }
+
+    let row = graph.resolve_row(spec)?;
+    let info = &graph.commits[row];
+    let meta = graph
+        .metadata(info.oid)
+        .map_err(|err| err.message().to_string())?;
+    writeln!(
+        out,
+        "Commit {} (row {}) {}",
+        short(info.oid),
+        row,
+        settings.control_chars.apply(&meta.summary)
+    )
+    .unwrap();
+
+    let trace = match info.branch_trace {
+        Some(trace) => trace,
+        None => {
+            writeln!(out, "  Not attributed to any branch.").unwrap();
+            return Ok(out);
+        }
+    };
+    let branch = &graph.all_branches[trace];
+    let steps = first_parent_steps(graph, branch.target, row);
+    match steps {
+        Some(0) => writeln!(out, "  Tip of branch '{}'.", branch.name),
+        Some(steps) => writeln!(
+            out,
+            "  Attributed to branch '{}', {} first-parent step(s) below its tip {}.",
+            branch.name,
+            steps,
+            short(branch.target)
+        ),
+        None => writeln!(out, "  Attributed to branch '{}'.", branch.name),
+    }
+    .unwrap();
+
+    writeln!(
+        out,
+        "  Branches are traced along first parents, most persistent first and existing\n  \
+         branches before merged ones. The first trace reaching a commit claims it."
+    )
+    .unwrap();
+    let competitors: Vec<_> = graph
+        .all_branches
+        .iter()
+        .enumerate()
+        .filter(|(idx, other)| {
+            *idx != trace && !other.is_tag && first_parent_steps(graph, other.target, row).is_some()
+        })
+        .collect();
+    if competitors.is_empty() {
+        writeln!(out, "  No other branch reaches this commit.").unwrap();
+    } else {
+        writeln!(out, "  Other branches reaching this commit, traced later:").unwrap();
+        for (idx, other) in competitors {
+            writeln!(
+                out,
+                "    {} (traced #{}, persistence tier {})",
+                other.name,
+                idx + 1,
+                other.persistence
+            )
+            .unwrap();
+        }
+    }
+    writeln!(out).unwrap();
+    explain_branch(graph, settings, trace, &mut out)?;
+    Ok(out)
+}
+
+/// Explains origin, persistence and order group of a branch.
+fn explain_branch(
+    graph: &GitGraph,
+    settings: &Settings,
+    index: usize,
+    out: &mut String,
+) -> Result<(), String> {
+    let branch = &graph.all_branches[index];
+    writeln!(
+        out,
+        "Branch '{}' (traced #{} of {})",
+        branch.name,
+        index + 1,
+        graph.all_branches.len()
+    )
+    .unwrap();
+    writeln!(out, "  Origin: {}", origin(graph, settings, branch)?).unwrap();
+
+    let persistence = &settings.branches.persistence;
+    writeln!(
+        out,
+        "  Persistence: tier {}, {}",
+        branch.persistence,
+        rule(persistence, branch.persistence as usize, "least persistent")
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "  Order group: {}, {}",
+        branch.visual.order_group,
+        rule(
+            &settings.branches.order,
+            branch.visual.order_group,
+            "right-most"
+        )
+    )
+    .unwrap();
+
+    // Counted by trace, as merge-derived branches may share their name with others
+    let claimed = graph
+        .commits
+        .iter()
+        .filter(|info| info.branch_trace == Some(index))
+        .count();
+    writeln!(
+        out,
+        "  Claimed {} commit(s), tracing from {}.",
+        claimed,
+        short(branch.target)
+    )
+    .unwrap();
+    Ok(())
+}
+
+/// Lists the merge commits whose summaries match none of the merge patterns, and whose
+/// merged-in branch is therefore named "unknown", with their summaries. Stashes are skipped.
+pub fn unparsed_merges(graph: &GitGraph, settings: &Settings) -> Result<String, String> {
+    let mut lines = vec![];
+    let mut merges = 0;
+    for (row, info) in graph.commits.iter().enumerate() {
+        let is_stash = info
+            .branches
+            .iter()
+            .any(|idx| graph.all_branches[*idx].name.starts_with("stash@{"));
+        if !info.is_merge || is_stash {
+            continue;
+        }
+        merges += 1;
+        let meta = graph
+            .metadata(info.oid)
+            .map_err(|err| err.message().to_string())?;
+        let summary = settings.control_chars.apply(&meta.summary);
+        if parse_merge_summary(&summary, &settings.merge_patterns).is_none() {
+            lines.push(format!("  {} @{:<5} {}", short(info.oid), row, summary));
+        }
+    }
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{} of {} merge(s) match no merge pattern, their merged-in branches are named 'unknown'",
+        lines.len(),
+        merges
+    )
+    .unwrap();
+    for line in lines {
+        writeln!(out, "{}", line).unwrap();
+    }
+    Ok(out)
+}
+
+/// Describes where a branch comes from: a ref, a stash, or a merge summary.
+fn origin(graph: &GitGraph, settings: &Settings, branch: &BranchInfo) -> Result<String, String> {
+    if branch.is_tag {
+        return Ok("tag".to_string());
+    }
+    if branch.name.starts_with("stash@{") {
+        return Ok("stash entry".to_string());
+    }
+    // Dangling commits are the only ones beyond the group of unknown branches
+    if branch.visual.order_group > settings.branches.order.len() {
+        return Ok("dangling commit".to_string());
+    }
+    if branch.name.contains("@{") {
+        return Ok("reflog entry".to_string());
+    }
+    let merge = match branch.merge_target {
+        None if branch.is_remote => return Ok("remote branch ref".to_string()),
+        None => return Ok("local branch ref".to_string()),
+        Some(merge) => merge,
+    };
+
+    let meta = graph
+        .metadata(merge)
+        .map_err(|err| err.message().to_string())?;
+    let summary = settings.control_chars.apply(&meta.summary);
+    let pattern = settings
+        .merge_patterns
+        .patterns
+        .iter()
+        .position(|regex| regex.captures(&summary).is_some_and(|c| c.len() == 2));
+    let matched = match pattern {
+        Some(idx) => format!(
+            "matching merge pattern #{} '{}'",
+            idx + 1,
+            settings.merge_patterns.patterns[idx].as_str()
+        ),
+        None => "matching no merge pattern, hence the fallback name".to_string(),
+    };
+    Ok(format!(
+        "derived from the 2nd parent of merge {} \"{}\",\n          {}",
+        short(merge),
+        summary,
+        matched
+    ))
+}
+
+/// Describes the regex at `index`, or the fallback if no regex matched.
+fn rule(patterns: &[Regex], index: usize, fallback: &str) -> String {
+    match patterns.get(index) {
+        Some(regex) => format!("matching '{}'", regex.as_str()),
+        None => format!("no pattern matched ({})", fallback),
+    }
+}
+
+/// Number of first-parent steps from `tip` to the commit at `row`, if it is reached.
+fn first_parent_steps(graph: &GitGraph, tip: Oid, row: usize) -> Option<usize> {
+    let mut steps = 0;
+    let mut curr = graph.indices.get(&tip).copied();
+    while let Some(index) = curr {
+        if index == row {
+            return Some(steps);
+        }
+        if index > row {
+            return None;
+        }
+        steps += 1;
+        curr = graph.commits[index].parents[0].and_then(|oid| graph.indices.get(&oid).copied());
+    }
+    None
+}
+
+fn short(oid: Oid) -> String {
+    oid.to_string()[..7].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{explain, explain_branch};
+    use crate::graph::GitGraph;
+    use crate::testing::{settings, TestRepo};
+
+    #[test]
+    fn claimed_commits_of_branches_merged_twice() {
+        let repo = TestRepo::bare("git-graph-explain");
+        let root = repo.commit("root", &[]);
+        let first = repo.commit("first", &[root]);
+        let merge = repo.commit("Merge branch 'feature/x'", &[root, first]);
+        let second = repo.commit("second a", &[merge]);
+        let second = repo.commit("second b", &[second]);
+        let tip = repo.commit("Merge branch 'feature/x'", &[merge, second]);
+        repo.reference("refs/heads/main", tip, false, "").unwrap();
+        repo.set_head("refs/heads/main").unwrap();
+
+        let settings = settings();
+        let graph = GitGraph::retaining(&repo, &settings, None, &[]).unwrap();
+        let mut claimed: Vec<String> = graph
+            .all_branches
+            .iter()
+            .enumerate()
+            .filter(|(_, branch)| branch.name == "feature/x")
+            .map(|(index, _)| {
+                let mut out = String::new();
+                explain_branch(&graph, &settings, index, &mut out).unwrap();
+                out.lines()
+                    .find(|line| line.contains("Claimed"))
+                    .unwrap()
+                    .trim()
+                    .to_string()
+            })
+            .collect();
+        claimed.sort();
+        assert_eq!(claimed.len(), 2);
+        assert!(claimed[0].starts_with("Claimed 1 commit(s)"));
+        assert!(claimed[1].starts_with("Claimed 2 commit(s)"));
+
+        let out = explain(&graph, &settings, &first.to_string()).unwrap();
+        assert!(out.contains("Tip of branch 'feature/x'"));
+        assert!(out.contains("Claimed 1 commit(s)"));
+    }
+}
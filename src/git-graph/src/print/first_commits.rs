@@ -0,0 +1,160 @@
+//! Each author's first commit over the life of a repository, as an annotated timeline.
+
+use crate::graph::GitGraph;
+use crate::print::format::format_date;
+use crate::settings::Settings;
+use git2::Time;
+use serde_derive::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Maximum number of file areas listed per commit.
+const MAX_AREAS: usize = 5;
+
+/// The first commit of an author.
+#[derive(Serialize)]
+pub struct FirstCommit {
+    pub author: String,
+    pub email: String,
+    pub oid: String,
+    /// Author date
+    pub date: String,
+    pub summary: String,
+    pub branch: Option<String>,
+    /// Top-level directories (with a trailing `/`) and files changed by the commit
+    pub areas: Vec<String>,
+    /// Number of commits by the author in the graph
+    pub commits: usize,
+}
+
+/// The first commit of each author (identified by email) in the graph, by author date,
+/// oldest first.
+pub fn first_commits(graph: &GitGraph, settings: &Settings) -> Result<Vec<FirstCommit>, String> {
+    // First commit's row and author time, and number of commits, by email
+    let mut authors: HashMap<Vec<u8>, (usize, Time, usize)> = HashMap::new();
+    for (row, info) in graph.commits.iter().enumerate() {
+        let meta = graph
+            .metadata(info.oid)
+            .map_err(|err| err.message().to_string())?;
+        let email = meta.author_email.to_ascii_lowercase();
+        let time = meta.author_time;
+        let entry = authors.entry(email).or_insert((row, time, 0));
+        // Rows are newest first, so older or equal times are earlier commits
+        if time.seconds() <= entry.1.seconds() {
+            *entry = (row, time, entry.2);
+        }
+        entry.2 += 1;
+    }
+    let mut authors: Vec<_> = authors.into_values().collect();
+    authors.sort_by_key(|(row, time, _)| (time.seconds(), usize::MAX - row));
+
+    let mut result = vec![];
+    for (row, time, commits) in authors {
+        let info = &graph.commits[row];
+        let meta = graph
+            .metadata(info.oid)
+            .map_err(|err| err.message().to_string())?;
+        result.push(FirstCommit {
+            author: settings.control_chars.apply(&meta.author_name),
+            email: settings.control_chars.apply(&meta.author_email),
+            oid: info.oid.to_string(),
+            date: format_date(time, "%Y-%m-%d"),
+            summary: settings.control_chars.apply(&meta.summary),
+            branch: graph
+                .branch_of(info.oid)
+                .map(|branch| settings.control_chars.apply(branch.name.as_bytes())),
+            areas: changed_areas(graph, row)?
+                .iter()
+                .map(|area| settings.control_chars.apply(area.as_bytes()))
+                .collect(),
+            commits,
+        });
+    }
+    Ok(result)
+}
+
+/// The top-level directories and files changed by the commit at the given row,
+/// relative to its first parent.
+fn changed_areas(graph: &GitGraph, row: usize) -> Result<Vec<String>, String> {
+    let repository = graph.repository()?;
+    let commit = repository
+        .find_commit(graph.commits[row].oid)
+        .map_err(|err| err.message().to_string())?;
+    let parent_tree = match commit.parent(0) {
+        Ok(parent) => Some(parent.tree().map_err(|err| err.message().to_string())?),
+        Err(_) => None,
+    };
+    let tree = commit.tree().map_err(|err| err.message().to_string())?;
+    let diff = repository
+        .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)
+        .map_err(|err| err.message().to_string())?;
+    let paths = diff.deltas().filter_map(|delta| {
+        delta
+            .new_file()
+            .path()
+            .or_else(|| delta.old_file().path())
+            .map(|path| path.to_string_lossy().to_string())
+    });
+    Ok(areas(paths))
+}
+
+/// The distinct top-level areas of paths, directories with a trailing `/`, in order of
+/// first occurrence.
+fn areas(paths: impl Iterator<Item = String>) -> Vec<String> {
+    let mut areas: Vec<String> = vec![];
+    for path in paths {
+        let area = match path.split_once('/') {
+            Some((dir, _)) => format!("{}/", dir),
+            None => path,
+        };
+        if !areas.contains(&area) {
+            areas.push(area);
+        }
+    }
+    areas
+}
+
+/// Formats first commits as a timeline, one entry per author.
+pub fn print_first_commits(first_commits: &[FirstCommit]) -> String {
+    let mut out = String::new();
+    for (idx, first) in first_commits.iter().enumerate() {
+        if idx > 0 {
+            writeln!(out, "{:10} │", "").unwrap();
+        }
+        writeln!(out, "{} ● {} <{}>", first.date, first.author, first.email).unwrap();
+        writeln!(out, "{:10} │   {} {}", "", &first.oid[..7], first.summary).unwrap();
+        let mut details = vec![match &first.branch {
+            Some(branch) => format!("on {}", branch),
+            None => "on no branch".to_string(),
+        }];
+        if !first.areas.is_empty() {
+            let mut areas = first
+                .areas
+                .iter()
+                .take(MAX_AREAS)
+                .cloned()
+                .collect::<Vec<_>>();
+            if first.areas.len() > MAX_AREAS {
+                areas.push(format!("+{} more", first.areas.len() - MAX_AREAS));
+            }
+            details.push(format!("touching {}", areas.join(", ")));
+        }
+        details.push(format!("{} commit(s) in total", first.commits));
+        writeln!(out, "{:10} │   {}", "", details.join("; ")).unwrap();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::areas;
+
+    #[test]
+    fn areas_are_top_level_and_distinct() {
+        let paths = ["src/main.rs", "README.md", "src/graph.rs", "docs/manual.md"];
+        assert_eq!(
+            areas(paths.iter().map(|path| path.to_string())),
+            vec!["src/", "README.md", "docs/"]
+        );
+    }
+}
@@ -1,7 +1,7 @@
 //! Formatting of commits.
 
 use chrono::{FixedOffset, Local, TimeZone};
-use git2::{Commit, Time};
+use git2::{Commit, Oid, Time};
 use lazy_static::lazy_static;
 use std::fmt::Write;
 use std::str::FromStr;
@@ -9,7 +9,7 @@ use textwrap::Options;
 use yansi::Paint;
 
 /// Commit formatting options.
-#[derive(Ord, PartialOrd, Eq, PartialEq)]
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq)]
 pub enum CommitFormat {
     OneLine,
     Short,
@@ -18,6 +18,84 @@ pub enum CommitFormat {
     Format(String),
 }
 
+/// Handling of control characters and escape sequences in commit texts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ControlChars {
+    /// Print texts unmodified.
+    Raw,
+    /// Remove control characters and escape sequences.
+    Strip,
+    /// Replace each control character or escape sequence by a string.
+    Replace(String),
+}
+
+impl FromStr for ControlChars {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "raw" => Ok(ControlChars::Raw),
+            "strip" => Ok(ControlChars::Strip),
+            _ => match str.strip_prefix("replace:") {
+                Some(replacement) => Ok(ControlChars::Replace(replacement.to_string())),
+                None => Err(format!(
+                    "Unknown control character handling '{}'. Must be one of [raw|strip|replace:<string>]",
+                    str
+                )),
+            },
+        }
+    }
+}
+
+impl ControlChars {
+    /// Makes a (possibly non-UTF-8) text safe for printing.
+    ///
+    /// ANSI/VT escape sequences are treated as a single control character,
+    /// tabs are turned into a space.
+    pub fn apply(&self, text: &[u8]) -> String {
+        let text = String::from_utf8_lossy(text);
+        let replacement = match self {
+            ControlChars::Raw => return text.into_owned(),
+            ControlChars::Strip => "",
+            ControlChars::Replace(str) => &str[..],
+        };
+
+        let mut out = String::with_capacity(text.len());
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\t' => out.push(' '),
+                '\x1b' => {
+                    match chars.next() {
+                        // CSI: parameters, terminated by a byte in 0x40..=0x7e
+                        Some('[') => {
+                            for c in chars.by_ref() {
+                                if ('\x40'..='\x7e').contains(&c) {
+                                    break;
+                                }
+                            }
+                        }
+                        // OSC, DCS, etc.: terminated by BEL or ESC \
+                        Some(']' | 'P' | 'X' | '^' | '_') => {
+                            while let Some(c) = chars.next() {
+                                if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some())
+                                {
+                                    break;
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                    out.push_str(replacement);
+                }
+                c if c.is_control() => out.push_str(replacement),
+                c => out.push(c),
+            }
+        }
+        out
+    }
+}
+
 impl FromStr for CommitFormat {
     type Err = String;
 
@@ -82,6 +160,7 @@ pub fn format_commit(
     branches: String,
     wrapping: &Option<Options>,
     hash_color: Option<u8>,
+    control_chars: &ControlChars,
 ) -> Result<Vec<String>, String> {
     let mut replacements = vec![];
 
@@ -185,42 +264,26 @@ pub fn format_commit(
                     }
                     REFS => {
                         match mode {
-                            MODE_SPACE => {
-                                if !branches.is_empty() {
-                                    write!(out, " ").unwrap()
-                                }
+                            MODE_SPACE if !branches.is_empty() => write!(out, " ").unwrap(),
+                            MODE_PLUS if !branches.is_empty() => {
+                                add_line(&mut lines, &mut out, wrapping)
                             }
-                            MODE_PLUS => {
-                                if !branches.is_empty() {
-                                    add_line(&mut lines, &mut out, wrapping)
-                                }
-                            }
-                            MODE_MINUS => {
-                                if branches.is_empty() {
-                                    out = remove_empty_lines(&mut lines, out)
-                                }
+                            MODE_MINUS if branches.is_empty() => {
+                                out = remove_empty_lines(&mut lines, out)
                             }
                             _ => {}
                         }
                         write!(out, "{}", branches)
                     }
                     SUBJECT => {
-                        let summary = commit.summary().unwrap_or("");
+                        let summary = control_chars.apply(commit.summary_bytes().unwrap_or(b""));
                         match mode {
-                            MODE_SPACE => {
-                                if !summary.is_empty() {
-                                    write!(out, " ").unwrap()
-                                }
-                            }
-                            MODE_PLUS => {
-                                if !summary.is_empty() {
-                                    add_line(&mut lines, &mut out, wrapping)
-                                }
+                            MODE_SPACE if !summary.is_empty() => write!(out, " ").unwrap(),
+                            MODE_PLUS if !summary.is_empty() => {
+                                add_line(&mut lines, &mut out, wrapping)
                             }
-                            MODE_MINUS => {
-                                if summary.is_empty() {
-                                    out = remove_empty_lines(&mut lines, out)
-                                }
+                            MODE_MINUS if summary.is_empty() => {
+                                out = remove_empty_lines(&mut lines, out)
                             }
                             _ => {}
                         }
@@ -232,7 +295,7 @@ pub fn format_commit(
                             MODE_PLUS => add_line(&mut lines, &mut out, wrapping),
                             _ => {}
                         }
-                        write!(out, "{}", &commit.author().name().unwrap_or(""))
+                        write!(out, "{}", control_chars.apply(commit.author().name_bytes()))
                     }
                     AUTHOR_EMAIL => {
                         match mode {
@@ -240,7 +303,11 @@ pub fn format_commit(
                             MODE_PLUS => add_line(&mut lines, &mut out, wrapping),
                             _ => {}
                         }
-                        write!(out, "{}", &commit.author().email().unwrap_or(""))
+                        write!(
+                            out,
+                            "{}",
+                            control_chars.apply(commit.author().email_bytes())
+                        )
                     }
                     AUTHOR_DATE => {
                         match mode {
@@ -276,7 +343,11 @@ pub fn format_commit(
                             MODE_PLUS => add_line(&mut lines, &mut out, wrapping),
                             _ => {}
                         }
-                        write!(out, "{}", &commit.committer().name().unwrap_or(""))
+                        write!(
+                            out,
+                            "{}",
+                            control_chars.apply(commit.committer().name_bytes())
+                        )
                     }
                     COMMITTER_EMAIL => {
                         match mode {
@@ -284,7 +355,11 @@ pub fn format_commit(
                             MODE_PLUS => add_line(&mut lines, &mut out, wrapping),
                             _ => {}
                         }
-                        write!(out, "{}", &commit.committer().email().unwrap_or(""))
+                        write!(
+                            out,
+                            "{}",
+                            control_chars.apply(commit.committer().email_bytes())
+                        )
                     }
                     COMMITTER_DATE => {
                         match mode {
@@ -315,69 +390,45 @@ pub fn format_commit(
                         write!(out, "{}", format_relative_time(commit.committer().when()))
                     }
                     BODY => {
-                        let message = commit
-                            .message()
-                            .unwrap_or("")
-                            .lines()
-                            .collect::<Vec<&str>>();
+                        let message = String::from_utf8_lossy(commit.message_bytes());
+                        let message = message.lines().collect::<Vec<&str>>();
 
                         let num_parts = message.len();
                         match mode {
-                            MODE_SPACE => {
-                                if num_parts > 2 {
-                                    write!(out, " ").unwrap()
-                                }
-                            }
-                            MODE_PLUS => {
-                                if num_parts > 2 {
-                                    add_line(&mut lines, &mut out, wrapping)
-                                }
-                            }
-                            MODE_MINUS => {
-                                if num_parts <= 2 {
-                                    out = remove_empty_lines(&mut lines, out)
-                                }
+                            MODE_SPACE if num_parts > 2 => write!(out, " ").unwrap(),
+                            MODE_PLUS if num_parts > 2 => add_line(&mut lines, &mut out, wrapping),
+                            MODE_MINUS if num_parts <= 2 => {
+                                out = remove_empty_lines(&mut lines, out)
                             }
                             _ => {}
                         }
                         for (cnt, line) in message.iter().enumerate() {
                             if cnt > 1 && (cnt < num_parts - 1 || !line.is_empty()) {
-                                write!(out, "{}", line).unwrap();
+                                write!(out, "{}", control_chars.apply(line.as_bytes())).unwrap();
                                 add_line(&mut lines, &mut out, wrapping);
                             }
                         }
                         Ok(())
                     }
                     BODY_RAW => {
-                        let message = commit
-                            .message()
-                            .unwrap_or("")
-                            .lines()
-                            .collect::<Vec<&str>>();
+                        let message = String::from_utf8_lossy(commit.message_bytes());
+                        let message = message.lines().collect::<Vec<&str>>();
 
                         let num_parts = message.len();
 
                         match mode {
-                            MODE_SPACE => {
-                                if !message.is_empty() {
-                                    write!(out, " ").unwrap()
-                                }
-                            }
-                            MODE_PLUS => {
-                                if !message.is_empty() {
-                                    add_line(&mut lines, &mut out, wrapping)
-                                }
+                            MODE_SPACE if !message.is_empty() => write!(out, " ").unwrap(),
+                            MODE_PLUS if !message.is_empty() => {
+                                add_line(&mut lines, &mut out, wrapping)
                             }
-                            MODE_MINUS => {
-                                if message.is_empty() {
-                                    out = remove_empty_lines(&mut lines, out)
-                                }
+                            MODE_MINUS if message.is_empty() => {
+                                out = remove_empty_lines(&mut lines, out)
                             }
                             _ => {}
                         }
                         for (cnt, line) in message.iter().enumerate() {
                             if cnt < num_parts - 1 || !line.is_empty() {
-                                write!(out, "{}", line).unwrap();
+                                write!(out, "{}", control_chars.apply(line.as_bytes())).unwrap();
                                 add_line(&mut lines, &mut out, wrapping);
                             }
                         }
@@ -397,26 +448,24 @@ pub fn format_commit(
     Ok(lines)
 }
 
-/// Format a commit for `CommitFormat::OneLine`.
+/// Format a commit for `CommitFormat::OneLine`, from its id and raw summary.
 pub fn format_oneline(
-    commit: &Commit,
+    id: Oid,
+    summary: &[u8],
     branches: String,
     wrapping: &Option<Options>,
     hash_color: Option<u8>,
+    control_chars: &ControlChars,
 ) -> Vec<String> {
     let mut out = String::new();
     if let Some(color) = hash_color {
-        write!(
-            out,
-            "{}",
-            Paint::fixed(color, &commit.id().to_string()[..7])
-        )
+        write!(out, "{}", Paint::fixed(color, &id.to_string()[..7]))
     } else {
-        write!(out, "{}", &commit.id().to_string()[..7])
+        write!(out, "{}", &id.to_string()[..7])
     }
     .unwrap();
 
-    write!(out, "{} {}", branches, commit.summary().unwrap_or("")).unwrap();
+    write!(out, "{} {}", branches, control_chars.apply(summary)).unwrap();
 
     if let Some(wrap) = wrapping {
         textwrap::fill(&out, wrap)
@@ -435,11 +484,28 @@ pub fn format(
     wrapping: &Option<Options>,
     hash_color: Option<u8>,
     format: &CommitFormat,
+    control_chars: &ControlChars,
 ) -> Result<Vec<String>, String> {
     match format {
-        CommitFormat::OneLine => return Ok(format_oneline(commit, branches, wrapping, hash_color)),
+        CommitFormat::OneLine => {
+            return Ok(format_oneline(
+                commit.id(),
+                commit.summary_bytes().unwrap_or(b""),
+                branches,
+                wrapping,
+                hash_color,
+                control_chars,
+            ))
+        }
         CommitFormat::Format(format) => {
-            return format_commit(format, commit, branches, wrapping, hash_color)
+            return format_commit(
+                format,
+                commit,
+                branches,
+                wrapping,
+                hash_color,
+                control_chars,
+            )
         }
         _ => {}
     }
@@ -473,8 +539,8 @@ pub fn format(
     write!(
         out,
         "Author: {} <{}>",
-        commit.author().name().unwrap_or(""),
-        commit.author().email().unwrap_or("")
+        control_chars.apply(commit.author().name_bytes()),
+        control_chars.apply(commit.author().email_bytes())
     )
     .map_err(|err| err.to_string())?;
     append_wrapped(&mut out_vec, out, wrapping);
@@ -484,8 +550,8 @@ pub fn format(
         write!(
             out,
             "Commit: {} <{}>",
-            commit.committer().name().unwrap_or(""),
-            commit.committer().email().unwrap_or("")
+            control_chars.apply(commit.committer().name_bytes()),
+            control_chars.apply(commit.committer().email_bytes())
         )
         .map_err(|err| err.to_string())?;
         append_wrapped(&mut out_vec, out, wrapping);
@@ -506,18 +572,25 @@ pub fn format(
         out_vec.push("".to_string());
         append_wrapped(
             &mut out_vec,
-            format!("    {}", commit.summary().unwrap_or("")),
+            format!(
+                "    {}",
+                control_chars.apply(commit.summary_bytes().unwrap_or(b""))
+            ),
             wrapping,
         );
         out_vec.push("".to_string());
     } else {
         out_vec.push("".to_string());
         let mut add_line = true;
-        for line in commit.message().unwrap_or("").lines() {
+        for line in String::from_utf8_lossy(commit.message_bytes()).lines() {
             if line.is_empty() {
                 out_vec.push(line.to_string());
             } else {
-                append_wrapped(&mut out_vec, format!("    {}", line), wrapping);
+                append_wrapped(
+                    &mut out_vec,
+                    format!("    {}", control_chars.apply(line.as_bytes())),
+                    wrapping,
+                );
             }
             add_line = !line.trim().is_empty();
         }
@@ -599,3 +672,40 @@ fn remove_empty_lines(lines: &mut Vec<String>, mut line: String) -> String {
     }
     line
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ControlChars;
+    use std::str::FromStr;
+
+    #[test]
+    fn control_chars() {
+        let text = b"a\tb\x1b[31mred\x1b[0m\x1b]0;title\x07c\rd\xffe";
+
+        assert_eq!(
+            ControlChars::Replace("?".to_string()).apply(text),
+            "a b?red??c?d\u{fffd}e"
+        );
+        assert_eq!(ControlChars::Strip.apply(text), "a bredcd\u{fffd}e");
+        assert_eq!(
+            ControlChars::Raw.apply(b"a\tb\x1b[0m"),
+            "a\tb\x1b[0m".to_string()
+        );
+    }
+
+    #[test]
+    fn parse_control_chars() {
+        assert_eq!(ControlChars::from_str("raw"), Ok(ControlChars::Raw));
+        assert_eq!(ControlChars::from_str("strip"), Ok(ControlChars::Strip));
+        assert_eq!(
+            ControlChars::from_str("replace:?"),
+            Ok(ControlChars::Replace("?".to_string()))
+        );
+        assert_eq!(
+            ControlChars::from_str("replace:"),
+            Ok(ControlChars::Replace(String::new()))
+        );
+        assert!(ControlChars::from_str("rwa").is_err());
+        assert!(ControlChars::from_str("?").is_err());
+    }
+}
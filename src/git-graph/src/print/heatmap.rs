@@ -0,0 +1,386 @@
+//! Activity heatmaps: commit counts per author or branch and time bucket.
+
+use crate::graph::GitGraph;
+use crate::settings::Settings;
+use chrono::{Datelike, Duration, FixedOffset, NaiveDate, TimeZone};
+use git2::Time;
+use itertools::Itertools;
+use serde_derive::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// What the rows of a heatmap are.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeatmapRows {
+    Author,
+    /// The branch a commit is attributed to, which is kept after merges
+    Branch,
+}
+
+impl FromStr for HeatmapRows {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "author" => Ok(HeatmapRows::Author),
+            "branch" => Ok(HeatmapRows::Branch),
+            _ => Err(format!(
+                "Unknown heatmap rows '{}'. Must be one of [author|branch]",
+                str
+            )),
+        }
+    }
+}
+
+/// The time span of a heatmap column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bucket {
+    Day,
+    /// ISO week, starting on Monday
+    Week,
+    Month,
+}
+
+impl FromStr for Bucket {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "day" => Ok(Bucket::Day),
+            "week" => Ok(Bucket::Week),
+            "month" => Ok(Bucket::Month),
+            _ => Err(format!(
+                "Unknown bucket '{}'. Must be one of [day|week|month]",
+                str
+            )),
+        }
+    }
+}
+
+impl Bucket {
+    /// The first day of the bucket containing the given day.
+    fn start(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Bucket::Day => date,
+            Bucket::Week => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+            Bucket::Month => date.with_day(1).unwrap(),
+        }
+    }
+
+    /// The first day of the bucket following the bucket starting at the given day.
+    fn next(&self, start: NaiveDate) -> NaiveDate {
+        match self {
+            Bucket::Day => start + Duration::days(1),
+            Bucket::Week => start + Duration::days(7),
+            Bucket::Month => match start.month() {
+                12 => NaiveDate::from_ymd_opt(start.year() + 1, 1, 1).unwrap(),
+                month => NaiveDate::from_ymd_opt(start.year(), month + 1, 1).unwrap(),
+            },
+        }
+    }
+
+    /// The label of the bucket starting at the given day.
+    fn label(&self, start: NaiveDate) -> String {
+        match self {
+            Bucket::Day => start.format("%Y-%m-%d"),
+            Bucket::Week => start.format("%G-W%V"),
+            Bucket::Month => start.format("%Y-%m"),
+        }
+        .to_string()
+    }
+}
+
+/// Output formats of heatmaps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeatmapFormat {
+    Csv,
+    Json,
+    /// A standalone HTML page with a colored table
+    Html,
+}
+
+impl FromStr for HeatmapFormat {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "csv" => Ok(HeatmapFormat::Csv),
+            "json" => Ok(HeatmapFormat::Json),
+            "html" => Ok(HeatmapFormat::Html),
+            _ => Err(format!(
+                "Unknown heatmap format '{}'. Must be one of [csv|json|html]",
+                str
+            )),
+        }
+    }
+}
+
+/// A row of a heatmap.
+#[derive(Serialize)]
+pub struct HeatmapRow {
+    /// Author or branch name
+    pub name: String,
+    /// Commit counts per bucket
+    pub counts: Vec<usize>,
+    pub total: usize,
+}
+
+/// Commit counts per author or branch (rows) and time bucket (columns).
+#[derive(Serialize)]
+pub struct Heatmap {
+    /// Labels of all buckets from the oldest to the newest commit, without gaps
+    pub buckets: Vec<String>,
+    /// Rows, most active first
+    pub rows: Vec<HeatmapRow>,
+}
+
+/// Counts the commits of a graph by author date. Commits are credited to the branch
+/// they are attributed to in the graph, not to the branch they were merged into.
+pub fn heatmap(
+    graph: &GitGraph,
+    settings: &Settings,
+    rows: HeatmapRows,
+    bucket: Bucket,
+) -> Result<Heatmap, String> {
+    let mut entries = vec![];
+    for info in &graph.commits {
+        let meta = graph
+            .metadata(info.oid)
+            .map_err(|err| err.message().to_string())?;
+        let name = match rows {
+            HeatmapRows::Author => settings.control_chars.apply(&meta.author_name),
+            HeatmapRows::Branch => graph
+                .branch_of(info.oid)
+                .map_or("(no branch)".to_string(), |branch| {
+                    settings.control_chars.apply(branch.name.as_bytes())
+                }),
+        };
+        let date = local_date(meta.author_time)
+            .ok_or_else(|| format!("Commit {} has an invalid author date", info.oid))?;
+        entries.push((name, bucket.start(date)));
+    }
+
+    let (Some(first), Some(last)) = (
+        entries.iter().map(|(_, start)| *start).min(),
+        entries.iter().map(|(_, start)| *start).max(),
+    ) else {
+        return Ok(Heatmap {
+            buckets: vec![],
+            rows: vec![],
+        });
+    };
+    let mut starts = vec![];
+    let mut start = first;
+    while start <= last {
+        starts.push(start);
+        start = bucket.next(start);
+    }
+    let columns: HashMap<NaiveDate, usize> = starts
+        .iter()
+        .enumerate()
+        .map(|(idx, start)| (*start, idx))
+        .collect();
+
+    let mut counts: HashMap<String, Vec<usize>> = HashMap::new();
+    for (name, start) in entries {
+        counts.entry(name).or_insert_with(|| vec![0; starts.len()])[columns[&start]] += 1;
+    }
+    let mut rows: Vec<HeatmapRow> = counts
+        .into_iter()
+        .map(|(name, counts)| HeatmapRow {
+            total: counts.iter().sum(),
+            name,
+            counts,
+        })
+        .collect();
+    rows.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.name.cmp(&b.name)));
+
+    Ok(Heatmap {
+        buckets: starts
+            .into_iter()
+            .map(|start| bucket.label(start))
+            .collect(),
+        rows,
+    })
+}
+
+/// The day of a time, in the time zone it was recorded in, if it is a valid date.
+fn local_date(time: Time) -> Option<NaiveDate> {
+    FixedOffset::east_opt(time.offset_minutes() * 60)
+        .and_then(|offset| offset.timestamp_opt(time.seconds(), 0).single())
+        .map(|date| date.date_naive())
+}
+
+/// Formats a heatmap in the given format.
+pub fn print_heatmap(
+    heatmap: &Heatmap,
+    rows: HeatmapRows,
+    format: HeatmapFormat,
+) -> Result<String, String> {
+    let header = match rows {
+        HeatmapRows::Author => "author",
+        HeatmapRows::Branch => "branch",
+    };
+    match format {
+        HeatmapFormat::Csv => Ok(print_csv(heatmap, header)),
+        HeatmapFormat::Json => serde_json::to_string_pretty(heatmap)
+            .map(|json| json + "\n")
+            .map_err(|err| err.to_string()),
+        HeatmapFormat::Html => Ok(print_html(heatmap, header)),
+    }
+}
+
+fn print_csv(heatmap: &Heatmap, header: &str) -> String {
+    let quote = |field: &str| {
+        if field.contains([',', '"', '\n']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        }
+    };
+    let mut out = String::new();
+    let buckets = heatmap.buckets.iter().map(String::as_str);
+    writeln!(
+        out,
+        "{}",
+        [header]
+            .into_iter()
+            .chain(buckets)
+            .chain(["total"])
+            .join(",")
+    )
+    .unwrap();
+    for row in &heatmap.rows {
+        let counts = row.counts.iter().map(usize::to_string);
+        writeln!(
+            out,
+            "{}",
+            [quote(&row.name)]
+                .into_iter()
+                .chain(counts)
+                .chain([row.total.to_string()])
+                .join(",")
+        )
+        .unwrap();
+    }
+    out
+}
+
+fn print_html(heatmap: &Heatmap, header: &str) -> String {
+    let escape = |text: &str| {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    };
+    let max = heatmap
+        .rows
+        .iter()
+        .flat_map(|row| row.counts.iter())
+        .max()
+        .copied()
+        .unwrap_or(0)
+        .max(1);
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">"
+    )
+    .unwrap();
+    writeln!(out, "<title>Commit activity by {}</title>", header).unwrap();
+    writeln!(
+        out,
+        "<style>\n\
+         table {{ border-collapse: collapse; font-family: sans-serif; font-size: 12px; }}\n\
+         th, td {{ border: 1px solid #ddd; padding: 2px 6px; text-align: right; }}\n\
+         th:first-child {{ text-align: left; }}\n\
+         </style>\n</head>\n<body>\n<table>"
+    )
+    .unwrap();
+    write!(out, "<tr><th>{}</th>", header).unwrap();
+    for bucket in &heatmap.buckets {
+        write!(out, "<th>{}</th>", bucket).unwrap();
+    }
+    writeln!(out, "<th>total</th></tr>").unwrap();
+    for row in &heatmap.rows {
+        write!(out, "<tr><th>{}</th>", escape(&row.name)).unwrap();
+        for count in &row.counts {
+            if *count == 0 {
+                write!(out, "<td></td>").unwrap();
+            } else {
+                write!(
+                    out,
+                    "<td style=\"background: rgba(33, 110, 57, {:.2})\">{}</td>",
+                    0.15 + 0.85 * *count as f32 / max as f32,
+                    count
+                )
+                .unwrap();
+            }
+        }
+        writeln!(out, "<td>{}</td></tr>", row.total).unwrap();
+    }
+    writeln!(out, "</table>\n</body>\n</html>").unwrap();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{settings, TestRepo};
+    use git2::ObjectType;
+
+    #[test]
+    fn buckets_start_label_and_advance() {
+        let date = NaiveDate::from_ymd_opt(2021, 1, 1).unwrap();
+        let week = Bucket::Week.start(date);
+        assert_eq!(week, NaiveDate::from_ymd_opt(2020, 12, 28).unwrap());
+        assert_eq!(Bucket::Week.label(week), "2020-W53");
+        assert_eq!(Bucket::Week.label(Bucket::Week.next(week)), "2021-W01");
+
+        let month = Bucket::Month.start(NaiveDate::from_ymd_opt(2021, 1, 31).unwrap());
+        assert_eq!(Bucket::Month.label(month), "2021-01");
+        assert_eq!(Bucket::Month.label(Bucket::Month.next(month)), "2021-02");
+        assert_eq!(Bucket::Day.label(Bucket::Day.next(date)), "2021-01-02");
+    }
+
+    #[test]
+    fn heatmap_of_empty_selection_and_invalid_dates() {
+        let repo = TestRepo::bare("git-graph-heatmap");
+        let settings = settings();
+        let graph = GitGraph::new(&repo, &settings, None).unwrap();
+        let empty = heatmap(&graph, &settings, HeatmapRows::Author, Bucket::Week).unwrap();
+        assert_eq!(
+            print_heatmap(&empty, HeatmapRows::Author, HeatmapFormat::Csv).unwrap(),
+            "author,total\n"
+        );
+
+        let c1 = repo.commit("Initial commit", &[]);
+        repo.reference("refs/heads/main", c1, true, "").unwrap();
+        let graph = GitGraph::new(&repo, &settings, None).unwrap();
+        let map = heatmap(&graph, &settings, HeatmapRows::Branch, Bucket::Day).unwrap();
+        assert_eq!(
+            print_heatmap(&map, HeatmapRows::Branch, HeatmapFormat::Csv).unwrap(),
+            "branch,1970-01-01,total\nmain,1,1\n"
+        );
+
+        // Written as a raw object, as signatures can't hold dates out of chrono's range
+        let tree = repo.find_commit(c1).unwrap().tree_id();
+        let raw = format!(
+            "tree {}\nparent {}\nauthor a <a@x> 99999999999999 +0000\n\
+             committer a <a@x> 0 +0000\n\nFar future\n",
+            tree, c1
+        );
+        let c2 = repo
+            .odb()
+            .unwrap()
+            .write(ObjectType::Commit, raw.as_bytes())
+            .unwrap();
+        repo.reference("refs/heads/main", c2, true, "").unwrap();
+        let graph = GitGraph::new(&repo, &settings, None).unwrap();
+        let err = heatmap(&graph, &settings, HeatmapRows::Author, Bucket::Week)
+            .err()
+            .unwrap();
+        assert_eq!(err, format!("Commit {} has an invalid author date", c2));
+    }
+}
@@ -0,0 +1,311 @@
+//! Create graphs in JSON format, for scripts and other tools.
+
+use crate::graph::{GitGraph, HeadInfo, SignatureStatus};
+use crate::print::stats::{layout_stats, LayoutStats};
+use crate::print::summary::{summarize, Summary};
+use crate::print::Rewrite;
+use crate::settings::{ReviewStatus, Settings};
+use git2::Oid;
+use serde_derive::Serialize;
+
+/// A commit row of the JSON output.
+#[derive(Serialize)]
+struct JsonCommit {
+    /// Row index, as accepted by `@N` addresses
+    row: usize,
+    oid: String,
+    summary: String,
+    parents: Vec<String>,
+    branch: Option<String>,
+    column: Option<usize>,
+    branches: Vec<String>,
+    tags: Vec<String>,
+    is_head: bool,
+    /// Names of the other worktrees whose HEAD is at the commit
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    worktrees: Vec<String>,
+    is_bookmarked: bool,
+    /// Whether the commit is a stash entry or the index commit of one
+    is_stash: bool,
+    /// Whether the commit is reachable only from reflogs
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    is_unreachable: bool,
+    /// Whether the commit's parents are missing, at the boundary of a shallow clone
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    shallow_boundary: bool,
+    /// Whether the commit's parents are grafted by `info/grafts` or a replace ref
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    grafted: bool,
+    /// Names of the branches containing the commit, if counted
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    containing_branches: Vec<String>,
+    /// The commit a `fixup!` or `squash!` commit will be squashed into, if computed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fixup_of: Option<String>,
+    /// Whether the commit is a merge base of the refs given by `--merge-base`
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    is_merge_base: bool,
+    /// Whether the commit is reachable from neither of the refs given by `--merge-base`
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    is_dimmed: bool,
+    /// Whether the commit matches the pickaxe search, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pickaxe_match: Option<bool>,
+    /// The old commit, if the commit was created by a rewrite given by a commit map
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rewritten_from: Option<String>,
+    /// Whether the old commit's parents map to the commit's parents, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parents_match: Option<bool>,
+    /// The new commit if rewritten by a commit map, a zero id if pruned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rewritten_to: Option<String>,
+    /// The single commit brought in by a merge collapsed into one node
+    #[serde(skip_serializing_if = "Option::is_none")]
+    collapsed: Option<String>,
+    /// The branch brought in by a merge, if only first parents are followed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    merged_from: Option<String>,
+    /// Number of commits hidden between the commit and each of its parents
+    #[serde(skip_serializing_if = "is_zero")]
+    hidden: [usize; 2],
+    /// Large blobs added by the commit, if a large blob threshold is set
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    large_blobs: Vec<JsonBlob>,
+    /// Submodules moved to another commit, if submodules are shown
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    submodule_bumps: Vec<JsonBump>,
+    /// Text of the commit's notes, if notes are shown
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    notes: Vec<String>,
+    /// One of `signed`, `unsigned`, `bad` and `unverifiable`, if signatures are verified
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signature: Option<&'static str>,
+    /// `reviewed` or `unreviewed`, if review status is shown
+    #[serde(skip_serializing_if = "Option::is_none")]
+    review: Option<&'static str>,
+}
+
+/// A large blob added by a commit, in the JSON output.
+#[derive(Serialize)]
+struct JsonBlob {
+    path: String,
+    size: u64,
+}
+
+/// A submodule's new commit, in the JSON output.
+#[derive(Serialize)]
+struct JsonBump {
+    path: String,
+    oid: String,
+}
+
+/// A branch of the JSON output.
+#[derive(Serialize)]
+struct JsonBranch {
+    name: String,
+    column: Option<usize>,
+    color: String,
+    is_remote: bool,
+    is_merged: bool,
+    /// Whether this is the default branch named by `origin/HEAD`, or its local branch
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    is_default: bool,
+    /// Whether its remote's HEAD points to this remote branch
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    is_remote_head: bool,
+    /// Whether the tip commit subject marks the branch as work in progress
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    is_wip: bool,
+    /// Commits ahead of the upstream branch, for local branches with an upstream
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ahead: Option<usize>,
+    /// Commits behind the upstream branch, for local branches with an upstream
+    #[serde(skip_serializing_if = "Option::is_none")]
+    behind: Option<usize>,
+}
+
+/// The JSON output.
+#[derive(Serialize)]
+struct JsonGraph {
+    commits: Vec<JsonCommit>,
+    branches: Vec<JsonBranch>,
+    summary: Summary,
+    stats: LayoutStats,
+    /// Ids of all bookmarked commits, including those not shown
+    bookmarks: Vec<String>,
+    /// The branch HEAD names, if it has no commits yet
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unborn_branch: Option<String>,
+}
+
+/// Creates a JSON representation of a graph, with the given bookmarked commits.
+pub fn print_json(
+    graph: &GitGraph,
+    settings: &Settings,
+    bookmarks: &[Oid],
+) -> Result<String, String> {
+    let commits = graph
+        .commits
+        .iter()
+        .enumerate()
+        .map(|(row, info)| {
+            let meta = graph
+                .metadata(info.oid)
+                .map_err(|err| err.message().to_string())?;
+            let trace = info.branch_trace.map(|trace| &graph.all_branches[trace]);
+            let (rewritten_from, parents_match, rewritten_to) =
+                match super::rewrite_of(graph, info, settings) {
+                    Some(Rewrite::From { old, parents_match }) => {
+                        (Some(old.to_string()), parents_match, None)
+                    }
+                    Some(Rewrite::To(new)) => {
+                        let new = new.unwrap_or_else(Oid::zero);
+                        (None, None, Some(new.to_string()))
+                    }
+                    None => (None, None, None),
+                };
+            Ok(JsonCommit {
+                row,
+                oid: info.oid.to_string(),
+                summary: settings.control_chars.apply(&meta.summary),
+                parents: info.parents.iter().flatten().map(Oid::to_string).collect(),
+                branch: trace.map(|branch| branch.name.clone()),
+                column: trace.and_then(|branch| branch.visual.column),
+                branches: info
+                    .branches
+                    .iter()
+                    .map(|idx| graph.all_branches[*idx].name.clone())
+                    .collect(),
+                tags: info
+                    .tags
+                    .iter()
+                    .map(|idx| graph.all_branches[*idx].name[5..].to_string())
+                    .collect(),
+                is_head: graph.head.as_ref().is_some_and(|head| head.is_at(info.oid)),
+                worktrees: graph
+                    .worktrees
+                    .iter()
+                    .filter(|worktree| worktree.head.is_at(info.oid))
+                    .map(|worktree| worktree.name.clone())
+                    .collect(),
+                is_bookmarked: bookmarks.contains(&info.oid),
+                is_stash: info.is_stash,
+                is_unreachable: info.is_unreachable,
+                shallow_boundary: info.shallow_boundary,
+                grafted: info.grafted,
+                containing_branches: graph
+                    .containing_branches_of(info.oid)
+                    .iter()
+                    .map(|idx| graph.all_branches[*idx].name.clone())
+                    .collect(),
+                fixup_of: info.fixup_of.map(|oid| oid.to_string()),
+                is_merge_base: info.is_merge_base,
+                is_dimmed: info.is_dimmed,
+                pickaxe_match: settings.pickaxe.as_ref().map(|_| info.pickaxe_match),
+                rewritten_from,
+                parents_match,
+                rewritten_to,
+                collapsed: info.collapsed.map(|oid| oid.to_string()),
+                merged_from: info.merged_from.clone(),
+                hidden: info.hidden,
+                large_blobs: graph
+                    .large_blobs_of(info.oid)
+                    .iter()
+                    .map(|blob| JsonBlob {
+                        path: blob.path.clone(),
+                        size: blob.size,
+                    })
+                    .collect(),
+                submodule_bumps: graph
+                    .submodule_bumps_of(info.oid)
+                    .iter()
+                    .map(|bump| JsonBump {
+                        path: bump.path.clone(),
+                        oid: bump.oid.to_string(),
+                    })
+                    .collect(),
+                notes: graph.notes_of(info.oid).to_vec(),
+                signature: info.signature.map(|status| match status {
+                    SignatureStatus::Signed => "signed",
+                    SignatureStatus::Unsigned => "unsigned",
+                    SignatureStatus::BadSignature => "bad",
+                    SignatureStatus::Unverifiable => "unverifiable",
+                }),
+                review: settings
+                    .reviews
+                    .as_ref()
+                    .map(|reviews| match reviews.status(info.oid) {
+                        ReviewStatus::Reviewed => "reviewed",
+                        ReviewStatus::Unreviewed => "unreviewed",
+                    }),
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let branches = graph
+        .all_branches
+        .iter()
+        .filter(|branch| !branch.is_tag)
+        .map(|branch| JsonBranch {
+            name: branch.name.clone(),
+            column: branch.visual.column,
+            color: branch.visual.svg_color.clone(),
+            is_remote: branch.is_remote,
+            is_merged: branch.is_merged,
+            is_default: branch.is_default,
+            is_remote_head: branch.is_remote_head,
+            is_wip: branch.is_wip,
+            ahead: branch.ahead_behind.map(|(ahead, _)| ahead),
+            behind: branch.ahead_behind.map(|(_, behind)| behind),
+        })
+        .collect();
+
+    let summary = summarize(graph)?;
+
+    serde_json::to_string_pretty(&JsonGraph {
+        commits,
+        branches,
+        summary,
+        stats: layout_stats(graph),
+        bookmarks: bookmarks.iter().map(Oid::to_string).collect(),
+        unborn_branch: match &graph.head {
+            Some(HeadInfo::Unborn { name }) => Some(name.clone()),
+            Some(HeadInfo::Detached { .. } | HeadInfo::Branch { .. }) | None => None,
+        },
+    })
+    .map_err(|err| err.to_string())
+}
+
+fn is_zero(hidden: &[usize; 2]) -> bool {
+    *hidden == [0, 0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::print_json;
+    use crate::graph::GitGraph;
+    use crate::testing::{settings, TestRepo};
+    use git2::Oid;
+
+    #[test]
+    fn bookmarks_of_shown_and_hidden_commits() {
+        let repo = TestRepo::bare("git-graph-json-bookmarks");
+        let first = repo.commit("first", &[]);
+        let second = repo.commit("second", &[first]);
+        repo.reference("refs/heads/main", second, true, "").unwrap();
+        let settings = settings();
+        let graph = GitGraph::new(&repo, &settings, None).unwrap();
+
+        let hidden = Oid::from_str("0123456789012345678901234567890123456789").unwrap();
+        let json = print_json(&graph, &settings, &[first, hidden]).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let bookmarks = json["bookmarks"].as_array().unwrap();
+        assert_eq!(bookmarks.len(), 2);
+        assert_eq!(bookmarks[1], hidden.to_string());
+        for commit in json["commits"].as_array().unwrap() {
+            let is_first = commit["oid"] == first.to_string();
+            assert_eq!(commit["is_bookmarked"], is_first);
+        }
+    }
+}
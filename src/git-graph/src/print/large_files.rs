@@ -0,0 +1,152 @@
+//! Report on large blobs added to a repository, for repository size investigations.
+
+use crate::graph::GitGraph;
+use crate::print::format::format_date;
+use crate::settings::Settings;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Binary size units, with their factors.
+const UNITS: [(&str, u64); 4] = [("G", 1 << 30), ("M", 1 << 20), ("K", 1 << 10), ("", 1)];
+
+/// Parses a size in bytes, with an optional binary unit suffix, like `500K`, `1M` or `2G`.
+pub fn parse_size(str: &str) -> Result<u64, String> {
+    let str = str.trim();
+    let upper = str.to_ascii_uppercase();
+    let number = upper
+        .strip_suffix('B')
+        .filter(|number| !number.is_empty())
+        .unwrap_or(&upper);
+    UNITS
+        .iter()
+        .find_map(|(unit, factor)| {
+            number
+                .strip_suffix(unit)
+                .and_then(|number| number.trim().parse::<f64>().ok())
+                .filter(|number| *number >= 0.0)
+                .map(|number| (number * *factor as f64) as u64)
+        })
+        .ok_or_else(|| {
+            format!(
+                "Can't parse size '{}'. Expected e.g. '500K', '1M' or '2G'",
+                str
+            )
+        })
+}
+
+/// Formats a size in bytes with a binary unit, like `1.5 MiB`.
+pub fn format_size(size: u64) -> String {
+    match UNITS
+        .iter()
+        .find(|(_, factor)| size >= *factor && *factor > 1)
+    {
+        Some((unit, factor)) => format!("{:.1} {}iB", size as f64 / *factor as f64, unit),
+        None => format!("{} B", size),
+    }
+}
+
+/// Lists the commits adding large blobs, largest first, with totals per branch.
+/// Requires [Settings::large_blob_threshold] to be set when building the graph.
+pub fn large_files_report(graph: &GitGraph, settings: &Settings) -> Result<String, String> {
+    let threshold = settings.large_blob_threshold.unwrap_or(0);
+    let mut commits: Vec<_> = graph
+        .commits
+        .iter()
+        .filter(|info| graph.large_blobs.contains_key(&info.oid))
+        .map(|info| {
+            let blobs = graph.large_blobs_of(info.oid);
+            (info, blobs.iter().map(|blob| blob.size).sum::<u64>())
+        })
+        .collect();
+    commits.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+
+    let mut out = String::new();
+    let blobs: usize = graph.large_blobs.values().map(Vec::len).sum();
+    let total: u64 = commits.iter().map(|(_, size)| size).sum();
+    writeln!(
+        out,
+        "{} commit(s) add {} blob(s) of at least {}, {} in total",
+        commits.len(),
+        blobs,
+        format_size(threshold),
+        format_size(total)
+    )
+    .unwrap();
+    if commits.is_empty() {
+        return Ok(out);
+    }
+
+    let branch_name = |oid| {
+        graph
+            .branch_of(oid)
+            .map_or("(no branch)".to_string(), |branch| {
+                settings.control_chars.apply(branch.name.as_bytes())
+            })
+    };
+    let mut by_branch: HashMap<String, (u64, usize)> = HashMap::new();
+    for (info, size) in &commits {
+        let entry = by_branch.entry(branch_name(info.oid)).or_default();
+        entry.0 += size;
+        entry.1 += 1;
+    }
+    let mut by_branch: Vec<_> = by_branch.into_iter().collect();
+    by_branch.sort_by(|a, b| b.1 .0.cmp(&a.1 .0).then_with(|| a.0.cmp(&b.0)));
+    let width = by_branch
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    writeln!(out, "\nBy branch:").unwrap();
+    for (name, (size, count)) in &by_branch {
+        writeln!(
+            out,
+            "  {:<width$}  {:>10} in {} commit(s)",
+            name,
+            format_size(*size),
+            count,
+            width = width
+        )
+        .unwrap();
+    }
+
+    writeln!(out, "\nCommits, largest first:").unwrap();
+    for (info, size) in &commits {
+        let meta = graph
+            .metadata(info.oid)
+            .map_err(|err| err.message().to_string())?;
+        writeln!(
+            out,
+            "  {:>10}  {} {} {}  {}",
+            format_size(*size),
+            &info.oid.to_string()[..7],
+            format_date(meta.author_time, "%Y-%m-%d"),
+            branch_name(info.oid),
+            settings.control_chars.apply(&meta.summary)
+        )
+        .unwrap();
+        for blob in graph.large_blobs_of(info.oid) {
+            let path = settings.control_chars.apply(blob.path.as_bytes());
+            writeln!(out, "  {:>10}    {}", format_size(blob.size), path).unwrap();
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_size, parse_size};
+
+    #[test]
+    fn parse_and_format_sizes() {
+        assert_eq!(parse_size("1000").unwrap(), 1000);
+        assert_eq!(parse_size("500k").unwrap(), 500 * 1024);
+        assert_eq!(parse_size("1.5MB").unwrap(), 3 << 19);
+        assert_eq!(parse_size("2G").unwrap(), 2 << 30);
+        assert!(parse_size("1T").is_err());
+        assert!(parse_size("-1M").is_err());
+
+        assert_eq!(format_size(1000), "1000 B");
+        assert_eq!(format_size(3 << 19), "1.5 MiB");
+        assert_eq!(format_size(2 << 30), "2.0 GiB");
+    }
+}
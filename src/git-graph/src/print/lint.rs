@@ -0,0 +1,181 @@
+//! Check commit messages against configured rules, grouped by branch and author.
+
+use crate::graph::GitGraph;
+use crate::settings::{LintRules, Settings};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Name of the subject length rule.
+const SUBJECT_LENGTH: &str = "subject-length";
+/// Name of the subject pattern rule, e.g. a conventional commit type.
+const SUBJECT_PATTERN: &str = "subject-pattern";
+/// Name of the ticket reference rule.
+const TICKET_REFERENCE: &str = "ticket-reference";
+
+/// Number of commits checked and violating, per branch or author.
+#[derive(Default)]
+struct Tally {
+    checked: usize,
+    violating: usize,
+}
+
+/// Checks the messages of all commits of the graph, and reports the violations
+/// per branch, followed by the number of violating commits per author.
+pub fn lint(graph: &GitGraph, settings: &Settings, rules: &LintRules) -> Result<String, String> {
+    let mut checked = 0;
+    let mut by_rule: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut by_branch: BTreeMap<String, (Tally, Vec<String>)> = BTreeMap::new();
+    let mut by_author: BTreeMap<String, Tally> = BTreeMap::new();
+    for info in &graph.commits {
+        if info.is_merge && !rules.include_merges {
+            continue;
+        }
+        let commit = graph
+            .commit(info.oid)
+            .map_err(|err| err.message().to_string())?;
+        let violations = check_message(rules, &String::from_utf8_lossy(commit.message_bytes()));
+        let meta = graph
+            .metadata(info.oid)
+            .map_err(|err| err.message().to_string())?;
+        let author = settings.control_chars.apply(&meta.author_name);
+        let branch = graph
+            .branch_of(info.oid)
+            .map_or("(no branch)".to_string(), |branch| {
+                settings.control_chars.apply(branch.name.as_bytes())
+            });
+
+        checked += 1;
+        let (branch_tally, lines) = by_branch.entry(branch).or_default();
+        let author_tally = by_author.entry(author.clone()).or_default();
+        branch_tally.checked += 1;
+        author_tally.checked += 1;
+        if violations.is_empty() {
+            continue;
+        }
+        branch_tally.violating += 1;
+        author_tally.violating += 1;
+        for rule in &violations {
+            *by_rule.entry(rule).or_default() += 1;
+        }
+        lines.push(format!(
+            "  {} {} ({}): {}",
+            &info.oid.to_string()[..7],
+            settings.control_chars.apply(&meta.summary),
+            author,
+            violations.join(", ")
+        ));
+    }
+
+    let violating: usize = by_author.values().map(|tally| tally.violating).sum();
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{} of {} commit message(s) violate the rules",
+        violating, checked
+    )
+    .unwrap();
+    for (rule, count) in &by_rule {
+        writeln!(out, "  {:<18} {}", rule, count).unwrap();
+    }
+    for (branch, (tally, lines)) in &by_branch {
+        if tally.violating > 0 {
+            writeln!(
+                out,
+                "\n{} ({} of {})",
+                branch, tally.violating, tally.checked
+            )
+            .unwrap();
+            for line in lines {
+                writeln!(out, "{}", line).unwrap();
+            }
+        }
+    }
+    if violating > 0 {
+        writeln!(out, "\nBy author:").unwrap();
+        for (author, tally) in &by_author {
+            writeln!(
+                out,
+                "  {:<24} {} of {}",
+                author, tally.violating, tally.checked
+            )
+            .unwrap();
+        }
+    }
+    Ok(out)
+}
+
+/// The names of the rules a commit message violates.
+fn check_message(rules: &LintRules, message: &str) -> Vec<&'static str> {
+    let subject = message.lines().next().unwrap_or("");
+    let mut violations = vec![];
+    if rules
+        .max_subject_length
+        .is_some_and(|max| subject.chars().count() > max)
+    {
+        violations.push(SUBJECT_LENGTH);
+    }
+    if rules
+        .subject_pattern
+        .as_ref()
+        .is_some_and(|pattern| !pattern.is_match(subject))
+    {
+        violations.push(SUBJECT_PATTERN);
+    }
+    if rules
+        .ticket_pattern
+        .as_ref()
+        .is_some_and(|pattern| !pattern.is_match(message))
+    {
+        violations.push(TICKET_REFERENCE);
+    }
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_message, lint};
+    use crate::graph::GitGraph;
+    use crate::settings::{LintRules, LintRulesDef};
+    use crate::testing::{settings, TestRepo};
+    use git2::{Repository, Signature, Time};
+
+    #[test]
+    fn check_message_default_rules() {
+        let rules = LintRules::from(LintRulesDef::default()).unwrap();
+        assert!(check_message(&rules, "fix(graph): Handle octopus merges\n\nFixes #12").is_empty());
+        assert!(check_message(&rules, "feat!: Drop old config format (GG-7)").is_empty());
+        assert_eq!(
+            check_message(&rules, "Handle octopus merges"),
+            vec!["subject-pattern", "ticket-reference"]
+        );
+        assert_eq!(
+            check_message(&rules, &format!("fix: {} #1", "x".repeat(70))),
+            vec!["subject-length"]
+        );
+
+        let rules = LintRules::from(LintRulesDef {
+            max_subject_length: 0,
+            ticket_pattern: String::new(),
+            ..LintRulesDef::default()
+        })
+        .unwrap();
+        assert!(check_message(&rules, &format!("fix: {}", "x".repeat(100))).is_empty());
+    }
+
+    #[test]
+    fn authors_are_sanitized() {
+        let repo = TestRepo::bare("git-graph-lint");
+        let sig = Signature::new("Eve\x1b[2J", "eve@x", &Time::new(0, 0)).unwrap();
+        let tree = repo.treebuilder(None).unwrap().write().unwrap();
+        let tree = repo.find_tree(tree).unwrap();
+        let oid = Repository::commit(&repo, None, &sig, &sig, "Bad subject", &tree, &[]).unwrap();
+        repo.reference("refs/heads/main", oid, true, "").unwrap();
+
+        let settings = settings();
+        let graph = GitGraph::retaining(&repo, &settings, None, &[]).unwrap();
+        let rules = LintRules::from(LintRulesDef::default()).unwrap();
+        let report = lint(&graph, &settings, &rules).unwrap();
+        assert!(report.contains("Eve\u{fffd}"));
+        assert!(!report.contains('\x1b'));
+    }
+}
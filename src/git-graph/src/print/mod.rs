@@ -1,13 +1,224 @@
 //! Create visual representations of git graphs.
 
-use crate::graph::GitGraph;
+use crate::graph::{
+    autosquash_subject, parse_merge_summary, BranchInfo, CommitInfo, GitGraph, HeadInfo,
+    SignatureStatus,
+};
+use crate::settings::{CommitMap, GraphView, LabelColors, ReviewStatus, Settings};
+use git2::Oid;
 use std::cmp::max;
 
 pub mod colors;
+pub mod compare;
+pub mod explain;
+pub mod first_commits;
 pub mod format;
+pub mod heatmap;
+pub mod json;
+pub mod large_files;
+pub mod lint;
+pub mod patches;
+pub mod screen;
+pub mod stats;
+pub mod summary;
 pub mod svg;
 pub mod unicode;
 
+/// Kinds of refs shown as labels next to commits.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RefKind {
+    Head,
+    Local,
+    Remote,
+    Tag,
+}
+
+/// Terminal color of a ref label. `branch_color` is the color of the label's branch,
+/// or the default HEAD color for HEAD.
+fn label_term_color(label_colors: LabelColors, kind: RefKind, branch_color: u8) -> u8 {
+    match (label_colors, kind) {
+        (LabelColors::Fixed, _) => 3,
+        (LabelColors::Branch, _) => branch_color,
+        (LabelColors::RefType, RefKind::Head) => 14,
+        (LabelColors::RefType, RefKind::Local) => 10,
+        (LabelColors::RefType, RefKind::Remote) => 9,
+        (LabelColors::RefType, RefKind::Tag) => 11,
+    }
+}
+
+/// SVG color of a ref label. `branch_color` is the color of the label's branch.
+fn label_svg_color(label_colors: LabelColors, kind: RefKind, branch_color: &str) -> &str {
+    match (label_colors, kind) {
+        (LabelColors::Fixed, _) => "darkgoldenrod",
+        (LabelColors::Branch, _) => branch_color,
+        (LabelColors::RefType, RefKind::Head) => "darkcyan",
+        (LabelColors::RefType, RefKind::Local) => "green",
+        (LabelColors::RefType, RefKind::Remote) => "red",
+        (LabelColors::RefType, RefKind::Tag) => "goldenrod",
+    }
+}
+
+/// The badge following the names of protected branches.
+const PROTECTED_BADGE: &str = "\u{1f512}";
+
+/// Whether a branch is protected, by its name or, for remote branches, by its name
+/// without the remote, like `main` for `origin/main`.
+fn is_protected(settings: &Settings, branch: &BranchInfo) -> bool {
+    let local_name = if branch.is_remote {
+        branch
+            .name
+            .split_once('/')
+            .map_or(&branch.name[..], |(_, name)| name)
+    } else {
+        &branch.name
+    };
+    !branch.is_tag
+        && settings
+            .protected_branches
+            .iter()
+            .any(|glob| glob.is_match(&branch.name) || glob.is_match(local_name))
+}
+
+/// The commits a local branch is ahead of and behind its upstream, like `↑2 ↓5`
+/// (`+2 -5` in ASCII). Counts of zero are left out, branches in sync get no label.
+fn ahead_behind_label(branch: &BranchInfo, ascii: bool) -> Option<String> {
+    let (ahead, behind) = branch.ahead_behind?;
+    let (up, down) = if ascii {
+        ('+', '-')
+    } else {
+        ('\u{2191}', '\u{2193}')
+    };
+    let parts: Vec<String> = [(up, ahead), (down, behind)]
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(arrow, count)| format!("{}{}", arrow, count))
+        .collect();
+    (!parts.is_empty()).then(|| parts.join(" "))
+}
+
+/// The label of a remote's HEAD, like `origin/HEAD`, shown after the remote branch it
+/// points to, as git log does.
+fn remote_head_label(branch: &BranchInfo) -> Option<String> {
+    let (remote, _) = branch.name.split_once('/')?;
+    branch.is_remote_head.then(|| format!("{}/HEAD", remote))
+}
+
+/// The header shown for an unborn HEAD, like `unborn branch main` in a repository
+/// without commits.
+fn unborn_header(graph: &GitGraph) -> Option<String> {
+    match &graph.head {
+        Some(HeadInfo::Unborn { name }) => Some(format!("unborn branch {}", name)),
+        Some(HeadInfo::Detached { .. } | HeadInfo::Branch { .. }) | None => None,
+    }
+}
+
+/// The number of branches containing a commit, like `⑂ 3 branches`, if counted. Only
+/// shown for commits in several branches, where the count differs from that of all
+/// children: at the tips of branches, and where their histories join.
+fn containment_badge(graph: &GitGraph, index: usize) -> Option<String> {
+    let count_of = |idx: usize| graph.containing_branches_of(graph.commits[idx].oid).len();
+    let count = count_of(index);
+    let same_as_child = graph
+        .children
+        .get(index)
+        .any(|child| count_of(child) == count);
+    (count > 1 && !same_as_child).then(|| format!("\u{2442} {} branches", count))
+}
+
+/// The autosquash badge of a `fixup!`, `squash!` or `amend!` commit, naming the commit
+/// it will be squashed into, like `[fixup of 91f3bb2]`.
+fn fixup_badge(graph: &GitGraph, info: &CommitInfo) -> Option<String> {
+    let target = info.fixup_of?;
+    let kind = graph
+        .metadata(info.oid)
+        .ok()
+        .and_then(|meta| {
+            autosquash_subject(&String::from_utf8_lossy(&meta.summary)).map(|(kind, _)| kind)
+        })
+        .unwrap_or("fixup");
+    Some(format!("[{} of {}]", kind, &target.to_string()[..7]))
+}
+
+/// The badge of a merge collapsed into one node, naming the single commit it brought in,
+/// like `[merged 91f3bb2]`.
+fn collapsed_badge(info: &CommitInfo) -> Option<String> {
+    info.collapsed
+        .map(|oid| format!("[merged {}]", &oid.to_string()[..7]))
+}
+
+/// The type badge of a branch or tag, if enabled: `tag` for tags, `remote` for remote
+/// branches, and the model's name of the branch type matched by `persistence` otherwise.
+fn type_badge<'a>(settings: &'a Settings, branch: &BranchInfo) -> Option<&'a str> {
+    if !settings.type_badges {
+        None
+    } else if branch.is_tag {
+        Some("tag")
+    } else if branch.is_remote {
+        Some("remote")
+    } else {
+        settings
+            .branches
+            .type_names
+            .get(branch.persistence as usize)
+            .filter(|name| !name.is_empty())
+            .map(|name| &name[..])
+    }
+}
+
+/// The review glyph of a commit, with its terminal and SVG colors, if review status is
+/// shown: a check mark for reviewed commits, a question mark for unreviewed ones.
+fn review_glyph(
+    settings: &Settings,
+    info: &CommitInfo,
+    ascii: bool,
+) -> Option<(&'static str, u8, &'static str)> {
+    match settings.reviews.as_ref()?.status(info.oid) {
+        ReviewStatus::Reviewed if ascii => Some(("+", 2, "green")),
+        ReviewStatus::Reviewed => Some(("\u{2713}", 2, "green")),
+        ReviewStatus::Unreviewed => Some(("?", 3, "darkorange")),
+    }
+}
+
+/// The signature badge of a commit, with its terminal and SVG colors, if signatures
+/// were verified. Unsigned commits get no badge.
+fn signature_badge(info: &CommitInfo) -> Option<(&'static str, u8, &'static str)> {
+    match info.signature? {
+        SignatureStatus::Signed => Some(("[signed]", 2, "green")),
+        SignatureStatus::BadSignature => Some(("[bad signature]", 1, "red")),
+        SignatureStatus::Unverifiable => Some(("[unverifiable signature]", 8, "gray")),
+        SignatureStatus::Unsigned => None,
+    }
+}
+
+/// Whether edges across hidden commits are summarized, with the number of commits hidden.
+fn summarizes_hidden(settings: &Settings) -> bool {
+    settings.simplify_by_decoration || settings.view == GraphView::Releases
+}
+
+/// The name of the branch merged in by a merge commit, parsed from its summary, if enabled.
+/// Also returns the branch traced by the merged-in parent, for its color.
+/// Merges summarized in first-parent mode always name the merged branch.
+fn merged_branch(
+    graph: &GitGraph,
+    settings: &Settings,
+    info: &CommitInfo,
+) -> Result<Option<(String, Option<usize>)>, String> {
+    if let Some(name) = &info.merged_from {
+        return Ok(Some((name.clone(), None)));
+    }
+    if !settings.merge_names || !info.is_merge {
+        return Ok(None);
+    }
+    let meta = graph
+        .metadata(info.oid)
+        .map_err(|err| err.message().to_string())?;
+    let summary = settings.control_chars.apply(&meta.summary);
+    let trace = info.parents[1]
+        .and_then(|parent| graph.indices.get(&parent))
+        .and_then(|idx| graph.commits[*idx].branch_trace);
+    Ok(parse_merge_summary(&summary, &settings.merge_patterns).map(|name| (name, trace)))
+}
+
 /// Find the index at which a between-branch connection
 /// has to deviate from the current branch's column.
 ///
@@ -19,18 +230,14 @@ fn get_deviate_index(graph: &GitGraph, index: usize, par_index: usize) -> usize
     let par_branch = &graph.all_branches[par_info.branch_trace.unwrap()];
 
     let mut min_split_idx = index;
-    for sibling_oid in &par_info.children {
-        if let Some(&sibling_index) = graph.indices.get(sibling_oid) {
-            if let Some(sibling) = graph.commits.get(sibling_index) {
-                if let Some(sibling_trace) = sibling.branch_trace {
-                    let sibling_branch = &graph.all_branches[sibling_trace];
-                    if sibling_oid != &info.oid
-                        && sibling_branch.visual.column == par_branch.visual.column
-                        && sibling_index > min_split_idx
-                    {
-                        min_split_idx = sibling_index;
-                    }
-                }
+    for sibling_index in graph.children.get(par_index) {
+        if let Some(sibling_trace) = graph.commits[sibling_index].branch_trace {
+            let sibling_branch = &graph.all_branches[sibling_trace];
+            if sibling_index != index
+                && sibling_branch.visual.column == par_branch.visual.column
+                && sibling_index > min_split_idx
+            {
+                min_split_idx = sibling_index;
             }
         }
     }
@@ -43,3 +250,92 @@ fn get_deviate_index(graph: &GitGraph, index: usize, par_index: usize) -> usize
         (par_index as i32 - 1) as usize
     }
 }
+
+/// Entries for a legend of branch colors and columns, sorted by column.
+///
+/// Returns tuples of column and the indices into `graph.all_branches` of all
+/// (differently named) branches shown in that column.
+fn legend_entries(graph: &GitGraph) -> Vec<(usize, Vec<usize>)> {
+    let mut entries: Vec<(usize, Vec<usize>)> = vec![];
+    for (idx, branch) in graph.all_branches.iter().enumerate() {
+        if branch.is_tag {
+            continue;
+        }
+        if let Some(column) = branch.visual.column {
+            let pos = match entries.binary_search_by_key(&column, |(col, _)| *col) {
+                Ok(pos) => pos,
+                Err(pos) => {
+                    entries.insert(pos, (column, vec![]));
+                    pos
+                }
+            };
+            let names = &mut entries[pos].1;
+            if !names
+                .iter()
+                .any(|other| graph.all_branches[*other].name == branch.name)
+            {
+                names.push(idx);
+            }
+        }
+    }
+    entries
+}
+
+/// How a commit takes part in a history rewrite given by [Settings::commit_map].
+enum Rewrite {
+    /// Created by the rewrite from an old commit. `parents_match` tells whether the old
+    /// commit's parents, mapped to their new ids, are the commit's parents. `None` if the
+    /// old commit is not in the repository any more.
+    From {
+        old: Oid,
+        parents_match: Option<bool>,
+    },
+    /// Rewritten to a new commit, or pruned if `None`
+    To(Option<Oid>),
+}
+
+/// The part of a commit in a history rewrite, if a commit map is given.
+fn rewrite_of(graph: &GitGraph, info: &CommitInfo, settings: &Settings) -> Option<Rewrite> {
+    let map = settings.commit_map.as_ref()?;
+    if let Some(old) = map.old_of(info.oid) {
+        let parents_match = graph.commit(old).ok().and_then(|old| {
+            let new = graph.commit(info.oid).ok()?;
+            let mapped = map_parents(graph, map, old.parent_ids().collect());
+            Some(mapped == new.parent_ids().collect::<Vec<_>>())
+        });
+        return Some(Rewrite::From { old, parents_match });
+    }
+    map.new_of(info.oid)
+        .map(|new| Rewrite::To(Some(new).filter(|new| !new.is_zero())))
+}
+
+/// The new ids of old parents. Pruned parents are replaced by their own parents,
+/// like git filter-repo does.
+fn map_parents(graph: &GitGraph, map: &CommitMap, parents: Vec<Oid>) -> Vec<Oid> {
+    let mut mapped = vec![];
+    let mut stack: Vec<Oid> = parents.into_iter().rev().collect();
+    while let Some(parent) = stack.pop() {
+        match map.new_of(parent) {
+            Some(new) if new.is_zero() => {
+                if let Ok(commit) = graph.commit(parent) {
+                    stack.extend(commit.parent_ids().rev());
+                }
+            }
+            new => {
+                let new = new.unwrap_or(parent);
+                if !mapped.contains(&new) {
+                    mapped.push(new);
+                }
+            }
+        }
+    }
+    mapped
+}
+
+/// The impact level of a commit, i.e. the number of impact thresholds
+/// reached by its changed lines. `None` if impact levels are not enabled.
+fn impact_level(info: &CommitInfo, settings: &Settings) -> Option<usize> {
+    let thresholds = settings.impact_thresholds.as_ref()?;
+    let lines = info.lines_changed?;
+    Some(thresholds.iter().filter(|t| lines >= **t).count())
+}
@@ -0,0 +1,93 @@
+//! Export the commits of a branch as a patch series, like `git format-patch`.
+
+use crate::graph::GitGraph;
+use git2::{Email, EmailCreateOptions};
+
+/// Maximum length of the subject part of patch file names, as used by git.
+const MAX_NAME_LENGTH: usize = 52;
+
+/// A patch of a series, in mbox format.
+pub struct Patch {
+    /// File name like `0001-Fix-a-bug.patch`
+    pub file_name: String,
+    pub content: Vec<u8>,
+}
+
+/// The series of patches of the commits attributed to a branch, oldest first.
+/// Merge commits are skipped, as by `git format-patch`.
+///
+/// Returns the patches, and the number of skipped merge commits.
+pub fn export_patches(
+    graph: &GitGraph,
+    branch: &str,
+    subject_prefix: &str,
+) -> Result<(Vec<Patch>, usize), String> {
+    let commits = graph.commits_of_branch(branch);
+    if commits.is_empty() {
+        return Err(format!("No commits attributed to branch '{}'", branch));
+    }
+    let (merges, commits): (Vec<_>, Vec<_>) = commits.into_iter().partition(|info| info.is_merge);
+
+    let mut options = EmailCreateOptions::new();
+    options.subject_prefix(subject_prefix);
+    let repository = graph.repository()?;
+    let mut patches = vec![];
+    for (idx, info) in commits.iter().rev().enumerate() {
+        let commit = repository
+            .find_commit(info.oid)
+            .map_err(|err| err.message().to_string())?;
+        let parent_tree = match commit.parent(0) {
+            Ok(parent) => Some(parent.tree().map_err(|err| err.message().to_string())?),
+            Err(_) => None,
+        };
+        let tree = commit.tree().map_err(|err| err.message().to_string())?;
+        let diff = repository
+            .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)
+            .map_err(|err| err.message().to_string())?;
+        let summary = commit.summary().unwrap_or("");
+        let email = Email::from_diff(
+            &diff,
+            idx + 1,
+            commits.len(),
+            &commit.id(),
+            summary,
+            commit.body().unwrap_or(""),
+            &commit.author(),
+            &mut options,
+        )
+        .map_err(|err| err.message().to_string())?;
+        patches.push(Patch {
+            file_name: format!("{:04}-{}.patch", idx + 1, sanitize(summary)),
+            content: email.as_slice().to_vec(),
+        });
+    }
+    Ok((patches, merges.len()))
+}
+
+/// A commit summary as part of a file name, like git does it: runs of characters other
+/// than letters, digits, '.' and '_' are replaced by a single '-'.
+fn sanitize(summary: &str) -> String {
+    let mut name = String::new();
+    for c in summary.chars() {
+        if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+            name.push(c);
+        } else if !name.ends_with('-') {
+            name.push('-');
+        }
+    }
+    let name: String = name.chars().take(MAX_NAME_LENGTH).collect();
+    name.trim_matches(|c| c == '-' || c == '.').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sanitize;
+
+    #[test]
+    fn sanitize_file_names() {
+        assert_eq!(sanitize("Fix: the (main) bug!"), "Fix-the-main-bug");
+        assert_eq!(sanitize("v1.2 release_notes"), "v1.2-release_notes");
+        assert_eq!(sanitize("...dots..."), "dots");
+        assert_eq!(sanitize(&"a".repeat(80)).len(), 52);
+    }
+}
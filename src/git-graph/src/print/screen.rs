@@ -0,0 +1,89 @@
+//! Redrawing the terminal row by row, see [Screen].
+
+use crossterm::cursor::MoveTo;
+use crossterm::style::Print;
+use crossterm::terminal::{Clear, ClearType};
+use crossterm::QueueableCommand;
+use std::io::{self, Write};
+
+/// The rows last drawn to the terminal. Redraws write only the rows that changed,
+/// instead of clearing the whole screen, so refreshes of large graphs don't flicker.
+#[derive(Default)]
+pub struct Screen {
+    rows: Vec<String>,
+    size: (u16, u16),
+}
+
+impl Screen {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The indices of the rows that differ from the rows drawn last, including
+    /// the rows drawn last that are not in `rows` anymore.
+    pub fn changed_rows(&self, rows: &[String]) -> Vec<usize> {
+        (0..rows.len().max(self.rows.len()))
+            .filter(|idx| rows.get(*idx) != self.rows.get(*idx))
+            .collect()
+    }
+
+    /// Draws rows to a terminal of the given size, writing only the changed ones.
+    /// Everything is redrawn after the terminal was resized.
+    pub fn draw<W: Write>(
+        &mut self,
+        out: &mut W,
+        rows: Vec<String>,
+        size: (u16, u16),
+    ) -> io::Result<()> {
+        if size != self.size {
+            out.queue(Clear(ClearType::All))?;
+            self.rows.clear();
+            self.size = size;
+        }
+        for idx in self.changed_rows(&rows) {
+            out.queue(MoveTo(0, idx as u16))?
+                .queue(Clear(ClearType::UntilNewLine))?;
+            if let Some(row) = rows.get(idx) {
+                out.queue(Print(row))?;
+            }
+        }
+        out.queue(MoveTo(0, rows.len() as u16))?;
+        out.flush()?;
+        self.rows = rows;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Screen;
+
+    #[test]
+    fn redraws_only_changed_rows() {
+        let rows = |rows: &[&str]| rows.iter().map(|row| row.to_string()).collect::<Vec<_>>();
+        let mut screen = Screen::new();
+        let mut out = vec![];
+        screen
+            .draw(&mut out, rows(&["a", "b", "c"]), (80, 25))
+            .unwrap();
+        assert!(screen.changed_rows(&rows(&["a", "b", "c"])).is_empty());
+        assert_eq!(screen.changed_rows(&rows(&["a", "x", "c", "d"])), [1, 3]);
+        assert_eq!(screen.changed_rows(&rows(&["a"])), [1, 2]);
+
+        // Only the changed row is written
+        let mut out = vec![];
+        screen
+            .draw(&mut out, rows(&["a", "x", "c"]), (80, 25))
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains('x') && !out.contains('a') && !out.contains('c'));
+
+        // Resizing redraws everything
+        let mut out = vec![];
+        screen
+            .draw(&mut out, rows(&["a", "x", "c"]), (100, 25))
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains('a') && out.contains('x') && out.contains('c'));
+    }
+}
@@ -0,0 +1,163 @@
+//! Layout statistics of a graph, for the JSON output.
+
+use crate::graph::GitGraph;
+use serde_derive::Serialize;
+
+/// Statistics of a graph's layout.
+#[derive(Serialize)]
+pub struct LayoutStats {
+    /// Maximum number of lanes in use in a single row
+    pub max_lanes: usize,
+    /// Number of lanes crossed by connections between columns
+    pub crossings: usize,
+    /// The branch with the most commits
+    pub longest_branch: Option<BranchLength>,
+    /// Merge commits, including merges summarized by `--first-parent`
+    pub merges: usize,
+}
+
+/// A branch and its number of commits.
+#[derive(Serialize)]
+pub struct BranchLength {
+    pub name: String,
+    pub commits: usize,
+}
+
+/// Computes the layout statistics of a graph.
+pub fn layout_stats(graph: &GitGraph) -> LayoutStats {
+    let num_cols = graph
+        .all_branches
+        .iter()
+        .filter_map(|branch| branch.visual.column)
+        .max()
+        .map_or(0, |max| max + 1);
+
+    // Rows covered by the vertical lines of each column, as drawn by the printers
+    let mut lines: Vec<Vec<(usize, usize)>> = vec![vec![]; num_cols];
+    let mut switches = vec![];
+    let mut lengths = vec![0; graph.all_branches.len()];
+    for (idx, info) in graph.commits.iter().enumerate() {
+        let Some(trace) = info.branch_trace else {
+            continue;
+        };
+        lengths[trace] += 1;
+        let Some(column) = graph.all_branches[trace].visual.column else {
+            continue;
+        };
+        lines[column].push((idx, idx));
+        for par_idx in info
+            .parents
+            .iter()
+            .flatten()
+            .filter_map(|p| graph.indices.get(p))
+        {
+            let par_trace = graph.commits[*par_idx].branch_trace;
+            let Some(par_column) = par_trace.and_then(|t| graph.all_branches[t].visual.column)
+            else {
+                continue;
+            };
+            if par_column == column {
+                lines[column].push((idx, *par_idx));
+            } else {
+                let split = super::get_deviate_index(graph, idx, *par_idx);
+                lines[column].push((idx, split));
+                lines[par_column].push((split, *par_idx));
+                switches.push((split, column.min(par_column), column.max(par_column)));
+            }
+        }
+    }
+    let lines: Vec<_> = lines.into_iter().map(merge_intervals).collect();
+
+    let crossings = switches
+        .iter()
+        .map(|(row, from, to)| {
+            lines[from + 1..*to]
+                .iter()
+                .filter(|intervals| passes(intervals, *row))
+                .count()
+        })
+        .sum();
+
+    let longest_branch = lengths
+        .iter()
+        .enumerate()
+        .filter(|(_, len)| **len > 0)
+        // First of the longest, like `main` before branches of equal length
+        .min_by_key(|(idx, len)| (std::cmp::Reverse(**len), *idx))
+        .map(|(idx, len)| BranchLength {
+            name: graph.all_branches[idx].name.clone(),
+            commits: *len,
+        });
+
+    LayoutStats {
+        max_lanes: max_overlap(&lines, graph.commits.len()),
+        crossings,
+        longest_branch,
+        merges: graph
+            .commits
+            .iter()
+            .filter(|info| info.is_merge || info.merged_from.is_some())
+            .count(),
+    }
+}
+
+/// Sorts and merges overlapping inclusive row intervals.
+fn merge_intervals(mut intervals: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+    intervals.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = vec![];
+    for (start, end) in intervals {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// Whether a line of the given merged intervals passes through a row,
+/// rather than starting or ending there.
+fn passes(intervals: &[(usize, usize)], row: usize) -> bool {
+    let pos = intervals.partition_point(|(start, _)| *start < row);
+    pos > 0 && intervals[pos - 1].1 > row
+}
+
+/// The maximum number of columns with a line in the same row.
+fn max_overlap(lines: &[Vec<(usize, usize)>], num_rows: usize) -> usize {
+    let mut deltas = vec![0isize; num_rows + 1];
+    for (start, end) in lines.iter().flatten() {
+        deltas[*start] += 1;
+        deltas[*end + 1] -= 1;
+    }
+    deltas
+        .iter()
+        .scan(0, |active, delta| {
+            *active += delta;
+            Some(*active)
+        })
+        .max()
+        .unwrap_or(0) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{max_overlap, merge_intervals, passes};
+
+    #[test]
+    fn lanes_and_crossings_of_intervals() {
+        let main = merge_intervals(vec![(3, 3), (0, 3), (3, 9)]);
+        let feature = merge_intervals(vec![(5, 6), (2, 4)]);
+        assert_eq!(main, vec![(0, 9)]);
+        assert_eq!(feature, vec![(2, 4), (5, 6)]);
+
+        let lines = [main.clone(), feature.clone()];
+        assert_eq!(max_overlap(&lines, 10), 2);
+        assert_eq!(max_overlap(&lines[..1], 10), 1);
+
+        assert!(passes(&main, 4));
+        assert!(!passes(&main, 0));
+        assert!(!passes(&main, 9));
+        assert!(passes(&feature, 3));
+        assert!(!passes(&feature, 5));
+        assert!(!passes(&feature, 8));
+    }
+}
@@ -0,0 +1,79 @@
+//! Summary of a graph's totals, for the footer and JSON output.
+
+use crate::graph::GitGraph;
+use crate::print::format::format_date;
+use git2::Time;
+use serde_derive::Serialize;
+use std::collections::HashSet;
+
+/// Totals of a graph.
+#[derive(Serialize)]
+pub struct Summary {
+    pub commits: usize,
+    /// Branches pointing to a commit shown
+    pub branches: usize,
+    /// Tags pointing to a commit shown
+    pub tags: usize,
+    pub merges: usize,
+    pub authors: usize,
+    /// Date of the oldest commit shown
+    pub first: Option<String>,
+    /// Date of the newest commit shown
+    pub last: Option<String>,
+    /// Days between the oldest and the newest commit
+    pub span_days: Option<i64>,
+}
+
+/// Computes the totals of a graph.
+pub fn summarize(graph: &GitGraph) -> Result<Summary, String> {
+    let mut authors = HashSet::new();
+    let mut first: Option<Time> = None;
+    let mut last: Option<Time> = None;
+    for info in &graph.commits {
+        let meta = graph
+            .metadata(info.oid)
+            .map_err(|err| err.message().to_string())?;
+        authors.insert(meta.author_email.clone());
+        let time = meta.time;
+        if first.is_none_or(|first| time.seconds() < first.seconds()) {
+            first = Some(time);
+        }
+        if last.is_none_or(|last| time.seconds() > last.seconds()) {
+            last = Some(time);
+        }
+    }
+
+    Ok(Summary {
+        commits: graph.commits.len(),
+        branches: graph.commits.iter().map(|info| info.branches.len()).sum(),
+        tags: graph.commits.iter().map(|info| info.tags.len()).sum(),
+        merges: graph.commits.iter().filter(|info| info.is_merge).count(),
+        authors: authors.len(),
+        first: first.map(|time| format_date(time, "%Y-%m-%d")),
+        last: last.map(|time| format_date(time, "%Y-%m-%d")),
+        span_days: first
+            .zip(last)
+            .map(|(first, last)| (last.seconds() - first.seconds()) / 86400),
+    })
+}
+
+/// Formats the totals of a graph as a single footer line.
+pub fn print_summary(summary: &Summary) -> String {
+    let plural = |count: usize, one: &str, many: &str| {
+        format!("{} {}", count, if count == 1 { one } else { many })
+    };
+    let mut text = format!(
+        "Σ {}, {}, {}, {}, {}",
+        plural(summary.commits, "commit", "commits"),
+        plural(summary.branches, "branch", "branches"),
+        plural(summary.tags, "tag", "tags"),
+        plural(summary.merges, "merge", "merges"),
+        plural(summary.authors, "author", "authors"),
+    );
+    if let (Some(first), Some(last), Some(days)) =
+        (&summary.first, &summary.last, summary.span_days)
+    {
+        text.push_str(&format!(", {} – {} ({} days)", first, last, days));
+    }
+    text
+}
@@ -1,13 +1,30 @@
 //! Create graphs in SVG format (Scalable Vector Graphics).
 
-use crate::graph::GitGraph;
-use crate::settings::Settings;
+use crate::graph::{CommitInfo, GitGraph};
+use crate::print::{
+    ahead_behind_label, collapsed_badge, containment_badge, fixup_badge, is_protected,
+    label_svg_color, merged_branch, remote_head_label, review_glyph, signature_badge, type_badge,
+    RefKind, PROTECTED_BADGE,
+};
+use crate::settings::{OutputParts, Settings};
 use svg::node::element::path::Data;
-use svg::node::element::{Circle, Line, Path};
+use svg::node::element::{Circle, Group, Line, Path, Rectangle, TSpan, Text};
 use svg::Document;
 
+const HEAD_COLOR: &str = "darkcyan";
+const SEPARATOR_COLOR: &str = "black";
+const BADGE_COLOR: &str = "gray";
+const PICKAXE_COLOR: &str = "orangered";
+const MERGE_BASE_COLOR: &str = "darkorange";
+const HEADER_HEIGHT: f32 = 20.0;
+/// Dash-dot pattern of edges to grafted parents
+const GRAFT_DASHES: &str = "8,3,2,3";
+
 /// Creates a SVG visual representation of a graph.
 pub fn print_svg(graph: &GitGraph, settings: &Settings) -> Result<String, String> {
+    if settings.output_parts == OutputParts::NoGraph {
+        return Err("SVG output requires the graph".to_string());
+    }
     let mut document = Document::new();
 
     let max_idx = graph.commits.len();
@@ -42,46 +59,235 @@ pub fn print_svg(graph: &GitGraph, settings: &Settings) -> Result<String, String
                         let par_info = &graph.commits[*par_idx];
                         let par_branch = &graph.all_branches[par_info.branch_trace.unwrap()];
 
-                        let color = if info.is_merge {
-                            &par_branch.visual.svg_color
+                        let (color, persistence) = if info.is_merge {
+                            (&par_branch.visual.svg_color, par_branch.persistence)
+                        } else {
+                            (branch_color, branch.persistence)
+                        };
+                        let width = stroke_width(settings, persistence);
+                        // Edges across hidden commits are dashed, and labeled with their number
+                        let hidden = if super::summarizes_hidden(settings) {
+                            info.hidden[p]
                         } else {
-                            branch_color
+                            0
                         };
 
                         if branch.visual.column == par_branch.visual.column {
-                            document = document.add(line(
+                            let mut edge = line(
                                 idx,
                                 branch.visual.column.unwrap(),
                                 *par_idx,
                                 par_branch.visual.column.unwrap(),
                                 color,
-                            ));
+                                width,
+                            );
+                            if info.grafted {
+                                edge = edge.set("stroke-dasharray", GRAFT_DASHES);
+                            } else if hidden > 0 {
+                                edge = edge.set("stroke-dasharray", "4,2");
+                            }
+                            document = document.add(edge);
                         } else {
                             let split_index = super::get_deviate_index(graph, idx, *par_idx);
-                            document = document.add(path(
+                            let mut edge = path(
                                 idx,
                                 branch.visual.column.unwrap(),
                                 *par_idx,
                                 par_branch.visual.column.unwrap(),
                                 split_index,
                                 color,
-                            ));
+                                width,
+                            );
+                            if info.grafted {
+                                edge = edge.set("stroke-dasharray", GRAFT_DASHES);
+                            } else if hidden > 0 {
+                                edge = edge.set("stroke-dasharray", "4,2");
+                            }
+                            document = document.add(edge);
+                        }
+                        if hidden > 0 {
+                            // The vertical part of the edge is in the parent's column for merges
+                            let column = if info.is_merge {
+                                par_branch.visual.column
+                            } else {
+                                branch.visual.column
+                            };
+                            document =
+                                document.add(hidden_count(idx, *par_idx, column.unwrap(), hidden));
                         }
                     }
                 }
             }
 
-            document = document.add(commit_dot(
-                idx,
-                branch.visual.column.unwrap(),
-                branch_color,
-                !info.is_merge,
-            ));
+            let radius = match (
+                super::impact_level(info, settings),
+                &settings.impact_thresholds,
+            ) {
+                (Some(level), Some(thresholds)) => {
+                    3.0 + 4.0 * level as f32 / thresholds.len().max(1) as f32
+                }
+                _ => 4.0,
+            };
+
+            if info.merged_from.is_some() {
+                document = document.add(stub_arrow(
+                    idx,
+                    branch.visual.column.unwrap(),
+                    BADGE_COLOR,
+                    stroke_width(settings, branch.persistence),
+                ));
+            }
+
+            if info.shallow_boundary {
+                document = document.add(truncation_stub(
+                    idx,
+                    branch.visual.column.unwrap(),
+                    stroke_width(settings, branch.persistence),
+                ));
+            }
+
+            if info.pickaxe_match {
+                document = document.add(
+                    commit_dot(
+                        idx,
+                        branch.visual.column.unwrap(),
+                        PICKAXE_COLOR,
+                        false,
+                        radius + 3.0,
+                    )
+                    .set("stroke-width", 2),
+                );
+            }
+
+            if info.is_merge_base {
+                document = document.add(
+                    commit_dot(
+                        idx,
+                        branch.visual.column.unwrap(),
+                        MERGE_BASE_COLOR,
+                        false,
+                        radius + 3.0,
+                    )
+                    .set("stroke-width", 2),
+                );
+            }
+
+            if info.is_stash {
+                document = document.add(stash_marker(
+                    idx,
+                    branch.visual.column.unwrap(),
+                    branch_color,
+                    radius,
+                ));
+            } else {
+                let mut dot = commit_dot(
+                    idx,
+                    branch.visual.column.unwrap(),
+                    branch_color,
+                    !info.is_merge && info.merged_from.is_none(),
+                    radius,
+                );
+                if info.is_dimmed {
+                    dot = dot.set("opacity", 0.3);
+                }
+                document = document.add(dot);
+            }
+        }
+    }
+    // Mark the target commits of branches in the lane of empty branches
+    for branch in &graph.all_branches {
+        if let (true, Some(column)) = (branch.has_no_commits(), branch.visual.column) {
+            if let Some(idx) = graph.indices.get(&branch.target) {
+                max_column = max_column.max(column);
+                document = document.add(
+                    commit_dot(*idx, column, &branch.visual.svg_color, false, 3.0)
+                        .set("stroke-dasharray", "2,1"),
+                );
+            }
+        }
+    }
+
+    // Connect commits to be squashed to their targets, left of the graph
+    for (idx, info) in graph.commits.iter().enumerate() {
+        let Some(target) = info.fixup_of else {
+            continue;
+        };
+        let column = |idx: usize| {
+            graph.commits[idx]
+                .branch_trace
+                .and_then(|trace| graph.all_branches[trace].visual.column)
+        };
+        let target_idx = graph.indices[&target];
+        if let (Some(column), Some(target_column)) = (column(idx), column(target_idx)) {
+            document = document.add(fixup_arc(idx, column, target_idx, target_column));
+        }
+    }
+
+    let (mut x_max, mut y_max) = commit_coord(max_idx + 1, max_column + 1);
+
+    let head_idx = graph
+        .head
+        .as_ref()
+        .and_then(|head| head.oid())
+        .and_then(|oid| graph.indices.get(&oid));
+    let mut max_label_chars = 0;
+    for (idx, info) in graph.commits.iter().enumerate() {
+        if settings.output_parts == OutputParts::GraphOnly {
+            break;
         }
+        let labels = ref_labels(graph, settings, info, head_idx == Some(&idx))?;
+        if !labels.is_empty() {
+            max_label_chars = max_label_chars.max(labels.iter().map(|l| l.0.len()).sum());
+            let (_, y) = commit_coord(idx, 0);
+            let text = labels.into_iter().fold(
+                Text::new("")
+                    .set("x", x_max)
+                    .set("y", y + 4.0)
+                    .set("font-family", "monospace")
+                    .set("font-size", 11),
+                |text, (label, color, style)| {
+                    let span = TSpan::new(label).set("fill", color);
+                    text.add(match style {
+                        LabelStyle::Plain => span,
+                        LabelStyle::Badge => span.set("font-size", 8).set("font-style", "italic"),
+                        LabelStyle::Protected => span
+                            .set("font-weight", "bold")
+                            .set("text-decoration", "underline"),
+                    })
+                },
+            );
+            document = document.add(text);
+        }
+    }
+    // Repositories without commits show the unborn branch in place of the first commit
+    if let Some(header) = super::unborn_header(graph).filter(|_| graph.commits.is_empty()) {
+        let head_color = label_svg_color(settings.label_colors, RefKind::Head, HEAD_COLOR);
+        let (_, y) = commit_coord(0, 0);
+        max_label_chars = max_label_chars.max(header.len());
+        document = document.add(
+            Text::new(header)
+                .set("x", x_max)
+                .set("y", y + 4.0)
+                .set("font-family", "monospace")
+                .set("font-size", 11)
+                .set("fill", head_color),
+        );
+    }
+    x_max += 7.0 * max_label_chars as f32;
+    if settings.legend {
+        let (legend, width, height) = legend_box(graph, x_max);
+        document = document.add(legend);
+        x_max += width;
+        y_max = y_max.max(height);
+    }
+    let mut y_min = 0.0;
+    if !settings.branches.order_names.is_empty() {
+        document = document.add(group_headers(graph, settings));
+        y_min = -HEADER_HEIGHT;
+        y_max += HEADER_HEIGHT;
     }
-    let (x_max, y_max) = commit_coord(max_idx + 1, max_column + 1);
     document = document
-        .set("viewBox", (0, 0, x_max, y_max))
+        .set("viewBox", (0.0, y_min, x_max, y_max))
         .set("width", x_max)
         .set("height", y_max);
 
@@ -90,18 +296,336 @@ pub fn print_svg(graph: &GitGraph, settings: &Settings) -> Result<String, String
     Ok(String::from_utf8(out).unwrap_or_else(|_| "Invalid UTF8 character.".to_string()))
 }
 
-fn commit_dot(index: usize, column: usize, color: &str, filled: bool) -> Circle {
+/// Adds a type badge after a label, marked by a leading space.
+/// The style of a label next to a commit.
+enum LabelStyle {
+    Plain,
+    /// Type badges and other annotations, set smaller
+    Badge,
+    /// Names of protected branches, set bold and underlined
+    Protected,
+}
+
+fn push_badge(labels: &mut Vec<(String, String, LabelStyle)>, badge: Option<&str>) {
+    if let Some(badge) = badge {
+        labels.push((
+            format!(" {}", badge),
+            BADGE_COLOR.to_string(),
+            LabelStyle::Badge,
+        ));
+    }
+}
+
+/// Ref labels of a commit, as text, color and style, including separators.
+fn ref_labels(
+    graph: &GitGraph,
+    settings: &Settings,
+    info: &CommitInfo,
+    is_head: bool,
+) -> Result<Vec<(String, String, LabelStyle)>, String> {
+    let label_colors = settings.label_colors;
+    let head_color = label_svg_color(label_colors, RefKind::Head, HEAD_COLOR).to_string();
+    let mut labels = vec![];
+
+    if let Some((glyph, _, color)) = review_glyph(settings, info, false) {
+        labels.push((format!("{} ", glyph), color.to_string(), LabelStyle::Plain));
+    }
+    let head_branch = graph.head.as_ref().and_then(|head| head.branch());
+    if is_head && head_branch.is_none() {
+        labels.push(("HEAD".to_string(), head_color.clone(), LabelStyle::Plain));
+    }
+    if !info.branches.is_empty() {
+        labels.push((
+            " (".to_string(),
+            SEPARATOR_COLOR.to_string(),
+            LabelStyle::Plain,
+        ));
+        for (idx, branch_index) in info.branches.iter().enumerate() {
+            let branch = &graph.all_branches[*branch_index];
+            if is_head && head_branch == Some(&branch.name) {
+                labels.push((
+                    "HEAD -> ".to_string(),
+                    head_color.clone(),
+                    LabelStyle::Plain,
+                ));
+            }
+            let kind = if branch.is_remote {
+                RefKind::Remote
+            } else {
+                RefKind::Local
+            };
+            let color = label_svg_color(label_colors, kind, &branch.visual.svg_color);
+            if is_protected(settings, branch) {
+                labels.push((
+                    branch.name.clone(),
+                    color.to_string(),
+                    LabelStyle::Protected,
+                ));
+                labels.push((
+                    format!(" {}", PROTECTED_BADGE),
+                    color.to_string(),
+                    LabelStyle::Plain,
+                ));
+            } else {
+                labels.push((branch.name.clone(), color.to_string(), LabelStyle::Plain));
+            }
+            if let Some(label) = ahead_behind_label(branch, false) {
+                labels.push((
+                    format!(" {}", label),
+                    BADGE_COLOR.to_string(),
+                    LabelStyle::Plain,
+                ));
+            }
+            push_badge(&mut labels, type_badge(settings, branch));
+            push_badge(&mut labels, branch.is_wip.then_some("wip"));
+            if let Some(label) = remote_head_label(branch) {
+                labels.push((format!(", {}", label), color.to_string(), LabelStyle::Plain));
+            }
+            if idx < info.branches.len() - 1 {
+                labels.push((
+                    ", ".to_string(),
+                    SEPARATOR_COLOR.to_string(),
+                    LabelStyle::Plain,
+                ));
+            }
+        }
+        labels.push((
+            ")".to_string(),
+            SEPARATOR_COLOR.to_string(),
+            LabelStyle::Plain,
+        ));
+    }
+    if !info.tags.is_empty() {
+        let curr_color = info
+            .branch_trace
+            .map(|trace| &graph.all_branches[trace].visual.svg_color);
+        labels.push((
+            " [".to_string(),
+            SEPARATOR_COLOR.to_string(),
+            LabelStyle::Plain,
+        ));
+        for (idx, tag_index) in info.tags.iter().enumerate() {
+            let tag = &graph.all_branches[*tag_index];
+            let color = label_svg_color(
+                label_colors,
+                RefKind::Tag,
+                curr_color.unwrap_or(&tag.visual.svg_color),
+            );
+            labels.push((
+                tag.name[5..].to_string(),
+                color.to_string(),
+                LabelStyle::Plain,
+            ));
+            push_badge(&mut labels, type_badge(settings, tag));
+            if idx < info.tags.len() - 1 {
+                labels.push((
+                    ", ".to_string(),
+                    SEPARATOR_COLOR.to_string(),
+                    LabelStyle::Plain,
+                ));
+            }
+        }
+        labels.push((
+            "]".to_string(),
+            SEPARATOR_COLOR.to_string(),
+            LabelStyle::Plain,
+        ));
+    }
+    for worktree in graph
+        .worktrees
+        .iter()
+        .filter(|worktree| worktree.head.is_at(info.oid))
+    {
+        labels.push((
+            format!(" {{worktree {}}}", worktree.name),
+            head_color.clone(),
+            LabelStyle::Plain,
+        ));
+    }
+    if let Some((name, trace)) = merged_branch(graph, settings, info)? {
+        let color = trace.map_or(SEPARATOR_COLOR, |trace| {
+            &graph.all_branches[trace].visual.svg_color
+        });
+        labels.push((
+            format!(" \u{2190} {}", name),
+            color.to_string(),
+            LabelStyle::Plain,
+        ));
+    }
+    if let Some((badge, _, color)) = signature_badge(info) {
+        labels.push((format!(" {}", badge), color.to_string(), LabelStyle::Badge));
+    }
+    if let Some(badge) = containment_badge(graph, graph.indices[&info.oid]) {
+        labels.push((
+            format!(" {}", badge),
+            BADGE_COLOR.to_string(),
+            LabelStyle::Badge,
+        ));
+    }
+    push_badge(&mut labels, fixup_badge(graph, info).as_deref());
+    push_badge(&mut labels, collapsed_badge(info).as_deref());
+    if info.is_merge_base {
+        labels.push((
+            " [merge base]".to_string(),
+            MERGE_BASE_COLOR.to_string(),
+            LabelStyle::Badge,
+        ));
+    }
+    Ok(labels)
+}
+
+/// Creates a header band above the graph, naming the order group of each range of columns.
+fn group_headers(graph: &GitGraph, settings: &Settings) -> Group {
+    let mut columns: Vec<(usize, usize, usize)> = vec![];
+    for info in &graph.commits {
+        if let Some(trace) = info.branch_trace {
+            let visual = &graph.all_branches[trace].visual;
+            let Some(column) = visual.column else {
+                continue;
+            };
+            match columns
+                .iter_mut()
+                .find(|(g, _, _)| *g == visual.order_group)
+            {
+                Some((_, min, max)) => {
+                    *min = (*min).min(column);
+                    *max = (*max).max(column);
+                }
+                None => columns.push((visual.order_group, column, column)),
+            }
+        }
+    }
+
+    let mut group = Group::new();
+    for (order_group, min, max) in columns {
+        if let Some(name) = settings.branches.order_names.get(order_group) {
+            let (x0, _) = commit_coord(0, min);
+            let (x1, _) = commit_coord(0, max);
+            group = group
+                .add(
+                    Rectangle::new()
+                        .set("x", x0 - 6.0)
+                        .set("y", 2.0 - HEADER_HEIGHT)
+                        .set("width", x1 - x0 + 12.0)
+                        .set("height", HEADER_HEIGHT - 6.0)
+                        .set("fill", "whitesmoke")
+                        .set("stroke", "gray")
+                        .set("stroke-width", 1),
+                )
+                .add(
+                    Text::new(name.as_str())
+                        .set("x", x0 - 4.0)
+                        .set("y", -7.0)
+                        .set("font-family", "sans-serif")
+                        .set("font-size", 9),
+                );
+        }
+    }
+    group
+}
+
+/// Creates a legend box of branch colors, placed to the right of the graph at `x0`.
+/// Returns the box and the width and height it occupies.
+fn legend_box(graph: &GitGraph, x0: f32) -> (Group, f32, f32) {
+    let entries = super::legend_entries(graph);
+    let max_chars = entries
+        .iter()
+        .map(|(_, branches)| {
+            branches
+                .iter()
+                .map(|idx| graph.all_branches[*idx].name.len() + 2)
+                .sum::<usize>()
+        })
+        .max()
+        .unwrap_or(0);
+    let width = 35.0 + 7.0 * max_chars as f32;
+    let height = 15.0 * entries.len() as f32 + 20.0;
+
+    let mut group = Group::new().add(
+        Rectangle::new()
+            .set("x", x0 + 5.0)
+            .set("y", 5.0)
+            .set("width", width - 10.0)
+            .set("height", height - 10.0)
+            .set("fill", "white")
+            .set("stroke", "gray")
+            .set("stroke-width", 1),
+    );
+    for (row, (_, branches)) in entries.iter().enumerate() {
+        let y = 15.0 * (row as f32 + 1.0);
+        let color = &graph.all_branches[branches[0]].visual.svg_color;
+        let names = branches
+            .iter()
+            .map(|idx| &graph.all_branches[*idx].name[..])
+            .collect::<Vec<_>>()
+            .join(", ");
+        group = group
+            .add(
+                Circle::new()
+                    .set("cx", x0 + 17.0)
+                    .set("cy", y)
+                    .set("r", 4)
+                    .set("fill", color.as_str())
+                    .set("stroke", color.as_str())
+                    .set("stroke-width", 1),
+            )
+            .add(
+                Text::new(names)
+                    .set("x", x0 + 27.0)
+                    .set("y", y + 4.0)
+                    .set("font-family", "monospace")
+                    .set("font-size", 11),
+            );
+    }
+    (group, width, height)
+}
+
+fn commit_dot(index: usize, column: usize, color: &str, filled: bool, radius: f32) -> Circle {
     let (x, y) = commit_coord(index, column);
     Circle::new()
         .set("cx", x)
         .set("cy", y)
-        .set("r", 4)
+        .set("r", radius)
         .set("fill", if filled { color } else { "white" })
         .set("stroke", color)
         .set("stroke-width", 1)
 }
 
-fn line(index1: usize, column1: usize, index2: usize, column2: usize, color: &str) -> Line {
+/// A hollow diamond, marking stash entries and their index commits.
+fn stash_marker(index: usize, column: usize, color: &str, radius: f32) -> Path {
+    let (x, y) = commit_coord(index, column);
+    let data = Data::new()
+        .move_to((x, y - radius - 1.0))
+        .line_to((x + radius + 1.0, y))
+        .line_to((x, y + radius + 1.0))
+        .line_to((x - radius - 1.0, y))
+        .close();
+    Path::new()
+        .set("d", data)
+        .set("fill", "white")
+        .set("stroke", color)
+        .set("stroke-width", 1)
+}
+
+/// The stroke width of the lines of a branch: graded from 3 for the most persistent
+/// branches to 1, if emphasized by [Settings::persistence_weight].
+fn stroke_width(settings: &Settings, persistence: u8) -> f32 {
+    match settings.persistence_weight {
+        Some(levels) if persistence < levels => {
+            1.0 + 2.0 * (levels - persistence) as f32 / levels as f32
+        }
+        _ => 1.0,
+    }
+}
+
+fn line(
+    index1: usize,
+    column1: usize,
+    index2: usize,
+    column2: usize,
+    color: &str,
+    width: f32,
+) -> Line {
     let (x1, y1) = commit_coord(index1, column1);
     let (x2, y2) = commit_coord(index2, column2);
     Line::new()
@@ -110,7 +634,71 @@ fn line(index1: usize, column1: usize, index2: usize, column2: usize, color: &st
         .set("x2", x2)
         .set("y2", y2)
         .set("stroke", color)
+        .set("stroke-width", width)
+}
+
+/// A short arrow pointing into a commit from the right, for a merged-in branch
+/// summarized in first-parent mode.
+fn stub_arrow(index: usize, column: usize, color: &str, width: f32) -> Path {
+    let (x, y) = commit_coord(index, column);
+    let data = Data::new()
+        .move_to((x + 11.0, y))
+        .line_to((x + 5.0, y))
+        .move_to((x + 8.0, y - 3.0))
+        .line_to((x + 5.0, y))
+        .line_to((x + 8.0, y + 3.0));
+    Path::new()
+        .set("d", data)
+        .set("fill", "none")
+        .set("stroke", color)
+        .set("stroke-width", width)
+}
+
+/// A dotted stub below a commit at the boundary of a shallow clone, for the missing history.
+fn truncation_stub(index: usize, column: usize, width: f32) -> Line {
+    let (x, y) = commit_coord(index, column);
+    Line::new()
+        .set("x1", x)
+        .set("y1", y)
+        .set("x2", x)
+        .set("y2", y + 12.0)
+        .set("stroke", BADGE_COLOR)
+        .set("stroke-width", width)
+        .set("stroke-dasharray", "1,2")
+}
+
+/// A dotted arc bulging left from a `fixup!` or `squash!` commit to the commit it will
+/// be squashed into.
+fn fixup_arc(index: usize, column: usize, target_index: usize, target_column: usize) -> Path {
+    let (x1, y1) = commit_coord(index, column);
+    let (x2, y2) = commit_coord(target_index, target_column);
+    let data = Data::new().move_to((x1 - 4.0, y1)).cubic_curve_to((
+        x1 - 12.0,
+        y1,
+        x2 - 12.0,
+        y2,
+        x2 - 4.0,
+        y2,
+    ));
+    Path::new()
+        .set("d", data)
+        .set("fill", "none")
+        .set("stroke", BADGE_COLOR)
         .set("stroke-width", 1)
+        .set("stroke-dasharray", "1,2")
+}
+
+/// The number of commits hidden on an edge, next to the middle of its vertical part.
+fn hidden_count(index: usize, par_index: usize, column: usize, count: usize) -> Text {
+    let (x, y1) = commit_coord(index, column);
+    let (_, y2) = commit_coord(par_index, column);
+    Text::new(count.to_string())
+        .set("x", x + 3.0)
+        .set("y", (y1 + y2) / 2.0 + 3.0)
+        .set("font-family", "monospace")
+        .set("font-size", 8)
+        .set("font-style", "italic")
+        .set("fill", BADGE_COLOR)
 }
 
 fn bold_line(index1: usize, column1: usize, index2: usize, column2: usize, color: &str) -> Line {
@@ -132,6 +720,7 @@ fn path(
     column2: usize,
     split_idx: usize,
     color: &str,
+    width: f32,
 ) -> Path {
     let c0 = commit_coord(index1, column1);
 
@@ -153,7 +742,7 @@ fn path(
         .set("d", data)
         .set("fill", "none")
         .set("stroke", color)
-        .set("stroke-width", 1)
+        .set("stroke-width", width)
 }
 
 fn commit_coord(index: usize, column: usize) -> (f32, f32) {
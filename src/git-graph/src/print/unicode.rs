@@ -1,13 +1,21 @@
 //! Create graphs in Unicode format with ANSI X3.64 / ISO 6429 colour codes
 
 use crate::graph::{CommitInfo, GitGraph, HeadInfo};
-use crate::print::format::CommitFormat;
-use crate::settings::{Characters, Settings};
+use crate::print::large_files::format_size;
+use crate::print::{
+    ahead_behind_label, collapsed_badge, containment_badge, fixup_badge, is_protected,
+    label_term_color, merged_branch, remote_head_label, review_glyph, signature_badge, type_badge,
+    RefKind, Rewrite, PROTECTED_BADGE,
+};
+use crate::settings::{Characters, OutputParts, Settings};
 use itertools::Itertools;
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
 use std::cmp::max;
 use std::collections::hash_map::Entry::{Occupied, Vacant};
 use std::collections::HashMap;
 use std::fmt::Write;
+use std::ops::Range;
 use textwrap::Options;
 use yansi::Paint;
 
@@ -29,9 +37,25 @@ const HOR_D: u8 = 13;
 const ARR_L: u8 = 14;
 const ARR_R: u8 = 15;
 
+const IMPACT_0: u8 = 16;
+/// Lanes continuing beyond the rows shown
+const CONT: u8 = 20;
+/// Target commits of branches in the lane of empty branches
+const EMPTY_MARK: u8 = 21;
+/// Marker of stash entries and their index commits
+const STASH: u8 = 22;
+/// Lines of edges to grafted parents
+const GRAFT: u8 = 23;
+/// The empty braille pattern, to which the dot bits are added
+const BRAILLE_BLANK: u32 = 0x2800;
+const NUM_IMPACT: u8 = 4;
+
 const WHITE: u8 = 7;
 const HEAD_COLOR: u8 = 14;
 const HASH_COLOR: u8 = 11;
+const BADGE_COLOR: u8 = 8;
+const MERGE_BASE_COLOR: u8 = 208;
+const GREP_COLOR: u8 = 9;
 
 /**
 UnicodeGraphInfo is a type alias for a tuple containing three elements:
@@ -56,18 +80,35 @@ pub type UnicodeGraphInfo = (Vec<String>, Vec<String>, Vec<usize>);
 
 /// Creates a text-based visual representation of a graph.
 pub fn print_unicode(graph: &GitGraph, settings: &Settings) -> Result<UnicodeGraphInfo, String> {
+    print_unicode_rows(graph, settings, settings.rows.clone())
+}
+
+/// Creates a text-based visual representation of the given rows of a graph, laid out as
+/// in the full graph. Lanes continuing beyond the first or last row are marked by an
+/// extra line. Commits outside the rows are mapped to the first or last line.
+pub fn print_unicode_rows(
+    graph: &GitGraph,
+    settings: &Settings,
+    rows: Option<Range<usize>>,
+) -> Result<UnicodeGraphInfo, String> {
     if graph.all_branches.is_empty() {
         return Ok((vec![], vec![], vec![]));
     }
+    // Merges summarized in first-parent mode need room for their incoming arrow
+    let has_stubs = graph.commits.iter().any(|info| info.merged_from.is_some());
     let num_cols = 2 * graph
         .all_branches
         .iter()
         .map(|b| b.visual.column.unwrap_or(0))
         .max()
         .unwrap()
-        + 1;
+        + if has_stubs { 2 } else { 1 };
 
-    let head_idx = graph.indices.get(&graph.head.oid);
+    let head_idx = graph
+        .head
+        .as_ref()
+        .and_then(|head| head.oid())
+        .and_then(|oid| graph.indices.get(&oid));
 
     let inserts = get_inserts(graph, settings.compact);
 
@@ -77,8 +118,13 @@ pub fn print_unicode(graph: &GitGraph, settings: &Settings) -> Result<UnicodeGra
         ("".to_string(), "".to_string())
     };
 
+    let graph_width = if settings.characters.braille {
+        num_cols.div_ceil(4)
+    } else {
+        num_cols
+    };
     let wrap_options = if let Some((width, _, _)) = settings.wrapping {
-        create_wrapping_options(width, &indent1, &indent2, num_cols + 4)?
+        create_wrapping_options(width, &indent1, &indent2, graph_width + 4)?
     } else {
         None
     };
@@ -105,19 +151,257 @@ pub fn print_unicode(graph: &GitGraph, settings: &Settings) -> Result<UnicodeGra
         };
 
         let head = if head_idx == Some(&idx) {
-            Some(&graph.head)
+            graph.head.as_ref()
         } else {
             None
         };
 
-        let lines = format(
-            &settings.format,
-            graph,
-            info,
-            head,
-            settings.colored,
-            &wrap_options,
-        )?;
+        let mut lines = format(graph, info, head, settings, &wrap_options)?;
+
+        if let Some(pattern) = settings
+            .message_pattern
+            .as_ref()
+            .filter(|_| settings.colored && !settings.invert_grep)
+        {
+            lines = lines
+                .iter()
+                .map(|line| highlight_matches(line, pattern))
+                .collect();
+        }
+
+        if let Some(level) = super::impact_level(info, settings) {
+            let glyph =
+                settings.characters.chars[(IMPACT_0 + (level as u8).min(NUM_IMPACT - 1)) as usize];
+            if let Some(first) = lines.first_mut() {
+                first.insert_str(0, &format!("{} ", glyph));
+            }
+        }
+
+        // Pickaxe matches are marked in front, with the other commits' texts aligned
+        if settings.pickaxe.is_some() {
+            let marker = if !info.pickaxe_match {
+                " ".to_string()
+            } else if settings.characters.chars.iter().all(char::is_ascii) {
+                "#".to_string()
+            } else {
+                "\u{25B6}".to_string()
+            };
+            let marker = if settings.colored && info.pickaxe_match {
+                Paint::fixed(9, marker).bold().to_string()
+            } else {
+                marker
+            };
+            if let Some(first) = lines.first_mut() {
+                first.insert_str(0, &format!("{} ", marker));
+            }
+        }
+
+        if let Some(rewrite) = super::rewrite_of(graph, info, settings) {
+            let (note, differs) = match rewrite {
+                Rewrite::From {
+                    old,
+                    parents_match: Some(false),
+                } => (
+                    format!("[was {}, parents differ]", &old.to_string()[..7]),
+                    true,
+                ),
+                Rewrite::From { old, .. } => (format!("[was {}]", &old.to_string()[..7]), false),
+                Rewrite::To(Some(new)) => (format!("[now {}]", &new.to_string()[..7]), false),
+                Rewrite::To(None) => ("[pruned]".to_string(), false),
+            };
+            let note = if settings.colored {
+                Paint::fixed(if differs { 9 } else { 8 }, note).to_string()
+            } else {
+                note
+            };
+            if let Some(first) = lines.first_mut() {
+                first.push_str(&format!(" {}", note));
+            }
+        }
+
+        if let Some(badge) = collapsed_badge(info) {
+            let badge = if settings.colored {
+                Paint::fixed(8, badge).to_string()
+            } else {
+                badge
+            };
+            if let Some(first) = lines.first_mut() {
+                first.push_str(&format!(" {}", badge));
+            }
+        }
+
+        if super::summarizes_hidden(settings) && info.hidden != [0, 0] {
+            let counts: Vec<String> = info
+                .hidden
+                .iter()
+                .filter(|hidden| **hidden > 0)
+                .map(usize::to_string)
+                .collect();
+            let total: usize = info.hidden.iter().sum();
+            let badge = format!(
+                "[{} {}]",
+                counts.join(" + "),
+                if total == 1 { "commit" } else { "commits" }
+            );
+            let badge = if settings.colored {
+                Paint::fixed(8, badge).to_string()
+            } else {
+                badge
+            };
+            if let Some(first) = lines.first_mut() {
+                first.push_str(&format!(" {}", badge));
+            }
+        }
+
+        if let Some(badge) = containment_badge(graph, idx) {
+            let badge = if settings.colored {
+                Paint::fixed(BADGE_COLOR, badge).to_string()
+            } else {
+                badge
+            };
+            if let Some(first) = lines.first_mut() {
+                first.push_str(&format!(" {}", badge));
+            }
+        }
+
+        if let Some(badge) = fixup_badge(graph, info) {
+            let badge = if settings.colored {
+                Paint::fixed(BADGE_COLOR, badge).to_string()
+            } else {
+                badge
+            };
+            if let Some(first) = lines.first_mut() {
+                first.push_str(&format!(" {}", badge));
+            }
+        }
+
+        if info.grafted {
+            let badge = "[grafted]";
+            let badge = if settings.colored {
+                Paint::fixed(8, badge).to_string()
+            } else {
+                badge.to_string()
+            };
+            if let Some(first) = lines.first_mut() {
+                first.push_str(&format!(" {}", badge));
+            }
+        }
+
+        if info.shallow_boundary {
+            let badge = "[history truncated]";
+            let badge = if settings.colored {
+                Paint::fixed(8, badge).to_string()
+            } else {
+                badge.to_string()
+            };
+            if let Some(first) = lines.first_mut() {
+                first.push_str(&format!(" {}", badge));
+            }
+        }
+
+        let large_blobs = graph.large_blobs_of(info.oid);
+        if !large_blobs.is_empty() {
+            let size = large_blobs.iter().map(|blob| blob.size).sum();
+            let badge = format!("[+{}]", format_size(size));
+            let badge = if settings.colored {
+                Paint::fixed(11, badge).to_string()
+            } else {
+                badge
+            };
+            if let Some(first) = lines.first_mut() {
+                first.push_str(&format!(" {}", badge));
+            }
+        }
+
+        if let Some((badge, color, _)) = signature_badge(info) {
+            let badge = if settings.colored {
+                Paint::fixed(color, badge).to_string()
+            } else {
+                badge.to_string()
+            };
+            if let Some(first) = lines.first_mut() {
+                first.push_str(&format!(" {}", badge));
+            }
+        }
+        if info.is_merge_base {
+            let badge = if settings.colored {
+                Paint::fixed(MERGE_BASE_COLOR, "[merge base]")
+                    .bold()
+                    .to_string()
+            } else {
+                "[merge base]".to_string()
+            };
+            if let Some(first) = lines.first_mut() {
+                first.push_str(&format!(" {}", badge));
+            }
+        }
+        let notes = graph.notes_of(info.oid);
+        if !notes.is_empty() && !settings.inline_notes {
+            let badge = match notes.len() {
+                1 => "[note]".to_string(),
+                count => format!("[{} notes]", count),
+            };
+            let badge = if settings.colored {
+                Paint::fixed(3, badge).to_string()
+            } else {
+                badge
+            };
+            if let Some(first) = lines.first_mut() {
+                first.push_str(&format!(" {}", badge));
+            }
+        }
+        if settings.inline_notes {
+            for line in notes.iter().flat_map(|note| note.lines()) {
+                let line = format!("    {}", settings.control_chars.apply(line.as_bytes()));
+                lines.push(if settings.colored {
+                    Paint::fixed(3, line).to_string()
+                } else {
+                    line
+                });
+            }
+        }
+
+        for bump in graph.submodule_bumps_of(info.oid) {
+            let arrow = if settings.characters.chars.iter().all(char::is_ascii) {
+                "->"
+            } else {
+                "\u{2192}"
+            };
+            let badge = format!("[{} {} {}]", bump.path, arrow, &bump.oid.to_string()[..7]);
+            let badge = if settings.colored {
+                Paint::fixed(14, badge).to_string()
+            } else {
+                badge
+            };
+            if let Some(first) = lines.first_mut() {
+                first.push_str(&format!(" {}", badge));
+            }
+        }
+
+        let ascii = settings.characters.chars.iter().all(char::is_ascii);
+        // Commits to be squashed are indented below their targets' level
+        if info.fixup_of.is_some() {
+            if let Some(first) = lines.first_mut() {
+                first.insert_str(0, if ascii { "> " } else { "\u{21b3} " });
+            }
+        }
+        if let Some((glyph, color, _)) = review_glyph(settings, info, ascii) {
+            let glyph = if settings.colored {
+                Paint::fixed(color, glyph).to_string()
+            } else {
+                glyph.to_string()
+            };
+            if let Some(first) = lines.first_mut() {
+                first.insert_str(0, &format!("{} ", glyph));
+            }
+        }
+
+        if settings.row_numbers {
+            let width = (graph.commits.len().max(1) - 1).to_string().len();
+            if let Some(first) = lines.first_mut() {
+                first.insert_str(0, &format!("{:>width$} ", idx, width = width));
+            }
+        }
 
         let num_lines = if lines.is_empty() { 0 } else { lines.len() - 1 };
         let max_inserts = max(cnt_inserts, num_lines);
@@ -140,6 +424,7 @@ pub fn print_unicode(graph: &GitGraph, settings: &Settings) -> Result<UnicodeGra
     );
 
     // Compute branch lines in grid
+    let mut grafted_lines = vec![];
     for (idx, info) in graph.commits.iter().enumerate() {
         if let Some(trace) = info.branch_trace {
             let branch = &graph.all_branches[trace];
@@ -148,10 +433,17 @@ pub fn print_unicode(graph: &GitGraph, settings: &Settings) -> Result<UnicodeGra
 
             let branch_color = branch.visual.term_color;
 
+            let is_merge = info.is_merge || info.merged_from.is_some();
             grid.set(
                 column * 2,
                 idx_map,
-                if info.is_merge { CIRCLE } else { DOT },
+                if info.is_stash {
+                    STASH
+                } else if is_merge {
+                    CIRCLE
+                } else {
+                    DOT
+                },
                 branch_color,
                 branch.persistence,
             );
@@ -174,6 +466,9 @@ pub fn print_unicode(graph: &GitGraph, settings: &Settings) -> Result<UnicodeGra
                             if par_idx_map > idx_map + 1 {
                                 vline(&mut grid, (idx_map, par_idx_map), column, color, pers);
                             }
+                            if info.grafted {
+                                grafted_lines.push(((idx_map, par_idx_map), column));
+                            }
                         } else {
                             let split_index = super::get_deviate_index(graph, idx, *par_idx);
                             let split_idx_map = index_map[split_index];
@@ -206,6 +501,21 @@ pub fn print_unicode(graph: &GitGraph, settings: &Settings) -> Result<UnicodeGra
                                                     color,
                                                     pers,
                                                 );
+                                                if info.grafted {
+                                                    grafted_lines.extend([
+                                                        (
+                                                            (idx_map, split_idx_map + insert_idx),
+                                                            column,
+                                                        ),
+                                                        (
+                                                            (
+                                                                split_idx_map + insert_idx,
+                                                                par_idx_map,
+                                                            ),
+                                                            par_column,
+                                                        ),
+                                                    ]);
+                                                }
                                             }
                                         }
                                     }
@@ -218,14 +528,284 @@ pub fn print_unicode(graph: &GitGraph, settings: &Settings) -> Result<UnicodeGra
         }
     }
 
+    // Edges to grafted parents are dashed, where not crossed by other lines
+    for ((from, to), column) in grafted_lines {
+        for row in (from + 1)..to {
+            if grid.cell(column * 2, row).character == VER {
+                grid.set_opt(column * 2, row, Some(GRAFT), None, None);
+            }
+        }
+    }
+
+    // Draw stub arrows for the branches merged into summarized merges
+    for (idx, info) in graph.commits.iter().enumerate() {
+        if let (Some(_), Some(trace)) = (&info.merged_from, info.branch_trace) {
+            let branch = &graph.all_branches[trace];
+            let x = branch.visual.column.unwrap() * 2 + 1;
+            if grid.cell(x, index_map[idx]).character == SPACE {
+                grid.set(x, index_map[idx], ARR_L, BADGE_COLOR, branch.persistence);
+            }
+        }
+    }
+
+    // Mark the target commits of branches in the lane of empty branches
+    for branch in &graph.all_branches {
+        if let (true, Some(column)) = (branch.has_no_commits(), branch.visual.column) {
+            if let Some(idx) = graph.indices.get(&branch.target) {
+                grid.set(
+                    column * 2,
+                    index_map[*idx],
+                    EMPTY_MARK,
+                    branch.visual.term_color,
+                    branch.persistence,
+                );
+            }
+        }
+    }
+
+    if let Some(rows) = rows {
+        (grid, text_lines) = slice_rows(&grid, text_lines, &mut index_map, rows);
+    }
+
     if settings.reverse_commit_order {
         text_lines.reverse();
         grid.reverse();
     }
 
-    let lines = print_graph(&settings.characters, &grid, text_lines, settings.colored);
+    let is_text: Vec<bool> = text_lines.iter().map(Option::is_some).collect();
+    let (mut g_lines, mut t_lines) = print_graph(
+        &settings.characters,
+        &grid,
+        text_lines,
+        settings.colored,
+        settings.persistence_weight,
+    );
+
+    match settings.output_parts {
+        OutputParts::Full => {}
+        OutputParts::GraphOnly => t_lines.iter_mut().for_each(|line| line.clear()),
+        OutputParts::NoGraph => {
+            g_lines = print_branch_names(graph, &index_map, g_lines.len(), settings);
+            // Without the graph, blank rows inserted for lines are meaningless
+            let keep = is_text;
+            let mut unreversed = keep.clone();
+            if settings.reverse_commit_order {
+                unreversed.reverse();
+            }
+            let new_rows: Vec<usize> = unreversed
+                .iter()
+                .scan(0, |kept, keep| {
+                    let row = *kept;
+                    *kept += *keep as usize;
+                    Some(row)
+                })
+                .collect();
+            let mut iter = keep.iter();
+            g_lines.retain(|_| *iter.next().unwrap());
+            let mut iter = keep.iter();
+            t_lines.retain(|_| *iter.next().unwrap());
+            index_map.iter_mut().for_each(|row| *row = new_rows[*row]);
+        }
+    }
+
+    Ok((g_lines, t_lines, index_map))
+}
+
+/// Cuts the lines of a range of rows out of a grid and its text lines, adding a line
+/// with continuity markers at each cut that lanes cross.
+fn slice_rows(
+    grid: &Grid,
+    mut text_lines: Vec<Option<String>>,
+    index_map: &mut [usize],
+    rows: Range<usize>,
+) -> (Grid, Vec<Option<String>>) {
+    let height = text_lines.len();
+    let line_of = |row: usize| index_map.get(row).copied().unwrap_or(height);
+    let (start, end) = (line_of(rows.start), line_of(rows.end.max(rows.start)));
+    let mut lines: Vec<Vec<GridCell>> = vec![];
+    let mut texts = vec![];
+    let cut = |above: usize, below: usize, text: String| {
+        let mut markers = vec![GridCell::EMPTY; grid.width];
+        for (x, marker) in markers.iter_mut().enumerate() {
+            let (upper, lower) = (grid.cell(x, above), grid.cell(x, below));
+            if connects_down(upper.character) && connects_up(lower.character) {
+                *marker = GridCell {
+                    character: CONT,
+                    ..lower
+                };
+            }
+        }
+        (markers, Some(text))
+    };
+    if start > 0 && start < height {
+        let (markers, text) = cut(start - 1, start, format!("({} newer commits)", rows.start));
+        lines.push(markers);
+        texts.push(text);
+    }
+    lines.extend((start..end).map(|y| grid.row(y).to_vec()));
+    texts.extend(text_lines.drain(start..end));
+    if end > start && end < height {
+        let below = index_map.len() - rows.end;
+        let (markers, text) = cut(end - 1, end, format!("({} older commits)", below));
+        lines.push(markers);
+        texts.push(text);
+    }
+
+    let offset = start.saturating_sub(usize::from(start > 0));
+    for line in index_map.iter_mut() {
+        *line = (*line).clamp(offset, offset + lines.len().max(1) - 1) - offset;
+    }
+    let sliced = Grid {
+        width: grid.width,
+        data: lines.concat(),
+    };
+    (sliced, texts)
+}
+
+/// Whether a grid symbol connects to the line below.
+fn connects_down(character: u8) -> bool {
+    matches!(
+        character,
+        VER | CROSS | R_D | L_D | VER_L | VER_R | HOR_D | DOT | CIRCLE | STASH | GRAFT
+    ) || (IMPACT_0..IMPACT_0 + NUM_IMPACT).contains(&character)
+}
+
+/// Whether a grid symbol connects to the line above.
+fn connects_up(character: u8) -> bool {
+    matches!(
+        character,
+        VER | CROSS | R_U | L_U | VER_L | VER_R | HOR_U | DOT | CIRCLE | STASH | GRAFT
+    ) || (IMPACT_0..IMPACT_0 + NUM_IMPACT).contains(&character)
+}
+
+/// Parses a range of rows like `100..200`, `100..` or `..200`, with an exclusive end.
+/// `100..=199` includes the end.
+pub fn parse_rows(str: &str) -> Result<Range<usize>, String> {
+    let error = || {
+        format!(
+            "Can't parse rows '{}'. Expected a range like '100..200', '100..' or '..200'",
+            str
+        )
+    };
+    let (start, end) = str.trim().split_once("..").ok_or_else(error)?;
+    let number = |str: &str| str.trim().parse::<usize>().map_err(|_| error());
+    let start = if start.is_empty() { 0 } else { number(start)? };
+    let end = match end.strip_prefix('=') {
+        Some(end) => number(end)?.saturating_add(1),
+        None if end.is_empty() => usize::MAX,
+        None => number(end)?,
+    };
+    if end < start {
+        return Err(error());
+    }
+    Ok(start..end)
+}
+
+/// Creates a column with the name of each commit's branch, to replace the graph-lines.
+fn print_branch_names(
+    graph: &GitGraph,
+    index_map: &[usize],
+    num_lines: usize,
+    settings: &Settings,
+) -> Vec<String> {
+    let width = graph
+        .commits
+        .iter()
+        .filter_map(|info| info.branch_trace)
+        .map(|trace| graph.all_branches[trace].name.chars().count())
+        .max()
+        .unwrap_or(0);
 
-    Ok((lines.0, lines.1, index_map))
+    let mut lines = vec![" ".repeat(width); num_lines];
+    for (idx, info) in graph.commits.iter().enumerate() {
+        if let Some(trace) = info.branch_trace {
+            let branch = &graph.all_branches[trace];
+            let name = format!("{:width$}", branch.name, width = width);
+            lines[index_map[idx]] = if settings.colored {
+                Paint::fixed(branch.visual.term_color, name).to_string()
+            } else {
+                name
+            };
+        }
+    }
+    if settings.reverse_commit_order {
+        lines.reverse();
+    }
+    lines
+}
+
+/// Creates the header line shown above the graph if HEAD is unborn, like
+/// `HEAD -> unborn branch main`.
+pub fn print_unborn_header(graph: &GitGraph, settings: &Settings) -> Option<String> {
+    let header = format!("HEAD -> {}", super::unborn_header(graph)?);
+    if settings.colored {
+        let color = label_term_color(settings.label_colors, RefKind::Head, HEAD_COLOR);
+        Some(Paint::fixed(color, header).to_string())
+    } else {
+        Some(header)
+    }
+}
+
+/// Creates a legend with one row per graph column, showing the
+/// column's color and the names of the branches drawn in it.
+///
+/// Returns graph-lines and text-lines, aligned with the output of [print_unicode].
+pub fn print_legend(graph: &GitGraph, settings: &Settings) -> (Vec<String>, Vec<String>) {
+    let num_cols = 2 * graph
+        .all_branches
+        .iter()
+        .map(|b| b.visual.column.unwrap_or(0))
+        .max()
+        .unwrap_or(0)
+        + 1;
+    let width = if settings.characters.braille {
+        num_cols.div_ceil(4)
+    } else {
+        num_cols
+    };
+
+    let mut g_lines = vec![];
+    let mut t_lines = vec![];
+    for (column, branches) in super::legend_entries(graph) {
+        let color = graph.all_branches[branches[0]].visual.term_color;
+        let (position, dot) = if settings.characters.braille {
+            let dots = braille_dots(&settings.characters, DOT);
+            let dots = if column % 2 == 1 {
+                to_right_dots(dots)
+            } else {
+                dots
+            };
+            (column / 2, braille_symbol(dots))
+        } else {
+            (column * 2, settings.characters.chars[DOT as usize])
+        };
+        let mut g_out = " ".repeat(position);
+        if settings.colored {
+            write!(g_out, "{}", Paint::fixed(color, dot)).unwrap();
+        } else {
+            g_out.push(dot);
+        }
+        g_out.push_str(&" ".repeat(width - position - 1));
+
+        let names = branches
+            .iter()
+            .map(|idx| {
+                let branch = &graph.all_branches[*idx];
+                if settings.colored {
+                    Paint::fixed(branch.visual.term_color, &branch.name).to_string()
+                } else {
+                    branch.name.clone()
+                }
+            })
+            .join(", ");
+
+        g_lines.push(g_out);
+        t_lines.push(names);
+    }
+    g_lines.push(" ".repeat(width));
+    t_lines.push(String::new());
+
+    (g_lines, t_lines)
 }
 
 /// Create `textwrap::Options` from width and indent.
@@ -271,7 +851,7 @@ fn vline(grid: &mut Grid, (from, to): (usize, usize), column: usize, color: u8,
             (None, None)
         };
         match curr {
-            DOT | CIRCLE => {}
+            DOT | CIRCLE | STASH => {}
             HOR => {
                 grid.set_opt(column * 2, i, Some(CROSS), Some(color), Some(pers));
             }
@@ -318,7 +898,7 @@ fn hline(
                     (None, None)
                 };
                 match curr {
-                    DOT | CIRCLE => {}
+                    DOT | CIRCLE | STASH => {}
                     VER => grid.set_opt(column, index, Some(CROSS), None, None),
                     HOR | CROSS | HOR_U | HOR_D => {
                         grid.set_opt(column, index, None, new_col, new_pers)
@@ -339,7 +919,7 @@ fn hline(
             (None, None)
         };
         match left {
-            DOT | CIRCLE => {}
+            DOT | CIRCLE | STASH => {}
             VER => grid.set_opt(from_2, index, Some(VER_R), new_col, new_pers),
             VER_L => grid.set_opt(from_2, index, Some(CROSS), None, None),
             VER_R => {}
@@ -356,7 +936,7 @@ fn hline(
             (None, None)
         };
         match right {
-            DOT | CIRCLE => {}
+            DOT | CIRCLE | STASH => {}
             VER => grid.set_opt(to_2, index, Some(VER_L), None, None),
             VER_L | HOR_U => grid.set_opt(to_2, index, None, new_col, new_pers),
             HOR | R_U => grid.set_opt(to_2, index, Some(HOR_U), new_col, new_pers),
@@ -376,7 +956,7 @@ fn hline(
                     (None, None)
                 };
                 match curr {
-                    DOT | CIRCLE => {}
+                    DOT | CIRCLE | STASH => {}
                     VER => grid.set_opt(column, index, Some(CROSS), None, None),
                     HOR | CROSS | HOR_U | HOR_D => {
                         grid.set_opt(column, index, None, new_col, new_pers)
@@ -397,7 +977,7 @@ fn hline(
             (None, None)
         };
         match left {
-            DOT | CIRCLE => {}
+            DOT | CIRCLE | STASH => {}
             VER => grid.set_opt(to_2, index, Some(VER_R), None, None),
             VER_R => grid.set_opt(to_2, index, None, new_col, new_pers),
             HOR | L_U => grid.set_opt(to_2, index, Some(HOR_U), new_col, new_pers),
@@ -413,7 +993,7 @@ fn hline(
             (None, None)
         };
         match right {
-            DOT | CIRCLE => {}
+            DOT | CIRCLE | STASH => {}
             VER => grid.set_opt(from_2, index, Some(VER_L), new_col, new_pers),
             VER_R => grid.set_opt(from_2, index, Some(CROSS), None, None),
             VER_L => grid.set_opt(from_2, index, None, new_col, new_pers),
@@ -584,33 +1164,44 @@ fn get_inserts(graph: &GitGraph, compact: bool) -> HashMap<usize, Vec<Vec<Occ>>>
 }
 
 /// Creates the complete graph visualization, incl. formatter commits.
+/// Lanes of branches with a persistence below `heavy_levels` are drawn in heavy lines.
 fn print_graph(
     characters: &Characters,
     grid: &Grid,
     text_lines: Vec<Option<String>>,
     color: bool,
+    heavy_levels: Option<u8>,
 ) -> (Vec<String>, Vec<String>) {
+    let is_heavy = |cell: &GridCell| heavy_levels.is_some_and(|levels| cell.pers < levels);
+    let char_of = |cell: &GridCell| {
+        if is_heavy(cell) {
+            heavy(cell.char(characters))
+        } else {
+            cell.char(characters)
+        }
+    };
     let mut g_lines = vec![];
     let mut t_lines = vec![];
 
-    for (row, line) in grid.data.chunks(grid.width).zip(text_lines.into_iter()) {
+    for (row, line) in grid.data.chunks(grid.width).zip(text_lines) {
         let mut g_out = String::new();
         let mut t_out = String::new();
 
-        if color {
+        if characters.braille {
+            print_braille_row(characters, row, color, &mut g_out);
+        } else if color {
             for cell in row {
                 if cell.character == SPACE {
                     write!(g_out, "{}", cell.char(characters))
+                } else if is_heavy(cell) {
+                    write!(g_out, "{}", Paint::fixed(cell.color, char_of(cell)).bold())
                 } else {
                     write!(g_out, "{}", Paint::fixed(cell.color, cell.char(characters)))
                 }
                 .unwrap();
             }
         } else {
-            let str = row
-                .iter()
-                .map(|cell| cell.char(characters))
-                .collect::<String>();
+            let str = row.iter().map(char_of).collect::<String>();
             write!(g_out, "{}", str).unwrap();
         }
 
@@ -625,34 +1216,165 @@ fn print_graph(
     (g_lines, t_lines)
 }
 
+/// The heavy variant of a thin or round box drawing character, the character itself otherwise.
+fn heavy(character: char) -> char {
+    match character {
+        '│' => '┃',
+        '─' => '━',
+        '┼' => '╋',
+        '└' | '╰' => '┗',
+        '┌' | '╭' => '┏',
+        '┐' | '╮' => '┓',
+        '┘' | '╯' => '┛',
+        '┤' => '┫',
+        '├' => '┣',
+        '┴' => '┻',
+        '┬' => '┳',
+        '┆' => '┇',
+        '╎' => '╏',
+        _ => character,
+    }
+}
+
+/// Renders a grid row as braille patterns, combining two lanes into each character.
+/// Only the lane cells are drawn; the cells between lanes are implied by the dots
+/// of adjacent lanes. Each character takes the color of its more persistent lane.
+fn print_braille_row(characters: &Characters, row: &[GridCell], color: bool, out: &mut String) {
+    let lanes: Vec<&GridCell> = row.iter().step_by(2).collect();
+    for pair in lanes.chunks(2) {
+        let left = braille_dots(characters, pair[0].character);
+        let right = pair
+            .get(1)
+            .map_or(0, |cell| braille_dots(characters, cell.character));
+        let symbol = braille_symbol(left | to_right_dots(right));
+
+        let cell = pair
+            .iter()
+            .filter(|cell| cell.character != SPACE)
+            .min_by_key(|cell| cell.pers);
+        match cell {
+            Some(cell) if color => write!(out, "{}", Paint::fixed(cell.color, symbol)),
+            _ => write!(out, "{}", symbol),
+        }
+        .unwrap();
+    }
+}
+
+/// The dot bits of a symbol of a braille charset, drawn in the left dot column.
+fn braille_dots(characters: &Characters, character: u8) -> u32 {
+    match character {
+        SPACE => 0,
+        _ => (characters.chars[character as usize] as u32).saturating_sub(BRAILLE_BLANK),
+    }
+}
+
+/// Moves dots from the left dot column (dots 1, 2, 3, 7) to the right one (dots 4, 5, 6, 8).
+fn to_right_dots(dots: u32) -> u32 {
+    ((dots & 0x07) << 3) | ((dots & 0x40) << 1)
+}
+
+fn braille_symbol(dots: u32) -> char {
+    char::from_u32(BRAILLE_BLANK + dots).unwrap_or(' ')
+}
+
+/// Highlights the matches of a pattern in a line, outside of its color codes.
+fn highlight_matches(line: &str, pattern: &Regex) -> String {
+    lazy_static! {
+        static ref COLOR_CODE: Regex = Regex::new("\x1b\\[[0-9;]*m").unwrap();
+    }
+    let highlight = |text: &str| {
+        pattern
+            .replace_all(text, |caps: &Captures| {
+                Paint::fixed(GREP_COLOR, &caps[0]).bold().to_string()
+            })
+            .to_string()
+    };
+    let mut result = String::new();
+    let mut last = 0;
+    for code in COLOR_CODE.find_iter(line) {
+        result.push_str(&highlight(&line[last..code.start()]));
+        result.push_str(code.as_str());
+        last = code.end();
+    }
+    result.push_str(&highlight(&line[last..]));
+    result
+}
+
 /// Format a commit.
 fn format(
-    format: &CommitFormat,
     graph: &GitGraph,
     info: &CommitInfo,
     head: Option<&HeadInfo>,
-    color: bool,
+    settings: &Settings,
     wrapping: &Option<Options>,
 ) -> Result<Vec<String>, String> {
-    let commit = graph
-        .repository
-        .find_commit(info.oid)
-        .map_err(|err| err.message().to_string())?;
-
-    let branch_str = format_branches(graph, info, head, color);
+    // Commits only reachable from reflogs, or dimmed by --merge-base, are grayed out as a whole
+    let grayed = settings.colored && (info.is_unreachable || info.is_dimmed);
+    let color = settings.colored && !grayed;
+    let mut branch_str = format_branches(graph, info, head, color, settings);
+    if let Some((name, trace)) = merged_branch(graph, settings, info)? {
+        let arrow = if settings.characters.chars.iter().all(char::is_ascii) {
+            "<-"
+        } else {
+            "\u{2190}"
+        };
+        let label = format!("{} {}", arrow, name);
+        match trace.filter(|_| color) {
+            Some(trace) => {
+                let branch_color = graph.all_branches[trace].visual.term_color;
+                write!(branch_str, " {}", Paint::fixed(branch_color, label))
+            }
+            None => write!(branch_str, " {}", label),
+        }
+        .unwrap();
+    }
 
     let hash_color = if color { Some(HASH_COLOR) } else { None };
 
-    crate::print::format::format(&commit, branch_str, wrapping, hash_color, format)
+    let lines = match graph.commit(info.oid) {
+        Ok(commit) => crate::print::format::format(
+            &commit,
+            branch_str,
+            wrapping,
+            hash_color,
+            &settings.format,
+            &settings.control_chars,
+        )?,
+        // Graphs without a repository, like those of a commit list, have only metadata
+        Err(_) => {
+            let meta = graph
+                .metadata(info.oid)
+                .map_err(|err| err.message().to_string())?;
+            crate::print::format::format_oneline(
+                info.oid,
+                &meta.summary,
+                branch_str,
+                wrapping,
+                hash_color,
+                &settings.control_chars,
+            )
+        }
+    };
+    if grayed {
+        Ok(lines
+            .into_iter()
+            .map(|line| Paint::fixed(BADGE_COLOR, line).to_string())
+            .collect())
+    } else {
+        Ok(lines)
+    }
 }
 
-/// Format branches and tags.
+/// Format branches and tags, with their type badges if enabled.
 pub fn format_branches(
     graph: &GitGraph,
     info: &CommitInfo,
     head: Option<&HeadInfo>,
     color: bool,
+    settings: &Settings,
 ) -> String {
+    let label_colors = settings.label_colors;
+    let head_color = label_term_color(label_colors, RefKind::Head, HEAD_COLOR);
     let curr_color = info
         .branch_trace
         .map(|branch_idx| &graph.all_branches[branch_idx].visual.term_color);
@@ -661,9 +1383,9 @@ pub fn format_branches(
 
     let head_str = "HEAD ->";
     if let Some(head) = head {
-        if !head.is_branch {
+        if head.branch().is_none() {
             if color {
-                write!(branch_str, " {}", Paint::fixed(HEAD_COLOR, head_str))
+                write!(branch_str, " {}", Paint::fixed(head_color, head_str))
             } else {
                 write!(branch_str, " {}", head_str)
             }
@@ -676,7 +1398,7 @@ pub fn format_branches(
 
         let branches = info.branches.iter().sorted_by_key(|br| {
             if let Some(head) = head {
-                head.name != graph.all_branches[**br].name
+                head.branch() != Some(&graph.all_branches[**br].name)
             } else {
                 false
             }
@@ -684,12 +1406,17 @@ pub fn format_branches(
 
         for (idx, branch_index) in branches.enumerate() {
             let branch = &graph.all_branches[*branch_index];
-            let branch_color = branch.visual.term_color;
+            let kind = if branch.is_remote {
+                RefKind::Remote
+            } else {
+                RefKind::Local
+            };
+            let branch_color = label_term_color(label_colors, kind, branch.visual.term_color);
 
             if let Some(head) = head {
-                if idx == 0 && head.is_branch {
+                if idx == 0 && head.branch().is_some() {
                     if color {
-                        write!(branch_str, "{} ", Paint::fixed(14, head_str))
+                        write!(branch_str, "{} ", Paint::fixed(head_color, head_str))
                     } else {
                         write!(branch_str, "{} ", head_str)
                     }
@@ -697,12 +1424,38 @@ pub fn format_branches(
                 }
             }
 
-            if color {
-                write!(branch_str, "{}", Paint::fixed(branch_color, &branch.name))
-            } else {
-                write!(branch_str, "{}", &branch.name)
+            let protected = is_protected(settings, branch);
+            match (color, protected) {
+                (true, true) => write!(
+                    branch_str,
+                    "{} {}",
+                    Paint::fixed(branch_color, &branch.name).bold().underline(),
+                    PROTECTED_BADGE
+                ),
+                (true, false) => write!(branch_str, "{}", Paint::fixed(branch_color, &branch.name)),
+                (false, true) => write!(branch_str, "{} {}", &branch.name, PROTECTED_BADGE),
+                (false, false) => write!(branch_str, "{}", &branch.name),
             }
             .unwrap();
+            let ascii = settings.characters.chars.iter().all(char::is_ascii);
+            if let Some(label) = ahead_behind_label(branch, ascii) {
+                if color {
+                    write!(branch_str, " {}", Paint::fixed(BADGE_COLOR, label))
+                } else {
+                    write!(branch_str, " {}", label)
+                }
+                .unwrap();
+            }
+            write_badge(&mut branch_str, type_badge(settings, branch), color);
+            write_badge(&mut branch_str, branch.is_wip.then_some("wip"), color);
+            if let Some(label) = remote_head_label(branch) {
+                if color {
+                    write!(branch_str, ", {}", Paint::fixed(branch_color, label))
+                } else {
+                    write!(branch_str, ", {}", label)
+                }
+                .unwrap();
+            }
 
             if idx < info.branches.len() - 1 {
                 write!(branch_str, ", ").unwrap();
@@ -715,14 +1468,19 @@ pub fn format_branches(
         write!(branch_str, " [").unwrap();
         for (idx, tag_index) in info.tags.iter().enumerate() {
             let tag = &graph.all_branches[*tag_index];
-            let tag_color = curr_color.unwrap_or(&tag.visual.term_color);
+            let tag_color = label_term_color(
+                label_colors,
+                RefKind::Tag,
+                *curr_color.unwrap_or(&tag.visual.term_color),
+            );
 
             if color {
-                write!(branch_str, "{}", Paint::fixed(*tag_color, &tag.name[5..]))
+                write!(branch_str, "{}", Paint::fixed(tag_color, &tag.name[5..]))
             } else {
                 write!(branch_str, "{}", &tag.name[5..])
             }
             .unwrap();
+            write_badge(&mut branch_str, type_badge(settings, tag), color);
 
             if idx < info.tags.len() - 1 {
                 write!(branch_str, ", ").unwrap();
@@ -731,9 +1489,39 @@ pub fn format_branches(
         write!(branch_str, "]").unwrap();
     }
 
+    for worktree in graph
+        .worktrees
+        .iter()
+        .filter(|worktree| worktree.head.is_at(info.oid))
+    {
+        let label = format!("{{worktree {}}}", worktree.name);
+        if color {
+            write!(branch_str, " {}", Paint::fixed(head_color, label))
+        } else {
+            write!(branch_str, " {}", label)
+        }
+        .unwrap();
+    }
+
     branch_str
 }
 
+/// Writes a type badge in brackets after a label.
+fn write_badge(out: &mut String, badge: Option<&str>, color: bool) {
+    if let Some(badge) = badge {
+        if color {
+            write!(
+                out,
+                " {}",
+                Paint::fixed(BADGE_COLOR, format!("[{}]", badge))
+            )
+        } else {
+            write!(out, " [{}]", badge)
+        }
+        .unwrap();
+    }
+}
+
 /// Occupied row ranges
 enum Occ {
     /// Horizontal position of commit markers
@@ -781,6 +1569,12 @@ struct GridCell {
 }
 
 impl GridCell {
+    const EMPTY: GridCell = GridCell {
+        character: SPACE,
+        color: WHITE,
+        pers: u8::MAX,
+    };
+
     pub fn char(&self, characters: &Characters) -> char {
         characters.chars[self.character as usize]
     }
@@ -807,6 +1601,12 @@ impl Grid {
     pub fn reverse(&mut self) {
         self.data.reverse();
     }
+    pub fn row(&self, y: usize) -> &[GridCell] {
+        &self.data[y * self.width..(y + 1) * self.width]
+    }
+    pub fn cell(&self, x: usize, y: usize) -> GridCell {
+        self.data[self.index(x, y)]
+    }
     /// Turn a 2D coordinate into an index of Grid.data
     pub fn index(&self, x: usize, y: usize) -> usize {
         y * self.width + x
@@ -844,3 +1644,30 @@ impl Grid {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{highlight_matches, parse_rows};
+    use regex::Regex;
+
+    #[test]
+    fn parse_row_ranges() {
+        assert_eq!(parse_rows("100..200").unwrap(), 100..200);
+        assert_eq!(parse_rows("100..=199").unwrap(), 100..200);
+        assert_eq!(parse_rows("100..").unwrap(), 100..usize::MAX);
+        assert_eq!(parse_rows("..200").unwrap(), 0..200);
+        assert!(parse_rows("200..100").is_err());
+        assert!(parse_rows("100").is_err());
+    }
+
+    #[test]
+    fn highlight_matches_outside_color_codes() {
+        yansi::Paint::enable();
+        let pattern = Regex::new("fix|m").unwrap();
+        let line = "\x1b[38;5;11m1a2b3c4\x1b[0m fix parser";
+        assert_eq!(
+            highlight_matches(line, &pattern),
+            "\x1b[38;5;11m1a2b3c4\x1b[0m \x1b[1;38;5;9mfix\x1b[0m parser"
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! Sources of commits and refs for laying out graphs, see [RepoProvider].
+//!
+//! [GitGraph::from_provider](crate::graph::GitGraph::from_provider) lays out the history
+//! of any provider: a git repository, or commits held in memory, like in tests or
+//! when the commits come from another tool (see [crate::layout]).
+
+use crate::graph::{CommitMeta, HeadInfo};
+use git2::{Oid, Repository, Sort};
+use std::collections::{BTreeMap, HashMap};
+
+/// The parents and metadata of a commit, as read from a [RepoProvider].
+pub struct ProvidedCommit {
+    pub parents: Vec<Oid>,
+    pub meta: CommitMeta,
+}
+
+/// A source of refs and commits to lay out.
+pub trait RepoProvider {
+    /// Full names of branches and tags, like `refs/heads/main` or `refs/tags/v1.0`,
+    /// with the commits they point to.
+    fn refs(&self) -> Result<Vec<(String, Oid)>, String>;
+    /// The current HEAD, if there is one. Unborn in repositories without commits.
+    fn head(&self) -> Result<Option<HeadInfo>, String>;
+    /// The ids of the commits to lay out, children before their parents.
+    fn walk(&self, max_count: Option<usize>) -> Result<Vec<Oid>, String>;
+    /// The parents and metadata of a commit returned by [RepoProvider::walk].
+    fn commit(&self, oid: Oid) -> Result<ProvidedCommit, String>;
+    /// The git repository behind the provider, if any. Graphs of a repository are laid
+    /// out from the repository itself, with all options, like filters and diff stats.
+    fn repository(&self) -> Option<&Repository> {
+        None
+    }
+}
+
+impl RepoProvider for Repository {
+    fn refs(&self) -> Result<Vec<(String, Oid)>, String> {
+        let to_string = |err: git2::Error| err.message().to_string();
+        let mut refs = vec![];
+        for reference in self.references().map_err(to_string)? {
+            let reference = reference.map_err(to_string)?;
+            let Some(name) = reference.name() else {
+                continue;
+            };
+            if !(reference.is_branch() || reference.is_remote() || reference.is_tag()) {
+                continue;
+            }
+            // Annotated tags point to tag objects, lightweight tags to commits
+            if let Ok(commit) = reference.peel_to_commit() {
+                refs.push((name.to_string(), commit.id()));
+            }
+        }
+        Ok(refs)
+    }
+
+    fn head(&self) -> Result<Option<HeadInfo>, String> {
+        HeadInfo::of_repository(self).map(Some)
+    }
+
+    fn walk(&self, max_count: Option<usize>) -> Result<Vec<Oid>, String> {
+        let to_string = |err: git2::Error| err.message().to_string();
+        let mut walk = self.revwalk().map_err(to_string)?;
+        walk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)
+            .map_err(to_string)?;
+        for (_, target) in RepoProvider::refs(self)? {
+            walk.push(target).map_err(to_string)?;
+        }
+        if let Some(oid) = RepoProvider::head(self)?.and_then(|head| head.oid()) {
+            walk.push(oid).map_err(to_string)?;
+        }
+        walk.take(max_count.unwrap_or(usize::MAX))
+            .collect::<Result<_, _>>()
+            .map_err(to_string)
+    }
+
+    fn commit(&self, oid: Oid) -> Result<ProvidedCommit, String> {
+        let commit = self
+            .find_commit(oid)
+            .map_err(|err| err.message().to_string())?;
+        Ok(ProvidedCommit {
+            parents: commit.parent_ids().collect(),
+            meta: CommitMeta::new(&commit),
+        })
+    }
+
+    fn repository(&self) -> Option<&Repository> {
+        Some(self)
+    }
+}
+
+/// Commits and refs held in memory. Commits are laid out in the order they were added,
+/// so children must be added before their parents.
+#[derive(Default)]
+pub struct MemoryRepo {
+    commits: Vec<(Oid, Vec<Oid>, CommitMeta)>,
+    indices: HashMap<Oid, usize>,
+    refs: BTreeMap<String, Oid>,
+    head: Option<HeadInfo>,
+}
+
+impl MemoryRepo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a commit. Parents need not be added, the history is cut off at missing parents.
+    pub fn add_commit(&mut self, oid: Oid, parents: &[Oid], meta: CommitMeta) {
+        self.indices.insert(oid, self.commits.len());
+        self.commits.push((oid, parents.to_vec(), meta));
+    }
+
+    /// Sets a ref, by its full name like `refs/heads/main`.
+    pub fn set_ref(&mut self, name: &str, target: Oid) {
+        self.refs.insert(name.to_string(), target);
+    }
+
+    pub fn set_head(&mut self, head: HeadInfo) {
+        self.head = Some(head);
+    }
+}
+
+impl RepoProvider for MemoryRepo {
+    fn refs(&self) -> Result<Vec<(String, Oid)>, String> {
+        Ok(self
+            .refs
+            .iter()
+            .map(|(name, target)| (name.clone(), *target))
+            .collect())
+    }
+
+    fn head(&self) -> Result<Option<HeadInfo>, String> {
+        Ok(self.head.clone())
+    }
+
+    fn walk(&self, max_count: Option<usize>) -> Result<Vec<Oid>, String> {
+        Ok(self
+            .commits
+            .iter()
+            .take(max_count.unwrap_or(usize::MAX))
+            .map(|(oid, _, _)| *oid)
+            .collect())
+    }
+
+    fn commit(&self, oid: Oid) -> Result<ProvidedCommit, String> {
+        let (_, parents, meta) = self
+            .indices
+            .get(&oid)
+            .map(|&idx| &self.commits[idx])
+            .ok_or_else(|| format!("Commit {} not found", oid))?;
+        Ok(ProvidedCommit {
+            parents: parents.clone(),
+            meta: meta.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MemoryRepo, RepoProvider};
+    use crate::testing::TestRepo;
+    use git2::Oid;
+
+    #[test]
+    fn repository_and_memory_providers_agree() {
+        let repo = TestRepo::bare("git-graph-provider");
+        let sig = TestRepo::signature();
+        let root = repo.commit("root", &[]);
+        let feature = repo.commit("feature", &[root]);
+        let merge = repo.commit("Merge branch 'feature'", &[root, feature]);
+        repo.reference("refs/heads/main", merge, false, "").unwrap();
+        repo.tag(
+            "v1",
+            &repo.find_object(root, None).unwrap(),
+            &sig,
+            "",
+            false,
+        )
+        .unwrap();
+        repo.note(&sig, &sig, None, root, "note", false).unwrap();
+        repo.set_head("refs/heads/main").unwrap();
+
+        // Tags are peeled, notes refs skipped
+        let mut refs = RepoProvider::refs(&*repo).unwrap();
+        refs.sort();
+        assert_eq!(
+            refs,
+            [
+                ("refs/heads/main".to_string(), merge),
+                ("refs/tags/v1".to_string(), root)
+            ]
+        );
+        let walk = repo.walk(None).unwrap();
+        assert_eq!(walk, [merge, feature, root]);
+        assert_eq!(repo.walk(Some(2)).unwrap().len(), 2);
+
+        let mut memory = MemoryRepo::new();
+        for oid in &walk {
+            let commit = RepoProvider::commit(&*repo, *oid).unwrap();
+            memory.add_commit(*oid, &commit.parents, commit.meta);
+        }
+        for (name, target) in &refs {
+            memory.set_ref(name, *target);
+        }
+        memory.set_head(RepoProvider::head(&*repo).unwrap().unwrap());
+
+        assert_eq!(memory.refs().unwrap(), refs);
+        assert_eq!(memory.walk(None).unwrap(), walk);
+        assert_eq!(memory.head().unwrap().unwrap().branch(), Some("main"));
+        let merge_commit = memory.commit(merge).unwrap();
+        assert_eq!(merge_commit.parents, [root, feature]);
+        assert_eq!(merge_commit.meta.summary, b"Merge branch 'feature'");
+        assert!(memory.commit(Oid::zero()).is_err());
+    }
+}
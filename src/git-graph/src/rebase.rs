@@ -0,0 +1,513 @@
+//! Simulation of a rebase, for previewing its result without touching the repository.
+//!
+//! Replayed commits are written to a [ScratchRepository], a temporary bare repository
+//! sharing the objects of the original one. Refs, index and working tree of the
+//! original repository are never modified.
+
+use git2::{Commit, ObjectType, Oid, Repository, Sort};
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Counts the temporary directories created by this process, for unique names.
+static TEMP_DIRS: AtomicUsize = AtomicUsize::new(0);
+
+/// What a rebase would do with a commit of the rebased branch.
+#[derive(Debug, PartialEq, Eq)]
+pub enum StepKind {
+    /// Replayed as the given new commit
+    Pick(Oid),
+    /// Dropped, as its patch is already upstream in the given commit
+    Upstream(Oid),
+    /// Dropped, as its changes are already contained in the new base
+    Empty,
+    /// Stops the rebase with conflicts in the given paths
+    Conflict(Vec<String>),
+    /// Not replayed, as the rebase stopped before
+    Pending,
+}
+
+/// A commit of the rebased branch, and what happens to it.
+pub struct RebaseStep {
+    pub oid: Oid,
+    pub kind: StepKind,
+}
+
+/// The outcome of a simulated rebase.
+pub struct RebasePlan {
+    /// The commits of the rebased branch, oldest first
+    pub steps: Vec<RebaseStep>,
+    /// The tip of the rebased branch: the last replayed commit, or the new base
+    pub tip: Oid,
+}
+
+/// Simulates rebasing `branch` onto `onto`, like `git rebase <onto> <branch>`.
+///
+/// Non-merge commits of `branch` not reachable from `onto` are replayed in order,
+/// except those with the patch-id of a commit of `onto` (already upstream). Replaying
+/// stops at the first conflict. New commits are written to `repository`, which should
+/// be a [ScratchRepository] to leave the original repository untouched.
+pub fn simulate(repository: &Repository, onto: Oid, branch: Oid) -> Result<RebasePlan, String> {
+    let upstream = patch_ids(repository, onto, branch)?;
+
+    let mut tip = repository
+        .find_commit(onto)
+        .map_err(|err| err.message().to_string())?;
+    let mut steps = vec![];
+    let mut stopped = false;
+    for oid in walk(repository, branch, onto, Sort::TOPOLOGICAL | Sort::REVERSE)? {
+        let commit = repository
+            .find_commit(oid)
+            .map_err(|err| err.message().to_string())?;
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        let kind = if stopped {
+            StepKind::Pending
+        } else if let Some(upstream) = upstream.get(&patch_id(repository, &commit)?) {
+            StepKind::Upstream(*upstream)
+        } else {
+            let kind = replay(repository, &commit, &tip)?;
+            if let StepKind::Pick(new_oid) = kind {
+                tip = repository
+                    .find_commit(new_oid)
+                    .map_err(|err| err.message().to_string())?;
+            }
+            stopped = matches!(kind, StepKind::Conflict(_));
+            kind
+        };
+        steps.push(RebaseStep { oid, kind });
+    }
+    Ok(RebasePlan {
+        steps,
+        tip: tip.id(),
+    })
+}
+
+/// Cherry-picks `commit` onto `onto`, writing the new commit if there are no conflicts.
+fn replay(repository: &Repository, commit: &Commit, onto: &Commit) -> Result<StepKind, String> {
+    let mut index = repository
+        .cherrypick_commit(commit, onto, 0, None)
+        .map_err(|err| err.message().to_string())?;
+    if index.has_conflicts() {
+        let paths = index
+            .conflicts()
+            .map_err(|err| err.message().to_string())?
+            .filter_map(|conflict| {
+                let conflict = conflict.ok()?;
+                let entry = conflict.our.or(conflict.their).or(conflict.ancestor)?;
+                Some(String::from_utf8_lossy(&entry.path).to_string())
+            })
+            .collect();
+        return Ok(StepKind::Conflict(paths));
+    }
+    let tree_oid = index
+        .write_tree_to(repository)
+        .map_err(|err| err.message().to_string())?;
+    if tree_oid == onto.tree_id() {
+        return Ok(StepKind::Empty);
+    }
+    let tree = repository
+        .find_tree(tree_oid)
+        .map_err(|err| err.message().to_string())?;
+    let new_oid = repository
+        .commit(
+            None,
+            &commit.author(),
+            &commit.committer(),
+            commit.message_raw().unwrap_or(""),
+            &tree,
+            &[onto],
+        )
+        .map_err(|err| err.message().to_string())?;
+    Ok(StepKind::Pick(new_oid))
+}
+
+/// Patch-ids of the non-merge commits reachable from `from`, but not from `hide`.
+fn patch_ids(repository: &Repository, from: Oid, hide: Oid) -> Result<HashMap<Oid, Oid>, String> {
+    let mut ids = HashMap::new();
+    for oid in walk(repository, from, hide, Sort::TOPOLOGICAL)? {
+        let commit = repository
+            .find_commit(oid)
+            .map_err(|err| err.message().to_string())?;
+        if commit.parent_count() <= 1 {
+            ids.insert(patch_id(repository, &commit)?, oid);
+        }
+    }
+    Ok(ids)
+}
+
+/// The patch-id of a commit's changes against its first parent, like `git patch-id --stable`.
+fn patch_id(repository: &Repository, commit: &Commit) -> Result<Oid, String> {
+    let parent_tree = match commit.parent(0) {
+        Ok(parent) => Some(parent.tree().map_err(|err| err.message().to_string())?),
+        Err(_) => None,
+    };
+    let tree = commit.tree().map_err(|err| err.message().to_string())?;
+    repository
+        .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)
+        .and_then(|diff| diff.patchid(None))
+        .map_err(|err| err.message().to_string())
+}
+
+/// The commits reachable from `from`, but not from `hide`.
+fn walk(repository: &Repository, from: Oid, hide: Oid, sort: Sort) -> Result<Vec<Oid>, String> {
+    let mut walk = repository
+        .revwalk()
+        .map_err(|err| err.message().to_string())?;
+    walk.set_sorting(sort)
+        .map_err(|err| err.message().to_string())?;
+    walk.push(from).map_err(|err| err.message().to_string())?;
+    walk.hide(hide).map_err(|err| err.message().to_string())?;
+    walk.collect::<Result<Vec<_>, _>>()
+        .map_err(|err| err.message().to_string())
+}
+
+/// A temporary bare repository sharing the objects of another repository through
+/// alternates, for writing simulated commits and refs, or holding a clone of a remote
+/// or the contents of a bundle.
+/// Deleted when dropped.
+pub struct ScratchRepository {
+    path: PathBuf,
+}
+
+impl ScratchRepository {
+    /// Creates a scratch repository for the given repository.
+    pub fn new(repository: &Repository) -> Result<Self, String> {
+        let scratch = Self::empty()?;
+        let objects = repository.commondir().join("objects");
+        let objects = objects.canonicalize().unwrap_or(objects);
+        std::fs::write(
+            scratch.path.join("objects").join("info").join("alternates"),
+            format!("{}\n", objects.display()),
+        )
+        .map_err(|err| err.to_string())?;
+        Ok(scratch)
+    }
+
+    /// Creates an empty scratch repository, sharing no objects.
+    pub fn empty() -> Result<Self, String> {
+        let scratch = ScratchRepository {
+            path: create_temp_dir("git-graph-scratch")?,
+        };
+        Repository::init_bare(&scratch.path).map_err(|err| err.message().to_string())?;
+        Ok(scratch)
+    }
+
+    /// Clones a remote repository into a scratch repository, like `git clone --bare`, to
+    /// show repositories that were not cloned. Only commits and trees are fetched: blobs
+    /// are missing from remotes supporting partial clones, and can't be read later.
+    /// Requires `git` on the path.
+    pub fn clone_remote(url: &str) -> Result<Self, String> {
+        let scratch = ScratchRepository {
+            path: create_temp_dir("git-graph-remote")?,
+        };
+        let output = Command::new("git")
+            .args([
+                "clone",
+                "--bare",
+                "--quiet",
+                "--filter=blob:none",
+                "--",
+                url,
+            ])
+            .arg(&scratch.path)
+            .output()
+            .map_err(|err| format!("Can't run git: {}", err))?;
+        if output.status.success() {
+            Ok(scratch)
+        } else {
+            let message = String::from_utf8_lossy(&output.stderr);
+            Err(format!(
+                "Can't clone {}: {}",
+                url,
+                message.lines().last().unwrap_or_default()
+            ))
+        }
+    }
+
+    /// Indexes the refs and objects of a bundle file, as written by `git bundle create`,
+    /// into a scratch repository, to show the bundle before unbundling it. The commits
+    /// a bundle depends on, but doesn't contain, are its history's shallow boundary.
+    pub fn from_bundle(file: &Path) -> Result<Self, String> {
+        let data =
+            std::fs::read(file).map_err(|err| format!("Can't read {}: {}", file.display(), err))?;
+        let header = read_bundle_header(&data)
+            .ok_or_else(|| format!("{} is not a git bundle", file.display()))?;
+        let scratch = ScratchRepository {
+            path: create_temp_dir("git-graph-bundle")?,
+        };
+        let repository =
+            Repository::init_bare(&scratch.path).map_err(|err| err.message().to_string())?;
+        let to_string = |err: git2::Error| err.message().to_string();
+
+        let odb = repository.odb().map_err(to_string)?;
+        let mut writer = odb.packwriter().map_err(to_string)?;
+        writer
+            .write_all(&data[header.pack..])
+            .map_err(|err| err.to_string())?;
+        writer
+            .commit()
+            .map_err(|err| format!("Can't index bundle {}: {}", file.display(), err.message()))?;
+
+        // Commits whose parents are prerequisites end the history, like in shallow clones
+        if !header.prerequisites.is_empty() {
+            let prerequisites: HashSet<Oid> = header.prerequisites.iter().copied().collect();
+            let mut boundary = vec![];
+            odb.foreach(|oid| {
+                if let Ok(commit) = repository.find_commit(*oid) {
+                    if commit.parent_ids().any(|id| prerequisites.contains(&id)) {
+                        boundary.push(oid.to_string());
+                    }
+                }
+                true
+            })
+            .map_err(to_string)?;
+            boundary.push(String::new());
+            std::fs::write(scratch.path.join("shallow"), boundary.join("\n"))
+                .map_err(|err| err.to_string())?;
+        }
+
+        let mut head = None;
+        for (name, oid) in &header.refs {
+            if name == "HEAD" {
+                head = Some(*oid);
+            } else {
+                repository
+                    .reference(name, *oid, true, "bundle")
+                    .map_err(to_string)?;
+            }
+        }
+        // HEAD is the branch it points to, or the first branch if the bundle has no HEAD
+        let branch = header.refs.iter().find(|(name, oid)| {
+            name.starts_with("refs/heads/") && head.is_none_or(|head| head == *oid)
+        });
+        match (
+            branch,
+            head.or_else(|| header.refs.first().map(|(_, oid)| *oid)),
+        ) {
+            (Some((name, _)), _) => repository.set_head(name).map_err(to_string)?,
+            (None, Some(oid)) => {
+                let commit = repository
+                    .find_object(oid, None)
+                    .and_then(|object| object.peel(ObjectType::Commit))
+                    .map_err(to_string)?;
+                repository
+                    .set_head_detached(commit.id())
+                    .map_err(to_string)?
+            }
+            (None, None) => return Err(format!("Bundle {} has no refs", file.display())),
+        }
+        Ok(scratch)
+    }
+
+    /// Opens the scratch repository.
+    pub fn open(&self) -> Result<Repository, String> {
+        // Unlike `open_bare`, reads the `shallow` file of bundles
+        Repository::open(&self.path).map_err(|err| err.message().to_string())
+    }
+}
+
+/// Whether the file at `path` is a git bundle, in version 2 or 3 of the format.
+pub fn is_bundle(path: &Path) -> bool {
+    let mut start = [0; 16];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut start))
+        .is_ok()
+        && (&start == b"# v2 git bundle\n" || &start == b"# v3 git bundle\n")
+}
+
+/// The refs and prerequisites listed by a bundle, and the offset of its pack data.
+struct BundleHeader {
+    refs: Vec<(String, Oid)>,
+    prerequisites: Vec<Oid>,
+    pack: usize,
+}
+
+/// Reads the header of a bundle: the signature, capabilities (from version 3),
+/// prerequisites like `-<oid> <comment>` and refs like `<oid> <name>`, up to an empty line.
+fn read_bundle_header(data: &[u8]) -> Option<BundleHeader> {
+    let mut header = BundleHeader {
+        refs: vec![],
+        prerequisites: vec![],
+        pack: 0,
+    };
+    let mut lines = data.split(|byte| *byte == b'\n');
+    let signature = lines.next()?;
+    if signature != b"# v2 git bundle" && signature != b"# v3 git bundle" {
+        return None;
+    }
+    header.pack = signature.len() + 1;
+    for line in lines {
+        header.pack += line.len() + 1;
+        if line.is_empty() {
+            return Some(header);
+        }
+        let line = String::from_utf8_lossy(line);
+        if line.starts_with('@') {
+            continue;
+        }
+        if let Some(prerequisite) = line.strip_prefix('-') {
+            let oid = prerequisite.split(' ').next()?;
+            header.prerequisites.push(Oid::from_str(oid).ok()?);
+        } else {
+            let (oid, name) = line.split_once(' ')?;
+            header
+                .refs
+                .push((name.to_string(), Oid::from_str(oid).ok()?));
+        }
+    }
+    None
+}
+
+/// Creates a new, empty directory in the system's temporary directory, for a single
+/// user. Existing directories are never reused, so each caller owns its directory,
+/// even with other instances in the same process or other processes sharing the
+/// temporary directory.
+pub fn create_temp_dir(prefix: &str) -> Result<PathBuf, String> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.subsec_nanos());
+    let mut builder = std::fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    for _ in 0..100 {
+        let count = TEMP_DIRS.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!(
+            "{}-{}-{}-{:08x}",
+            prefix,
+            std::process::id(),
+            count,
+            nanos
+        ));
+        match builder.create(&path) {
+            Ok(()) => return Ok(path),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(format!("Can't create {}: {}", path.display(), err)),
+        }
+    }
+    Err("Can't create a temporary directory".to_string())
+}
+
+impl Drop for ScratchRepository {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_bundle, simulate, ScratchRepository, StepKind};
+    use crate::testing::{TempDir, TestRepo};
+    use git2::{Oid, Repository};
+
+    /// Commits a file with the given content on top of `parent`, with the content as message.
+    fn commit(repo: &TestRepo, parent: Option<Oid>, file: &str, content: &str) -> Oid {
+        let blob = repo.blob(content.as_bytes()).unwrap();
+        let base = parent.map(|oid| repo.find_commit(oid).unwrap().tree().unwrap());
+        let mut builder = repo.treebuilder(base.as_ref()).unwrap();
+        builder.insert(file, blob, 0o100644).unwrap();
+        let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+        let parents: Vec<_> = parent.into_iter().collect();
+        repo.commit_tree(content, &tree, &parents)
+    }
+
+    #[test]
+    fn simulate_drops_upstream_patches_and_stops_at_conflicts() {
+        let repo = TestRepo::bare("git-graph-rebase");
+        let root = commit(&repo, None, "a", "root");
+        let upstream = commit(&repo, Some(root), "b", "fix");
+        let main = commit(&repo, Some(upstream), "c", "main");
+
+        // The same change as upstream, with a different message
+        let feature = commit(&repo, Some(root), "b", "fix");
+        let feature = repo.find_commit(feature).unwrap();
+        let feature = feature
+            .amend(None, None, None, None, Some("Cherry-picked fix"), None)
+            .unwrap();
+        let feature = commit(&repo, Some(feature), "d", "new");
+        let conflict = commit(&repo, Some(feature), "c", "feature");
+        let pending = commit(&repo, Some(conflict), "e", "later");
+
+        let scratch = ScratchRepository::new(&repo).unwrap();
+        let plan = simulate(&scratch.open().unwrap(), main, pending).unwrap();
+        let kinds: Vec<_> = plan.steps.iter().map(|step| &step.kind).collect();
+        assert_eq!(kinds.len(), 4);
+        assert_eq!(kinds[0], &StepKind::Upstream(upstream));
+        assert_eq!(kinds[1], &StepKind::Pick(plan.tip));
+        assert_eq!(kinds[2], &StepKind::Conflict(vec!["c".to_string()]));
+        assert_eq!(kinds[3], &StepKind::Pending);
+        assert_eq!(plan.steps[3].oid, pending);
+
+        // Nothing is written to the original repository
+        assert!(repo.find_commit(plan.tip).is_err());
+    }
+
+    #[test]
+    fn scratch_repositories_of_linked_worktrees_share_objects() {
+        let repo = TestRepo::init("git-graph-scratch-worktree");
+        let root = commit(&repo, None, "a", "root");
+        repo.reference("refs/heads/main", root, true, "").unwrap();
+        repo.set_head("refs/heads/main").unwrap();
+        let dir = TempDir::new("git-graph-scratch-linked");
+        let worktree = repo
+            .worktree("linked", &dir.path().join("linked"), None)
+            .unwrap();
+        let linked = Repository::open_from_worktree(&worktree).unwrap();
+
+        let scratch = ScratchRepository::new(&linked).unwrap();
+        assert!(scratch.open().unwrap().find_commit(root).is_ok());
+    }
+
+    #[test]
+    fn bundles_are_indexed_with_prerequisites_as_boundary() {
+        let repo = TestRepo::bare("git-graph-bundled");
+        let path = repo.path();
+        let root = commit(&repo, None, "a", "root");
+        let main = commit(&repo, Some(root), "b", "main");
+        let feature = commit(&repo, Some(main), "c", "feature");
+
+        // A bundle of `root..feature`, like `git bundle create`
+        let mut walk = repo.revwalk().unwrap();
+        walk.push(feature).unwrap();
+        walk.hide(root).unwrap();
+        let mut builder = repo.packbuilder().unwrap();
+        builder.insert_walk(&mut walk).unwrap();
+        let mut pack = git2::Buf::new();
+        builder.write_buf(&mut pack).unwrap();
+        let mut bundle = format!(
+            "# v2 git bundle\n-{} root\n{} HEAD\n{} refs/heads/main\n{} refs/heads/feature\n\n",
+            root, feature, main, feature
+        )
+        .into_bytes();
+        bundle.extend_from_slice(&pack);
+        let file = path.join("test.bundle");
+        std::fs::write(&file, bundle).unwrap();
+        assert!(is_bundle(&file));
+        assert!(!is_bundle(&path.join("HEAD")));
+
+        let scratch = ScratchRepository::from_bundle(&file).unwrap();
+        let bundled = scratch.open().unwrap();
+        assert_eq!(bundled.head().unwrap().name(), Some("refs/heads/feature"));
+        assert_eq!(bundled.refname_to_id("refs/heads/main").unwrap(), main);
+        assert!(bundled.find_commit(root).is_err());
+        let shallow = std::fs::read_to_string(bundled.path().join("shallow")).unwrap();
+        assert_eq!(shallow.trim(), main.to_string());
+        assert_eq!(bundled.find_commit(main).unwrap().parent_count(), 0);
+    }
+
+    #[test]
+    fn scratch_repositories_have_own_directories() {
+        let first = ScratchRepository::empty().unwrap();
+        let second = ScratchRepository::empty().unwrap();
+        assert_ne!(first.path, second.path);
+
+        let second_repo = second.open().unwrap();
+        drop(first);
+        assert!(second.path.join("HEAD").exists());
+        assert!(second_repo.odb().is_ok());
+    }
+}
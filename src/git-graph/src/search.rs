@@ -0,0 +1,149 @@
+//! Filtering the commits of a graph by a query, as typed into the pager's filter input.
+//!
+//! The matching commits are laid out again on their own, with [restricted_settings],
+//! so that edges through hidden commits connect the matches directly.
+
+use crate::graph::{CommitInfo, GitGraph};
+use crate::settings::Settings;
+
+/// Prefix of queries matching the commits of a branch by name, instead of by text.
+pub const BRANCH_FILTER: &str = "branch:";
+
+/// Lower-case text of a commit for filtering: summary, author and refs.
+pub fn search_text(graph: &GitGraph, info: &CommitInfo) -> String {
+    let mut text = String::new();
+    if let Ok(meta) = graph.metadata(info.oid) {
+        for bytes in [&meta.summary, &meta.author_name, &meta.author_email] {
+            text.push_str(&String::from_utf8_lossy(bytes));
+            text.push('\n');
+        }
+    }
+    for idx in info.branches.iter().chain(&info.tags) {
+        text.push_str(&graph.all_branches[*idx].name);
+        text.push('\n');
+    }
+    text.to_lowercase()
+}
+
+/// The rows of the commits matching a query, case-insensitively, given the
+/// [search_text] of each commit. Queries starting with [BRANCH_FILTER] match the
+/// commits attributed to branches of that name.
+pub fn matching_rows(graph: &GitGraph, texts: &[String], query: &str) -> Vec<usize> {
+    let query = query.to_lowercase();
+    match query.strip_prefix(BRANCH_FILTER) {
+        Some(name) => (0..graph.commits.len())
+            .filter(|row| {
+                graph
+                    .branch_of(graph.commits[*row].oid)
+                    .is_some_and(|branch| branch.name.eq_ignore_ascii_case(name))
+            })
+            .collect(),
+        None => (0..texts.len())
+            .filter(|row| texts[*row].contains(&query))
+            .collect(),
+    }
+}
+
+/// Settings laying out only the commits at the given rows of a graph, without
+/// legend and summary.
+pub fn restricted_settings(settings: &Settings, graph: &GitGraph, rows: &[usize]) -> Settings {
+    let mut settings = settings.clone();
+    settings.commit_list = Some(rows.iter().map(|row| graph.commits[*row].oid).collect());
+    settings.legend = false;
+    settings.summary = false;
+    settings
+}
+
+/// For each of `num_lines` lines of the layout of some of a graph's commits, the line
+/// of the same commit in the output of the full graph. Other lines map to `None`.
+pub fn origin_lines(
+    graph: &GitGraph,
+    commit_lines: &[usize],
+    filtered: &GitGraph,
+    filtered_commit_lines: &[usize],
+    num_lines: usize,
+) -> Vec<Option<usize>> {
+    let mut origins = vec![None; num_lines];
+    for (info, line) in filtered.commits.iter().zip(filtered_commit_lines) {
+        if let Some(row) = graph.indices.get(&info.oid) {
+            origins[*line] = Some(commit_lines[*row]);
+        }
+    }
+    origins
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::print::unicode::print_unicode;
+    use crate::testing::{settings, TestRepo};
+    use git2::Oid;
+
+    #[test]
+    fn filter_lays_out_matches_again() {
+        let repo = TestRepo::bare("git-graph-search");
+        let c1 = repo.commit("Initial commit", &[]);
+        let c2 = repo.commit("Fix the parser", &[c1]);
+        let c3 = repo.commit("Update docs", &[c2]);
+        let c4 = repo.commit("Fix the printer", &[c3]);
+        let f1 = repo.commit("Add filters", &[c2]);
+        let f2 = repo.commit("Fix filters", &[f1]);
+        repo.reference("refs/heads/main", c4, true, "").unwrap();
+        repo.reference("refs/heads/feature/filter", f2, true, "")
+            .unwrap();
+
+        let settings = settings();
+        let graph = GitGraph::new(&repo, &settings, None).unwrap();
+        let texts: Vec<String> = graph
+            .commits
+            .iter()
+            .map(|info| search_text(&graph, info))
+            .collect();
+        let row = |oid: &Oid| graph.indices[oid];
+        assert!(texts[row(&c4)].contains("fix the printer"));
+        assert!(texts[row(&c4)].contains("a@x"));
+        assert!(texts[row(&c4)].contains("main"));
+
+        let oids_of = |rows: Vec<usize>| -> Vec<Oid> {
+            let mut oids: Vec<Oid> = rows.iter().map(|row| graph.commits[*row].oid).collect();
+            oids.sort();
+            oids
+        };
+        let mut expected = vec![c2, c4, f2];
+        expected.sort();
+        assert_eq!(oids_of(matching_rows(&graph, &texts, "FIX")), expected);
+        let mut expected = vec![f1, f2];
+        expected.sort();
+        assert_eq!(
+            oids_of(matching_rows(&graph, &texts, "branch:Feature/Filter")),
+            expected
+        );
+        assert!(matching_rows(&graph, &texts, "branch:fix").is_empty());
+
+        // Hidden commits in between are elided, the matches stay connected
+        let rows = matching_rows(&graph, &texts, "fix");
+        let restricted = restricted_settings(&settings, &graph, &rows);
+        let filtered = GitGraph::new(&repo, &restricted, None).unwrap();
+        assert_eq!(filtered.commits.len(), 3);
+        let parent = |oid: Oid| filtered.commits[filtered.indices[&oid]].parents[0];
+        assert_eq!(parent(c4), Some(c2));
+        assert_eq!(parent(f2), Some(c2));
+        assert_eq!(filtered.branch_of(f2).unwrap().name, "feature/filter");
+
+        let (_, _, commit_lines) = print_unicode(&graph, &settings).unwrap();
+        let (g_lines, _, filtered_lines) = print_unicode(&filtered, &restricted).unwrap();
+        let origins = origin_lines(
+            &graph,
+            &commit_lines,
+            &filtered,
+            &filtered_lines,
+            g_lines.len(),
+        );
+        assert_eq!(origins.len(), g_lines.len());
+        for oid in [c2, c4, f2] {
+            let line = filtered_lines[filtered.indices[&oid]];
+            assert_eq!(origins[line], Some(commit_lines[row(&oid)]));
+        }
+        assert_eq!(origins.iter().flatten().count(), 3);
+    }
+}
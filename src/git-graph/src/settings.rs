@@ -10,22 +10,64 @@
 //!   * [Characters] The symbols to use when rendering a graph as text.
 //!   * [BranchSettings] Control how a graph is formatted.
 //!   * [BranchOrder] Determines the left-to-right order of branches.
+//!   * [EmptyBranches] How branches without commits of their own are shown.
 //!   * [MergePatterns] Regex that extract branch names from a merge commit.
+//!   * [StashMode] Whether stash entries are shown in the graph.
+//!   * [GraphView] Restricts the graph to a subset of the history.
+//! * [KeyBindings] Keys of the interactive pager.
+//! * [CommitMap] Old and new commits of a history rewrite.
+//! * [Reviews] Review status of commits, for post-commit review.
+//! * [Actions] External commands runnable on a commit.
+//! * [Aliases] User-defined subcommands expanding to arguments.
 
-use crate::print::format::CommitFormat;
+use crate::build::BuildProgress;
+use crate::filter::Filter;
+use crate::print::colors::to_terminal_color;
+use crate::print::format::{CommitFormat, ControlChars};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+use git2::Oid;
+use lazy_static::lazy_static;
 use regex::{Error, Regex};
 use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
 use std::str::FromStr;
+use std::sync::Arc;
 
-/// Repository settings for the branching model.
+lazy_static! {
+    /// Color names and hex codes accepted for SVG output
+    static ref SVG_COLOR: Regex = Regex::new(r"^([a-zA-Z]+|#[0-9a-fA-F]{3}|#[0-9a-fA-F]{6})$").unwrap();
+    /// The first word of a persistence pattern, naming branch types without a type name
+    static ref PATTERN_WORD: Regex = Regex::new(r"[a-zA-Z][a-zA-Z0-9_-]*").unwrap();
+}
+
+/// Repository settings for the branching model, saved filters, protected branches and
+/// work-in-progress patterns.
 /// Used to read repo's git-graph.toml
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Default)]
 pub struct RepoSettings {
     /// The repository's branching model
-    pub model: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Selection expressions by name, for `--filter`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub filters: BTreeMap<String, String>,
+    /// Globs of the names of protected branches, marked with a lock badge
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub protected: Vec<String>,
+    /// Patterns for the tip commit subjects of work-in-progress branches,
+    /// replacing [DEFAULT_WIP_PATTERNS]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wip: Option<Vec<String>>,
 }
 
+/// Patterns for the tip commit subjects of work-in-progress branches, unless configured:
+/// subjects starting with `WIP`, and the commits to squash by `git rebase --autosquash`.
+pub const DEFAULT_WIP_PATTERNS: [&str; 3] =
+    [r"^[Ww][Ii][Pp]([^A-Za-z0-9_]|$)", "^fixup! ", "^squash! "];
+
 /// Ordering policy for branches in visual columns.
+#[derive(Clone)]
 pub enum BranchOrder {
     /// Recommended! Shortest branches are inserted left-most.
     ///
@@ -39,7 +81,388 @@ pub enum BranchOrder {
     LongestFirst(bool),
 }
 
+/// Handling of branches that have no commits of their own, as their commits are all
+/// claimed by other branches, e.g. a branch pointing into `main`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmptyBranches {
+    /// Only the labels are shown, at the branch's target commit.
+    Label,
+    /// The branches are not shown at all.
+    Hide,
+    /// The branches share an extra column, with a marker at each target commit.
+    Lane,
+}
+
+impl FromStr for EmptyBranches {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "label" => Ok(EmptyBranches::Label),
+            "hide" => Ok(EmptyBranches::Hide),
+            "lane" => Ok(EmptyBranches::Lane),
+            _ => Err(format!(
+                "Unknown empty branches mode '{}'. Must be one of [label|hide|lane]",
+                str
+            )),
+        }
+    }
+}
+
+/// Coloring of remote branches, relative to their local counterparts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RemoteColors {
+    /// Branches of the remote `origin` take the color of their local branch.
+    Origin,
+    /// Branches of any remote take the color of their local branch.
+    All,
+    /// Remote branches take the colors for unknown branches, distinct from local branches.
+    Distinct,
+}
+
+impl FromStr for RemoteColors {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "origin" => Ok(RemoteColors::Origin),
+            "all" => Ok(RemoteColors::All),
+            "distinct" => Ok(RemoteColors::Distinct),
+            _ => Err(format!(
+                "Unknown remote colors '{}'. Must be one of [origin|all|distinct]",
+                str
+            )),
+        }
+    }
+}
+
+/// Handling of stash entries (`refs/stash`) when walking the repository.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StashMode {
+    /// Stashes are excluded from the graph.
+    Hide,
+    /// Stash commits are shown as nodes, without their index/untracked parents.
+    Show,
+    /// Stash commits are shown as nodes, together with their index parent.
+    ShowWithParents,
+}
+
+impl FromStr for StashMode {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "hide" => Ok(StashMode::Hide),
+            "show" => Ok(StashMode::Show),
+            "show-with-parents" => Ok(StashMode::ShowWithParents),
+            _ => Err(format!(
+                "Unknown stash mode '{}'. Must be one of [hide|show|show-with-parents]",
+                str
+            )),
+        }
+    }
+}
+
+/// A search of diff contents, like git's pickaxe options, highlighting the matching commits.
+#[derive(Clone)]
+pub enum Pickaxe {
+    /// Commits changing the number of occurrences of a string, like `git log -S`
+    Occurrences(String),
+    /// Commits adding or removing lines matching a pattern, like `git log -G`
+    Lines(Regex),
+}
+
+/// A function or other symbol of a file, to show the commits changing, for `trace-symbol`.
+#[derive(Clone)]
+pub struct Symbol {
+    /// The file's path, relative to the repository root
+    pub path: String,
+    /// The name of the symbol
+    pub name: String,
+    /// Pattern for the line where the symbol is defined
+    pub definition: Regex,
+}
+
+impl Symbol {
+    /// Creates a symbol, matching its definition like `fn name`, `def name`, `class name`
+    /// or a C-style `int name(`.
+    pub fn new(path: &str, name: &str) -> Result<Self, String> {
+        if name.is_empty() {
+            return Err("Symbol name must not be empty".to_string());
+        }
+        let name_regex = regex::escape(name);
+        let definition = Regex::new(&format!(
+            r"(?-u:\b)(?:fn|def|func|function|class|struct|enum|trait|interface|type|sub)[ \t]+{0}(?-u:\b)|^[A-Za-z_][^;]*(?-u:\b){0}[ \t]*\([^;]*$",
+            name_regex
+        ))
+        .map_err(|err| err.to_string())?;
+        Ok(Symbol {
+            path: path.trim_start_matches("./").to_string(),
+            name: name.to_string(),
+            definition,
+        })
+    }
+}
+
+/// Globs for the names of the branches to show, like `release/*` or `origin/main`.
+/// The history of other branches is not walked at all.
+#[derive(Clone, Default)]
+pub struct BranchFilter {
+    /// Show only branches matching any of these. All branches if empty
+    pub include: Vec<Regex>,
+    /// Hide the branches matching any of these
+    pub exclude: Vec<Regex>,
+}
+
+impl BranchFilter {
+    /// Creates a filter from globs, see [crate::filter::glob].
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self, String> {
+        let globs = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|pattern| {
+                    crate::filter::glob(pattern)
+                        .map_err(|err| format!("Invalid branch pattern '{}': {}", pattern, err))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(BranchFilter {
+            include: globs(include)?,
+            exclude: globs(exclude)?,
+        })
+    }
+
+    /// Whether all branches are shown.
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Whether to show a branch, by its short name like `main` or `origin/main`.
+    pub fn matches(&self, name: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|glob| glob.is_match(name)))
+            && !self.exclude.iter().any(|glob| glob.is_match(name))
+    }
+}
+
+/// Restriction of the tags shown.
+#[derive(Clone, Default)]
+pub struct TagFilter {
+    /// Show only tags matching any of these globs. All tags if empty
+    pub include: Vec<Regex>,
+    /// Show only annotated tags, no lightweight ones
+    pub annotated_only: bool,
+    /// Show only the latest tags of each prefix, see [tag_prefix]
+    pub latest: Option<usize>,
+}
+
+impl TagFilter {
+    /// Creates a filter from globs, see [crate::filter::glob].
+    pub fn new(
+        include: &[String],
+        annotated_only: bool,
+        latest: Option<usize>,
+    ) -> Result<Self, String> {
+        let include = include
+            .iter()
+            .map(|pattern| {
+                crate::filter::glob(pattern)
+                    .map_err(|err| format!("Invalid tag pattern '{}': {}", pattern, err))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(TagFilter {
+            include,
+            annotated_only,
+            latest,
+        })
+    }
+
+    /// Whether all tags are shown.
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && !self.annotated_only && self.latest.is_none()
+    }
+
+    /// Selects the tags to show, given by name, whether they are annotated and the
+    /// time of their commit. The latest tags of a prefix are those of the newest commits.
+    pub fn select(&self, tags: &[(&str, bool, i64)]) -> Vec<bool> {
+        let mut keep: Vec<bool> = tags
+            .iter()
+            .map(|(name, annotated, _)| {
+                (self.include.is_empty() || self.include.iter().any(|glob| glob.is_match(name)))
+                    && (*annotated || !self.annotated_only)
+            })
+            .collect();
+        if let Some(latest) = self.latest {
+            let mut by_prefix: HashMap<&str, Vec<usize>> = HashMap::new();
+            for (idx, (name, _, _)) in tags.iter().enumerate().filter(|(idx, _)| keep[*idx]) {
+                by_prefix.entry(tag_prefix(name)).or_default().push(idx);
+            }
+            for mut indices in by_prefix.into_values() {
+                // Of tags on commits of the same time, the last name is the latest
+                indices.sort_by_key(|idx| std::cmp::Reverse((tags[*idx].2, tags[*idx].0)));
+                for idx in indices.into_iter().skip(latest) {
+                    keep[idx] = false;
+                }
+            }
+        }
+        keep
+    }
+}
+
+/// The prefix of a tag name before its version, like `v` of `v1.2.0`,
+/// or `lib-a/` of `lib-a/0.3`: everything before the first digit.
+pub fn tag_prefix(name: &str) -> &str {
+    let end = name
+        .find(|c: char| c.is_ascii_digit())
+        .unwrap_or(name.len());
+    &name[..end]
+}
+
+/// Restriction of the graph to a subset of the history.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphView {
+    /// The full history.
+    All,
+    /// Only commits pointed to by signed tags, plus the paths between them on
+    /// the branches carrying them, if among the [Settings::release_levels]
+    /// most persistent levels.
+    SignedReleases,
+    /// Only commits pointed to by the tags shown, with parents rewritten
+    /// to the nearest tagged ancestors.
+    Releases,
+}
+
+impl FromStr for GraphView {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "all" => Ok(GraphView::All),
+            "signed-releases" => Ok(GraphView::SignedReleases),
+            "releases" => Ok(GraphView::Releases),
+            _ => Err(format!(
+                "Unknown view '{}'. Must be one of [all|signed-releases|releases]",
+                str
+            )),
+        }
+    }
+}
+
+/// Parts of the output to print.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputParts {
+    /// Graph and commit metadata.
+    Full,
+    /// Only the graph, without commit metadata.
+    GraphOnly,
+    /// Only commit metadata, with the branch name of each commit in place of the graph.
+    NoGraph,
+}
+
+/// Coloring of ref labels (branches, tags, HEAD).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LabelColors {
+    /// Labels are drawn in the color of their branch.
+    Branch,
+    /// All labels are drawn in a fixed decoration color.
+    Fixed,
+    /// Labels are colored by ref type (local, remote, tag, HEAD).
+    RefType,
+}
+
+impl FromStr for LabelColors {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "branch" => Ok(LabelColors::Branch),
+            "fixed" => Ok(LabelColors::Fixed),
+            "ref-type" => Ok(LabelColors::RefType),
+            _ => Err(format!(
+                "Unknown label colors '{}'. Must be one of [branch|fixed|ref-type]",
+                str
+            )),
+        }
+    }
+}
+
+/// Color themes, replacing the branch colors of a branching model.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Theme {
+    /// The colors defined by the branching model.
+    Default,
+    /// Safe for red-green color blindness with reduced green sensitivity.
+    Deuteranopia,
+    /// Safe for red-green color blindness with reduced red sensitivity.
+    Protanopia,
+    /// Safe for blue-yellow color blindness.
+    Tritanopia,
+}
+
+impl FromStr for Theme {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "default" => Ok(Theme::Default),
+            "deuteranopia" => Ok(Theme::Deuteranopia),
+            "protanopia" => Ok(Theme::Protanopia),
+            "tritanopia" => Ok(Theme::Tritanopia),
+            _ => Err(format!(
+                "Unknown theme '{}'. Must be one of [default|deuteranopia|protanopia|tritanopia]",
+                str
+            )),
+        }
+    }
+}
+
+/// The hues replaced by themes, by their terminal and SVG color names.
+const THEME_HUES: [(&[&str], &[&str]); 6] = [
+    (&["blue"], &["blue"]),
+    (&["yellow"], &["orange", "yellow"]),
+    (&["magenta"], &["purple", "magenta", "violet"]),
+    (&["cyan"], &["turquoise", "cyan"]),
+    (&["green"], &["green"]),
+    (&["red"], &["red"]),
+];
+
+impl Theme {
+    /// The replacement of each of the [THEME_HUES], as a terminal color index and an SVG color.
+    fn palette(&self) -> Option<[(&'static str, &'static str); 6]> {
+        match self {
+            Theme::Default => None,
+            // Okabe-Ito palette
+            Theme::Deuteranopia => Some([
+                ("25", "#0072b2"),
+                ("214", "#e69f00"),
+                ("175", "#cc79a7"),
+                ("74", "#56b4e9"),
+                ("36", "#009e73"),
+                ("166", "#d55e00"),
+            ]),
+            // IBM design palette, without reds dimmed for protanopes
+            Theme::Protanopia => Some([
+                ("69", "#648fff"),
+                ("214", "#ffb000"),
+                ("162", "#dc267f"),
+                ("99", "#785ef0"),
+                ("36", "#009e73"),
+                ("202", "#fe6100"),
+            ]),
+            // Hues along the red-cyan axis, distinct in lightness
+            Theme::Tritanopia => Some([
+                ("25", "#0072b2"),
+                ("218", "#f4a6c0"),
+                ("89", "#882255"),
+                ("79", "#44bb99"),
+                ("142", "#999933"),
+                ("161", "#d81b60"),
+            ]),
+        }
+    }
+}
+
 /// Top-level settings
+#[derive(Clone)]
 pub struct Settings {
     /// Reverse the order of commits
     pub reverse_commit_order: bool,
@@ -49,16 +472,138 @@ pub struct Settings {
     pub compact: bool,
     /// Colored text-based graph
     pub colored: bool,
+    /// Show a legend of branch colors and columns
+    pub legend: bool,
+    /// Parts of the output to print
+    pub output_parts: OutputParts,
+    /// Prefix each commit with its row index
+    pub row_numbers: bool,
+    /// Append a footer with totals
+    pub summary: bool,
+    /// Coloring of ref labels
+    pub label_colors: LabelColors,
     /// Include remote branches?
     pub include_remote: bool,
+    /// Coloring of remote branches
+    pub remote_colors: RemoteColors,
+    /// How to handle stash entries
+    pub stash_mode: StashMode,
+    /// Subset of the history to show
+    pub view: GraphView,
+    /// Number of the most persistent levels of the branching model along which
+    /// [GraphView::SignedReleases] keeps the paths between signed releases
+    pub release_levels: usize,
+    /// Ascending thresholds of changed lines for commit impact levels.
+    /// Diff stats are only computed if this is set.
+    pub impact_thresholds: Option<Vec<usize>>,
+    /// Minimum size in bytes of added blobs to mark commits with a size badge.
+    /// Blob sizes are only computed if this is set.
+    pub large_blob_threshold: Option<u64>,
+    /// Number of recent commits of each submodule to show next to the graph.
+    /// Submodule commits set by the superproject's commits are only found if this is set.
+    pub submodules: Option<usize>,
+    /// Count the branches containing each commit, to mark where branches share history
+    pub branch_counts: bool,
+    /// Count the commits local branches are ahead of and behind their upstream branches
+    pub ahead_behind: bool,
+    /// Group `fixup!`, `squash!` and `amend!` commits with the commits they will be
+    /// squashed into by `git rebase --autosquash`
+    pub autosquash: bool,
+    /// Full names of the notes refs whose notes are shown. Notes are only read if not empty.
+    pub notes_refs: Vec<String>,
+    /// Show the text of notes below their commits, instead of a marker
+    pub inline_notes: bool,
+    /// Verify commit signatures, to mark commits by their signature status
+    pub verify_signatures: bool,
+    /// Collapse merges bringing in a single commit into one node
+    pub collapse_trivial_merges: bool,
+    /// Follow only the first parents of merges, summarizing merged-in branches by name
+    pub first_parent: bool,
+    /// Show the parents and metadata of commits replacing others through `git replace`,
+    /// like git log
+    pub replace_refs: bool,
+    /// Show only commits pointed to by refs, and the commits where their histories fork
+    pub simplify_by_decoration: bool,
+    /// Commit correspondences of a history rewrite, shown as annotations
+    pub commit_map: Option<CommitMap>,
+    /// Revisions and ranges to show the history of, like `A..B`, `A...B` or `^A`, like
+    /// git log. The history of all refs if empty
+    pub revision_ranges: Vec<String>,
+    /// Pathspecs to show the commits changing, following renames. All commits if empty
+    pub path_filters: Vec<String>,
+    /// Patterns for the `Name <email>` of authors to show the commits of. All if empty
+    pub author_filters: Vec<Regex>,
+    /// Patterns for the `Name <email>` of committers to show the commits of. All if empty
+    pub committer_filters: Vec<Regex>,
+    /// Show only unsigned commits. Requires `verify_signatures`
+    pub unsigned_only: bool,
+    /// Review status of commits, shown as a glyph per commit
+    pub reviews: Option<Reviews>,
+    /// Show only commits of a review status. Requires `reviews`
+    pub review_filter: Option<ReviewStatus>,
+    /// Branches to show, and to walk the history of
+    pub branch_filter: BranchFilter,
+    /// Tags to show, and the commits of the release view
+    pub tag_filter: TagFilter,
+    /// Globs of protected branches, shown with a lock badge and a distinct label style
+    pub protected_branches: Vec<Regex>,
+    /// Patterns for the tip commit subjects of work-in-progress branches, marked with a badge
+    pub wip_patterns: Vec<Regex>,
+    /// Hide work-in-progress branches, and the commits only they contain
+    pub hide_wip: bool,
+    /// Pattern for the messages of the commits to show, like `git log --grep`
+    pub message_pattern: Option<Regex>,
+    /// Show the commits whose message does not match `message_pattern` instead
+    pub invert_grep: bool,
+    /// Show only commits committed at or after this time, in seconds since the epoch
+    pub since: Option<i64>,
+    /// Show only commits committed at or before this time, in seconds since the epoch
+    pub until: Option<i64>,
+    /// Selection expression combining filters, like `author:alice AND NOT tag:*-rc*`
+    pub selection: Option<Filter>,
+    /// Paths to show the commits whose tree contains any of. All commits if empty
+    pub contains_paths: Vec<String>,
+    /// Pattern to show the commits with added or removed lines matching, like `git log -G`
+    pub content_pattern: Option<Regex>,
+    /// Function or other symbol to show the commits changing, like `git log -L :name:file`
+    pub symbol: Option<Symbol>,
+    /// Report the progress of slow filters on stderr
+    pub progress: bool,
+    /// Counts the commits read while building a graph, and cancels the build
+    pub build_progress: Option<Arc<BuildProgress>>,
+    /// Diff content search, highlighting the matching commits
+    pub pickaxe: Option<Pickaxe>,
+    /// Two commits to highlight the merge bases of, dimming the commits reachable from neither
+    pub merge_base: Option<[Oid; 2]>,
+    /// Also show commits only reachable from the reflogs of HEAD and local branches
+    pub reflog: bool,
+    /// Also show dangling commits, reachable from no ref, stash or reflog
+    pub dangling: bool,
+    /// Show only these commits, with parents rewritten to their nearest listed ancestors
+    pub commit_list: Option<Vec<Oid>>,
+    /// Draw branches with a persistence index below this number of levels in heavier lines
+    pub persistence_weight: Option<u8>,
+    /// Show the name of the merged-in branch, parsed from the summary, on merge commits
+    pub merge_names: bool,
+    /// Show type badges (like `feature`, `remote` or `tag`) next to branch and tag labels
+    pub type_badges: bool,
+    /// Pick branch colors by a hash of the branch name and this seed, rather than by
+    /// the order in which branches are found, for colors that don't shift between runs
+    pub color_seed: Option<u64>,
+    /// Show only this range of rows of the laid-out graph, marking lanes crossing the cuts
+    pub rows: Option<Range<usize>>,
     /// Formatting for commits
     pub format: CommitFormat,
+    /// Handling of control characters in commit texts
+    pub control_chars: ControlChars,
     /// Text wrapping options
     pub wrapping: Option<(Option<usize>, Option<usize>, Option<usize>)>,
     /// Characters to use for text-based graph
     pub characters: Characters,
     /// Branch column sorting algorithm
     pub branch_order: BranchOrder,
+    /// Handling of branches without commits of their own
+    pub empty_branches: EmptyBranches,
     /// Settings for branches
     pub branches: BranchSettings,
     /// Regex patterns for finding branch names in merge commit summaries
@@ -67,11 +612,27 @@ pub struct Settings {
 
 /// Helper for reading BranchSettings, required due to RegEx.
 #[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct BranchSettingsDef {
+    /// Short description of the model, shown when listing models
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
     /// Branch persistence
     pub persistence: Vec<String>,
     /// Branch ordering
     pub order: Vec<String>,
+    /// Names of the order groups, for group headers in SVG output.
+    /// May have one more entry than `order`, naming the group of all other branches.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub order_names: Vec<String>,
+    /// Names of the branch types matched by `persistence`, in the same order, shown as
+    /// type badges next to branch labels.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub type_names: Vec<String>,
+    /// Branches that may follow second parents across "foxtrot" merges, i.e. merges of
+    /// the branch into another branch that became the branch's own history.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub follow_second_parents: Vec<String>,
     /// Branch colors
     pub terminal_colors: ColorsDef,
     /// Branch colors for SVG output
@@ -80,15 +641,164 @@ pub struct BranchSettingsDef {
 
 /// Helper for reading branch colors, required due to RegEx.
 #[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct ColorsDef {
     matches: Vec<(String, Vec<String>)>,
     unknown: Vec<String>,
 }
 
+/// A problem found in a branching model definition.
+pub struct ModelIssue {
+    /// Path of the offending key, e.g. `order[1]`
+    pub key: String,
+    /// The offending value
+    pub value: String,
+    pub message: String,
+}
+
 impl BranchSettingsDef {
+    /// The built-in model of the given name, if any.
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "git-flow" => Some(BranchSettingsDef::git_flow()),
+            "simple" => Some(BranchSettingsDef::simple()),
+            "none" => Some(BranchSettingsDef::none()),
+            _ => None,
+        }
+    }
+
+    /// Checks regexes and colors of the model, without failing at the first problem.
+    pub fn validate(&self) -> Vec<ModelIssue> {
+        let mut issues = vec![];
+        let mut check_regex = |key: String, str: &str| {
+            if let Err(err) = Regex::new(str) {
+                issues.push(ModelIssue {
+                    key,
+                    value: str.to_string(),
+                    message: match err {
+                        // Only keep the actual error of multi-line syntax errors
+                        Error::Syntax(str) => format!(
+                            "invalid regex: {}",
+                            str.lines()
+                                .last()
+                                .unwrap_or("")
+                                .trim_start_matches("error: ")
+                        ),
+                        err => format!("invalid regex: {}", err),
+                    },
+                });
+            }
+        };
+        for (idx, str) in self.persistence.iter().enumerate() {
+            check_regex(format!("persistence[{}]", idx), str);
+        }
+        for (idx, str) in self.order.iter().enumerate() {
+            check_regex(format!("order[{}]", idx), str);
+        }
+        for (idx, str) in self.follow_second_parents.iter().enumerate() {
+            check_regex(format!("follow_second_parents[{}]", idx), str);
+        }
+        for (name, colors) in [
+            ("terminal_colors", &self.terminal_colors),
+            ("svg_colors", &self.svg_colors),
+        ] {
+            for (idx, (str, _)) in colors.matches.iter().enumerate() {
+                check_regex(format!("{}.matches[{}]", name, idx), str);
+            }
+        }
+
+        let mut check_colors = |key: String, colors: &[String], terminal: bool| {
+            if colors.is_empty() {
+                issues.push(ModelIssue {
+                    key: key.clone(),
+                    value: String::new(),
+                    message: "at least one color is required".to_string(),
+                });
+            }
+            for color in colors {
+                let message = if terminal {
+                    to_terminal_color(color).err()
+                } else if !SVG_COLOR.is_match(color) {
+                    Some(format!("Color {} is not a color name or hex code", color))
+                } else {
+                    None
+                };
+                if let Some(message) = message {
+                    issues.push(ModelIssue {
+                        key: key.clone(),
+                        value: color.clone(),
+                        message,
+                    });
+                }
+            }
+        };
+        for (name, colors, terminal) in [
+            ("terminal_colors", &self.terminal_colors, true),
+            ("svg_colors", &self.svg_colors, false),
+        ] {
+            for (idx, (_, matches)) in colors.matches.iter().enumerate() {
+                check_colors(format!("{}.matches[{}]", name, idx), matches, terminal);
+            }
+            check_colors(format!("{}.unknown", name), &colors.unknown, terminal);
+        }
+
+        if self.order_names.len() > self.order.len() + 1 {
+            issues.push(ModelIssue {
+                key: "order_names".to_string(),
+                value: String::new(),
+                message: format!(
+                    "{} names for {} order groups (at most one more than `order`)",
+                    self.order_names.len(),
+                    self.order.len() + 1
+                ),
+            });
+        }
+        if self.type_names.len() > self.persistence.len() {
+            issues.push(ModelIssue {
+                key: "type_names".to_string(),
+                value: String::new(),
+                message: format!(
+                    "{} names for {} branch types (at most one per entry of `persistence`)",
+                    self.type_names.len(),
+                    self.persistence.len()
+                ),
+            });
+        }
+        issues
+    }
+
+    /// Replaces the model's colors of the hues known to the theme, including the colors
+    /// of unknown branches. Other colors are kept.
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        let Some(palette) = theme.palette() else {
+            return self;
+        };
+        let replace = |colors: &mut ColorsDef, terminal: bool| {
+            let lists = colors.matches.iter_mut().map(|(_, colors)| colors);
+            for color in lists.chain([&mut colors.unknown]).flatten() {
+                let name = color.strip_prefix("bright_").unwrap_or(color);
+                let hue = THEME_HUES.iter().position(|(terminal_names, svg_names)| {
+                    if terminal {
+                        terminal_names.contains(&name)
+                    } else {
+                        svg_names.contains(&&color[..])
+                    }
+                });
+                if let Some(hue) = hue {
+                    let (terminal_color, svg_color) = palette[hue];
+                    *color = if terminal { terminal_color } else { svg_color }.to_string();
+                }
+            }
+        };
+        replace(&mut self.terminal_colors, true);
+        replace(&mut self.svg_colors, false);
+        self
+    }
+
     /// The Git-Flow model.
     pub fn git_flow() -> Self {
         BranchSettingsDef {
+            description: "Git-Flow: main, develop, release/hotfix and feature branches".to_string(),
             persistence: vec![
                 r"^(master|main|trunk)$".to_string(),
                 r"^(develop|dev)$".to_string(),
@@ -102,6 +812,16 @@ impl BranchSettingsDef {
                 r"^(hotfix|release).*$".to_string(),
                 r"^(develop|dev)$".to_string(),
             ],
+            order_names: vec![],
+            type_names: vec![
+                "main".to_string(),
+                "develop".to_string(),
+                "feature".to_string(),
+                "release".to_string(),
+                "hotfix".to_string(),
+                "bugfix".to_string(),
+            ],
+            follow_second_parents: vec![],
             terminal_colors: ColorsDef {
                 matches: vec![
                     (
@@ -149,11 +869,15 @@ impl BranchSettingsDef {
     /// Simple feature-based model.
     pub fn simple() -> Self {
         BranchSettingsDef {
+            description: "Simple feature-based model with a single main branch".to_string(),
             persistence: vec![r"^(master|main|trunk)$".to_string()],
             order: vec![
                 r"^tags/.*$".to_string(),
                 r"^(master|main|trunk)$".to_string(),
             ],
+            order_names: vec![],
+            type_names: vec!["main".to_string()],
+            follow_second_parents: vec![],
             terminal_colors: ColorsDef {
                 matches: vec![
                     (
@@ -193,8 +917,12 @@ impl BranchSettingsDef {
     /// Very simple model without any defined branch roles.
     pub fn none() -> Self {
         BranchSettingsDef {
+            description: "No branch roles, all branches treated equally".to_string(),
             persistence: vec![],
             order: vec![],
+            order_names: vec![],
+            type_names: vec![],
+            follow_second_parents: vec![],
             terminal_colors: ColorsDef {
                 matches: vec![],
                 unknown: vec![
@@ -223,11 +951,18 @@ impl BranchSettingsDef {
 }
 
 /// Settings defining branching models
+#[derive(Clone)]
 pub struct BranchSettings {
     /// Branch persistence
     pub persistence: Vec<Regex>,
     /// Branch ordering
     pub order: Vec<Regex>,
+    /// Names of the order groups
+    pub order_names: Vec<String>,
+    /// Names of the branch types matched by `persistence`
+    pub type_names: Vec<String>,
+    /// Branches that may follow second parents across foxtrot merges
+    pub follow_second_parents: Vec<Regex>,
     /// Branch colors
     pub terminal_colors: Vec<(Regex, Vec<String>)>,
     /// Colors for branches not matching any of `colors`
@@ -246,12 +981,25 @@ impl BranchSettings {
             .map(|str| Regex::new(str))
             .collect::<Result<Vec<_>, Error>>()?;
 
+        // Name branch types without a name by the first word of their pattern
+        let mut type_names = def.type_names;
+        for pattern in def.persistence.iter().skip(type_names.len()) {
+            let word = PATTERN_WORD.find(pattern).map_or("", |word| word.as_str());
+            type_names.push(word.to_string());
+        }
+
         let order = def
             .order
             .iter()
             .map(|str| Regex::new(str))
             .collect::<Result<Vec<_>, Error>>()?;
 
+        let follow_second_parents = def
+            .follow_second_parents
+            .iter()
+            .map(|str| Regex::new(str))
+            .collect::<Result<Vec<_>, Error>>()?;
+
         let terminal_colors = def
             .terminal_colors
             .matches
@@ -273,6 +1021,9 @@ impl BranchSettings {
         Ok(BranchSettings {
             persistence,
             order,
+            order_names: def.order_names,
+            type_names,
+            follow_second_parents,
             terminal_colors,
             terminal_colors_unknown,
             svg_colors,
@@ -282,6 +1033,7 @@ impl BranchSettings {
 }
 
 /// RegEx patterns for extracting branch names from merge commit summaries.
+#[derive(Clone)]
 pub struct MergePatterns {
     /// The patterns. Evaluated in the given order.
     pub patterns: Vec<Regex>,
@@ -308,9 +1060,116 @@ impl Default for MergePatterns {
     }
 }
 
+impl MergePatterns {
+    /// The default patterns, preceded by additional patterns. Each pattern must have
+    /// exactly one capture group, capturing the name of the merged-in branch.
+    pub fn with_patterns(patterns: &[String]) -> Result<Self, String> {
+        let mut extra = patterns
+            .iter()
+            .map(|str| {
+                let regex = Regex::new(str)
+                    .map_err(|err| format!("Invalid merge pattern '{}': {}", str, err))?;
+                if regex.captures_len() != 2 {
+                    return Err(format!(
+                        "Merge pattern '{}' must have exactly one capture group for the branch name",
+                        str
+                    ));
+                }
+                Ok(regex)
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        extra.extend(MergePatterns::default().patterns);
+        Ok(MergePatterns { patterns: extra })
+    }
+}
+
+/// Parses patterns matching the `Name <email>` of authors or committers, like
+/// `git log --author`.
+pub fn identity_patterns(patterns: &[String]) -> Result<Vec<Regex>, String> {
+    patterns
+        .iter()
+        .map(|str| {
+            Regex::new(str).map_err(|err| format!("Invalid identity pattern '{}': {}", str, err))
+        })
+        .collect()
+}
+
+/// Parses a pattern for lines added or removed by commits, like `git log -G`.
+pub fn content_pattern(str: &str) -> Result<Regex, String> {
+    Regex::new(str).map_err(|err| format!("Invalid content pattern '{}': {}", str, err))
+}
+
+pub fn message_pattern(str: &str) -> Result<Regex, String> {
+    Regex::new(str).map_err(|err| format!("Invalid message pattern '{}': {}", str, err))
+}
+
+/// Parses a pattern for the tip commit subjects of work-in-progress branches.
+pub fn wip_pattern(str: &str) -> Result<Regex, String> {
+    Regex::new(str).map_err(|err| format!("Invalid WIP pattern '{}': {}", str, err))
+}
+
+/// Parses a date of `--since` or `--until`, in seconds since the epoch. Either an absolute
+/// date like `2024-01-31` (at midnight), `2024-01-31 12:00` or RFC 3339, in local time
+/// unless an offset is given, or a relative date like `2 weeks ago` or `3.days`.
+pub fn parse_date(str: &str, now: DateTime<Local>) -> Result<i64, String> {
+    let str = str.trim();
+    let error = || {
+        format!(
+            "Can't parse date '{}'. Expected a date like '2024-01-31', '2024-01-31 12:00' \
+             or '2 weeks ago'",
+            str
+        )
+    };
+    let local = |time: NaiveDateTime| {
+        Local
+            .from_local_datetime(&time)
+            .earliest()
+            .map(|time| time.timestamp())
+            .ok_or_else(error)
+    };
+
+    if let Ok(time) = DateTime::parse_from_rfc3339(str) {
+        return Ok(time.timestamp());
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(str, format) {
+            return local(time);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(str, "%Y-%m-%d") {
+        if let Some(time) = date.and_hms_opt(0, 0, 0) {
+            return local(time);
+        }
+    }
+
+    let words: Vec<&str> = str
+        .split(|c: char| c == '.' || c.is_whitespace())
+        .filter(|word| !word.is_empty())
+        .collect();
+    let (count, unit) = match words[..] {
+        [count, unit] | [count, unit, "ago"] => (count, unit),
+        _ => return Err(error()),
+    };
+    let count: i64 = count.parse().map_err(|_| error())?;
+    let seconds = match unit.trim_end_matches('s') {
+        "second" => 1,
+        "minute" => 60,
+        "hour" => 3600,
+        "day" => 86400,
+        "week" => 7 * 86400,
+        "month" => 30 * 86400,
+        "year" => 365 * 86400,
+        _ => return Err(error()),
+    };
+    Ok(now.timestamp() - count * seconds)
+}
+
 /// The characters used for drawing text-based graphs.
+#[derive(Clone)]
 pub struct Characters {
     pub chars: Vec<char>,
+    /// Whether the graph characters are braille patterns, combined two lanes per character
+    pub braille: bool,
 }
 
 impl FromStr for Characters {
@@ -323,7 +1182,8 @@ impl FromStr for Characters {
             "bold" | "b" => Ok(Characters::bold()),
             "double" | "d" => Ok(Characters::double()),
             "ascii" | "a" => Ok(Characters::ascii()),
-            _ => Err(format!("Unknown characters/style '{}'. Must be one of [normal|thin|round|bold|double|ascii]", str)),
+            "braille" | "br" => Ok(Characters::braille()),
+            _ => Err(format!("Unknown characters/style '{}'. Must be one of [normal|thin|round|bold|double|ascii|braille]", str)),
         }
     }
 }
@@ -332,31 +1192,44 @@ impl Characters {
     /// Default/thin graphs
     pub fn thin() -> Self {
         Characters {
-            chars: " ●○│─┼└┌┐┘┤├┴┬<>".chars().collect(),
+            chars: " ●○│─┼└┌┐┘┤├┴┬<>▁▃▅█┆◌◇╎".chars().collect(),
+            braille: false,
         }
     }
     /// Graphs with rounded corners
     pub fn round() -> Self {
         Characters {
-            chars: " ●○│─┼╰╭╮╯┤├┴┬<>".chars().collect(),
+            chars: " ●○│─┼╰╭╮╯┤├┴┬<>▁▃▅█┆◌◇╎".chars().collect(),
+            braille: false,
         }
     }
     /// Bold/fat graphs
     pub fn bold() -> Self {
         Characters {
-            chars: " ●○┃━╋┗┏┓┛┫┣┻┳<>".chars().collect(),
+            chars: " ●○┃━╋┗┏┓┛┫┣┻┳<>▁▃▅█┇◌◇╏".chars().collect(),
+            braille: false,
         }
     }
     /// Double-lined graphs
     pub fn double() -> Self {
         Characters {
-            chars: " ●○║═╬╚╔╗╝╣╠╩╦<>".chars().collect(),
+            chars: " ●○║═╬╚╔╗╝╣╠╩╦<>▁▃▅█┆◌◇╎".chars().collect(),
+            braille: false,
         }
     }
     /// ASCII-only graphs
     pub fn ascii() -> Self {
         Characters {
-            chars: " *o|-+'..'||++<>".chars().collect(),
+            chars: " *o|-+'..'||++<>.:+#:~@:".chars().collect(),
+            braille: false,
+        }
+    }
+    /// High-density graphs of braille patterns, with two lanes per character.
+    /// Each symbol only uses the left dot column; horizontal lines run through the second dot row.
+    pub fn braille() -> Self {
+        Characters {
+            chars: " ⠆⡁⡇⠂⡇⠃⡆⡆⠃⡇⡇⠃⡆⠂⠂▁▃▅█⠅⠁⡄⠅".chars().collect(),
+            braille: true,
         }
     }
 
@@ -369,6 +1242,571 @@ impl Characters {
         chars.swap(12, 13);
         chars.swap(14, 15);
 
-        Characters { chars }
+        Characters {
+            chars,
+            braille: self.braille,
+        }
+    }
+}
+
+/// Actions of the interactive pager, bound to keys by [KeyBindings].
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum PagerAction {
+    Down,
+    Up,
+    PageDown,
+    PageUp,
+    Top,
+    End,
+    Jump,
+    Filter,
+    Bookmark,
+    NextBookmark,
+    PrevBookmark,
+    RunAction,
+    Bundle,
+    Slice,
+    Help,
+    Quit,
+}
+
+impl PagerAction {
+    /// All actions, in the order shown in the help overlay.
+    pub const ALL: [PagerAction; 16] = [
+        PagerAction::Down,
+        PagerAction::Up,
+        PagerAction::PageDown,
+        PagerAction::PageUp,
+        PagerAction::Top,
+        PagerAction::End,
+        PagerAction::Jump,
+        PagerAction::Filter,
+        PagerAction::Bookmark,
+        PagerAction::NextBookmark,
+        PagerAction::PrevBookmark,
+        PagerAction::RunAction,
+        PagerAction::Bundle,
+        PagerAction::Slice,
+        PagerAction::Help,
+        PagerAction::Quit,
+    ];
+
+    /// The action's name in key binding files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            PagerAction::Down => "down",
+            PagerAction::Up => "up",
+            PagerAction::PageDown => "page-down",
+            PagerAction::PageUp => "page-up",
+            PagerAction::Top => "top",
+            PagerAction::End => "end",
+            PagerAction::Jump => "jump",
+            PagerAction::Filter => "filter",
+            PagerAction::Bookmark => "bookmark",
+            PagerAction::NextBookmark => "next-bookmark",
+            PagerAction::PrevBookmark => "prev-bookmark",
+            PagerAction::RunAction => "run-action",
+            PagerAction::Bundle => "bundle",
+            PagerAction::Slice => "slice",
+            PagerAction::Help => "help",
+            PagerAction::Quit => "quit",
+        }
+    }
+
+    /// Short description for the help overlay.
+    pub fn description(&self) -> &'static str {
+        match self {
+            PagerAction::Down => "Scroll down one line",
+            PagerAction::Up => "Scroll up one line",
+            PagerAction::PageDown => "Scroll down one page",
+            PagerAction::PageUp => "Scroll up one page",
+            PagerAction::Top => "Go to the first line",
+            PagerAction::End => "Go to the last page",
+            PagerAction::Jump => "Jump to a commit (revision or @N)",
+            PagerAction::Filter => "Filter commits by summary, author or ref",
+            PagerAction::Bookmark => "Toggle a bookmark on the marked commit",
+            PagerAction::NextBookmark => "Go to the next bookmark",
+            PagerAction::PrevBookmark => "Go to the previous bookmark",
+            PagerAction::RunAction => "Run an external action on the marked commit",
+            PagerAction::Bundle => "Write the bookmarked commits to a bundle file",
+            PagerAction::Slice => {
+                "Write the graph from the first to the last bookmark to a text file"
+            }
+            PagerAction::Help => "Show this help",
+            PagerAction::Quit => "Quit",
+        }
+    }
+}
+
+impl FromStr for PagerAction {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        PagerAction::ALL
+            .into_iter()
+            .find(|action| action.name() == str)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown pager action '{}'. Must be one of [{}]",
+                    str,
+                    PagerAction::ALL.map(|action| action.name()).join("|")
+                )
+            })
+    }
+}
+
+/// Names of keys other than characters, usable in [KeyBindings].
+const NAMED_KEYS: [&str; 13] = [
+    "Down",
+    "Up",
+    "Left",
+    "Right",
+    "PageDown",
+    "PageUp",
+    "Home",
+    "End",
+    "Enter",
+    "Esc",
+    "Space",
+    "Tab",
+    "Backspace",
+];
+
+/// Key bindings of the interactive pager.
+///
+/// Keys are single characters (case-sensitive), or one of the named keys like
+/// `Down`, `PageDown` or `Space`, optionally prefixed with `Ctrl-`.
+pub struct KeyBindings {
+    /// Keys of each action, in the order of [PagerAction::ALL]
+    keys: Vec<Vec<String>>,
+}
+
+impl Default for KeyBindings {
+    /// Vim-style keys plus arrow and page keys.
+    fn default() -> Self {
+        let keys = |keys: &[&str]| keys.iter().map(|key| key.to_string()).collect();
+        KeyBindings {
+            keys: PagerAction::ALL
+                .iter()
+                .map(|action| match action {
+                    PagerAction::Down => keys(&["Down", "j"]),
+                    PagerAction::Up => keys(&["Up", "k"]),
+                    PagerAction::PageDown => keys(&["PageDown", "Enter", "Space", "Ctrl-f"]),
+                    PagerAction::PageUp => keys(&["PageUp", "Ctrl-b"]),
+                    PagerAction::Top => keys(&["Home", "g"]),
+                    PagerAction::End => keys(&["End", "G"]),
+                    PagerAction::Jump => keys(&[":"]),
+                    PagerAction::Filter => keys(&["/"]),
+                    PagerAction::Bookmark => keys(&["m"]),
+                    PagerAction::NextBookmark => keys(&["B"]),
+                    PagerAction::PrevBookmark => keys(&["b"]),
+                    PagerAction::RunAction => keys(&["a"]),
+                    PagerAction::Bundle => keys(&["w"]),
+                    PagerAction::Slice => keys(&["s"]),
+                    PagerAction::Help => keys(&["?"]),
+                    PagerAction::Quit => keys(&["q", "Esc", "Ctrl-c"]),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl KeyBindings {
+    /// Creates key bindings from the defaults, replacing the keys of the actions
+    /// given in `bindings` (action name to keys).
+    pub fn from(bindings: HashMap<String, Vec<String>>) -> Result<Self, String> {
+        let mut key_bindings = KeyBindings::default();
+        for (name, keys) in bindings {
+            let action = PagerAction::from_str(&name)?;
+            for key in &keys {
+                let base = key.strip_prefix("Ctrl-").unwrap_or(key);
+                if base.chars().count() != 1 && !NAMED_KEYS.contains(&base) {
+                    return Err(format!(
+                        "Unknown key '{}' for pager action '{}'. Must be a single character or one of [{}], optionally prefixed with 'Ctrl-'",
+                        key,
+                        name,
+                        NAMED_KEYS.join("|")
+                    ));
+                }
+            }
+            key_bindings.keys[action as usize] = keys;
+        }
+        Ok(key_bindings)
+    }
+
+    /// The action bound to a key, if any.
+    pub fn action(&self, key: &str) -> Option<PagerAction> {
+        PagerAction::ALL
+            .into_iter()
+            .find(|action| self.keys[*action as usize].iter().any(|k| k == key))
+    }
+
+    /// The keys bound to an action.
+    pub fn keys(&self, action: PagerAction) -> &[String] {
+        &self.keys[action as usize]
+    }
+}
+
+/// Placeholders of [Actions] command templates.
+const ACTION_PLACEHOLDERS: [&str; 3] = ["oid", "short", "branch"];
+
+/// External commands runnable on a commit, by name.
+///
+/// Commands are shell command templates with placeholders `{oid}`, `{short}`
+/// (the abbreviated oid) and `{branch}` (the branch the commit is attributed to).
+#[derive(Default)]
+pub struct Actions {
+    commands: BTreeMap<String, String>,
+}
+
+impl Actions {
+    /// Creates actions from commands by name, checking their placeholders.
+    pub fn from(commands: BTreeMap<String, String>) -> Result<Self, String> {
+        for (name, command) in &commands {
+            for placeholder in placeholders(command) {
+                if !ACTION_PLACEHOLDERS.contains(&placeholder) {
+                    return Err(format!(
+                        "Unknown placeholder '{{{}}}' in action '{}'. Must be one of [{}]",
+                        placeholder,
+                        name,
+                        ACTION_PLACEHOLDERS.join("|")
+                    ));
+                }
+            }
+        }
+        Ok(Actions { commands })
+    }
+
+    /// The names of all actions, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        self.commands.keys().map(|name| &name[..]).collect()
+    }
+
+    /// The command of an action, with placeholders replaced by the given values.
+    /// Values are quoted for the shell if necessary.
+    pub fn command(&self, name: &str, values: &[(&str, &str)]) -> Result<String, String> {
+        let template = self.commands.get(name).ok_or_else(|| {
+            format!(
+                "Unknown action '{}'. Available actions: [{}]",
+                name,
+                self.names().join("|")
+            )
+        })?;
+        let mut command = template.clone();
+        for (placeholder, value) in values {
+            command = command.replace(&format!("{{{}}}", placeholder), &shell_quote(value));
+        }
+        Ok(command)
+    }
+}
+
+/// The names of `{name}` placeholders in a command template.
+fn placeholders(template: &str) -> impl Iterator<Item = &str> {
+    template.split('{').skip(1).filter_map(|part| {
+        let (name, _) = part.split_once('}')?;
+        name.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+            .then_some(name)
+    })
+}
+
+/// Quotes a value for the shell, unless it consists of safe characters only.
+fn shell_quote(value: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "/._-@+".contains(c);
+    if !value.is_empty() && value.chars().all(is_safe) {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+/// User-defined subcommands, by name, expanding to arguments before they are parsed.
+///
+/// Arguments are given as a single string, split at whitespace outside of quotes,
+/// like `--svg --model simple --max-count 200`.
+#[derive(Default)]
+pub struct Aliases {
+    args: BTreeMap<String, Vec<String>>,
+}
+
+impl Aliases {
+    /// Creates aliases from argument strings by name. Aliases must not shadow
+    /// the given built-in subcommands.
+    pub fn from(aliases: BTreeMap<String, String>, subcommands: &[&str]) -> Result<Self, String> {
+        let mut args = BTreeMap::new();
+        for (name, line) in aliases {
+            if name.is_empty() || name.starts_with('-') || name.contains(char::is_whitespace) {
+                return Err(format!("Invalid alias name '{}'", name));
+            }
+            if subcommands.contains(&name.as_str()) {
+                return Err(format!(
+                    "Alias '{}' would shadow the subcommand of the same name",
+                    name
+                ));
+            }
+            let split = split_args(&line).map_err(|err| format!("{} in alias '{}'", err, name))?;
+            args.insert(name, split);
+        }
+        Ok(Aliases { args })
+    }
+
+    /// The arguments an alias expands to.
+    pub fn get(&self, name: &str) -> Option<&[String]> {
+        self.args.get(name).map(|args| &args[..])
+    }
+}
+
+/// Splits a string into arguments at whitespace, like a shell. Single quotes
+/// keep everything literally, double quotes and backslashes escape whitespace.
+fn split_args(line: &str) -> Result<Vec<String>, String> {
+    let mut args = vec![];
+    let mut arg: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => args.extend(arg.take()),
+            '\'' => {
+                let arg = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err("Unclosed single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let arg = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => arg.extend(chars.next()),
+                        Some(c) => arg.push(c),
+                        None => return Err("Unclosed double quote".to_string()),
+                    }
+                }
+            }
+            '\\' => arg.get_or_insert_with(String::new).extend(chars.next()),
+            c => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(arg);
+    Ok(args)
+}
+
+/// Old and new commit ids of a history rewrite, as in the `commit-map` of
+/// git filter-repo or the `rewritten-list` of git rebase.
+/// New ids are zero for commits pruned by the rewrite.
+#[derive(Clone, Default)]
+pub struct CommitMap {
+    new_of: HashMap<Oid, Oid>,
+    old_of: HashMap<Oid, Oid>,
+}
+
+impl CommitMap {
+    /// Creates a map from pairs of old and new commit ids.
+    pub fn from_pairs(pairs: impl IntoIterator<Item = (Oid, Oid)>) -> Self {
+        let mut map = CommitMap::default();
+        for (old, new) in pairs {
+            map.new_of.insert(old, new);
+            if !new.is_zero() {
+                // Squashed commits map several old ids to one new, keep the first
+                map.old_of.entry(new).or_insert(old);
+            }
+        }
+        map
+    }
+
+    /// The new id of a rewritten commit, zero if it was pruned.
+    pub fn new_of(&self, old: Oid) -> Option<Oid> {
+        self.new_of.get(&old).copied()
+    }
+
+    /// The old id of a commit created by the rewrite.
+    pub fn old_of(&self, new: Oid) -> Option<Oid> {
+        self.old_of.get(&new).copied()
+    }
+
+    /// The number of rewritten commits.
+    pub fn len(&self) -> usize {
+        self.new_of.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.new_of.is_empty()
+    }
+}
+
+/// Whether a commit was reviewed, for teams reviewing commits after they land.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReviewStatus {
+    Reviewed,
+    Unreviewed,
+}
+
+impl FromStr for ReviewStatus {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "reviewed" => Ok(ReviewStatus::Reviewed),
+            "unreviewed" => Ok(ReviewStatus::Unreviewed),
+            _ => Err(format!(
+                "Unknown review status '{}'. Must be one of [reviewed|unreviewed]",
+                str
+            )),
+        }
+    }
+}
+
+/// Review status of commits, as read from an annotations file, e.g. exported
+/// from a forge's API. Commits not listed are unreviewed.
+#[derive(Clone, Debug, Default)]
+pub struct Reviews {
+    statuses: HashMap<Oid, ReviewStatus>,
+}
+
+impl Reviews {
+    /// Creates the review status of commits from pairs of commit ids and statuses.
+    /// Later pairs override earlier ones for the same commit.
+    pub fn from_pairs(pairs: impl IntoIterator<Item = (Oid, ReviewStatus)>) -> Self {
+        Reviews {
+            statuses: pairs.into_iter().collect(),
+        }
+    }
+
+    /// The review status of a commit.
+    pub fn status(&self, oid: Oid) -> ReviewStatus {
+        self.statuses
+            .get(&oid)
+            .copied()
+            .unwrap_or(ReviewStatus::Unreviewed)
+    }
+}
+
+/// Rules for commit messages, as read from a lint configuration file.
+/// Empty patterns disable the respective rule.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct LintRulesDef {
+    /// Maximum number of characters of the subject line, 0 for no limit
+    pub max_subject_length: usize,
+    /// Regex the subject line must match, like a conventional commit type
+    pub subject_pattern: String,
+    /// Regex that must match somewhere in the message, like a ticket reference
+    pub ticket_pattern: String,
+    /// Also check merge commits, which usually have generated messages
+    pub include_merges: bool,
+}
+
+impl Default for LintRulesDef {
+    fn default() -> Self {
+        LintRulesDef {
+            max_subject_length: 72,
+            subject_pattern:
+                r"^(build|chore|ci|docs|feat|fix|perf|refactor|revert|style|test)(\(.+\))?!?: "
+                    .to_string(),
+            ticket_pattern: r"#[0-9]+|[A-Z][A-Z0-9]+-[0-9]+".to_string(),
+            include_merges: false,
+        }
+    }
+}
+
+/// Rules for commit messages, checked by the lint report.
+pub struct LintRules {
+    pub max_subject_length: Option<usize>,
+    pub subject_pattern: Option<Regex>,
+    pub ticket_pattern: Option<Regex>,
+    pub include_merges: bool,
+}
+
+impl LintRules {
+    pub fn from(def: LintRulesDef) -> Result<Self, Error> {
+        let regex = |str: &str| {
+            if str.is_empty() {
+                Ok(None)
+            } else {
+                Regex::new(str).map(Some)
+            }
+        };
+        Ok(LintRules {
+            max_subject_length: Some(def.max_subject_length).filter(|max| *max > 0),
+            subject_pattern: regex(&def.subject_pattern)?,
+            ticket_pattern: regex(&def.ticket_pattern)?,
+            include_merges: def.include_merges,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_date, tag_prefix, BranchFilter, TagFilter};
+    use chrono::{DateTime, Local, NaiveDate, TimeZone};
+
+    #[test]
+    fn branch_filter_globs() {
+        let strings = |globs: &[&str]| {
+            globs
+                .iter()
+                .map(|glob| glob.to_string())
+                .collect::<Vec<_>>()
+        };
+        let filter =
+            BranchFilter::new(&strings(&["release/*", "main"]), &strings(&["*-old"])).unwrap();
+        assert!(filter.matches("main"));
+        assert!(filter.matches("release/1.0"));
+        assert!(!filter.matches("release/0.9-old"));
+        assert!(!filter.matches("origin/main"));
+
+        let filter = BranchFilter::new(&[], &strings(&["dependabot/*"])).unwrap();
+        assert!(filter.matches("feature/x"));
+        assert!(!filter.matches("dependabot/npm/lodash"));
+        assert!(!filter.is_empty());
+        assert!(BranchFilter::default().is_empty());
+    }
+
+    #[test]
+    fn tag_filter_selects_latest_per_prefix() {
+        assert_eq!(tag_prefix("v1.2.0"), "v");
+        assert_eq!(tag_prefix("lib-a/0.3"), "lib-a/");
+        assert_eq!(tag_prefix("nightly"), "nightly");
+
+        let tags = [
+            ("v1.0", true, 10),
+            ("v1.1", false, 20),
+            ("v2.0-rc1", true, 30),
+            ("lib/0.1", true, 5),
+            ("lib/0.2", true, 15),
+        ];
+        let filter = TagFilter::new(&[], false, Some(1)).unwrap();
+        assert_eq!(filter.select(&tags), [false, false, true, false, true]);
+        let filter = TagFilter::new(&[], true, Some(2)).unwrap();
+        assert_eq!(filter.select(&tags), [true, false, true, true, true]);
+        let filter = TagFilter::new(&["v*".to_string()], false, None).unwrap();
+        assert_eq!(filter.select(&tags), [true, true, true, false, false]);
+        assert!(TagFilter::default().is_empty());
+    }
+
+    #[test]
+    fn parse_absolute_and_relative_dates() {
+        let at = |m, d, h, min| -> DateTime<Local> {
+            let time = NaiveDate::from_ymd_opt(2024, m, d)
+                .and_then(|date| date.and_hms_opt(h, min, 0))
+                .unwrap();
+            Local.from_local_datetime(&time).unwrap()
+        };
+        let now = at(3, 10, 12, 0);
+        let local = |d, h, min| at(1, d, h, min).timestamp();
+        assert_eq!(parse_date("2024-01-31", now), Ok(local(31, 0, 0)));
+        assert_eq!(parse_date("2024-01-31 12:30", now), Ok(local(31, 12, 30)));
+        assert_eq!(parse_date("2024-01-31T12:30:00+00:00", now), Ok(1706704200));
+        assert_eq!(
+            parse_date("2 weeks ago", now),
+            Ok(now.timestamp() - 14 * 86400)
+        );
+        assert_eq!(parse_date("3.days", now), Ok(now.timestamp() - 3 * 86400));
+        assert_eq!(parse_date("1 hour", now), Ok(now.timestamp() - 3600));
+        assert!(parse_date("last tuesday", now).is_err());
+        assert!(parse_date("2 fortnights ago", now).is_err());
     }
 }
@@ -0,0 +1,144 @@
+//! Compact snapshots of a graph's layout, see [Snapshot].
+//!
+//! Snapshots are stored as notes in the repository itself, so layouts can be compared
+//! as of earlier commits, and the notes ref's history is an audit trail of the layout.
+
+use crate::graph::GraphData;
+use git2::{Oid, Repository};
+use serde_derive::{Deserialize, Serialize};
+
+/// The notes ref snapshots are stored in.
+pub const SNAPSHOT_NOTES_REF: &str = "refs/notes/commitlens";
+
+/// Version of the snapshot format, increased on incompatible changes.
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// The layout of a graph: the columns of its branches, and the branch of each commit.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    /// When the snapshot was taken, in seconds since the epoch
+    pub time: i64,
+    /// The commit of HEAD, `None` if HEAD is unborn
+    pub head: Option<String>,
+    /// All branches and tags of the layout, including merged and deleted branches
+    pub branches: Vec<SnapshotBranch>,
+    /// The commits from top to bottom, with the index of their branch in `branches`
+    pub commits: Vec<(String, Option<usize>)>,
+}
+
+/// A branch or tag of a [Snapshot].
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotBranch {
+    pub name: String,
+    pub target: String,
+    pub column: Option<usize>,
+}
+
+impl Snapshot {
+    /// The snapshot of a graph's layout, taken at the given time.
+    pub fn new(graph: &GraphData, time: i64) -> Self {
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            time,
+            head: graph
+                .head
+                .as_ref()
+                .and_then(|head| head.oid())
+                .map(|oid| oid.to_string()),
+            branches: graph
+                .all_branches
+                .iter()
+                .map(|branch| SnapshotBranch {
+                    name: branch.name.clone(),
+                    target: branch.target.to_string(),
+                    column: branch.visual.column,
+                })
+                .collect(),
+            commits: graph
+                .commits
+                .iter()
+                .map(|info| (info.oid.to_string(), info.branch_trace))
+                .collect(),
+        }
+    }
+
+    /// The snapshot as compact JSON, on a single line.
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string(self).map_err(|err| err.to_string())
+    }
+}
+
+/// Stores a snapshot as the note of its HEAD commit in [SNAPSHOT_NOTES_REF], replacing an
+/// earlier snapshot of the same commit. Returns the id of the note.
+pub fn store_snapshot(repository: &Repository, snapshot: &Snapshot) -> Result<Oid, String> {
+    let head = snapshot
+        .head
+        .as_deref()
+        .ok_or_else(|| "Can't store a snapshot without commits".to_string())?;
+    let head = Oid::from_str(head).map_err(|err| err.message().to_string())?;
+    let signature = repository
+        .signature()
+        .map_err(|err| format!("Can't write note: {}", err.message()))?;
+    repository
+        .note(
+            &signature,
+            &signature,
+            Some(SNAPSHOT_NOTES_REF),
+            head,
+            &snapshot.to_json()?,
+            true,
+        )
+        .map_err(|err| err.message().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{store_snapshot, Snapshot, SnapshotBranch, SNAPSHOT_NOTES_REF};
+    use crate::graph::GitGraph;
+    use crate::testing::{settings, TestRepo};
+
+    #[test]
+    fn snapshots_are_stored_as_notes_of_head() {
+        let repo = TestRepo::bare("git-graph-snapshot");
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "a").unwrap();
+        config.set_str("user.email", "a@x").unwrap();
+        let head = repo.commit("root", &[]);
+
+        let mut snapshot = Snapshot {
+            version: 1,
+            time: 0,
+            head: Some(head.to_string()),
+            branches: vec![SnapshotBranch {
+                name: "main".to_string(),
+                target: head.to_string(),
+                column: Some(0),
+            }],
+            commits: vec![(head.to_string(), Some(0))],
+        };
+        let json = snapshot.to_json().unwrap();
+        assert!(!json.contains('\n'));
+        store_snapshot(&repo, &snapshot).unwrap();
+
+        // A new snapshot of the same commit replaces the note
+        snapshot.time = 1;
+        store_snapshot(&repo, &snapshot).unwrap();
+        let note = repo.find_note(Some(SNAPSHOT_NOTES_REF), head).unwrap();
+        let stored: Snapshot = serde_json::from_str(note.message().unwrap()).unwrap();
+        assert_eq!(stored, snapshot);
+    }
+
+    #[test]
+    fn snapshots_of_unborn_head_have_no_head() {
+        let repo = TestRepo::bare("git-graph-snapshot-unborn");
+        let graph = GitGraph::new(&repo, &settings(), None).unwrap();
+        let snapshot = Snapshot::new(&graph, 0);
+        assert_eq!(snapshot.head, None);
+        assert!(snapshot.to_json().unwrap().contains("\"head\":null"));
+        assert_eq!(
+            store_snapshot(&repo, &snapshot),
+            Err("Can't store a snapshot without commits".to_string())
+        );
+    }
+}
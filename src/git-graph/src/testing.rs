@@ -0,0 +1,174 @@
+//! Fixtures shared by the tests of all modules.
+
+use crate::print::format::{CommitFormat, ControlChars};
+use crate::rebase::create_temp_dir;
+use crate::settings::{
+    BranchOrder, BranchSettings, BranchSettingsDef, Characters, EmptyBranches, GraphView,
+    LabelColors, MergePatterns, OutputParts, RemoteColors, Settings, StashMode,
+};
+use git2::{Oid, Repository, Signature, Time, Tree};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// A temporary directory, deleted when dropped, also when a test fails.
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new(prefix: &str) -> Self {
+        TempDir {
+            path: create_temp_dir(prefix).unwrap(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// A repository in a [TempDir], with helpers for writing commits.
+pub struct TestRepo {
+    repo: Repository,
+    _dir: TempDir,
+}
+
+impl TestRepo {
+    /// A new bare repository.
+    pub fn bare(prefix: &str) -> Self {
+        let dir = TempDir::new(prefix);
+        let repo = Repository::init_bare(dir.path()).unwrap();
+        TestRepo { repo, _dir: dir }
+    }
+
+    /// A new repository with a working tree.
+    pub fn init(prefix: &str) -> Self {
+        let dir = TempDir::new(prefix);
+        let repo = Repository::init(dir.path()).unwrap();
+        TestRepo { repo, _dir: dir }
+    }
+
+    /// The author and committer of all test commits, at the epoch.
+    pub fn signature() -> Signature<'static> {
+        Signature::new("a", "a@x", &Time::new(0, 0)).unwrap()
+    }
+
+    /// Commits an empty tree.
+    pub fn commit(&self, message: &str, parents: &[Oid]) -> Oid {
+        let tree = self.repo.treebuilder(None).unwrap().write().unwrap();
+        self.commit_tree(message, &self.repo.find_tree(tree).unwrap(), parents)
+    }
+
+    /// Commits a tree of the given files and their contents.
+    pub fn commit_files(&self, message: &str, parents: &[Oid], files: &[(&str, &str)]) -> Oid {
+        let mut builder = self.repo.treebuilder(None).unwrap();
+        for (name, content) in files {
+            let blob = self.repo.blob(content.as_bytes()).unwrap();
+            builder.insert(name, blob, 0o100644).unwrap();
+        }
+        let tree = self.repo.find_tree(builder.write().unwrap()).unwrap();
+        self.commit_tree(message, &tree, parents)
+    }
+
+    /// Commits a tree, without updating any ref.
+    pub fn commit_tree(&self, message: &str, tree: &Tree, parents: &[Oid]) -> Oid {
+        let sig = Self::signature();
+        let parents: Vec<_> = parents
+            .iter()
+            .map(|oid| self.repo.find_commit(*oid).unwrap())
+            .collect();
+        let parents: Vec<_> = parents.iter().collect();
+        self.repo
+            .commit(None, &sig, &sig, message, tree, &parents)
+            .unwrap()
+    }
+}
+
+impl Deref for TestRepo {
+    type Target = Repository;
+
+    fn deref(&self) -> &Repository {
+        &self.repo
+    }
+}
+
+/// The settings of the command line tool without options, with the git-flow model
+/// and without colors.
+pub fn settings() -> Settings {
+    Settings {
+        reverse_commit_order: false,
+        debug: false,
+        compact: false,
+        colored: false,
+        legend: false,
+        output_parts: OutputParts::Full,
+        row_numbers: false,
+        summary: false,
+        label_colors: LabelColors::Branch,
+        include_remote: true,
+        remote_colors: RemoteColors::Origin,
+        stash_mode: StashMode::Hide,
+        view: GraphView::All,
+        release_levels: 2,
+        impact_thresholds: None,
+        large_blob_threshold: None,
+        submodules: None,
+        branch_counts: false,
+        ahead_behind: false,
+        autosquash: false,
+        notes_refs: vec![],
+        inline_notes: false,
+        verify_signatures: false,
+        collapse_trivial_merges: false,
+        first_parent: false,
+        replace_refs: false,
+        simplify_by_decoration: false,
+        commit_map: None,
+        revision_ranges: vec![],
+        path_filters: vec![],
+        author_filters: vec![],
+        committer_filters: vec![],
+        unsigned_only: false,
+        reviews: None,
+        review_filter: None,
+        branch_filter: Default::default(),
+        tag_filter: Default::default(),
+        protected_branches: vec![],
+        wip_patterns: vec![],
+        hide_wip: false,
+        message_pattern: None,
+        invert_grep: false,
+        since: None,
+        until: None,
+        selection: None,
+        contains_paths: vec![],
+        content_pattern: None,
+        symbol: None,
+        progress: false,
+        build_progress: None,
+        pickaxe: None,
+        merge_base: None,
+        reflog: false,
+        dangling: false,
+        commit_list: None,
+        persistence_weight: None,
+        merge_names: false,
+        type_badges: false,
+        color_seed: None,
+        rows: None,
+        format: CommitFormat::OneLine,
+        control_chars: ControlChars::Replace("\u{fffd}".to_string()),
+        wrapping: None,
+        characters: Characters::thin(),
+        branch_order: BranchOrder::ShortestFirst(true),
+        empty_branches: EmptyBranches::Label,
+        branches: BranchSettings::from(BranchSettingsDef::git_flow()).unwrap(),
+        merge_patterns: MergePatterns::default(),
+    }
+}
@@ -0,0 +1,196 @@
+//! Structural comparison of a graph with the output of `git log`, to check that
+//! git-graph shows the same history as git itself.
+//!
+//! The log is produced by the `git` executable, as an independent reference.
+
+use crate::graph::GitGraph;
+use crate::settings::{Settings, StashMode};
+use git2::Oid;
+use std::collections::HashMap;
+use std::fmt;
+use std::process::Command;
+
+/// A structural difference between a graph and `git log`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Mismatch {
+    /// A commit listed by git, but not shown in the graph
+    Missing(Oid),
+    /// A commit shown in the graph, but not listed by git
+    Extra(Oid),
+    /// A commit with different parents, or parents in a different order
+    Parents {
+        oid: Oid,
+        graph: Vec<Oid>,
+        git: Vec<Oid>,
+    },
+    /// A commit shown below one of its parents
+    Order { oid: Oid, parent: Oid },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let short = |oid: &Oid| oid.to_string()[..7].to_string();
+        let list = |oids: &[Oid]| oids.iter().map(short).collect::<Vec<_>>().join(" ");
+        match self {
+            Mismatch::Missing(oid) => write!(f, "{} missing from the graph", short(oid)),
+            Mismatch::Extra(oid) => write!(f, "{} not listed by git log", short(oid)),
+            Mismatch::Parents { oid, graph, git } => write!(
+                f,
+                "{} has parents [{}] in the graph, but [{}] in git log",
+                short(oid),
+                list(graph),
+                list(git)
+            ),
+            Mismatch::Order { oid, parent } => write!(
+                f,
+                "{} is shown below its parent {}",
+                short(oid),
+                short(parent)
+            ),
+        }
+    }
+}
+
+/// Runs `git log` for the refs the graph was built from, and compares its commits and
+/// their parents with the graph. With a commit limit, commits missing from the graph
+/// are not reported, as git orders commits differently.
+pub fn verify_against_git_log(
+    graph: &GitGraph,
+    settings: &Settings,
+    limited: bool,
+) -> Result<Vec<Mismatch>, String> {
+    let mut command = Command::new("git");
+    command
+        .arg("--git-dir")
+        .arg(graph.repository()?.path())
+        .args(["log", "--topo-order", "--format=%H %P"]);
+    if settings.stash_mode == StashMode::Hide {
+        command.arg("--exclude=refs/stash");
+    }
+    if settings.revision_ranges.is_empty() {
+        command.arg("--glob=*");
+    } else {
+        command.args(&settings.revision_ranges).arg("--");
+    }
+    let output = command
+        .output()
+        .map_err(|err| format!("Can't run git: {}", err))?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr);
+        return Err(format!(
+            "git log failed: {}",
+            message.lines().last().unwrap_or_default()
+        ));
+    }
+    let log = parse_log(&String::from_utf8_lossy(&output.stdout))?;
+    let commits: Vec<(Oid, Vec<Oid>)> = graph
+        .commits
+        .iter()
+        .map(|info| (info.oid, info.parents.iter().flatten().copied().collect()))
+        .collect();
+    Ok(compare(&commits, &log, !limited))
+}
+
+/// Parses lines of commit ids, each followed by its parent ids.
+fn parse_log(log: &str) -> Result<Vec<(Oid, Vec<Oid>)>, String> {
+    log.lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let oids = line
+                .split_whitespace()
+                .map(Oid::from_str)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| format!("Unexpected git log output '{}': {}", line, err))?;
+            Ok((oids[0], oids[1..].to_vec()))
+        })
+        .collect()
+}
+
+/// Compares the commits of a graph, in row order, with those of git log.
+/// Parents in the graph must match the first two parents listed by git.
+fn compare(
+    commits: &[(Oid, Vec<Oid>)],
+    log: &[(Oid, Vec<Oid>)],
+    report_missing: bool,
+) -> Vec<Mismatch> {
+    let rows: HashMap<Oid, usize> = commits
+        .iter()
+        .enumerate()
+        .map(|(row, (oid, _))| (*oid, row))
+        .collect();
+    let git_parents: HashMap<Oid, &Vec<Oid>> =
+        log.iter().map(|(oid, parents)| (*oid, parents)).collect();
+
+    let mut mismatches = vec![];
+    if report_missing {
+        for (oid, _) in log {
+            if !rows.contains_key(oid) {
+                mismatches.push(Mismatch::Missing(*oid));
+            }
+        }
+    }
+    for (row, (oid, parents)) in commits.iter().enumerate() {
+        match git_parents.get(oid) {
+            None => mismatches.push(Mismatch::Extra(*oid)),
+            Some(git) if git.iter().take(2).ne(parents.iter()) => {
+                mismatches.push(Mismatch::Parents {
+                    oid: *oid,
+                    graph: parents.clone(),
+                    git: git.to_vec(),
+                })
+            }
+            Some(_) => {}
+        }
+        for parent in parents {
+            if rows.get(parent).is_some_and(|parent_row| *parent_row < row) {
+                mismatches.push(Mismatch::Order {
+                    oid: *oid,
+                    parent: *parent,
+                });
+            }
+        }
+    }
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compare, Mismatch};
+    use git2::Oid;
+
+    #[test]
+    fn compare_reports_structural_mismatches() {
+        let oid = |n: u8| Oid::from_bytes(&[n; 20]).unwrap();
+        let log = vec![
+            (oid(4), vec![oid(3), oid(2)]),
+            (oid(3), vec![oid(1)]),
+            (oid(2), vec![oid(1)]),
+            (oid(1), vec![]),
+            (oid(0), vec![]),
+        ];
+        let graph = vec![
+            (oid(4), vec![oid(2), oid(3)]),
+            (oid(2), vec![oid(1)]),
+            (oid(1), vec![]),
+            (oid(3), vec![oid(1)]),
+            (oid(9), vec![]),
+        ];
+        assert_eq!(
+            compare(&graph, &log, true),
+            vec![
+                Mismatch::Missing(oid(0)),
+                Mismatch::Parents {
+                    oid: oid(4),
+                    graph: vec![oid(2), oid(3)],
+                    git: vec![oid(3), oid(2)],
+                },
+                Mismatch::Order {
+                    oid: oid(3),
+                    parent: oid(1),
+                },
+                Mismatch::Extra(oid(9)),
+            ]
+        );
+        assert!(compare(&log, &log, true).is_empty());
+    }
+}